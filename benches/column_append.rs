@@ -0,0 +1,103 @@
+//! Building insert columns value by value versus in bulk.
+
+use clickhouse_rs::{
+    row,
+    types::{Block, StringColumnData, VectorColumnData},
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const ROWS: usize = 1_000_000;
+const BLOCK_ROWS: usize = 10_000_000;
+
+fn bench_uint64(c: &mut Criterion) {
+    let values: Vec<u64> = (0..ROWS as u64).collect();
+    let mut group = c.benchmark_group("UInt64 column of 1M values");
+
+    group.bench_function("append", |b| {
+        b.iter(|| {
+            let mut column = VectorColumnData::<u64>::with_capacity(ROWS);
+            for &value in &values {
+                column.append(value);
+            }
+            black_box(column)
+        })
+    });
+
+    group.bench_function("append_slice", |b| {
+        b.iter(|| {
+            let mut column = VectorColumnData::<u64>::with_capacity(ROWS);
+            column.append_slice(&values);
+            black_box(column)
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_string(c: &mut Criterion) {
+    let values: Vec<String> = (0..ROWS).map(|i| format!("value-{}", i)).collect();
+    let mut group = c.benchmark_group("String column of 1M values");
+
+    group.bench_function("append", |b| {
+        b.iter(|| {
+            let mut column = StringColumnData::with_capacity(ROWS);
+            for value in &values {
+                column.append(value);
+            }
+            black_box(column)
+        })
+    });
+
+    group.bench_function("append_iter", |b| {
+        b.iter(|| {
+            let mut column = StringColumnData::with_capacity(ROWS);
+            column.append_iter(&values);
+            black_box(column)
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_block(c: &mut Criterion) {
+    let values: Vec<u64> = (0..BLOCK_ROWS as u64).collect();
+    let options: Vec<Option<u64>> = values
+        .iter()
+        .map(|&v| Some(v).filter(|v| v % 3 != 0))
+        .collect();
+    let mut group = c.benchmark_group("block of 10M rows");
+    group.sample_size(10);
+
+    group.bench_function("UInt64 rows", |b| {
+        b.iter(|| {
+            let mut block = Block::with_capacity(BLOCK_ROWS);
+            for &value in &values {
+                block.push(row! { x: value }).unwrap();
+            }
+            black_box(block)
+        })
+    });
+
+    group.bench_function("UInt64 slice", |b| {
+        b.iter(|| black_box(Block::new().column("x", &values[..])))
+    });
+
+    group.bench_function("Nullable(UInt64) rows", |b| {
+        b.iter(|| {
+            let mut block = Block::with_capacity(BLOCK_ROWS);
+            for &value in &options {
+                block.push(row! { x: value }).unwrap();
+            }
+            black_box(block)
+        })
+    });
+
+    group.bench_function("Nullable(UInt64) slice", |b| {
+        b.iter(|| black_box(Block::new().column("x", &options[..])))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_uint64, bench_string, bench_block);
+criterion_main!(benches);
@@ -0,0 +1,364 @@
+//! Decoding large multi-block results with `PacketReader`.
+
+use std::io::{Cursor, Read};
+
+use clickhouse_rs::types::{Block, Packet, PacketReader, Value};
+use clickhouse_rs_cityhash_sys::city_hash_128;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+const BLOCKS: usize = 100;
+const ROWS: usize = 10_000;
+
+fn uvarint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn string(buffer: &mut Vec<u8>, value: &str) {
+    uvarint(buffer, value.len() as u64);
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+/// Data packet header and block info of a block of `columns` columns and
+/// `rows` rows.
+fn block_header(buffer: &mut Vec<u8>, columns: usize, rows: usize) {
+    uvarint(buffer, 1); // data packet
+    string(buffer, ""); // temporary table name
+    buffer.extend_from_slice(&[1, 0, 2, 0xff, 0xff, 0xff, 0xff, 0]); // block info
+    uvarint(buffer, columns as u64);
+    uvarint(buffer, rows as u64);
+}
+
+/// Block info, columns and `rows` rows of
+/// `(n UInt64, s String, x Nullable(Int32))` of the `block`th block.
+fn block_body(buffer: &mut Vec<u8>, block: usize, rows: usize) {
+    buffer.extend_from_slice(&[1, 0, 2, 0xff, 0xff, 0xff, 0xff, 0]); // block info
+    uvarint(buffer, 3);
+    uvarint(buffer, rows as u64);
+
+    string(buffer, "n");
+    string(buffer, "UInt64");
+    for i in 0..rows {
+        buffer.extend_from_slice(&((block * rows + i) as u64).to_le_bytes());
+    }
+
+    string(buffer, "s");
+    string(buffer, "String");
+    for i in 0..rows {
+        string(buffer, &format!("row-{}", block * rows + i));
+    }
+
+    string(buffer, "x");
+    string(buffer, "Nullable(Int32)");
+    for i in 0..rows {
+        buffer.push((i % 2 == 0) as u8);
+    }
+    for i in 0..rows {
+        buffer.extend_from_slice(&(i as i32).to_le_bytes());
+    }
+}
+
+/// `BLOCKS` blocks of `ROWS` rows of `block_body`.
+fn response() -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    for block in 0..BLOCKS {
+        uvarint(&mut buffer, 1); // data packet
+        string(&mut buffer, ""); // temporary table name
+        block_body(&mut buffer, block, ROWS);
+    }
+
+    uvarint(&mut buffer, 5); // end of stream
+    buffer
+}
+
+/// `blocks` LZ4 compressed blocks of `rows` rows of `block_body`.
+fn compressed_response(blocks: usize, rows: usize) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    let mut body = Vec::new();
+
+    for block in 0..blocks {
+        uvarint(&mut buffer, 1); // data packet
+        string(&mut buffer, ""); // temporary table name
+
+        body.clear();
+        block_body(&mut body, block, rows);
+
+        let data = lz4::block::compress(&body, None, false).unwrap();
+        let mut chunk = vec![0x82];
+        chunk.extend_from_slice(&(data.len() as u32 + 9).to_le_bytes());
+        chunk.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(&data);
+
+        let hash = city_hash_128(&chunk);
+        buffer.extend_from_slice(&hash.lo.to_le_bytes());
+        buffer.extend_from_slice(&hash.hi.to_le_bytes());
+        buffer.extend_from_slice(&chunk);
+    }
+
+    uvarint(&mut buffer, 5); // end of stream
+    buffer
+}
+
+/// `BLOCKS` blocks of `(s String, f FixedString(8))`.
+fn string_response() -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    for block in 0..BLOCKS {
+        block_header(&mut buffer, 2, ROWS);
+
+        string(&mut buffer, "s");
+        string(&mut buffer, "String");
+        for i in 0..ROWS {
+            string(&mut buffer, &format!("row-{}", block * ROWS + i));
+        }
+
+        string(&mut buffer, "f");
+        string(&mut buffer, "FixedString(8)");
+        for i in 0..ROWS {
+            buffer.extend_from_slice(&((block * ROWS + i) as u64).to_le_bytes());
+        }
+    }
+
+    uvarint(&mut buffer, 5); // end of stream
+    buffer
+}
+
+/// `BLOCKS` blocks of a single column of type `sql_type` with `width` byte
+/// values.
+fn fixed_width_response(sql_type: &str, width: usize) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    for block in 0..BLOCKS {
+        block_header(&mut buffer, 1, ROWS);
+        string(&mut buffer, "v");
+        string(&mut buffer, sql_type);
+        for i in 0..ROWS {
+            let value = ((block * ROWS + i) as u64).to_le_bytes();
+            buffer.extend_from_slice(&value[..width]);
+        }
+    }
+
+    uvarint(&mut buffer, 5); // end of stream
+    buffer
+}
+
+/// Reads every block of `reader`, dropping each one before the next.
+fn read_blocks<R: Read>(mut reader: PacketReader<R>) -> usize {
+    let mut rows = 0;
+    loop {
+        match reader.read_packet().unwrap() {
+            Packet::Block(block) => rows += black_box(block).row_count(),
+            Packet::Eof(()) => return rows,
+            _ => panic!("unexpected packet"),
+        }
+    }
+}
+
+fn bench_block_reuse(c: &mut Criterion) {
+    let source = response();
+    let mut group = c.benchmark_group("100 blocks of 10k rows");
+
+    group.bench_function("fresh blocks", |b| {
+        b.iter(|| read_blocks(PacketReader::new(Cursor::new(&source[..]))))
+    });
+
+    group.bench_function("reused blocks", |b| {
+        b.iter(|| read_blocks(PacketReader::new(Cursor::new(&source[..])).reuse_blocks(true)))
+    });
+
+    group.finish();
+
+    let source = compressed_response(1_000, 1_000);
+    let mut group = c.benchmark_group("1k compressed blocks of 1k rows");
+
+    group.bench_function("fresh blocks", |b| {
+        b.iter(|| read_blocks(PacketReader::new(Cursor::new(&source[..])).compression(true)))
+    });
+
+    group.bench_function("reused blocks", |b| {
+        b.iter(|| {
+            let reader = PacketReader::new(Cursor::new(&source[..]))
+                .compression(true)
+                .reuse_blocks(true);
+            read_blocks(reader)
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_string_access(c: &mut Criterion) {
+    let source = string_response();
+    let mut group = c.benchmark_group("100 blocks of 10k strings");
+
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            let mut reader = PacketReader::new(Cursor::new(&source[..]));
+            let mut total = 0;
+            while let Packet::Block(block) = reader.read_packet().unwrap() {
+                for row in 0..block.row_count() {
+                    let s: &str = block.get(row, "s").unwrap();
+                    let f: &[u8] = block.get(row, "f").unwrap();
+                    total += s.len() + f.len();
+                }
+            }
+            total
+        })
+    });
+
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            let mut reader = PacketReader::new(Cursor::new(&source[..]));
+            let mut total = 0;
+            while let Packet::Block(block) = reader.read_packet().unwrap() {
+                for row in 0..block.row_count() {
+                    let s: String = block.get(row, "s").unwrap();
+                    let f: Vec<u8> = block.get(row, "f").unwrap();
+                    total += s.len() + f.len();
+                }
+            }
+            total
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_numeric_load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("100 blocks of 10k numbers");
+    group.throughput(Throughput::Elements((BLOCKS * ROWS) as u64));
+
+    for &(sql_type, width) in &[
+        ("Float64", 8),
+        ("UInt16", 2),
+        ("Date", 2),
+        ("DateTime", 4),
+        ("Decimal(18, 4)", 8),
+    ] {
+        let source = fixed_width_response(sql_type, width);
+        group.bench_function(sql_type, |b| {
+            b.iter(|| read_blocks(PacketReader::new(Cursor::new(&source[..]))))
+        });
+    }
+
+    // The same amount of data read value by value, as the loaders used to.
+    let source = fixed_width_response("Float64", 8);
+    group.bench_function("Float64 value by value", |b| {
+        b.iter(|| {
+            let mut reader = Cursor::new(&source[..]);
+            let mut values = Vec::with_capacity(BLOCKS * ROWS);
+            let mut value = [0; 8];
+            for _ in 0..BLOCKS * ROWS {
+                reader.read_exact(&mut value).unwrap();
+                values.push(f64::from_le_bytes(value));
+            }
+            black_box(values)
+        })
+    });
+
+    group.finish();
+}
+
+/// A block of 1M rows of an `Enum8` of 50 variants.
+fn enum_block() -> Block {
+    let variants: Vec<String> = (0..50).map(|i| format!("'v{}' = {}", i, i)).collect();
+    let rows = 1_000_000;
+
+    let mut buffer = Vec::new();
+    block_header(&mut buffer, 1, rows);
+    string(&mut buffer, "e");
+    string(&mut buffer, &format!("Enum8({})", variants.join(", ")));
+    buffer.extend((0..rows).map(|i| (i % 50) as u8));
+    uvarint(&mut buffer, 5); // end of stream
+
+    match PacketReader::new(Cursor::new(&buffer[..]))
+        .read_packet()
+        .unwrap()
+    {
+        Packet::Block(block) => block,
+        _ => panic!("unexpected packet"),
+    }
+}
+
+fn bench_enum_labels(c: &mut Criterion) {
+    let block = enum_block();
+    let mut group = c.benchmark_group("Enum8 column of 1M rows");
+    group.sample_size(10);
+
+    group.bench_function("enum_labels", |b| {
+        b.iter(|| black_box(block.columns()[0].enum_labels().unwrap()))
+    });
+
+    // Scanning the variants of every value, as labels used to be resolved.
+    group.bench_function("scan per value", |b| {
+        b.iter(|| {
+            let labels: Vec<String> = (0..block.row_count())
+                .map(|row| match block.get(row, "e").unwrap() {
+                    Value::Enum8(variants, value) => {
+                        variants
+                            .into_iter()
+                            .find(|(_, v)| *v == value.internal())
+                            .unwrap()
+                            .0
+                    }
+                    _ => unreachable!(),
+                })
+                .collect();
+            black_box(labels)
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_string_load(c: &mut Criterion) {
+    let rows = 1_000_000;
+    let mut buffer = Vec::new();
+    block_header(&mut buffer, 1, rows);
+    string(&mut buffer, "s");
+    string(&mut buffer, "String");
+    let start = buffer.len();
+    for i in 0..rows {
+        string(&mut buffer, &format!("{:08}", i));
+    }
+    let end = buffer.len();
+    uvarint(&mut buffer, 5); // end of stream
+
+    let mut group = c.benchmark_group("String column of 1M rows");
+
+    group.bench_function("load", |b| {
+        b.iter(|| read_blocks(PacketReader::new(Cursor::new(&buffer[..]))))
+    });
+
+    // The same strings read into one allocation each.
+    group.bench_function("String per value", |b| {
+        b.iter(|| {
+            let mut reader = Cursor::new(&buffer[start..end]);
+            let mut values = Vec::with_capacity(rows);
+            let mut len = [0; 1];
+            for _ in 0..rows {
+                reader.read_exact(&mut len).unwrap();
+                let mut value = vec![0; len[0] as usize];
+                reader.read_exact(&mut value).unwrap();
+                values.push(String::from_utf8(value).unwrap());
+            }
+            black_box(values)
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_block_reuse,
+    bench_string_access,
+    bench_numeric_load,
+    bench_enum_labels,
+    bench_string_load
+);
+criterion_main!(benches);
@@ -1,18 +1,29 @@
-use std::io::{self, Read};
+use std::{
+    cmp,
+    collections::HashMap,
+    io::{self, Read},
+};
 
 use chrono_tz::Tz;
 
 use crate::{
     binary::{protocol, ReadEx},
     errors::{DriverError, Error, ServerError, Result},
-    types::{Block, Packet, ProfileInfo, Progress, ServerInfo},
+    types::{Block, LogEntry, Packet, ProfileInfo, Progress, RecycledBlock, ServerInfo},
 };
 
 /// The internal clickhouse response parser.
 pub(crate) struct Parser<T> {
     reader: T,
     tz: Option<Tz>,
+    tz_override: Option<Tz>,
     compress: bool,
+    revision: u64,
+    // Storage for data blocks, set if blocks are decoded into the storage
+    // of the previous one
+    recycled: Option<RecycledBlock>,
+    // Whether the values of data blocks are decoded on first access only
+    lazy: bool,
 }
 
 /// The parser can be used to parse clickhouse responses into values.  Generally
@@ -24,14 +35,41 @@ impl<'a, T: Read> Parser<T> {
     /// than one value can be behind the reader in which case the parser can
     /// be invoked multiple times.  In other words: the stream does not have
     /// to be terminated.
-    pub(crate) fn new(reader: T, tz: Option<Tz>, compress: bool) -> Parser<T> {
+    pub(crate) fn new(
+        reader: T,
+        tz: Option<Tz>,
+        tz_override: Option<Tz>,
+        compress: bool,
+        revision: u64,
+    ) -> Parser<T> {
         Self {
             reader,
             tz,
+            tz_override,
             compress,
+            revision,
+            recycled: None,
+            lazy: false,
         }
     }
 
+    /// Defers decoding the values of the columns of data blocks until they
+    /// are accessed. Takes precedence over `with_recycled`.
+    pub(crate) fn with_lazy_columns(self, lazy: bool) -> Self {
+        Self { lazy, ..self }
+    }
+
+    /// Decodes data blocks into the columns of `recycled` that aren't
+    /// referenced anymore, then keeps the last data block for the next one.
+    pub(crate) fn with_recycled(self, recycled: Option<RecycledBlock>) -> Self {
+        Self { recycled, ..self }
+    }
+
+    /// Returns the block and buffers to decode the next data block into.
+    pub(crate) fn into_recycled(self) -> Option<RecycledBlock> {
+        self.recycled
+    }
+
     /// Parses a single value out of the stream. If there are multiple
     /// values you can call this multiple times. If the reader is not yet
     /// ready this will block.
@@ -43,35 +81,122 @@ impl<'a, T: Read> Parser<T> {
             protocol::SERVER_PROGRESS => Ok(self.parse_progress()?),
             protocol::SERVER_PROFILE_INFO => Ok(self.parse_profile_info()?),
             protocol::SERVER_EXCEPTION => Ok(self.parse_exception()?),
-            protocol::SERVER_DATA | protocol::SERVER_TOTALS | protocol::SERVER_EXTREMES => {
-                Ok(self.parse_block()?)
-            }
+            protocol::SERVER_DATA => Ok(Packet::Block(self.parse_data_block()?)),
+            protocol::SERVER_TOTALS => Ok(Packet::Totals(self.parse_block()?)),
+            protocol::SERVER_EXTREMES => Ok(Packet::Extremes(self.parse_block()?)),
+            protocol::SERVER_LOG => Ok(self.parse_log()?),
             protocol::SERVER_END_OF_STREAM => Ok(Packet::Eof(())),
+            protocol::SERVER_TABLE_COLUMNS => {
+                // Column defaults of the table of an insert, not used.
+                self.reader.skip_string()?; // table
+                self.reader.skip_string()?; // columns
+                self.parse_packet()
+            }
             _ => Err(Error::Driver(DriverError::UnknownPacket { packet })),
         }
     }
 
-    fn parse_block(&mut self) -> Result<Packet<()>> {
+    fn parse_block(&mut self) -> Result<Block> {
+        match self.tz {
+            None => Err(Error::Driver(DriverError::UnexpectedPacket)),
+            Some(tz) => {
+                self.reader.skip_string()?;
+                Block::load(&mut self.reader, tz, self.tz_override, self.compress)
+            }
+        }
+    }
+
+    fn parse_data_block(&mut self) -> Result<Block> {
+        if let (true, Some(tz)) = (self.lazy, self.tz) {
+            self.reader.skip_string()?;
+            return Block::load_lazy(&mut self.reader, tz, self.tz_override, self.compress);
+        }
+
+        let (tz, mut recycled) = match (self.tz, self.recycled.take()) {
+            (Some(tz), Some(recycled)) => (tz, recycled),
+            _ => return self.parse_block(),
+        };
+
+        let result = self.reader.skip_string().and_then(|_| {
+            Block::load_reusing(
+                &mut self.reader,
+                tz,
+                self.tz_override,
+                self.compress,
+                &mut recycled,
+            )
+        });
+        self.recycled = Some(recycled);
+        result
+    }
+
+    fn parse_log(&mut self) -> Result<Packet<()>> {
         match self.tz {
             None => Err(Error::Driver(DriverError::UnexpectedPacket)),
             Some(tz) => {
                 self.reader.skip_string()?;
-                let block = Block::load(&mut self.reader, tz, self.compress)?;
-                Ok(Packet::Block(block))
+                // Log blocks are never compressed.
+                let block = Block::load(&mut self.reader, tz, None, false)?;
+                Ok(Packet::Log(LogEntry::from_block(&block)?))
             }
         }
     }
 
     fn parse_server_info(&mut self) -> Result<Packet<()>> {
+        let name = self.reader.read_string()?;
+        let major_version = self.reader.read_uvarint()?;
+        let minor_version = self.reader.read_uvarint()?;
+        let revision = self.reader.read_uvarint()?;
+
+        // The server only sends what the client's revision knows about.
+        let common_revision = cmp::min(revision, self.revision);
+        if common_revision >= protocol::DBMS_MIN_REVISION_WITH_VERSIONED_PARALLEL_REPLICAS_PROTOCOL
+        {
+            self.reader.read_uvarint()?;
+        }
+        let timezone = match self.reader.read_string()?.parse() {
+            Ok(tz) => tz,
+            Err(err) => return Err(io::Error::new(io::ErrorKind::Other, err).into()),
+        };
+        let display_name =
+            if common_revision >= protocol::DBMS_MIN_REVISION_WITH_SERVER_DISPLAY_NAME {
+                self.reader.read_string()?
+            } else {
+                String::new()
+            };
+        let patch_version = if common_revision >= protocol::DBMS_MIN_REVISION_WITH_VERSION_PATCH {
+            Some(self.reader.read_uvarint()?)
+        } else {
+            None
+        };
+        if common_revision >= protocol::DBMS_MIN_REVISION_WITH_CHUNKED_PACKETS {
+            self.reader.skip_string()?; // chunked sends
+            self.reader.skip_string()?; // chunked receives
+        }
+        if common_revision >= protocol::DBMS_MIN_REVISION_WITH_PASSWORD_COMPLEXITY_RULES {
+            for _ in 0..self.reader.read_uvarint()? {
+                self.reader.skip_string()?; // pattern
+                self.reader.skip_string()?; // message
+            }
+        }
+        if common_revision >= protocol::DBMS_MIN_REVISION_WITH_INTERSERVER_SECRET_V2 {
+            self.reader.read_scalar::<u64>()?; // nonce
+        }
+        let settings = if common_revision >= protocol::DBMS_MIN_REVISION_WITH_SERVER_SETTINGS {
+            read_settings(&mut self.reader)?
+        } else {
+            HashMap::new()
+        };
+
         let server_info = ServerInfo {
-            name: self.reader.read_string()?,
-            major_version: self.reader.read_uvarint()?,
-            minor_version: self.reader.read_uvarint()?,
-            revision: self.reader.read_uvarint()?,
-            timezone: match self.reader.read_string()?.parse() {
-                Ok(tz) => tz,
-                Err(err) => return Err(io::Error::new(io::ErrorKind::Other, err).into()),
-            },
+            name,
+            display_name,
+            major_version,
+            minor_version,
+            patch_version,
+            revision,
+            timezone,
+            settings,
         };
 
         trace!("[hello]        <- {:?}", &server_info);
@@ -79,12 +204,18 @@ impl<'a, T: Read> Parser<T> {
     }
 
     fn parse_progress(&mut self) -> Result<Packet<()>> {
-        let progress = Progress {
+        let mut progress = Progress {
             rows: self.reader.read_uvarint()?,
             bytes: self.reader.read_uvarint()?,
             total_rows: self.reader.read_uvarint()?,
+            ..Progress::default()
         };
 
+        if self.revision >= protocol::DBMS_MIN_REVISION_WITH_CLIENT_WRITE_INFO {
+            progress.written_rows = self.reader.read_uvarint()?;
+            progress.written_bytes = self.reader.read_uvarint()?;
+        }
+
         trace!(
             "[process] <- Progress: rows={}, bytes={}, total rows={}",
             progress.rows,
@@ -110,13 +241,7 @@ impl<'a, T: Read> Parser<T> {
     }
 
     fn parse_exception(&mut self) -> Result<Packet<()>> {
-        let exception = ServerError {
-            code: self.reader.read_scalar()?,
-            name: self.reader.read_string()?,
-            message: self.reader.read_string()?,
-            stack_trace: self.reader.read_string()?,
-            handle: None,
-        };
+        let exception = read_exception(&mut self.reader)?;
 
         warn!("server exception: {:?}", exception);
         Ok(Packet::Exception(exception, None))
@@ -127,3 +252,408 @@ impl<'a, T: Read> Parser<T> {
         Ok(Packet::Pong(()))
     }
 }
+
+/// Reads settings serialized as strings with flags, up to the empty name.
+fn read_settings<R: ReadEx>(reader: &mut R) -> Result<HashMap<String, String>> {
+    let mut settings = HashMap::new();
+    loop {
+        let name = reader.read_string()?;
+        if name.is_empty() {
+            return Ok(settings);
+        }
+        reader.read_uvarint()?; // flags
+        settings.insert(name, reader.read_string()?);
+    }
+}
+
+fn read_exception<R: ReadEx>(reader: &mut R) -> Result<ServerError> {
+    let code = reader.read_scalar()?;
+    let name = reader.read_string()?;
+    let message = reader.read_string()?;
+    let stack_trace = reader.read_string()?;
+    let has_nested: bool = reader.read_scalar()?;
+    let nested = if has_nested {
+        Some(Box::new(read_exception(reader)?))
+    } else {
+        None
+    };
+
+    Ok(ServerError {
+        code,
+        name,
+        message,
+        stack_trace: Some(stack_trace).filter(|trace| !trace.is_empty()),
+        nested,
+        handle: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use chrono::prelude::*;
+
+    use super::*;
+    use crate::{
+        binary::{read_ex::test_reader::TrickleReader, Encoder},
+        types::LogLevel,
+    };
+
+    fn encode_progress(with_write_info: bool) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.uvarint(protocol::SERVER_PROGRESS);
+        encoder.uvarint(10);
+        encoder.uvarint(80);
+        encoder.uvarint(100);
+        if with_write_info {
+            encoder.uvarint(2);
+            encoder.uvarint(16);
+        }
+        encoder.get_buffer()
+    }
+
+    #[test]
+    fn test_parse_log() {
+        let time = Tz::UTC.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap();
+
+        let mut encoder = Encoder::new();
+        encoder.uvarint(protocol::SERVER_LOG);
+        encoder.string("");
+        encoder.write_bytes(&[1, 0, 2, 255, 255, 255, 255, 0]); // block info
+        encoder.uvarint(8);
+        encoder.uvarint(1);
+
+        encoder.string("event_time");
+        encoder.string("DateTime");
+        encoder.write(time.timestamp() as u32);
+        encoder.string("event_time_microseconds");
+        encoder.string("UInt32");
+        encoder.write(42_u32);
+        encoder.string("host_name");
+        encoder.string("String");
+        encoder.string("localhost");
+        encoder.string("query_id");
+        encoder.string("String");
+        encoder.string("id");
+        encoder.string("thread_id");
+        encoder.string("UInt64");
+        encoder.write(7_u64);
+        encoder.string("priority");
+        encoder.string("Int8");
+        encoder.write(7_i8);
+        encoder.string("source");
+        encoder.string("String");
+        encoder.string("executeQuery");
+        encoder.string("text");
+        encoder.string("String");
+        encoder.string("Read 1 rows");
+        let source = encoder.get_buffer();
+
+        // Log blocks are sent uncompressed even if compression is enabled.
+        let mut parser = Parser::new(Cursor::new(&source), Some(Tz::UTC), None, true, 54406);
+
+        match parser.parse_packet().unwrap() {
+            Packet::Log(entries) => assert_eq!(
+                entries,
+                vec![LogEntry {
+                    time,
+                    time_microseconds: 42,
+                    host: "localhost".into(),
+                    query_id: "id".into(),
+                    thread_id: 7,
+                    priority: LogLevel::Debug,
+                    source: "executeQuery".into(),
+                    text: "Read 1 rows".into(),
+                }]
+            ),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn test_parse_totals_and_extremes() {
+        let block = Block::new().column("s", vec![45_u64]);
+
+        let mut encoder = Encoder::new();
+        for packet in &[protocol::SERVER_TOTALS, protocol::SERVER_EXTREMES] {
+            encoder.uvarint(*packet);
+            encoder.string("");
+            block.write(&mut encoder, false);
+        }
+        let source = encoder.get_buffer();
+        let mut parser = Parser::new(Cursor::new(&source), Some(Tz::UTC), None, false, 54213);
+
+        match parser.parse_packet().unwrap() {
+            Packet::Totals(totals) => assert_eq!(totals, block),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+        match parser.parse_packet().unwrap() {
+            Packet::Extremes(extremes) => assert_eq!(extremes, block),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+    }
+
+    fn encode_data(block: &Block, compress: bool) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.uvarint(protocol::SERVER_DATA);
+        encoder.string("");
+        block.write(&mut encoder, compress);
+        encoder.get_buffer()
+    }
+
+    fn sample_block() -> Block {
+        Block::new()
+            .column("id", vec![1_u32, 2, 3])
+            .column("name", vec!["a", "", "a longer name"])
+            .column("score", vec![Some(-1_i64), None, Some(i64::MAX)])
+            .column(
+                "tags",
+                vec![
+                    vec!["x".to_string()],
+                    vec![],
+                    vec!["y".to_string(), "z".to_string()],
+                ],
+            )
+            .column("ratio", vec![0.5_f64, 1.5, -2.0])
+    }
+
+    #[test]
+    fn test_parse_data_byte_by_byte() {
+        let expected = sample_block();
+
+        for &compress in &[false, true] {
+            let source = encode_data(&expected, compress);
+            let mut parser = Parser::new(
+                TrickleReader::new(&source),
+                Some(Tz::UTC),
+                None,
+                compress,
+                54213,
+            );
+
+            match parser.parse_packet().unwrap() {
+                Packet::Block(block) => assert_eq!(block, expected),
+                packet => panic!("unexpected packet {:?}", packet),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_truncated_data() {
+        let block = sample_block();
+
+        for &compress in &[false, true] {
+            let source = encode_data(&block, compress);
+            for len in 0..source.len() {
+                let mut parser = Parser::new(
+                    Cursor::new(&source[..len]),
+                    Some(Tz::UTC),
+                    None,
+                    compress,
+                    54213,
+                );
+                match parser.parse_packet() {
+                    Err(Error::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::WouldBlock),
+                    res => panic!(
+                        "unexpected result {:?} for {} of {} bytes",
+                        res,
+                        len,
+                        source.len()
+                    ),
+                }
+            }
+        }
+    }
+
+    fn encode_hello(revision: u64) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.uvarint(protocol::SERVER_HELLO);
+        encoder.string("ClickHouse");
+        encoder.uvarint(20);
+        encoder.uvarint(3);
+        encoder.uvarint(revision);
+        if revision >= protocol::DBMS_MIN_REVISION_WITH_VERSIONED_PARALLEL_REPLICAS_PROTOCOL {
+            encoder.uvarint(4);
+        }
+        encoder.string("Europe/Moscow");
+        if revision >= protocol::DBMS_MIN_REVISION_WITH_SERVER_DISPLAY_NAME {
+            encoder.string("replica-1");
+        }
+        if revision >= protocol::DBMS_MIN_REVISION_WITH_VERSION_PATCH {
+            encoder.uvarint(19);
+        }
+        if revision >= protocol::DBMS_MIN_REVISION_WITH_CHUNKED_PACKETS {
+            encoder.string("notchunked");
+            encoder.string("notchunked");
+        }
+        if revision >= protocol::DBMS_MIN_REVISION_WITH_PASSWORD_COMPLEXITY_RULES {
+            encoder.uvarint(1);
+            encoder.string(".{12}");
+            encoder.string("be at least 12 characters long");
+        }
+        if revision >= protocol::DBMS_MIN_REVISION_WITH_INTERSERVER_SECRET_V2 {
+            encoder.write(0x1234_u64);
+        }
+        if revision >= protocol::DBMS_MIN_REVISION_WITH_SERVER_SETTINGS {
+            for (name, value) in &[("network_compression_method", "LZ4"), ("max_threads", "8")] {
+                encoder.string(name);
+                encoder.uvarint(1); // important
+                encoder.string(value);
+            }
+            encoder.string("");
+        }
+        encoder.get_buffer()
+    }
+
+    #[test]
+    fn test_parse_server_info() {
+        let source = encode_hello(54213);
+        let mut parser = Parser::new(Cursor::new(&source), None, None, false, 54213);
+        match parser.parse_packet().unwrap() {
+            Packet::Hello(_, info) => {
+                assert_eq!(info.name, "ClickHouse");
+                assert_eq!(info.display_name, "");
+                assert_eq!(info.version(), (20, 3, 0));
+                assert_eq!(info.patch_version, None);
+                assert_eq!(info.timezone, Tz::Europe__Moscow);
+            }
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+
+        let source = encode_hello(54405);
+        let mut parser = Parser::new(Cursor::new(&source), None, None, false, 54405);
+        match parser.parse_packet().unwrap() {
+            Packet::Hello(_, info) => {
+                assert_eq!(info.display_name, "replica-1");
+                assert_eq!(info.version(), (20, 3, 19));
+                assert_eq!(info.patch_version, Some(19));
+                assert_eq!(info.revision, 54405);
+                assert!(info.settings.is_empty());
+            }
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn test_parse_server_settings() {
+        let revision = protocol::DBMS_MIN_REVISION_WITH_SERVER_SETTINGS;
+        let mut source = encode_hello(revision);
+        source.push(protocol::SERVER_PONG as u8);
+
+        let mut parser = Parser::new(Cursor::new(&source), None, None, false, revision);
+        match parser.parse_packet().unwrap() {
+            Packet::Hello(_, info) => {
+                assert_eq!(info.version(), (20, 3, 19));
+                assert_eq!(info.timezone, Tz::Europe__Moscow);
+                assert_eq!(info.settings.len(), 2);
+                assert_eq!(info.settings["network_compression_method"], "LZ4");
+                assert_eq!(info.settings["max_threads"], "8");
+            }
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+        // The whole packet is consumed.
+        assert!(matches!(parser.parse_packet().unwrap(), Packet::Pong(_)));
+    }
+
+    #[test]
+    fn test_skip_table_columns() {
+        let block = Block::new().column("id", Vec::<u32>::new());
+
+        let mut encoder = Encoder::new();
+        encoder.uvarint(protocol::SERVER_TABLE_COLUMNS);
+        encoder.string("");
+        encoder.string("columns format version: 1\n1 columns:\n`id` UInt32\n");
+        let mut source = encoder.get_buffer();
+        source.extend(encode_data(&block, false));
+
+        let mut parser = Parser::new(Cursor::new(&source), Some(Tz::UTC), None, false, 54442);
+        match parser.parse_packet().unwrap() {
+            Packet::Block(header) => assert_eq!(header, block),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn test_parse_progress() {
+        let source = encode_progress(false);
+        let mut parser = Parser::new(Cursor::new(&source), None, None, false, 54213);
+
+        match parser.parse_packet().unwrap() {
+            Packet::Progress(progress) => assert_eq!(
+                progress,
+                Progress {
+                    rows: 10,
+                    bytes: 80,
+                    total_rows: 100,
+                    written_rows: 0,
+                    written_bytes: 0,
+                }
+            ),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn test_parse_progress_with_write_info() {
+        let source = encode_progress(true);
+        let revision = protocol::DBMS_MIN_REVISION_WITH_CLIENT_WRITE_INFO;
+        let mut parser = Parser::new(Cursor::new(&source), None, None, false, revision);
+
+        match parser.parse_packet().unwrap() {
+            Packet::Progress(progress) => {
+                assert_eq!(progress.rows, 10);
+                assert_eq!(progress.written_rows, 2);
+                assert_eq!(progress.written_bytes, 16);
+            }
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn test_parse_nested_exception() {
+        let mut encoder = Encoder::new();
+        encoder.uvarint(protocol::SERVER_EXCEPTION);
+        encoder.write(241_u32);
+        encoder.string("DB::Exception");
+        encoder.string("Received from shard-2.");
+        encoder.string("");
+        encoder.write(1_u8);
+        encoder.write(60_u32);
+        encoder.string("DB::Exception");
+        encoder.string("Table default.t doesn't exist.");
+        encoder.string("0. DB::Exception::Exception()");
+        encoder.write(0_u8);
+        let source = encoder.get_buffer();
+
+        let mut parser = Parser::new(Cursor::new(&source), None, None, false, 54213);
+        let exception = match parser.parse_packet().unwrap() {
+            Packet::Exception(exception, _) => exception,
+            packet => panic!("unexpected packet {:?}", packet),
+        };
+        assert_eq!(parser.reader.position() as usize, source.len());
+
+        assert_eq!(exception.code, 241);
+        assert_eq!(exception.stack_trace, None);
+        let nested = exception.nested.as_deref().unwrap();
+        assert_eq!(nested.code, 60);
+        assert_eq!(
+            nested.stack_trace.as_deref(),
+            Some("0. DB::Exception::Exception()")
+        );
+        assert!(nested.nested.is_none());
+
+        assert!(exception.is_memory_limit());
+        assert!(exception.is_missing_table());
+        assert!(!exception.is_timeout());
+        assert_eq!(
+            exception.message(),
+            "Received from shard-2.\nTable default.t doesn't exist."
+        );
+        assert_eq!(
+            exception.to_string(),
+            "ERROR DB::Exception (241): Received from shard-2.\n\
+             Caused by: ERROR DB::Exception (60): Table default.t doesn't exist."
+        );
+    }
+}
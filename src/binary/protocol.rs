@@ -1,9 +1,23 @@
 pub const DBMS_MIN_REVISION_WITH_QUOTA_KEY_IN_CLIENT_INFO: u64 = 54060;
+pub const DBMS_MIN_REVISION_WITH_SERVER_DISPLAY_NAME: u64 = 54372;
+pub const DBMS_MIN_REVISION_WITH_VERSION_PATCH: u64 = 54401;
+pub const DBMS_MIN_REVISION_WITH_SERVER_LOGS: u64 = 54406;
+pub const DBMS_MIN_REVISION_WITH_COLUMN_DEFAULTS_METADATA: u64 = 54410;
+pub const DBMS_MIN_REVISION_WITH_CLIENT_WRITE_INFO: u64 = 54420;
 pub const DBMS_MIN_REVISION_WITH_SETTINGS_SERIALIZED_AS_STRINGS: u64 = 54429;
+pub const DBMS_MIN_REVISION_WITH_INTERSERVER_SECRET: u64 = 54441;
+pub const DBMS_MIN_REVISION_WITH_OPENTELEMETRY: u64 = 54442;
+pub const DBMS_MIN_REVISION_WITH_CUSTOM_SERIALIZATION: u64 = 54454;
+pub const DBMS_MIN_REVISION_WITH_PASSWORD_COMPLEXITY_RULES: u64 = 54461;
+pub const DBMS_MIN_REVISION_WITH_INTERSERVER_SECRET_V2: u64 = 54462;
+pub const DBMS_MIN_REVISION_WITH_CHUNKED_PACKETS: u64 = 54470;
+pub const DBMS_MIN_REVISION_WITH_VERSIONED_PARALLEL_REPLICAS_PROTOCOL: u64 = 54471;
+pub const DBMS_MIN_REVISION_WITH_SERVER_SETTINGS: u64 = 54474;
 
 pub const CLIENT_HELLO: u64 = 0;
 pub const CLIENT_QUERY: u64 = 1;
 pub const CLIENT_DATA: u64 = 2;
+pub const CLIENT_CANCEL: u64 = 3;
 pub const CLIENT_PING: u64 = 4;
 
 pub const COMPRESS_ENABLE: u64 = 1;
@@ -20,3 +34,5 @@ pub const SERVER_END_OF_STREAM: u64 = 5;
 pub const SERVER_PROFILE_INFO: u64 = 6;
 pub const SERVER_TOTALS: u64 = 7;
 pub const SERVER_EXTREMES: u64 = 8;
+pub const SERVER_LOG: u64 = 10;
+pub const SERVER_TABLE_COLUMNS: u64 = 11;
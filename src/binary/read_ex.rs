@@ -35,6 +35,7 @@ where
                     return Err(ret.into());
                 }
                 Ok(nread) => i += nread,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
                 Err(e) => return Err(From::from(e)),
             }
         }
@@ -99,7 +100,7 @@ where
 
     fn read_str_into_buffer(&mut self, pool: &mut StringPool) -> Result<()> {
         let str_len = self.read_uvarint()? as usize;
-        let buffer = pool.allocate(str_len);
+        let buffer = pool.allocate_contiguous(str_len);
         self.read_bytes(buffer)?;
         Ok(())
     }
@@ -117,3 +118,63 @@ fn test_read_uvarint() {
 
     assert_eq!(actual, 1346)
 }
+
+#[cfg(test)]
+pub(crate) mod test_reader {
+    use std::io;
+
+    /// Reader returning at most one byte per call, failing every other call
+    /// with `Interrupted`, as a slow or signal-interrupted socket would.
+    pub(crate) struct TrickleReader<'a> {
+        source: &'a [u8],
+        interrupt: bool,
+    }
+
+    impl<'a> TrickleReader<'a> {
+        pub(crate) fn new(source: &'a [u8]) -> Self {
+            Self {
+                source,
+                interrupt: true,
+            }
+        }
+    }
+
+    impl<'a> io::Read for TrickleReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+
+            match (self.source.split_first(), buf.first_mut()) {
+                (Some((byte, rest)), Some(target)) => {
+                    *target = *byte;
+                    self.source = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_read_byte_by_byte() {
+    use super::{Encoder, ReadEx};
+
+    let mut encoder = Encoder::new();
+    encoder.uvarint(1346);
+    encoder.string("abc");
+    encoder.write(0x0102_0304_u32);
+    let source = encoder.get_buffer();
+
+    let mut reader = test_reader::TrickleReader::new(&source);
+    assert_eq!(reader.read_uvarint().unwrap(), 1346);
+    assert_eq!(reader.read_string().unwrap(), "abc");
+    assert_eq!(reader.read_scalar::<u32>().unwrap(), 0x0102_0304);
+
+    match reader.read_scalar::<u8>() {
+        Err(Error::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::WouldBlock),
+        res => panic!("unexpected result {:?}", res),
+    }
+}
@@ -1,29 +1,35 @@
-use crate::binary::Encoder;
+use crate::{binary::Encoder, types::Options};
 
 pub static CLIENT_NAME: &str = "Rust SQLDriver";
 
-pub const CLICK_HOUSE_REVISION: u64 = 54213;
+pub const CLICK_HOUSE_REVISION: u64 = 54442;
 pub const CLICK_HOUSE_DBMSVERSION_MAJOR: u64 = 1;
 pub const CLICK_HOUSE_DBMSVERSION_MINOR: u64 = 1;
 
-pub fn write(encoder: &mut Encoder) {
-    encoder.string(CLIENT_NAME);
-    encoder.uvarint(CLICK_HOUSE_DBMSVERSION_MAJOR);
-    encoder.uvarint(CLICK_HOUSE_DBMSVERSION_MINOR);
+/// Writes the name and version of the client, as configured in `options`.
+pub fn write(encoder: &mut Encoder, options: &Options) {
+    let (major, minor) = options.client_version;
+    encoder.string(&options.client_name);
+    encoder.uvarint(major);
+    encoder.uvarint(minor);
     encoder.uvarint(CLICK_HOUSE_REVISION);
 }
 
-pub fn description() -> String {
+/// Returns the name and version the client introduces itself with.
+pub fn description(options: &Options) -> String {
+    let (major, minor) = options.client_version;
     format!(
         "{} {}.{}.{}",
-        CLIENT_NAME,
-        CLICK_HOUSE_DBMSVERSION_MAJOR,
-        CLICK_HOUSE_DBMSVERSION_MINOR,
-        CLICK_HOUSE_REVISION
+        options.client_name, major, minor, CLICK_HOUSE_REVISION
     )
 }
 
 #[test]
 fn test_description() {
-    assert_eq!(description(), "Rust SQLDriver 1.1.54213")
+    assert_eq!(description(&Options::default()), "Rust SQLDriver 1.1.54442");
+
+    let options = Options::default()
+        .client_name("my-service/1.2.3")
+        .client_version(1, 2);
+    assert_eq!(description(&options), "my-service/1.2.3 1.2.54442");
 }
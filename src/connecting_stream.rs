@@ -1,9 +1,9 @@
-use std::io;
+use std::{io, net::SocketAddr, thread};
 
-use futures::{future::FutureResult, SelectOk};
+use futures::{future::FutureResult, sync::oneshot, SelectOk};
 use tokio::net::{tcp::ConnectFuture, TcpStream};
 use tokio::prelude::*;
-use url::Url;
+use url::{Host, Url};
 
 #[cfg(feature = "tls")]
 use native_tls::TlsConnector;
@@ -101,106 +101,120 @@ impl State {
     }
 }
 
+/// Resolves the socket addresses of `addr`.
+///
+/// Host names are looked up on a separate thread, as the system resolver
+/// blocks; this way the lookup doesn't stall the executor and counts
+/// against the connection timeout.
+pub(crate) fn resolve(
+    addr: &Url,
+) -> Box<dyn Future<Item = Vec<SocketAddr>, Error = ConnectionError> + Send> {
+    if let Some(Host::Domain(_)) = addr.host() {
+        let (sender, receiver) = oneshot::channel();
+        let addr = addr.clone();
+        let lookup = thread::Builder::new()
+            .name("clickhouse-resolve".into())
+            .spawn(move || {
+                let _ = sender.send(addr.socket_addrs(|| None));
+            });
+
+        if let Err(err) = lookup {
+            return Box::new(future::err(err.into()));
+        }
+
+        return Box::new(receiver.then(|result| match result {
+            Ok(addresses) => addresses.map_err(ConnectionError::from),
+            Err(_) => Err(io::Error::other("address lookup was aborted").into()),
+        }));
+    }
+
+    Box::new(future::result(addr.socket_addrs(|| None)).from_err())
+}
+
 pub(crate) struct ConnectingStream {
     state: State,
 }
 
 #[cfg(feature = "tls")]
 impl ConnectingStream {
-    pub(crate) fn new(addr: &Url, options: &Options) -> Self {
-        match addr.socket_addrs(|| None) {
-            Ok(addresses) => {
-                let streams: Vec<_> = addresses
-                    .iter()
-                    .map(|address| TcpStream::connect(address))
-                    .collect();
-
-                if streams.is_empty() {
-                    let err = io::Error::new(
-                        io::ErrorKind::InvalidInput,
-                        "Could not resolve to any address.",
-                    );
-                    return Self {
-                        state: State::tcp_err(err),
-                    };
-                }
+    pub(crate) fn new(addr: &Url, addresses: Vec<SocketAddr>, options: &Options) -> Self {
+        let streams: Vec<_> = addresses
+            .iter()
+            .map(|address| TcpStream::connect(address))
+            .collect();
+
+        if streams.is_empty() {
+            let err = io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Could not resolve to any address.",
+            );
+            return Self {
+                state: State::tcp_err(err),
+            };
+        }
+
+        let socket = future::select_ok(streams);
 
-                let socket = future::select_ok(streams);
+        if !options.secure {
+            return Self {
+                state: State::tcp_wait(socket),
+            };
+        }
 
-                if !options.secure {
-                    return Self {
-                        state: State::tcp_wait(socket),
-                    };
+        match addr.host_str().map(|host| host.to_owned()) {
+            None => Self {
+                state: State::tls_host_err(),
+            },
+            Some(host) => {
+                let mut builder = TlsConnector::builder();
+                builder.danger_accept_invalid_certs(options.skip_verify);
+                if let Some(certificate) = options.certificate.clone() {
+                    let native_cert = native_tls::Certificate::from(certificate);
+                    builder.add_root_certificate(native_cert);
+                }
+                if let Some(identity) = options.identity.clone() {
+                    builder.identity(native_tls::Identity::from(identity));
                 }
 
-                match addr.host_str().map(|host| host.to_owned()) {
-                    None => {
-                        Self {
-                            state: State::tls_host_err(),
-                        }
-                    },
-                    Some(host) => {
-                        let mut builder = TlsConnector::builder();
-                        builder.danger_accept_invalid_certs(options.skip_verify);
-                        if let Some(certificate) = options.certificate.clone() {
-                            let native_cert = native_tls::Certificate::from(certificate);
-                            builder.add_root_certificate(native_cert);
-                        }
-
-                        Self {
-                            state: State::tls_wait(
-                                Box::new(
-                                    socket
-                                        .from_err::<ConnectionError>()
-                                        .join(builder.build().into_future().from_err())
-                                        .and_then(move |((s, _), cx)| {
-                                            let cx = tokio_tls::TlsConnector::from(cx);
-
-                                            cx.connect(&host, s).from_err()
-                                        }))
-                            )
-                        }
-                    },
+                Self {
+                    state: State::tls_wait(Box::new(
+                        socket
+                            .from_err::<ConnectionError>()
+                            .join(builder.build().into_future().from_err())
+                            .and_then(move |((s, _), cx)| {
+                                let cx = tokio_tls::TlsConnector::from(cx);
+
+                                cx.connect(&host, s).from_err()
+                            }),
+                    )),
                 }
             }
-            Err(err) => Self {
-                state: State::tcp_err(err),
-            },
         }
     }
 }
 
 #[cfg(not(feature = "tls"))]
 impl ConnectingStream {
-    pub(crate) fn new(addr: &Url, _options: &Options) -> Self
-        where
-    {
-        match addr.socket_addrs(|| None) {
-            Ok(addresses) => {
-                let streams: Vec<_> = addresses
-                    .iter()
-                    .map(|address| TcpStream::connect(address))
-                    .collect();
-
-                if streams.is_empty() {
-                    let err = io::Error::new(
-                        io::ErrorKind::InvalidInput,
-                        "Could not resolve to any address.",
-                    );
-                    return Self {
-                        state: State::tcp_err(err),
-                    };
-                }
+    pub(crate) fn new(_addr: &Url, addresses: Vec<SocketAddr>, _options: &Options) -> Self {
+        let streams: Vec<_> = addresses
+            .iter()
+            .map(|address| TcpStream::connect(address))
+            .collect();
+
+        if streams.is_empty() {
+            let err = io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Could not resolve to any address.",
+            );
+            return Self {
+                state: State::tcp_err(err),
+            };
+        }
 
-                let socket = future::select_ok(streams);
+        let socket = future::select_ok(streams);
 
-                Self {
-                    state: State::tcp_wait(socket),
-                }
-            }
-            Err(err) => Self {
-                state: State::tcp_err(err),
-            },
+        Self {
+            state: State::tcp_wait(socket),
         }
     }
 }
@@ -213,3 +227,20 @@ impl Future for ConnectingStream {
         self.state.poll()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let addr = Url::parse("tcp://127.0.0.1:9000").unwrap();
+        let addresses = resolve(&addr).wait().unwrap();
+        assert_eq!(addresses, vec!["127.0.0.1:9000".parse().unwrap()]);
+
+        let addr = Url::parse("tcp://localhost:9000").unwrap();
+        let addresses = resolve(&addr).wait().unwrap();
+        assert!(addresses.iter().all(|address| address.port() == 9000));
+        assert!(!addresses.is_empty());
+    }
+}
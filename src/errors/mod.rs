@@ -1,4 +1,6 @@
-use std::{borrow::Cow, io, mem, result, str::Utf8Error, string::FromUtf8Error, sync::Arc};
+use std::{
+    borrow::Cow, error, fmt, io, mem, result, str::Utf8Error, string::FromUtf8Error, sync::Arc,
+};
 
 use thiserror::Error;
 use tokio::prelude::*;
@@ -36,19 +38,101 @@ pub enum Error {
 
     #[error("From SQL error: `{}`", _0)]
     FromSql(#[source] FromSqlError),
+
+    #[error(
+        "Value {value} in row {row_index} of column \"{column}\" is out of range for {target_type}"
+    )]
+    ValueOutOfRange {
+        column: String,
+        row_index: usize,
+        value: String,
+        target_type: String,
+    },
+
+    #[error("Block does not match the table: {}", join_problems(.problems))]
+    SchemaMismatch { problems: Vec<SchemaProblem> },
+
+    #[error("Timed out waiting for a connection from the pool")]
+    PoolTimeout,
+
+    #[error("Too many tasks are waiting for a connection from the pool")]
+    PoolExhausted,
 }
 
 /// This type represents Clickhouse server error.
-#[derive(Debug, Error, Clone)]
-#[error("ERROR {} ({}): {}", name, code, message)]
+///
+/// `code` is one of the constants in [`codes`](codes/index.html). An
+/// exception the server caught while handling another one, e.g. on a remote
+/// shard, is kept in `nested` and is also the `source()` of the error.
+#[derive(Debug, Clone)]
 pub struct ServerError {
     pub code: u32,
     pub name: String,
     pub message: String,
-    pub stack_trace: String,
+    pub stack_trace: Option<String>,
+    pub nested: Option<Box<ServerError>>,
     pub handle: Option<Arc<ClientHandle>>,
 }
 
+impl ServerError {
+    /// Returns the message of the exception followed by the ones of the
+    /// nested exceptions, one per line.
+    pub fn message(&self) -> String {
+        let mut message = self.message.clone();
+        for nested in self.chain().skip(1) {
+            message.push('\n');
+            message.push_str(&nested.message);
+        }
+        message
+    }
+
+    /// Whether a table the query refers to does not exist.
+    pub fn is_missing_table(&self) -> bool {
+        self.has_code(&[codes::UNKNOWN_TABLE])
+    }
+
+    /// Whether the query exceeded `max_execution_time` or a socket timed out
+    /// on the server side.
+    pub fn is_timeout(&self) -> bool {
+        self.has_code(&[codes::TIMEOUT_EXCEEDED, codes::SOCKET_TIMEOUT])
+    }
+
+    /// Whether the query exceeded a memory limit.
+    pub fn is_memory_limit(&self) -> bool {
+        self.has_code(&[codes::MEMORY_LIMIT_EXCEEDED])
+    }
+
+    /// Whether this or any nested exception has one of `codes`.
+    fn has_code(&self, codes: &[u32]) -> bool {
+        self.chain().any(|err| codes.contains(&err.code))
+    }
+
+    fn chain(&self) -> impl Iterator<Item = &ServerError> {
+        let mut next = Some(self);
+        std::iter::from_fn(move || {
+            let current = next?;
+            next = current.nested.as_deref();
+            Some(current)
+        })
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ERROR {} ({}): {}", self.name, self.code, self.message)?;
+        if let Some(nested) = &self.nested {
+            write!(f, "\nCaused by: {}", nested)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for ServerError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.nested.as_deref().map(|nested| nested as _)
+    }
+}
+
 /// This type enumerates connection errors.
 #[derive(Debug, Error)]
 pub enum ConnectionError {
@@ -102,6 +186,102 @@ pub enum DriverError {
 
     #[error("Invalid utf-8 sequence.")]
     Utf8Error(Utf8Error),
+
+    #[error(
+        "Query has {} placeholders but {} parameters were bound.",
+        placeholders,
+        params
+    )]
+    ParamsCount { placeholders: usize, params: usize },
+
+    #[error(
+        "Parameter {} has DateTime64 precision {} but at most 9 is supported.",
+        index,
+        precision
+    )]
+    ParamPrecision { index: usize, precision: u32 },
+
+    #[error("Column `{}` has {} rows but the block has {}.", name, rows, expected)]
+    ColumnLength {
+        name: String,
+        rows: usize,
+        expected: usize,
+    },
+
+    #[error("Corrupted compressed data: {}.", _0)]
+    CorruptedData(String),
+
+    #[error("Inserter is closed.")]
+    InserterClosed,
+
+    #[error("Query returned no rows.")]
+    NoRows,
+
+    #[error("Query returned more than one row.")]
+    TooManyRows,
+
+    #[error("Row has {} columns but {} were expected.", columns, expected)]
+    ColumnCount { columns: usize, expected: usize },
+
+    #[error("Column `{}` appears more than once in the block.", name)]
+    DuplicateColumn { name: String },
+
+    #[error("Mask has {} entries but the block has {} rows.", mask, rows)]
+    MaskLength { mask: usize, rows: usize },
+
+    #[error("Row {} is out of range for a block of {} rows.", index, rows)]
+    RowOutOfRange { index: usize, rows: usize },
+
+    #[error("Column `{}` of type {} can't be sorted.", name, sql_type)]
+    UnsortableColumn { name: String, sql_type: String },
+}
+
+/// Broad classes of errors, see `Error::kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The socket failed or the connection could not be established.
+    Network,
+    /// The server sent something the driver cannot make sense of, e.g. an
+    /// unknown packet or corrupted compressed data.
+    Protocol,
+    /// The server reported an exception.
+    Server,
+    /// The request was rejected by the driver itself, e.g. a bad URL or a
+    /// value that cannot be converted.
+    Driver,
+    /// A socket operation or the whole query took too long.
+    Timeout,
+    /// No connection could be taken from the pool.
+    Pool,
+}
+
+/// A difference between a block being inserted and the columns the server
+/// expects, reported in `Error::SchemaMismatch`.
+#[derive(Clone, Debug, PartialEq, Error)]
+pub enum SchemaProblem {
+    #[error("column \"{name}\" ({sql_type}) is missing")]
+    MissingColumn { name: String, sql_type: String },
+
+    #[error("column \"{name}\" is not in the table")]
+    ExtraColumn { name: String },
+
+    #[error("column \"{name}\" is {actual}, expected {expected}")]
+    TypeMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("column \"{name}\" is out of order, set `Query::reorder_columns` to reorder it")]
+    OutOfOrder { name: String },
+
+    #[error("column \"{name}\" is given more than once")]
+    DuplicateColumn { name: String },
+}
+
+fn join_problems(problems: &[SchemaProblem]) -> String {
+    let problems: Vec<_> = problems.iter().map(SchemaProblem::to_string).collect();
+    problems.join("; ")
 }
 
 /// This type enumerates cast from sql type errors.
@@ -118,6 +298,112 @@ pub enum FromSqlError {
 
     #[error("Unsupported operation.")]
     UnsupportedOperation,
+
+    /// No column is called `name`.
+    #[error("column '{}' not found, the columns are: {}", name, available.join(", "))]
+    UnknownColumn {
+        name: String,
+        available: Vec<String>,
+    },
+
+    /// Several columns are called `name` when ignoring case, see
+    /// `Block::with_case_insensitive_columns`.
+    #[error("column '{}' is ambiguous, it matches {}", name, matches.join(", "))]
+    AmbiguousColumn { name: String, matches: Vec<String> },
+
+    /// An `Enum8` or `Enum16` value that has no label, or a label that is
+    /// not a variant of the Rust enum it is read as.
+    #[error("value {} is not in the enum", value)]
+    UnknownEnumValue { value: String },
+
+    /// A value read with `Block::get` or `Row::get` that couldn't be
+    /// converted, `source` tells why.
+    #[error("column '{column}' ({sql_type}) cannot be read as {rust_type} at row {row_index}")]
+    Column {
+        column: String,
+        column_index: usize,
+        sql_type: String,
+        rust_type: Cow<'static, str>,
+        row_index: usize,
+        #[source]
+        source: Box<FromSqlError>,
+    },
+}
+
+impl Error {
+    /// Returns the class of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Io(err) if err.kind() == io::ErrorKind::TimedOut => ErrorKind::Timeout,
+            Error::Io(_) | Error::Connection(_) => ErrorKind::Network,
+            Error::Driver(DriverError::Timeout) => ErrorKind::Timeout,
+            Error::Driver(DriverError::Overflow)
+            | Error::Driver(DriverError::UnknownPacket { .. })
+            | Error::Driver(DriverError::UnexpectedPacket)
+            | Error::Driver(DriverError::Utf8Error(_))
+            | Error::Driver(DriverError::CorruptedData(_)) => ErrorKind::Protocol,
+            Error::Server(_) => ErrorKind::Server,
+            Error::PoolTimeout | Error::PoolExhausted => ErrorKind::Pool,
+            Error::Driver(_)
+            | Error::Other(_)
+            | Error::Url(_)
+            | Error::FromSql(_)
+            | Error::ValueOutOfRange { .. }
+            | Error::SchemaMismatch { .. } => ErrorKind::Driver,
+        }
+    }
+
+    /// Whether the same request can safely be sent again: it failed because
+    /// of a transient condition (see `is_transient`) or before anything was
+    /// sent because the pool had no connection to spare.
+    pub fn is_retryable(&self) -> bool {
+        self.is_transient() || self.kind() == ErrorKind::Pool
+    }
+
+    /// Whether the connection the error happened on is left in an unknown
+    /// state and must be discarded: network, protocol and timeout errors.
+    ///
+    /// A connection that reported a server exception is still in sync with
+    /// the server and is handed back to the pool. Connections failing this
+    /// way inside the driver are never handed out again; the driver
+    /// cancels and drains a query that exceeded its own `with_timeout`
+    /// deadline to keep the connection.
+    pub fn is_fatal_for_connection(&self) -> bool {
+        matches!(
+            self.kind(),
+            ErrorKind::Network | ErrorKind::Protocol | ErrorKind::Timeout
+        )
+    }
+
+    /// Whether the error is likely caused by a transient network or server
+    /// condition, so the same request can succeed when sent again, possibly
+    /// to another replica.
+    ///
+    /// This is the default classification of the query retry policy.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Io(err) | Error::Connection(ConnectionError::IoError(err)) => matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::NotConnected
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+                    | io::ErrorKind::TimedOut
+            ),
+            Error::Driver(DriverError::Timeout) => true,
+            Error::Server(err) => matches!(
+                err.code,
+                codes::SOCKET_TIMEOUT
+                    | codes::NETWORK_ERROR
+                    | codes::TOO_MANY_SIMULTANEOUS_QUERIES
+                    | codes::ALL_CONNECTION_TRIES_FAILED
+                    | codes::ALL_REPLICAS_ARE_STALE
+            ),
+            _ => false,
+        }
+    }
 }
 
 impl From<DriverError> for Error {
@@ -128,6 +414,11 @@ impl From<DriverError> for Error {
 
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self {
+        // Errors passed through an `io::Read`, e.g. the compressed reader,
+        // come back as they were raised.
+        if err.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            return *err.into_inner().unwrap().downcast::<Error>().unwrap();
+        }
         Error::Io(err)
     }
 }
@@ -217,7 +508,45 @@ impl From<Error> for io::Error {
     fn from(err: Error) -> Self {
         match err {
             Error::Io(error) => error,
-            e => io::Error::new(io::ErrorKind::Other, e.to_string()),
+            e => io::Error::new(io::ErrorKind::Other, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_kind() {
+        let reset = Error::from(io::Error::from(io::ErrorKind::ConnectionReset));
+        assert_eq!(reset.kind(), ErrorKind::Network);
+        assert!(reset.is_retryable());
+        assert!(reset.is_fatal_for_connection());
+
+        let timeout = Error::Driver(DriverError::Timeout);
+        assert_eq!(timeout.kind(), ErrorKind::Timeout);
+        assert!(timeout.is_fatal_for_connection());
+
+        assert_eq!(Error::PoolExhausted.kind(), ErrorKind::Pool);
+        assert!(Error::PoolTimeout.is_retryable());
+        assert!(!Error::PoolTimeout.is_fatal_for_connection());
+
+        let params = Error::Driver(DriverError::ParamsCount {
+            placeholders: 1,
+            params: 2,
+        });
+        assert_eq!(params.kind(), ErrorKind::Driver);
+        assert!(!params.is_retryable());
+        assert!(!params.is_fatal_for_connection());
+    }
+
+    #[test]
+    fn test_error_through_io() {
+        let err = io::Error::from(Error::Driver(DriverError::CorruptedData("x".into())));
+        match Error::from(err) {
+            Error::Driver(DriverError::CorruptedData(message)) => assert_eq!(message, "x"),
+            err => panic!("unexpected error {:?}", err),
         }
     }
 }
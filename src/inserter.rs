@@ -0,0 +1,448 @@
+use std::{
+    fmt, mem,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    executor::{DefaultExecutor, Executor},
+    prelude::*,
+};
+use tokio_timer::Delay;
+
+use crate::{
+    errors::{DriverError, Error},
+    pool::Pool,
+    types::{column, Block, Either, RowBuilder, ValueRef},
+};
+
+const DEFAULT_MAX_ROWS: usize = 100_000;
+const DEFAULT_MAX_BYTES: usize = 64 * 1024 * 1024;
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(5);
+
+/// Counters of a `BufferedInserter`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct InserterStats {
+    /// Rows pushed but not yet flushed.
+    pub rows_buffered: u64,
+    /// Rows written to the table.
+    pub rows_flushed: u64,
+    /// Rows dropped because their flush failed.
+    pub rows_failed: u64,
+    /// Number of successful flushes.
+    pub flushes: u64,
+}
+
+/// Collects rows pushed one at a time and inserts them into a table in
+/// batches.
+///
+/// The buffer is flushed once it holds `max_rows` rows or about `max_bytes`
+/// bytes, or when its oldest row is older than `max_age`. A connection is
+/// only taken from the pool for the duration of a flush, which is retried
+/// like any other insert with `Options::retry_inserts`. Rows of a flush
+/// that failed anyway are dropped and counted in `InserterStats::rows_failed`.
+///
+/// ```rust,no_run
+/// # use clickhouse_rs::{row, BufferedInserter, Pool};
+/// # use futures::{future, Future};
+/// # use std::time::Duration;
+/// let pool = Pool::new("tcp://localhost:9000");
+/// let inserter = BufferedInserter::new(pool, "events")
+///     .max_rows(10_000)
+///     .max_age(Duration::from_secs(1));
+///
+/// let done = future::lazy(move || {
+///     tokio::spawn(inserter.flush_periodically());
+///     inserter
+///         .push_row(row! { id: 1_u32, name: "foo" })
+///         .and_then(move |_| inserter.close())
+/// });
+///
+/// tokio::run(
+///     done.map(|stats| println!("{} rows written", stats.rows_flushed))
+///         .map_err(|err| eprintln!("{}", err)),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct BufferedInserter {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    pool: Pool,
+    table: String,
+    state: Mutex<State>,
+    rows_flushed: AtomicU64,
+    rows_failed: AtomicU64,
+    flushes: AtomicU64,
+}
+
+struct State {
+    block: Block,
+    schema: Option<Block>,
+    first_row_at: Option<Instant>,
+    bytes: usize,
+    max_rows: usize,
+    max_bytes: usize,
+    max_age: Duration,
+    flush_on_drop: bool,
+    closed: bool,
+}
+
+impl fmt::Debug for BufferedInserter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BufferedInserter")
+            .field("table", &self.inner.table)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl BufferedInserter {
+    /// Creates an inserter into `table` taking connections from `pool`.
+    pub fn new<S: Into<String>>(pool: Pool, table: S) -> Self {
+        let state = State {
+            block: Block::new(),
+            schema: None,
+            first_row_at: None,
+            bytes: 0,
+            max_rows: DEFAULT_MAX_ROWS,
+            max_bytes: DEFAULT_MAX_BYTES,
+            max_age: DEFAULT_MAX_AGE,
+            flush_on_drop: true,
+            closed: false,
+        };
+
+        Self {
+            inner: Arc::new(Inner {
+                pool,
+                table: table.into(),
+                state: Mutex::new(state),
+                rows_flushed: AtomicU64::new(0),
+                rows_failed: AtomicU64::new(0),
+                flushes: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Flush once this many rows are buffered (defaults to `100000`).
+    pub fn max_rows(self, max_rows: usize) -> Self {
+        self.inner.lock().max_rows = max_rows.max(1);
+        self
+    }
+
+    /// Flush once the buffered rows take about this many bytes
+    /// (defaults to 64 MiB).
+    pub fn max_bytes(self, max_bytes: usize) -> Self {
+        self.inner.lock().max_bytes = max_bytes;
+        self
+    }
+
+    /// Flush once the oldest buffered row is this old (defaults to `5 sec`).
+    ///
+    /// The age is checked on every push and by `flush_periodically`.
+    pub fn max_age(self, max_age: Duration) -> Self {
+        self.inner.lock().max_age = max_age;
+        self
+    }
+
+    /// Whether the rows still buffered when the last clone of the inserter
+    /// is dropped are flushed in the background (the default) or only
+    /// reported in the log.
+    pub fn flush_on_drop(self, flush_on_drop: bool) -> Self {
+        self.inner.lock().flush_on_drop = flush_on_drop;
+        self
+    }
+
+    /// Converts every flushed batch to the columns of `schema`, e.g. a
+    /// sample block or the header of the table, before sending it.
+    ///
+    /// A batch that doesn't fit fails with `Error::SchemaMismatch` without
+    /// taking a connection.
+    pub fn with_schema(self, schema: Block) -> Self {
+        self.inner.lock().schema = Some(schema);
+        self
+    }
+
+    /// Current counters of the inserter.
+    pub fn stats(&self) -> InserterStats {
+        let rows_buffered = self.inner.lock().block.row_count() as u64;
+        self.inner.stats(rows_buffered)
+    }
+
+    /// Adds a row to the buffer, the returned future completes once the
+    /// buffer is flushed if the row made it reach one of the limits.
+    pub fn push_row<R: RowBuilder>(&self, row: R) -> impl Future<Item = (), Error = Error> {
+        let block = {
+            let mut state = self.inner.lock();
+            if state.closed {
+                return Either::Left(future::err(Error::Driver(DriverError::InserterClosed)));
+            }
+
+            if let Err(err) = state.block.push(row) {
+                return Either::Left(future::err(err));
+            }
+
+            let row = state.block.row_count() - 1;
+            state.bytes += row_size(&state.block, row);
+            if state.first_row_at.is_none() {
+                state.first_row_at = Some(Instant::now());
+            }
+
+            if !state.is_full() {
+                return Either::Left(future::ok(()));
+            }
+            state.take()
+        };
+
+        Either::Right(self.inner.clone().send(block))
+    }
+
+    /// Sends the buffered rows now.
+    pub fn flush(&self) -> impl Future<Item = (), Error = Error> {
+        let block = self.inner.lock().take();
+        self.inner.clone().send(block)
+    }
+
+    /// Flushes the buffered rows and returns the final counters, rows
+    /// pushed afterwards are rejected with `DriverError::InserterClosed`.
+    pub fn close(&self) -> impl Future<Item = InserterStats, Error = Error> {
+        let block = {
+            let mut state = self.inner.lock();
+            state.closed = true;
+            state.take()
+        };
+
+        let inner = self.inner.clone();
+        inner.clone().send(block).map(move |_| inner.stats(0))
+    }
+
+    /// Returns a future that flushes the buffer whenever its oldest row gets
+    /// older than `max_age`, to be spawned next to the inserter.
+    ///
+    /// The future completes after the inserter is closed or dropped. Failed
+    /// flushes are logged and counted, they don't stop it.
+    pub fn flush_periodically(&self) -> impl Future<Item = (), Error = ()> {
+        let inner = Arc::downgrade(&self.inner);
+        let max_age = self.inner.lock().max_age;
+        future::loop_fn((inner, max_age), |(inner, wait)| {
+            Delay::new(Instant::now() + wait)
+                .map_err(|err| error!("Inserter timer failed: {}", err))
+                .and_then(move |_| {
+                    let strong = match Weak::upgrade(&inner) {
+                        Some(strong) => strong,
+                        None => return future::Either::A(future::ok(future::Loop::Break(()))),
+                    };
+
+                    let (block, wait) = {
+                        let mut state = strong.lock();
+                        if state.closed {
+                            return future::Either::A(future::ok(future::Loop::Break(())));
+                        }
+                        match state.first_row_at.map(|at| at.elapsed()) {
+                            Some(age) if age >= state.max_age => (state.take(), state.max_age),
+                            Some(age) => (Block::new(), state.max_age - age),
+                            None => (Block::new(), state.max_age),
+                        }
+                    };
+
+                    future::Either::B(strong.send(block).then(move |res| {
+                        if let Err(err) = res {
+                            warn!("Periodic flush of buffered rows failed: {}", err);
+                        }
+                        Ok(future::Loop::Continue((inner, wait)))
+                    }))
+                })
+        })
+    }
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn stats(&self, rows_buffered: u64) -> InserterStats {
+        InserterStats {
+            rows_buffered,
+            rows_flushed: self.rows_flushed.load(Ordering::Relaxed),
+            rows_failed: self.rows_failed.load(Ordering::Relaxed),
+            flushes: self.flushes.load(Ordering::Relaxed),
+        }
+    }
+
+    fn send(self: Arc<Self>, block: Block) -> impl Future<Item = (), Error = Error> {
+        if block.is_empty() {
+            return Either::Left(future::ok(()));
+        }
+
+        let rows = block.row_count() as u64;
+        let schema = self.lock().schema.clone();
+        let block = match schema {
+            Some(schema) => match block.cast_to(&schema, true) {
+                Ok(block) => block,
+                Err(err) => {
+                    self.rows_failed.fetch_add(rows, Ordering::Relaxed);
+                    return Either::Left(future::err(err));
+                }
+            },
+            None => block,
+        };
+
+        let table = self.table.clone();
+        Either::Right(
+            self.pool
+                .get_handle()
+                .and_then(move |c| c.insert(table, block))
+                .then(move |res| match res {
+                    Ok(_) => {
+                        self.rows_flushed.fetch_add(rows, Ordering::Relaxed);
+                        self.flushes.fetch_add(1, Ordering::Relaxed);
+                        Ok(())
+                    }
+                    Err(err) => {
+                        self.rows_failed.fetch_add(rows, Ordering::Relaxed);
+                        Err(err)
+                    }
+                }),
+        )
+    }
+}
+
+impl State {
+    fn is_full(&self) -> bool {
+        self.block.row_count() >= self.max_rows
+            || self.bytes >= self.max_bytes
+            || self
+                .first_row_at
+                .is_some_and(|at| at.elapsed() >= self.max_age)
+    }
+
+    fn take(&mut self) -> Block {
+        self.first_row_at = None;
+        self.bytes = 0;
+        mem::replace(&mut self.block, Block::new())
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let (block, flush_on_drop) = {
+            let mut state = self.lock();
+            (
+                mem::replace(&mut state.block, Block::new()),
+                state.flush_on_drop,
+            )
+        };
+        if block.is_empty() {
+            return;
+        }
+
+        let rows = block.row_count();
+        if !flush_on_drop {
+            warn!("Dropping {} buffered rows for `{}`.", rows, self.table);
+            return;
+        }
+
+        let table = self.table.clone();
+        let fut = self
+            .pool
+            .get_handle()
+            .and_then(move |c| c.insert(table, block))
+            .map(|_| ())
+            .map_err(move |err| warn!("Flushing {} buffered rows on drop failed: {}", rows, err));
+
+        if DefaultExecutor::current().spawn(Box::new(fut)).is_err() {
+            warn!(
+                "Dropping {} buffered rows for `{}`, no executor to flush them.",
+                rows, self.table
+            );
+        }
+    }
+}
+
+fn row_size(block: &Block, row: usize) -> usize {
+    block
+        .columns()
+        .iter()
+        .map(|column| value_size(&column.at(row)))
+        .sum()
+}
+
+fn value_size(value: &ValueRef) -> usize {
+    match value {
+        ValueRef::UInt8(_) | ValueRef::Int8(_) | ValueRef::Enum8(..) => 1,
+        ValueRef::UInt16(_) | ValueRef::Int16(_) | ValueRef::Enum16(..) | ValueRef::Date(..) => 2,
+        ValueRef::UInt32(_)
+        | ValueRef::Int32(_)
+        | ValueRef::Float32(_)
+        | ValueRef::DateTime(..)
+        | ValueRef::Ipv4(_) => 4,
+        ValueRef::Ipv6(_) | ValueRef::Uuid(_) => 16,
+        ValueRef::String(s) => s.len() + 1,
+        ValueRef::Nullable(column::Either::Left(_)) => 1,
+        ValueRef::Nullable(column::Either::Right(inner)) => 1 + value_size(inner),
+        ValueRef::Array(_, values) => 8 + values.iter().map(value_size).sum::<usize>(),
+        ValueRef::Tuple(_, values) => values.iter().map(value_size).sum(),
+        _ => 8,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::row;
+
+    fn inserter() -> BufferedInserter {
+        BufferedInserter::new(Pool::new("tcp://localhost:9000"), "t")
+    }
+
+    #[test]
+    fn test_value_size() {
+        let block = Block::new()
+            .column("a", vec![1_u8])
+            .column("b", vec!["foo"])
+            .column("c", vec![Some(2_u32)])
+            .column("d", vec![vec![1_u64, 2, 3]]);
+        assert_eq!(row_size(&block, 0), 1 + 4 + 5 + 32);
+    }
+
+    #[test]
+    fn test_push_row_flushes_at_max_rows() {
+        let inserter = inserter().max_rows(3).flush_on_drop(false);
+        for i in 0..2_u32 {
+            inserter.push_row(row! { i: i }).wait().unwrap();
+        }
+        assert_eq!(inserter.stats().rows_buffered, 2);
+
+        let mut state = inserter.inner.lock();
+        state.block.push(row! { i: 2_u32 }).unwrap();
+        assert!(state.is_full());
+        assert_eq!(state.take().row_count(), 3);
+        assert_eq!(state.block.row_count(), 0);
+    }
+
+    #[test]
+    fn test_is_full_at_max_bytes() {
+        let inserter = inserter().max_bytes(10).flush_on_drop(false);
+        inserter.push_row(row! { s: "12345678" }).wait().unwrap();
+        assert!(!inserter.inner.lock().is_full());
+
+        let mut state = inserter.inner.lock();
+        state.bytes += 9;
+        assert!(state.is_full());
+    }
+
+    #[test]
+    fn test_push_row_after_close() {
+        let inserter = inserter();
+        inserter.inner.lock().closed = true;
+        match inserter.push_row(row! { i: 1_u32 }).wait() {
+            Err(Error::Driver(DriverError::InserterClosed)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+}
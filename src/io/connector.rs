@@ -0,0 +1,59 @@
+use std::{fmt, io, sync::Arc};
+
+use futures::Future;
+use tokio::io::{AsyncRead, AsyncWrite};
+use url::Url;
+
+/// Byte stream the native protocol is spoken over, e.g. an in-process
+/// tunnel or a scripted test double. Any `AsyncRead + AsyncWrite` stream
+/// that can be sent between threads is one.
+///
+/// As for sockets, reads and writes that can't make progress must return
+/// `io::ErrorKind::WouldBlock` and wake the current task once they can.
+pub trait Transport: AsyncRead + AsyncWrite + Send + Sync {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Sync> Transport for T {}
+
+/// Future of a connection opened by a `Connector`.
+pub type ConnectFuture = Box<dyn Future<Item = Box<dyn Transport>, Error = io::Error> + Send>;
+
+/// Opens the connections of a client instead of TCP (or TLS) sockets, see
+/// `Options::with_connector`.
+///
+/// ```
+/// # use std::{io, sync::Arc};
+/// # use futures::future;
+/// # use url::Url;
+/// # use clickhouse_rs::{ConnectFuture, Connector, types::Options};
+/// struct Tunnel;
+///
+/// impl Connector for Tunnel {
+///     fn connect(&self, addr: &Url) -> ConnectFuture {
+///         let err = io::Error::new(io::ErrorKind::NotConnected, format!("no tunnel to {}", addr));
+///         Box::new(future::err(err))
+///     }
+/// }
+///
+/// let options = Options::default().with_connector(Arc::new(Tunnel));
+/// ```
+pub trait Connector: Send + Sync {
+    /// Opens a connection to `addr`, one of the hosts of the options. The
+    /// handshake is done by the client on the returned stream.
+    fn connect(&self, addr: &Url) -> ConnectFuture;
+}
+
+/// Shared connector of `Options`.
+#[derive(Clone)]
+pub(crate) struct ConnectorHandle(pub(crate) Arc<dyn Connector>);
+
+impl fmt::Debug for ConnectorHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Connector")
+    }
+}
+
+impl PartialEq for ConnectorHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::as_ptr(&self.0) as *const u8 == Arc::as_ptr(&other.0) as *const u8
+    }
+}
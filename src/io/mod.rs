@@ -1,9 +1,13 @@
+pub use self::connector::{ConnectFuture, Connector, Transport};
 pub(crate) use self::{
     box_future::{BoxFuture, BoxStream},
+    connector::ConnectorHandle,
     stream::Stream,
     transport::ClickhouseTransport,
 };
 
 mod box_future;
+mod connector;
 pub(crate) mod stream;
+pub(crate) mod timeout_stream;
 pub(crate) mod transport;
@@ -7,6 +7,8 @@ use tokio::net::TcpStream;
 #[cfg(feature = "tls")]
 use tokio_tls::TlsStream;
 
+use crate::io::Transport;
+
 #[cfg(feature = "tls")]
 type SecureTcpStream = TlsStream<TcpStream>;
 
@@ -14,6 +16,7 @@ pub(crate) enum Stream {
     Plain(TcpStream),
     #[cfg(feature = "tls")]
     Secure(SecureTcpStream),
+    Custom(Box<dyn Transport>),
 }
 
 impl From<TcpStream> for Stream {
@@ -29,12 +32,20 @@ impl From<SecureTcpStream> for Stream {
     }
 }
 
+impl From<Box<dyn Transport>> for Stream {
+    fn from(stream: Box<dyn Transport>) -> Stream {
+        Self::Custom(stream)
+    }
+}
+
+// Socket options don't apply to custom transports and are ignored.
 impl Stream {
     pub(crate) fn set_nodelay(&mut self, nodelay: bool) -> io::Result<()> {
         match *self {
             Self::Plain(ref mut stream) => stream.set_nodelay(nodelay),
             #[cfg(feature = "tls")]
             Self::Secure(ref mut stream) => stream.get_mut().get_mut().set_nodelay(nodelay),
+            Self::Custom(_) => Ok(()),
         }.map_err(|err| io::Error::new(err.kind(), format!("set_nodelay error: {}", err)))
     }
 
@@ -43,8 +54,34 @@ impl Stream {
             Self::Plain(ref mut stream) => stream.set_keepalive(keepalive),
             #[cfg(feature = "tls")]
             Self::Secure(ref mut stream) => stream.get_mut().get_mut().set_keepalive(keepalive),
+            Self::Custom(_) => Ok(()),
         }.map_err(|err| io::Error::new(err.kind(), format!("set_keepalive error: {}", err)))
     }
+
+    pub(crate) fn set_send_buffer_size(&mut self, size: usize) -> io::Result<()> {
+        match self.tcp() {
+            Some(stream) => stream.set_send_buffer_size(size),
+            None => Ok(()),
+        }
+        .map_err(|err| io::Error::new(err.kind(), format!("set_send_buffer_size error: {}", err)))
+    }
+
+    pub(crate) fn set_recv_buffer_size(&mut self, size: usize) -> io::Result<()> {
+        match self.tcp() {
+            Some(stream) => stream.set_recv_buffer_size(size),
+            None => Ok(()),
+        }
+        .map_err(|err| io::Error::new(err.kind(), format!("set_recv_buffer_size error: {}", err)))
+    }
+
+    fn tcp(&self) -> Option<&TcpStream> {
+        match *self {
+            Self::Plain(ref stream) => Some(stream),
+            #[cfg(feature = "tls")]
+            Self::Secure(ref stream) => Some(stream.get_ref().get_ref()),
+            Self::Custom(_) => None,
+        }
+    }
 }
 
 impl io::Read for Stream {
@@ -53,6 +90,7 @@ impl io::Read for Stream {
             Self::Plain(ref mut stream) => stream.read(buf),
             #[cfg(feature = "tls")]
             Self::Secure(ref mut stream) => stream.read(buf),
+            Self::Custom(ref mut stream) => stream.read(buf),
         }
     }
 }
@@ -63,6 +101,7 @@ impl io::Write for Stream {
             Self::Plain(ref mut stream) => stream.write(buf),
             #[cfg(feature = "tls")]
             Self::Secure(ref mut stream) => stream.write(buf),
+            Self::Custom(ref mut stream) => stream.write(buf),
         }
     }
 
@@ -71,6 +110,52 @@ impl io::Write for Stream {
             Self::Plain(ref mut stream) => stream.flush(),
             #[cfg(feature = "tls")]
             Self::Secure(ref mut stream) => stream.flush(),
+            Self::Custom(ref mut stream) => stream.flush(),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::net;
+
+    use tokio::reactor::Handle;
+
+    use super::*;
+
+    fn connected_stream() -> (Stream, net::TcpListener) {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let stream = TcpStream::from_std(stream, &Handle::default()).unwrap();
+        (stream.into(), listener)
+    }
+
+    #[test]
+    fn test_socket_options() {
+        let (mut stream, _listener) = connected_stream();
+
+        stream.set_nodelay(true).unwrap();
+        stream.set_keepalive(Some(Duration::from_secs(60))).unwrap();
+        assert!(stream.tcp().unwrap().nodelay().unwrap());
+        assert_eq!(
+            stream.tcp().unwrap().keepalive().unwrap(),
+            Some(Duration::from_secs(60))
+        );
+
+        stream.set_nodelay(false).unwrap();
+        stream.set_keepalive(None).unwrap();
+        assert!(!stream.tcp().unwrap().nodelay().unwrap());
+        assert_eq!(stream.tcp().unwrap().keepalive().unwrap(), None);
+    }
+
+    #[test]
+    fn test_buffer_sizes() {
+        let (mut stream, _listener) = connected_stream();
+
+        // The sizes are hints, the system may double or cap them.
+        stream.set_send_buffer_size(64 * 1024).unwrap();
+        stream.set_recv_buffer_size(32 * 1024).unwrap();
+        assert!(stream.tcp().unwrap().send_buffer_size().unwrap() >= 32 * 1024);
+        assert!(stream.tcp().unwrap().recv_buffer_size().unwrap() >= 16 * 1024);
+    }
+}
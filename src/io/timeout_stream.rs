@@ -0,0 +1,153 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use tokio::prelude::*;
+use tokio_timer::Delay;
+
+/// Socket wrapper that fails reads and writes which make no progress for
+/// longer than the configured timeouts.
+///
+/// The deadline is armed when an operation first returns `WouldBlock`,
+/// moved on every successful operation, and the task is woken once it
+/// elapses. An expired deadline is reported as `io::ErrorKind::TimedOut`.
+pub(crate) struct TimeoutStream<S> {
+    inner: S,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    read_delay: Option<Delay>,
+    write_delay: Option<Delay>,
+}
+
+impl<S> TimeoutStream<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        Self {
+            inner,
+            read_timeout: None,
+            write_timeout: None,
+            read_delay: None,
+            write_delay: None,
+        }
+    }
+
+    pub(crate) fn set_timeouts(&mut self, read: Option<Duration>, write: Option<Duration>) {
+        self.read_timeout = read;
+        self.write_timeout = write;
+        self.reset();
+    }
+
+    /// Forgets the deadlines of previous operations, e.g. before a new
+    /// request is sent on a connection that was idle.
+    pub(crate) fn reset(&mut self) {
+        self.read_delay = None;
+        self.write_delay = None;
+    }
+}
+
+fn poll_deadline(
+    delay: &mut Option<Delay>,
+    timeout: Option<Duration>,
+    operation: &str,
+) -> io::Result<()> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return Ok(()),
+    };
+
+    let delay = delay.get_or_insert_with(|| Delay::new(Instant::now() + timeout));
+    match delay.poll() {
+        Ok(Async::Ready(())) => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("socket {} timed out after {:?}", operation, timeout),
+        )),
+        Ok(Async::NotReady) => Ok(()),
+        Err(err) => Err(io::Error::other(err)),
+    }
+}
+
+impl<S: io::Read> io::Read for TimeoutStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.inner.read(buf) {
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                poll_deadline(&mut self.read_delay, self.read_timeout, "read")?;
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+            result => {
+                self.read_delay = None;
+                result
+            }
+        }
+    }
+}
+
+impl<S: io::Write> io::Write for TimeoutStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.inner.write(buf) {
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                poll_deadline(&mut self.write_delay, self.write_timeout, "write")?;
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+            result => {
+                self.write_delay = None;
+                result
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use super::*;
+
+    /// A socket that never receives anything.
+    struct Stalled;
+
+    impl io::Read for Stalled {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+    }
+
+    fn read_once<S: io::Read + Send + 'static>(stream: TimeoutStream<S>) -> io::Result<usize> {
+        let mut stream = stream;
+        let done = future::poll_fn(move || {
+            let mut buf = [0_u8; 16];
+            match stream.read(&mut buf) {
+                Ok(n) => Ok(Async::Ready(n)),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => Ok(Async::NotReady),
+                Err(err) => Err(err),
+            }
+        });
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(done)
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        let mut stream = TimeoutStream::new(Stalled);
+        stream.set_timeouts(Some(Duration::from_millis(100)), None);
+
+        let start = Instant::now();
+        let err = read_once(stream).unwrap_err();
+        let elapsed = start.elapsed();
+
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(elapsed >= Duration::from_millis(100));
+        assert!(elapsed < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_read_progress() {
+        let mut stream = TimeoutStream::new(io::Cursor::new(vec![1_u8, 2, 3]));
+        stream.set_timeouts(Some(Duration::from_millis(100)), None);
+        assert_eq!(read_once(stream).unwrap(), 3);
+    }
+}
@@ -7,25 +7,34 @@ use std::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    time::{Duration, Instant},
 };
 
 use chrono_tz::Tz;
 use futures::{Async, Poll, Stream};
-use tokio::prelude::*;
+use tokio::{
+    executor::{DefaultExecutor, Executor},
+    prelude::*,
+};
+use url::Url;
 
 use crate::{
-    binary::Parser,
+    binary::protocol,
+    client_info,
     errors::{DriverError, Error},
-    io::{BoxFuture, Stream as InnerStream},
+    io::{timeout_stream::TimeoutStream, BoxFuture, Stream as InnerStream},
     pool::{Inner, PoolBinding},
-    types::{Block, Cmd, Context, Packet},
+    types::{
+        Block, CloseReason, Cmd, Context, Packet, PacketReader, PoolEvents, Query, QueryCounts,
+        QueryLog, QueryStats, RecycledBlock,
+    },
     ClientHandle, Pool,
 };
 
 /// Line transport
 pub(crate) struct ClickhouseTransport {
     // Inner socket
-    inner: InnerStream,
+    inner: TimeoutStream<InnerStream>,
     // Set to true when inner.read returns Ok(0);
     done: bool,
     // Buffered read data
@@ -36,10 +45,51 @@ pub(crate) struct ClickhouseTransport {
     wr: io::Cursor<Vec<u8>>,
     // Queued commands
     cmds: VecDeque<Cmd>,
-    // Server time zone
+    // Time zone of Date/DateTime columns, the server one unless configured
     timezone: Option<Tz>,
+    // Time zone configured in the options
+    default_timezone: Option<Tz>,
+    // Time zone requested by the current query
+    tz_override: Option<Tz>,
+    // The last data block, its storage is reused by the next one if the
+    // current query asked for it
+    recycled: Option<RecycledBlock>,
+    // Whether the current query decodes columns on first access only
+    lazy_columns: bool,
+    // Rows per data block the current insert is split into, if it overrides
+    // the options
+    insert_block_size: Option<usize>,
+    // Protocol revision agreed with the server
+    revision: u64,
     compress: bool,
     status: Arc<TransportStatus>,
+    // When the connection was established
+    created_at: Instant,
+    // When the connection was last handed back to the pool
+    idle_since: Instant,
+    // Set when the socket failed or the stream is out of sync, the
+    // connection must not be reused
+    broken: bool,
+    // Hooks called on connection and query events
+    events: Option<Arc<dyn PoolEvents>>,
+    // Address of the server, reported to the hooks
+    addr: Option<Url>,
+    // The query waiting for the end of its response
+    query: Option<QueryTrace>,
+    // Why the connection is closed unless it is broken
+    close_reason: CloseReason,
+}
+
+struct QueryTrace {
+    id: String,
+    sql: String,
+    started_at: Instant,
+    rows_read: u64,
+    bytes_read: u64,
+    rows_written: u64,
+    bytes_written: u64,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
 }
 
 enum PacketStreamState {
@@ -58,26 +108,216 @@ pub(crate) struct PacketStream {
     inner: Option<ClickhouseTransport>,
     state: PacketStreamState,
     read_block: bool,
+    // Client parts used to return the connection if the stream is dropped early
+    cancel: Option<(Context, PoolBinding)>,
+    // Set if the query timed out, the connection is closed on drop then
+    timed_out: Option<TimeoutFlag>,
+}
+
+/// Shared by a query future and its packet stream, set when the query timed
+/// out. The connection of a timed out query is closed instead of being
+/// drained and given back to the pool.
+#[derive(Clone, Default)]
+pub(crate) struct TimeoutFlag(Arc<AtomicBool>);
+
+impl TimeoutFlag {
+    pub(crate) fn set(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    fn is_set(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
 }
 
 impl ClickhouseTransport {
     pub fn new(inner: InnerStream, compress: bool, pool: Option<Pool>) -> Self {
         ClickhouseTransport {
-            inner,
+            inner: TimeoutStream::new(inner),
             done: false,
             rd: vec![],
             buf_is_incomplete: false,
             wr: io::Cursor::new(vec![]),
             cmds: VecDeque::new(),
             timezone: None,
+            default_timezone: None,
+            tz_override: None,
+            recycled: None,
+            lazy_columns: false,
+            insert_block_size: None,
+            revision: client_info::CLICK_HOUSE_REVISION,
             compress,
             status: Arc::new(TransportStatus::new(pool)),
+            created_at: Instant::now(),
+            idle_since: Instant::now(),
+            broken: false,
+            events: None,
+            addr: None,
+            query: None,
+            close_reason: CloseReason::Released,
+        }
+    }
+
+    pub(crate) fn set_events(&mut self, events: Option<Arc<dyn PoolEvents>>, addr: Url) {
+        self.events = events;
+        self.addr = Some(addr);
+    }
+
+    /// Sets the reason reported when the connection is closed.
+    pub(crate) fn set_close_reason(&mut self, reason: CloseReason) {
+        self.close_reason = reason;
+    }
+
+    /// Sets timeouts of single socket reads and writes.
+    pub(crate) fn set_timeouts(&mut self, read: Option<Duration>, write: Option<Duration>) {
+        self.inner.set_timeouts(read, write);
+    }
+
+    /// Sets the time zone of `Date` and `DateTime` columns without one,
+    /// instead of the server time zone.
+    pub(crate) fn set_default_timezone(&mut self, timezone: Option<Tz>) {
+        self.default_timezone = timezone;
+    }
+
+    /// Whether the socket failed or the stream is out of sync and the
+    /// connection must be discarded.
+    pub(crate) fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    fn io_error(&mut self, err: io::Error) -> Error {
+        let err = if err.kind() == io::ErrorKind::TimedOut {
+            warn!("[transport] {}", err);
+            Error::Driver(DriverError::Timeout)
+        } else {
+            err.into()
+        };
+        self.fail(&err);
+        err
+    }
+
+    /// Ends the running query with `err` and marks the connection broken
+    /// if the error leaves it unusable.
+    fn fail(&mut self, err: &Error) {
+        if err.is_fatal_for_connection() {
+            self.broken = true;
+        }
+        self.finish_query(Some(err));
+    }
+
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn start_query(&mut self, query: &Query, context: &Context) {
+        let (id, sql) = (query.get_id(), query.get_sql());
+        if let Some(events) = &self.events {
+            events.query_started(id);
+        } else if !cfg!(feature = "tracing") {
+            return;
+        }
+
+        self.query = Some(QueryTrace {
+            id: id.to_string(),
+            sql: sql.to_string(),
+            started_at: Instant::now(),
+            rows_read: 0,
+            bytes_read: 0,
+            rows_written: 0,
+            bytes_written: 0,
+            #[cfg(feature = "tracing")]
+            span: {
+                let sql_len = context
+                    .options
+                    .get()
+                    .ok()
+                    .and_then(|options| options.trace_sql);
+                crate::trace::query_span(id, context.addr.as_ref(), sql, sql_len)
+            },
+        });
+    }
+
+    fn trace_packet(&mut self, packet: &Packet<()>) {
+        match packet {
+            Packet::Progress(progress) => {
+                if let Some(query) = &mut self.query {
+                    query.rows_read += progress.rows;
+                    query.bytes_read += progress.bytes;
+                    query.rows_written += progress.written_rows;
+                    query.bytes_written += progress.written_bytes;
+                }
+            }
+            Packet::Exception(exception, _) => {
+                self.finish_query(Some(&Error::Server(exception.clone())))
+            }
+            Packet::Eof(_) => self.finish_query(None),
+            _ => {}
+        }
+    }
+
+    fn finish_query(&mut self, error: Option<&Error>) {
+        self.recycled = None;
+        let query = match self.query.take() {
+            Some(query) => query,
+            None => return,
+        };
+        let counts = QueryCounts {
+            rows_read: query.rows_read,
+            bytes_read: query.bytes_read,
+            rows_written: query.rows_written,
+            bytes_written: query.bytes_written,
+        };
+        #[cfg(feature = "tracing")]
+        crate::trace::finish_query(&query.span, counts, error);
+
+        if let Some(events) = &self.events {
+            let duration = query.started_at.elapsed();
+            events.query_finished(&query.id, counts, duration, error);
+            events.query_logged(&QueryLog {
+                sql: &query.sql,
+                query_id: &query.id,
+                duration,
+                rows_read: query.rows_read,
+                bytes_read: query.bytes_read,
+                rows_written: query.rows_written,
+                bytes_written: query.bytes_written,
+                error,
+            });
         }
     }
 
     pub(crate) fn set_inside(&self, value: bool) {
         self.status.inside.store(value, Ordering::Release);
     }
+
+    /// Time since the connection was established.
+    pub(crate) fn age(&self) -> Duration {
+        self.created_at.elapsed()
+    }
+
+    /// Time since the connection was last handed back to the pool.
+    pub(crate) fn idle_time(&self) -> Duration {
+        self.idle_since.elapsed()
+    }
+
+    pub(crate) fn set_idle(&mut self) {
+        self.idle_since = Instant::now();
+    }
+}
+
+impl Drop for ClickhouseTransport {
+    fn drop(&mut self) {
+        if self.query.is_some() {
+            let err = Error::Io(io::ErrorKind::ConnectionAborted.into());
+            self.finish_query(Some(&err));
+        }
+
+        let reason = if self.broken {
+            CloseReason::Broken
+        } else {
+            self.close_reason
+        };
+        if let Some(events) = &self.events {
+            events.connection_closed(self.addr.as_ref(), reason);
+        }
+    }
 }
 
 impl Drop for TransportStatus {
@@ -157,13 +397,21 @@ impl ClickhouseTransport {
         let ret = {
             let mut cursor = Cursor::new(&self.rd);
             let res = {
-                let mut parser = Parser::new(&mut cursor, self.timezone, self.compress);
-                parser.parse_packet()
+                let mut reader = PacketReader::new(&mut cursor)
+                    .with_timezones(self.timezone, self.tz_override)
+                    .compression(self.compress)
+                    .revision(self.revision)
+                    .with_recycled(self.recycled.take())
+                    .lazy_columns(self.lazy_columns);
+                let res = reader.read_packet();
+                self.recycled = reader.into_recycled();
+                res
             };
             pos = cursor.position() as usize;
 
             if let Ok(Packet::Hello(_, ref packet)) = res {
-                self.timezone = Some(packet.timezone);
+                self.timezone = Some(self.default_timezone.unwrap_or(packet.timezone));
+                self.revision = packet.protocol_revision();
             }
 
             match res {
@@ -172,6 +420,17 @@ impl ClickhouseTransport {
             }
         };
 
+        match &ret {
+            Ok(Async::Ready(Some(packet))) => self.trace_packet(packet),
+            Err(err) => {
+                // Whatever the error, the rest of the packet is left unread
+                // and the next one cannot be found.
+                self.broken = true;
+                self.fail(err);
+            }
+            _ => {}
+        }
+
         match ret {
             Ok(Async::NotReady) => (),
             _ => {
@@ -197,15 +456,37 @@ impl ClickhouseTransport {
                         return Ok(Async::Ready(()));
                     }
                     Some(cmd) => {
-                        let bytes = cmd.get_packed_command()?;
-                        self.wr = Cursor::new(bytes)
+                        if let Cmd::SendQuery(ref query, ref context) = cmd {
+                            self.start_query(query, context);
+                            self.tz_override = query.get_timezone();
+                            self.lazy_columns = query.get_lazy_columns();
+                            self.insert_block_size = query.get_max_insert_block_size();
+                            self.recycled = if query.get_reuse_blocks() {
+                                Some(RecycledBlock::default())
+                            } else {
+                                None
+                            };
+                            if query.get_logs_level().is_some()
+                                && self.revision < protocol::DBMS_MIN_REVISION_WITH_SERVER_LOGS
+                            {
+                                warn!(
+                                    "server logs are not sent at protocol revision {}",
+                                    self.revision
+                                );
+                            }
+                        }
+                        let bytes = cmd.get_packed_command(self.insert_block_size)?;
+                        self.wr = Cursor::new(bytes);
+                        self.inner.reset();
                     }
                 }
             }
 
             // Try to write the remaining buffer
-            if !self.wr_flush()? {
-                return Ok(Async::NotReady);
+            match self.wr_flush() {
+                Ok(true) => {}
+                Ok(false) => return Ok(Async::NotReady),
+                Err(err) => return Err(self.io_error(err)),
             }
         }
     }
@@ -239,7 +520,7 @@ impl Stream for ClickhouseTransport {
                         break;
                     }
 
-                    return Err(e.into());
+                    return Err(self.io_error(e));
                 }
             }
         }
@@ -254,28 +535,56 @@ impl Stream for ClickhouseTransport {
 }
 
 impl PacketStream {
+    /// Makes the stream cancel the running query when it is dropped before
+    /// the end of the response; the connection is then drained in the
+    /// background and handed back to the pool.
+    pub(crate) fn cancel_on_drop(mut self, context: Context, pool: PoolBinding) -> Self {
+        self.cancel = Some((context, pool));
+        self
+    }
+
+    /// Makes the stream close the connection instead of draining it when it
+    /// is dropped after `timed_out` was set.
+    pub(crate) fn close_on_timeout(mut self, timed_out: TimeoutFlag) -> Self {
+        self.timed_out = Some(timed_out);
+        self
+    }
+
+    /// Closes the connection on drop, see `close_on_timeout`.
+    pub(crate) fn mark_timed_out(&mut self) {
+        self.timed_out
+            .get_or_insert_with(TimeoutFlag::default)
+            .set();
+    }
+
     pub(crate) fn read_block(
         mut self,
         context: Context,
         pool: PoolBinding,
-    ) -> BoxFuture<(ClientHandle, Option<Block>)> {
+    ) -> BoxFuture<(ClientHandle, Option<Block>, QueryStats)> {
         self.read_block = true;
 
+        let acc = (None, None, QueryStats::default());
         Box::new(
-            self.fold((None, None), move |(c, b), package| match package {
+            self.fold(acc, move |(c, b, mut stats), package| match package {
                 Packet::Eof(inner) => {
                     let client = ClientHandle {
                         inner: Some(inner),
                         context: context.clone(),
                         pool: pool.clone(),
                     };
-                    future::ok::<_, Error>((Some(client), b))
+                    future::ok::<_, Error>((Some(client), b, stats))
+                }
+                Packet::Block(block) => future::ok::<_, Error>((c, Some(block), stats)),
+                Packet::Progress(_) | Packet::ProfileInfo(_) => {
+                    stats.update(&package);
+                    future::ok::<_, Error>((c, b, stats))
                 }
-                Packet::Block(block) => future::ok::<_, Error>((c, Some(block))),
+                Packet::Log(_) => future::ok::<_, Error>((c, b, stats)),
                 Packet::Exception(e, _) => future::err(Error::Server(e)),
                 _ => future::err(Error::Driver(DriverError::UnexpectedPacket)),
             })
-            .map(|(c, b)| (c.unwrap(), b)),
+            .map(|(c, b, stats)| (c.unwrap(), b, stats)),
         )
     }
 }
@@ -334,13 +643,75 @@ impl Stream for PacketStream {
 }
 
 impl ClickhouseTransport {
+    /// Writes `req` to the socket without waiting for any response, e.g. a
+    /// data block in the middle of an insert. The future resolves once the
+    /// whole command is handed over to the socket.
+    pub(crate) fn send_only(mut self, req: Cmd) -> impl Future<Item = Self, Error = Error> {
+        self.cmds.push_back(req);
+        let mut transport = Some(self);
+        future::poll_fn(move || {
+            try_ready!(transport.as_mut().unwrap().send());
+            Ok(Async::Ready(transport.take().unwrap()))
+        })
+    }
+
     pub(crate) fn call(mut self, req: Cmd) -> PacketStream {
         self.cmds.push_back(req);
         PacketStream {
             inner: Some(self),
             state: PacketStreamState::Ask,
             read_block: false,
+            cancel: None,
+            timed_out: None,
+        }
+    }
+}
+
+impl Drop for PacketStream {
+    fn drop(&mut self) {
+        let (mut transport, (context, pool)) = match (self.inner.take(), self.cancel.take()) {
+            (Some(transport), Some(rest)) if !transport.is_broken() => (transport, rest),
+            _ => return,
+        };
+
+        if self.timed_out.as_ref().is_some_and(TimeoutFlag::is_set) {
+            debug!("[cancel] query timed out, closing the connection");
+            transport.set_close_reason(CloseReason::Broken);
+            return;
         }
+
+        let timeout = match context.options.get() {
+            Ok(options) => options.ping_timeout,
+            Err(_) => return,
+        };
+
+        debug!("[cancel] query was dropped before completion");
+        let drain = transport
+            .call(Cmd::Cancel)
+            .fold(None, |acc, packet| -> Result<_, Error> {
+                Ok(match packet {
+                    Packet::Eof(inner) => Some(inner),
+                    Packet::Exception(_, inner) => inner,
+                    _ => acc,
+                })
+            })
+            .timeout(timeout)
+            .map(move |transport| {
+                // Dropping the handle gives the connection back to the pool.
+                if let Some(transport) = transport {
+                    let mut client = ClientHandle {
+                        inner: Some(transport),
+                        context,
+                        pool,
+                    };
+                    if !client.pool.is_attached() && client.pool.is_some() {
+                        client.pool.attach();
+                    }
+                }
+            })
+            .map_err(|err| warn!("[cancel] unable to drain connection: {}", err));
+
+        let _ = DefaultExecutor::current().spawn(Box::new(drain));
     }
 }
 
@@ -350,3 +721,102 @@ fn is_block<T>(packet: &Option<Packet<T>>) -> bool {
         _ => false,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{io::Write, net, thread};
+
+    use tokio::{net::TcpStream, reactor::Handle, runtime::current_thread};
+
+    use super::*;
+    use crate::{binary::Encoder, errors::ErrorKind};
+
+    /// Connects a transport to a socket that sends `response` and closes.
+    fn transport(response: Vec<u8>) -> ClickhouseTransport {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            socket.write_all(&response).unwrap();
+        });
+
+        let stream = net::TcpStream::connect(addr).unwrap();
+        let stream = TcpStream::from_std(stream, &Handle::default()).unwrap();
+        let mut transport = ClickhouseTransport::new(stream.into(), true, None);
+        transport.timezone = Some(Tz::UTC);
+        transport
+    }
+
+    fn next_packet(transport: &mut ClickhouseTransport) -> Result<Option<Packet<()>>, Error> {
+        current_thread::block_on_all(future::poll_fn(|| transport.poll()))
+    }
+
+    #[test]
+    fn test_server_exception_keeps_connection() {
+        let mut encoder = Encoder::new();
+        encoder.uvarint(protocol::SERVER_EXCEPTION);
+        encoder.write(60_u32);
+        encoder.string("DB::Exception");
+        encoder.string("Table default.t doesn't exist.");
+        encoder.string("");
+        encoder.write(0_u8);
+
+        let mut transport = transport(encoder.get_buffer());
+        match next_packet(&mut transport) {
+            Ok(Some(Packet::Exception(err, _))) => {
+                let err = Error::Server(err);
+                assert_eq!(err.kind(), ErrorKind::Server);
+                assert!(!err.is_fatal_for_connection());
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(!transport.is_broken());
+    }
+
+    #[test]
+    fn test_unknown_packet_breaks_connection() {
+        let mut transport = transport(vec![0x7f]);
+        let err = next_packet(&mut transport).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Protocol);
+        assert!(err.is_fatal_for_connection());
+        assert!(transport.is_broken());
+    }
+
+    #[test]
+    fn test_corrupted_data_breaks_connection() {
+        let mut encoder = Encoder::new();
+        encoder.uvarint(protocol::SERVER_DATA);
+        encoder.string("");
+        encoder.write(0_u64); // checksum
+        encoder.write(0_u64);
+        encoder.write(0x82_u8); // LZ4
+        encoder.write(13_u32);
+        encoder.write(4_u32);
+        encoder.write(0_u32);
+
+        let mut transport = transport(encoder.get_buffer());
+        match next_packet(&mut transport) {
+            Err(err @ Error::Driver(DriverError::CorruptedData(_))) => {
+                assert_eq!(err.kind(), ErrorKind::Protocol);
+                assert!(!err.is_retryable());
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(transport.is_broken());
+    }
+
+    #[test]
+    fn test_unparsable_block_breaks_connection() {
+        let mut encoder = Encoder::new();
+        encoder.uvarint(protocol::SERVER_DATA);
+        encoder.string("");
+        encoder.write(0_u64);
+
+        let mut transport = transport(encoder.get_buffer());
+        transport.compress = false;
+        transport.timezone = None;
+        let err = next_packet(&mut transport).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Protocol);
+        assert!(transport.is_broken());
+    }
+}
@@ -1,15 +1,110 @@
+use std::time::Instant;
+
 use tokio::prelude::*;
+use tokio_timer::Delay;
 
-use crate::{errors::Error, pool::Pool, ClientHandle};
+use crate::{
+    errors::{Error, Result},
+    io::BoxFuture,
+    pool::Pool,
+    ClientHandle,
+};
 
 /// Future that resolves to a `ClientHandle`.
 pub struct GetHandle {
     pool: Pool,
+    ping: Option<BoxFuture<ClientHandle>>,
+    waiting: bool,
+    deadline: Option<Instant>,
+    delay: Option<Delay>,
+    started_at: Option<Instant>,
 }
 
 impl GetHandle {
     pub(crate) fn new(pool: &Pool) -> Self {
-        Self { pool: pool.clone() }
+        let started_at = match &pool.events {
+            Some(events) => {
+                events.checkout_started();
+                Some(Instant::now())
+            }
+            None => None,
+        };
+
+        Self {
+            pool: pool.clone(),
+            ping: None,
+            waiting: false,
+            deadline: pool.wait_timeout.map(|timeout| Instant::now() + timeout),
+            delay: None,
+            started_at,
+        }
+    }
+
+    fn finish(&mut self, error: Option<&Error>) {
+        if let (Some(events), Some(started_at)) = (&self.pool.events, self.started_at.take()) {
+            events.checkout_finished(started_at.elapsed(), error);
+        }
+    }
+
+    fn wait(&mut self) -> Result<()> {
+        if !self.waiting {
+            self.pool.start_waiting()?;
+            self.waiting = true;
+        }
+
+        if let Some(deadline) = self.deadline {
+            let delay = self.delay.get_or_insert_with(|| Delay::new(deadline));
+            if delay.poll()?.is_ready() {
+                return Err(Error::PoolTimeout);
+            }
+        }
+        Ok(())
+    }
+
+    fn stop_waiting(&mut self) {
+        if self.waiting {
+            self.pool.stop_waiting();
+            self.waiting = false;
+        }
+    }
+
+    fn poll_handle(&mut self) -> Poll<ClientHandle, Error> {
+        loop {
+            if let Some(ping) = self.ping.as_mut() {
+                match ping.poll() {
+                    Ok(Async::Ready(client)) => {
+                        self.ping = None;
+                        return Ok(Async::Ready(client));
+                    }
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Err(err) => {
+                        // The failed connection is gone, take another one.
+                        warn!(
+                            "[pool] dropping connection that failed to answer ping: {}",
+                            err
+                        );
+                        self.ping = None;
+                    }
+                }
+            }
+
+            let client = match self.pool.poll()? {
+                Async::Ready(client) => client,
+                Async::NotReady => {
+                    if let Err(err) = self.wait() {
+                        self.stop_waiting();
+                        return Err(err);
+                    }
+                    return Ok(Async::NotReady);
+                }
+            };
+            self.stop_waiting();
+
+            if !self.pool.needs_ping(&client) {
+                return Ok(Async::Ready(client));
+            }
+            self.ping = Some(Box::new(client.ping()));
+        }
     }
 }
 
@@ -18,6 +113,18 @@ impl Future for GetHandle {
     type Error = Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.pool.poll()
+        let result = self.poll_handle();
+        match &result {
+            Ok(Async::Ready(_)) => self.finish(None),
+            Err(err) => self.finish(Some(err)),
+            Ok(Async::NotReady) => {}
+        }
+        result
+    }
+}
+
+impl Drop for GetHandle {
+    fn drop(&mut self) {
+        self.stop_waiting();
     }
 }
@@ -0,0 +1,212 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use url::Url;
+
+use crate::types::LoadBalancing;
+
+#[derive(Clone, Copy, Default)]
+struct HostState {
+    // Time of the last failure, reset once the host is reachable again.
+    failed_at: Option<Instant>,
+    // Number of failures in a row.
+    failures: usize,
+}
+
+/// Hosts of a pool along with their recent failures.
+pub(crate) struct Hosts {
+    hosts: Vec<Url>,
+    states: Mutex<Vec<HostState>>,
+    next: AtomicUsize,
+}
+
+impl Hosts {
+    pub(crate) fn new(hosts: Vec<Url>) -> Self {
+        let states = Mutex::new(vec![HostState::default(); hosts.len()]);
+        Self {
+            hosts,
+            states,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the hosts in the order a new connection should try them.
+    ///
+    /// The starting host is chosen by `policy`. Hosts that failed less than
+    /// `cooldown` ago are moved to the end, those with fewer failures in a
+    /// row first, so they are only retried when every other host is
+    /// unreachable too.
+    pub(crate) fn candidates(&self, cooldown: Duration, policy: LoadBalancing) -> Vec<Url> {
+        let n = self.hosts.len();
+        if n == 0 {
+            return Vec::new();
+        }
+
+        let start = match policy {
+            LoadBalancing::RoundRobin => self.next.fetch_add(1, Ordering::SeqCst),
+            LoadBalancing::Random => random() as usize,
+            LoadBalancing::FirstAlive => 0,
+        };
+        let states = self.states.lock().unwrap();
+
+        let mut alive = Vec::with_capacity(n);
+        let mut quarantined = Vec::new();
+        for i in 0..n {
+            let index = (start + i) % n;
+            match states[index].failed_at {
+                Some(time) if time.elapsed() < cooldown => quarantined.push(index),
+                _ => alive.push(index),
+            }
+        }
+        quarantined.sort_by_key(|&index| states[index].failures);
+
+        alive
+            .into_iter()
+            .chain(quarantined)
+            .map(|index| self.hosts[index].clone())
+            .collect()
+    }
+
+    pub(crate) fn mark_failed(&self, host: &Url) {
+        self.update(host, |state| {
+            state.failed_at = Some(Instant::now());
+            state.failures += 1;
+        });
+    }
+
+    pub(crate) fn mark_alive(&self, host: &Url) {
+        self.update(host, |state| *state = HostState::default());
+    }
+
+    fn update<F: Fn(&mut HostState)>(&self, host: &Url, f: F) {
+        let mut states = self.states.lock().unwrap();
+        for (i, h) in self.hosts.iter().enumerate() {
+            if h == host {
+                f(&mut states[i]);
+            }
+        }
+    }
+}
+
+fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hosts() -> Hosts {
+        Hosts::new(vec![
+            Url::parse("tcp://host1:9000").unwrap(),
+            Url::parse("tcp://host2:9000").unwrap(),
+            Url::parse("tcp://host3:9000").unwrap(),
+        ])
+    }
+
+    fn names(urls: Vec<Url>) -> Vec<String> {
+        urls.iter()
+            .map(|url| url.host_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_candidates_rotate() {
+        let hosts = hosts();
+        let cooldown = Duration::from_secs(30);
+        let policy = LoadBalancing::RoundRobin;
+
+        assert_eq!(
+            names(hosts.candidates(cooldown, policy)),
+            vec!["host1", "host2", "host3"]
+        );
+        assert_eq!(
+            names(hosts.candidates(cooldown, policy)),
+            vec!["host2", "host3", "host1"]
+        );
+    }
+
+    #[test]
+    fn test_candidates_first_alive() {
+        let hosts = hosts();
+        let cooldown = Duration::from_secs(30);
+        let policy = LoadBalancing::FirstAlive;
+
+        assert_eq!(
+            names(hosts.candidates(cooldown, policy)),
+            vec!["host1", "host2", "host3"]
+        );
+        assert_eq!(
+            names(hosts.candidates(cooldown, policy)),
+            vec!["host1", "host2", "host3"]
+        );
+
+        hosts.mark_failed(&Url::parse("tcp://host1:9000").unwrap());
+        assert_eq!(
+            names(hosts.candidates(cooldown, policy)),
+            vec!["host2", "host3", "host1"]
+        );
+    }
+
+    #[test]
+    fn test_candidates_random() {
+        let hosts = hosts();
+        let cooldown = Duration::from_secs(30);
+
+        for _ in 0..10 {
+            let mut candidates = names(hosts.candidates(cooldown, LoadBalancing::Random));
+            candidates.sort();
+            assert_eq!(candidates, vec!["host1", "host2", "host3"]);
+        }
+    }
+
+    #[test]
+    fn test_failed_host_is_quarantined() {
+        let hosts = hosts();
+        let host1 = Url::parse("tcp://host1:9000").unwrap();
+        let policy = LoadBalancing::RoundRobin;
+
+        hosts.mark_failed(&host1);
+        assert_eq!(
+            names(hosts.candidates(Duration::from_secs(30), policy)),
+            vec!["host2", "host3", "host1"]
+        );
+        assert_eq!(
+            names(hosts.candidates(Duration::from_secs(0), policy)),
+            vec!["host2", "host3", "host1"]
+        );
+        assert_eq!(
+            names(hosts.candidates(Duration::from_secs(0), policy)),
+            vec!["host3", "host1", "host2"]
+        );
+
+        hosts.mark_failed(&host1);
+        hosts.mark_alive(&host1);
+        assert_eq!(
+            names(hosts.candidates(Duration::from_secs(30), policy)),
+            vec!["host1", "host2", "host3"]
+        );
+    }
+
+    #[test]
+    fn test_quarantined_hosts_ordered_by_failures() {
+        let hosts = hosts();
+        let host1 = Url::parse("tcp://host1:9000").unwrap();
+        let host2 = Url::parse("tcp://host2:9000").unwrap();
+
+        hosts.mark_failed(&host1);
+        hosts.mark_failed(&host1);
+        hosts.mark_failed(&host2);
+        assert_eq!(
+            names(hosts.candidates(Duration::from_secs(30), LoadBalancing::FirstAlive)),
+            vec!["host3", "host2", "host1"]
+        );
+    }
+}
@@ -1,29 +1,32 @@
 use std::{
     fmt, mem, sync::atomic::{self, Ordering},
     sync::Arc,
+    time::Duration,
 };
 
 use tokio::prelude::{*, task::{self, Task}};
+use url::Url;
 
 use crate::{
+    errors::{Error, Result},
     io::BoxFuture,
+    types::{CloseReason, Context, IntoOptions, OptionsSource, PoolEvents},
     Client, ClientHandle,
-    errors::Result,
-    types::{IntoOptions, OptionsSource},
 };
 
 pub use self::futures::GetHandle;
-use url::Url;
+pub(crate) use self::hosts::Hosts;
 
 mod futures;
+mod hosts;
 
 pub(crate) struct Inner {
     new: crossbeam::queue::ArrayQueue<BoxFuture<ClientHandle>>,
     idle: crossbeam::queue::ArrayQueue<ClientHandle>,
     tasks: crossbeam::queue::SegQueue<Task>,
     ongoing: atomic::AtomicUsize,
-    hosts: Vec<Url>,
-    connections_num: atomic::AtomicUsize,
+    waiting: atomic::AtomicUsize,
+    pub(crate) hosts: Hosts,
 }
 
 impl Inner {
@@ -105,6 +108,36 @@ pub struct Pool {
     pub(crate) inner: Arc<Inner>,
     min: usize,
     max: usize,
+    // Minimal idle time of a connection to be pinged on checkout, if enabled
+    ping_after: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    max_lifetime: Option<Duration>,
+    pub(crate) wait_timeout: Option<Duration>,
+    max_waiters: Option<usize>,
+    warmup_require_all: bool,
+    pub(crate) events: Option<Arc<dyn PoolEvents>>,
+}
+
+/// Snapshot of the pool usage returned by `Pool::status`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PoolStatus {
+    /// Connections handed out to clients.
+    pub in_use: usize,
+    /// Open connections waiting in the pool.
+    pub idle: usize,
+    /// Tasks waiting for a free connection.
+    pub waiting: usize,
+    /// Upper bound of open connections.
+    pub max: usize,
+}
+
+/// Outcome of `Pool::warmup`.
+#[derive(Debug, Default)]
+pub struct WarmupReport {
+    /// Connections opened and parked in the pool.
+    pub opened: usize,
+    /// Connections that failed to open, with the host each was opened to.
+    pub failures: Vec<(Url, Error)>,
 }
 
 #[derive(Debug)]
@@ -140,13 +173,28 @@ impl Pool {
         let mut min = 5;
         let mut max = 10;
         let mut hosts = vec![];
+        let mut ping_after = None;
+        let mut idle_timeout = None;
+        let mut max_lifetime = None;
+        let mut wait_timeout = None;
+        let mut max_waiters = None;
+        let mut warmup_require_all = false;
+        let mut events = None;
 
         match options_src.get() {
             Ok(opt) => {
                 min = opt.pool_min;
                 max = opt.pool_max;
-                hosts.push(opt.addr.clone());
-                hosts.extend(opt.alt_hosts.iter().cloned());
+                hosts = opt.hosts();
+                if opt.ping_before_checkout {
+                    ping_after = Some(opt.checkout_ping_after);
+                }
+                idle_timeout = opt.idle_timeout;
+                max_lifetime = opt.max_lifetime;
+                wait_timeout = opt.pool_wait_timeout;
+                max_waiters = opt.pool_max_waiters;
+                warmup_require_all = opt.warmup_require_all;
+                events = opt.events();
             }
             Err(err) => error!("{}", err),
         }
@@ -156,8 +204,8 @@ impl Pool {
             idle: crossbeam::queue::ArrayQueue::new(max),
             tasks: crossbeam::queue::SegQueue::new(),
             ongoing: atomic::AtomicUsize::new(0),
-            connections_num: atomic::AtomicUsize::new(0),
-            hosts,
+            waiting: atomic::AtomicUsize::new(0),
+            hosts: Hosts::new(hosts),
         });
 
         Self {
@@ -165,6 +213,23 @@ impl Pool {
             inner,
             min,
             max,
+            ping_after,
+            idle_timeout,
+            max_lifetime,
+            wait_timeout,
+            max_waiters,
+            warmup_require_all,
+            events,
+        }
+    }
+
+    /// Returns the current usage of the pool.
+    pub fn status(&self) -> PoolStatus {
+        PoolStatus {
+            in_use: self.inner.ongoing.load(Ordering::Acquire),
+            idle: self.inner.idle.len(),
+            waiting: self.inner.waiting.load(Ordering::Acquire),
+            max: self.max,
         }
     }
 
@@ -182,6 +247,73 @@ impl Pool {
         GetHandle::new(self)
     }
 
+    /// Opens `n` connections at once and parks them in the pool, so that
+    /// the first checkouts don't pay for connecting and the handshake. The
+    /// connections are spread over the hosts of the pool, and no more are
+    /// opened than `pool_max` allows.
+    ///
+    /// Fails if no connection could be opened, or with `warmup_require_all`
+    /// if any of them failed. Connections that were opened stay in the pool
+    /// either way.
+    pub fn warmup(&self, n: usize) -> BoxFuture<WarmupReport> {
+        let options = match self.options.get() {
+            Ok(options) => options.as_ref().to_owned(),
+            Err(err) => return Box::new(future::err(err)),
+        };
+
+        let n = n.min(self.max.saturating_sub(self.inner.conn_count()));
+        let hosts = self
+            .inner
+            .hosts
+            .candidates(options.host_cooldown, options.load_balancing);
+        if n > 0 && hosts.is_empty() {
+            return Box::new(future::err(Error::Other("No hosts to connect to.".into())));
+        }
+
+        let context = Context {
+            options: self.options.clone(),
+            ..Context::default()
+        };
+        let connections: Vec<_> = (0..n)
+            .map(|i| {
+                let host = hosts[i % hosts.len()].clone();
+                Client::open_host(host.clone(), &options, context.clone(), Some(self.clone()))
+                    .then(move |result| Ok::<_, Error>((host, result)))
+            })
+            .collect();
+
+        let pool = self.clone();
+        let fut = future::join_all(connections).and_then(move |results| {
+            let mut report = WarmupReport::default();
+            for (host, result) in results {
+                match result {
+                    Ok(client) => {
+                        pool.inner.hosts.mark_alive(&host);
+                        if pool.inner.idle.push(client).is_ok() {
+                            report.opened += 1;
+                        }
+                    }
+                    Err(err) => {
+                        warn!("[pool] warmup failed to connect to {}: {}", host, err);
+                        pool.inner.hosts.mark_failed(&host);
+                        report.failures.push((host, err));
+                    }
+                }
+            }
+            info!("[pool] warmed up {} of {} connections", report.opened, n);
+
+            let failed =
+                !report.failures.is_empty() && (pool.warmup_require_all || report.opened == 0);
+            if failed {
+                let (_, err) = report.failures.swap_remove(0);
+                return Err(err);
+            }
+            Ok(report)
+        });
+
+        Box::new(fut)
+    }
+
     fn poll(&mut self) -> Result<Async<ClientHandle>> {
         self.handle_futures()?;
 
@@ -234,14 +366,25 @@ impl Pool {
     }
 
     fn take_conn(&mut self) -> Option<ClientHandle> {
-        if let Ok(mut client) = self.inner.idle.pop() {
+        while let Ok(mut client) = self.inner.idle.pop() {
+            if self.is_expired(&client, true) {
+                // Dropping an idle connection just closes the socket.
+                info!(
+                    "[pool] closing expired connection to {:?}",
+                    client.context.addr
+                );
+                if let Some(inner) = client.inner.as_mut() {
+                    inner.set_close_reason(CloseReason::Expired);
+                }
+                continue;
+            }
+
             client.pool = PoolBinding::Attached(self.clone());
             client.set_inside(false);
             self.inner.ongoing.fetch_add(1, Ordering::AcqRel);
-            Some(client)
-        } else {
-            None
+            return Some(client);
         }
+        None
     }
 
     fn return_conn(&mut self, mut client: ClientHandle) {
@@ -251,8 +394,19 @@ impl Pool {
         client.pool = PoolBinding::None;
         client.set_inside(true);
 
-        if self.inner.idle.len() < min && is_attached {
+        // The state of a session or a database switch must not leak into
+        // other handles.
+        let expired = self.is_expired(&client, false);
+        let has_state = client.context.session || client.context.database.is_some();
+        if self.inner.idle.len() < min && is_attached && !expired && !has_state {
+            if let Some(inner) = client.inner.as_mut() {
+                inner.set_idle();
+            }
             let _ = self.inner.idle.push(client);
+        } else if expired {
+            if let Some(inner) = client.inner.as_mut() {
+                inner.set_close_reason(CloseReason::Expired);
+            }
         }
         self.inner.ongoing.fetch_sub(1, Ordering::AcqRel);
 
@@ -260,11 +414,51 @@ impl Pool {
             task.notify()
         }
     }
+}
+
+impl Pool {
+    /// Whether `client` is broken, past `max_lifetime` or, if it is `idle`,
+    /// past `idle_timeout`.
+    fn is_expired(&self, client: &ClientHandle, idle: bool) -> bool {
+        let inner = match client.inner.as_ref() {
+            Some(inner) if !inner.is_broken() => inner,
+            _ => return true,
+        };
+
+        let too_old = match self.max_lifetime {
+            Some(max) => inner.age() >= max,
+            None => false,
+        };
+        let idle_too_long = match self.idle_timeout {
+            Some(max) if idle => inner.idle_time() >= max,
+            _ => false,
+        };
+        too_old || idle_too_long
+    }
+
+    /// Registers a task waiting for a free connection, fails if there are
+    /// already `max_waiters` of them.
+    pub(crate) fn start_waiting(&self) -> Result<()> {
+        let waiting = self.inner.waiting.fetch_add(1, Ordering::AcqRel);
+        match self.max_waiters {
+            Some(max) if waiting >= max => {
+                self.inner.waiting.fetch_sub(1, Ordering::AcqRel);
+                Err(Error::PoolExhausted)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn stop_waiting(&self) {
+        self.inner.waiting.fetch_sub(1, Ordering::AcqRel);
+    }
 
-    pub(crate) fn get_addr(&self) -> &Url {
-        let n = self.inner.hosts.len();
-        let index = self.inner.connections_num.fetch_add(1, Ordering::SeqCst);
-        &self.inner.hosts[index % n]
+    /// Whether `client` should be pinged before it is handed out.
+    pub(crate) fn needs_ping(&self, client: &ClientHandle) -> bool {
+        match (self.ping_after, client.inner.as_ref()) {
+            (Some(ping_after), Some(inner)) => inner.idle_time() >= ping_after,
+            _ => false,
+        }
     }
 }
 
@@ -294,21 +488,21 @@ mod test {
             atomic::{AtomicBool, AtomicUsize, Ordering},
             Arc,
         },
-        thread::spawn,
+        thread::{self, spawn},
         time::{Duration, Instant},
     };
 
     use tokio::prelude::*;
 
     use crate::{
-        errors::Error,
+        errors::{DriverError, Error},
         io::BoxFuture,
         test_misc::DATABASE_URL,
-        types::{Block, Options},
+        types::{Block, LoadBalancing, Options, PoolEvents},
         ClientHandle,
     };
 
-    use super::Pool;
+    use super::{Pool, PoolStatus};
     use url::Url;
 
     /// Same as `tokio::run`, but will panic if future panics and will return the result
@@ -345,6 +539,46 @@ mod test {
         run(done).unwrap();
     }
 
+    #[test]
+    fn test_warmup() {
+        let options = Options::from_str(DATABASE_URL.as_str())
+            .unwrap()
+            .pool_min(3)
+            .pool_max(3);
+        let pool = Pool::new(options);
+
+        let report = run(pool.warmup(5)).unwrap();
+        assert_eq!(report.opened, 3);
+        assert!(report.failures.is_empty());
+        assert_eq!(pool.status().idle, 3);
+
+        let done = pool.get_handle().and_then(ClientHandle::ping);
+        drop(run(done).unwrap());
+        assert_eq!(pool.status().idle, 3);
+    }
+
+    #[test]
+    fn test_warmup_failures() {
+        let options = Options::from_str("tcp://127.0.0.1:1?connection_timeout=100ms").unwrap();
+        let pool = Pool::new(options);
+        run(pool.warmup(2)).unwrap_err();
+        assert_eq!(pool.status().idle, 0);
+
+        let url = format!(
+            "{}&alt_hosts=127.0.0.1:1&connection_timeout=100ms",
+            DATABASE_URL.as_str()
+        );
+        let pool = Pool::new(url.as_str());
+        let report = run(pool.warmup(2)).unwrap();
+        assert_eq!(report.opened, 1);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].0.port(), Some(1));
+        assert_eq!(pool.status().idle, 1);
+
+        let pool = Pool::new(format!("{}&warmup_require_all=true", url));
+        run(pool.warmup(2)).unwrap_err();
+    }
+
     #[test]
     fn test_detach() {
         let pool = Pool::new(DATABASE_URL.as_str());
@@ -361,6 +595,121 @@ mod test {
         assert_eq!(pool.info().idle_len, 0);
     }
 
+    #[test]
+    fn test_idle_timeout() {
+        let options = Options::from_str(DATABASE_URL.as_str())
+            .unwrap()
+            .idle_timeout(Some(Duration::from_millis(100)));
+        let pool = Pool::new(options);
+
+        let done = pool.get_handle().and_then(ClientHandle::ping);
+        drop(run(done).unwrap());
+        assert_eq!(pool.info().idle_len, 1);
+
+        thread::sleep(Duration::from_millis(200));
+        let done = pool.get_handle().and_then(ClientHandle::ping);
+        let client = run(done).unwrap();
+        assert_eq!(pool.info().idle_len, 0);
+        drop(client);
+        assert_eq!(pool.info().idle_len, 1);
+    }
+
+    #[test]
+    fn test_ping_before_checkout() {
+        let options = Options::from_str(DATABASE_URL.as_str())
+            .unwrap()
+            .ping_before_checkout(true);
+        let pool = Pool::new(options);
+
+        for _ in 0..3 {
+            let done = pool
+                .get_handle()
+                .and_then(|c| c.query("SELECT 1").fetch_all());
+            run(done).unwrap();
+        }
+        assert_eq!(pool.info().idle_len, 1);
+    }
+
+    #[test]
+    fn test_pool_wait_timeout() {
+        let options = Options::from_str(DATABASE_URL.as_str())
+            .unwrap()
+            .pool_min(1)
+            .pool_max(1)
+            .pool_wait_timeout(Some(Duration::from_millis(100)));
+        let pool = Pool::new(options);
+
+        let client = run(pool.get_handle()).unwrap();
+        assert_eq!(pool.status().in_use, 1);
+
+        let start = Instant::now();
+        match run(pool.get_handle()) {
+            Err(Error::PoolTimeout) => {}
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("unexpected connection"),
+        }
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(pool.status().waiting, 0);
+
+        drop(client);
+        run(pool.get_handle()).unwrap();
+    }
+
+    #[test]
+    fn test_pool_max_waiters() {
+        let options = Options::default().pool_max(4).pool_max_waiters(Some(1));
+        let pool = Pool::new(options);
+
+        pool.start_waiting().unwrap();
+        match pool.start_waiting() {
+            Err(Error::PoolExhausted) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(pool.status().waiting, 1);
+
+        pool.stop_waiting();
+        assert_eq!(
+            pool.status(),
+            PoolStatus {
+                in_use: 0,
+                idle: 0,
+                waiting: 0,
+                max: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_checkout_events() {
+        #[derive(Default)]
+        struct Checkouts {
+            started: AtomicUsize,
+            failed: AtomicUsize,
+        }
+
+        impl PoolEvents for Checkouts {
+            fn checkout_started(&self) {
+                self.started.fetch_add(1, Ordering::SeqCst);
+            }
+
+            fn checkout_finished(&self, _wait: Duration, error: Option<&Error>) {
+                if error.is_some() {
+                    self.failed.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        }
+
+        let events = Arc::new(Checkouts::default());
+        let options = Options::from_str("tcp://127.0.0.1:1?connection_timeout=100ms")
+            .unwrap()
+            .with_event_handler(events.clone());
+        let pool = Pool::new(options);
+
+        assert!(run(pool.get_handle()).is_err());
+        assert_eq!(events.started.load(Ordering::SeqCst), 1);
+        assert_eq!(events.failed.load(Ordering::SeqCst), 1);
+    }
+
     #[test]
     fn test_many_connection() {
         let options = Options::from_str(DATABASE_URL.as_str())
@@ -517,6 +866,59 @@ mod test {
         assert_eq!(info.idle_len, 0);
     }
 
+    #[test]
+    fn test_query_with_timeout() {
+        let pool = Pool::new(DATABASE_URL.as_str());
+
+        let done = pool.get_handle().and_then(|c| {
+            c.query("SELECT sleep(3)")
+                .with_timeout(Duration::from_millis(100))
+                .fetch_all()
+        });
+
+        let start = Instant::now();
+        match run(done) {
+            Err(Error::Driver(DriverError::Timeout)) => {}
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        let done = pool.get_handle().and_then(|c| {
+            c.query("SELECT number, sleep(1) FROM numbers(3) SETTINGS max_block_size = 1")
+                .with_timeout(Duration::from_millis(1500))
+                .stream_blocks()
+                .for_each(|_| Ok(()))
+        });
+        match run(done) {
+            Err(Error::Driver(DriverError::Timeout)) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        // The connections of timed out queries are closed.
+        let info = pool.info();
+        assert_eq!(info.ongoing, 0);
+        assert_eq!(info.idle_len, 0);
+    }
+
+    #[test]
+    fn test_cancel_on_drop() {
+        let pool = Pool::new(DATABASE_URL.as_str());
+
+        let done = pool.get_handle().and_then(|c| {
+            c.query("SELECT sleep(1) FROM numbers(30) SETTINGS max_block_size = 1")
+                .stream_blocks()
+                .take(1)
+                .for_each(|_| Ok(()))
+        });
+
+        run(done).unwrap();
+
+        let info = pool.info();
+        assert_eq!(info.ongoing, 0);
+        assert_eq!(info.tasks_len, 0);
+        assert_eq!(info.idle_len, 1);
+    }
+
     #[test]
     fn test_wrong_insert() {
         let pool = Pool::new(DATABASE_URL.as_str());
@@ -592,10 +994,17 @@ mod test {
     fn test_get_addr() {
         let options = Options::from_str("tcp://host1:9000?alt_hosts=host2:9000,host3:9000").unwrap();
         let pool = Pool::new(options);
-
-        assert_eq!(pool.get_addr(), &Url::from_str("tcp://host1:9000").unwrap());
-        assert_eq!(pool.get_addr(), &Url::from_str("tcp://host2:9000").unwrap());
-        assert_eq!(pool.get_addr(), &Url::from_str("tcp://host3:9000").unwrap());
-        assert_eq!(pool.get_addr(), &Url::from_str("tcp://host1:9000").unwrap())
+        let get_addr = || {
+            let hosts = pool
+                .inner
+                .hosts
+                .candidates(Duration::from_secs(30), LoadBalancing::RoundRobin);
+            hosts[0].clone()
+        };
+
+        assert_eq!(get_addr(), Url::from_str("tcp://host1:9000").unwrap());
+        assert_eq!(get_addr(), Url::from_str("tcp://host2:9000").unwrap());
+        assert_eq!(get_addr(), Url::from_str("tcp://host3:9000").unwrap());
+        assert_eq!(get_addr(), Url::from_str("tcp://host1:9000").unwrap())
     }
 }
@@ -0,0 +1,370 @@
+use std::{
+    cmp, fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::prelude::*;
+use tokio_timer::Delay;
+use uuid::Uuid;
+
+use crate::{
+    errors::Error,
+    io::BoxFuture,
+    reconnect,
+    types::{Options, PoolEvents, Query},
+    ClientHandle, Pool,
+};
+
+/// Shared retry classifier of `Options`.
+#[derive(Clone)]
+pub(crate) struct RetryClassifier(pub(crate) Arc<dyn Fn(&Error) -> bool + Send + Sync>);
+
+impl fmt::Debug for RetryClassifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RetryClassifier")
+    }
+}
+
+impl PartialEq for RetryClassifier {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::as_ptr(&self.0) as *const u8 == Arc::as_ptr(&other.0) as *const u8
+    }
+}
+
+/// When and how often a failed query is sent again.
+#[derive(Clone)]
+struct RetryPolicy {
+    retries: usize,
+    backoff: Duration,
+    backoff_max: Duration,
+    classifier: Option<RetryClassifier>,
+    events: Option<Arc<dyn PoolEvents>>,
+}
+
+impl RetryPolicy {
+    fn new(options: &Options) -> Self {
+        Self {
+            retries: options.query_retries,
+            backoff: options.retry_backoff,
+            backoff_max: options.retry_backoff_max,
+            classifier: options.retry_classifier.clone(),
+            events: options.events(),
+        }
+    }
+
+    /// Whether the query is sent again after its `attempt`-th retry (`0` for
+    /// the first try) failed with `err`.
+    fn should_retry(&self, attempt: usize, err: &Error) -> bool {
+        if attempt >= self.retries {
+            return false;
+        }
+
+        match &self.classifier {
+            Some(classifier) => (classifier.0)(err),
+            None => err.is_transient(),
+        }
+    }
+
+    /// The delay before the retry following `attempt`: the backoff doubled
+    /// for each previous retry, capped by the maximum, of which a random
+    /// part up to a half is taken off so that clients failing together
+    /// don't retry together.
+    fn delay(&self, attempt: usize) -> Duration {
+        let factor = 1_u32.checked_shl(attempt as u32).unwrap_or(u32::MAX);
+        let delay = match self.backoff.checked_mul(factor) {
+            Some(delay) => cmp::min(delay, self.backoff_max),
+            None => self.backoff_max,
+        };
+
+        let jitter = Uuid::new_v4().as_u128() as u64 as f64 / u64::MAX as f64;
+        delay - (delay / 2).mul_f64(jitter)
+    }
+}
+
+/// Runs `operation` with `query` on `handle` and, if the query is
+/// `idempotent`, runs it again on a fresh connection while it fails with a
+/// retryable error.
+///
+/// Fresh connections come from the pool of `handle` or are opened with its
+/// options, so they go through the usual host selection and a retry can
+/// land on another replica. Each retry gets a new query id unless the id
+/// was set by the caller, see `Query::renew_id`. Queries of a session are
+/// never retried.
+pub(crate) fn with_retries<T, F>(
+    handle: ClientHandle,
+    query: Query,
+    idempotent: bool,
+    operation: F,
+) -> BoxFuture<T>
+where
+    F: Fn(ClientHandle, Query) -> BoxFuture<T> + Send + Sync + 'static,
+    T: Send + 'static,
+{
+    let policy = match handle.context.options.get() {
+        Ok(options) => RetryPolicy::new(&options),
+        Err(err) => return Box::new(future::err(err)),
+    };
+
+    // Retries go through a new connection, which would lose the session.
+    if !idempotent || policy.retries == 0 || handle.context.session {
+        return operation(handle, query);
+    }
+
+    let pool: Option<Pool> = handle.pool.clone().into();
+    let source = handle.context.options.clone();
+    let database = handle.context.database.clone();
+    let operation = Arc::new(operation);
+
+    let fut = future::loop_fn((Some(handle), query, 0), move |(handle, query, attempt)| {
+        let handle: BoxFuture<ClientHandle> = match handle {
+            Some(handle) => Box::new(future::ok(handle)),
+            None => reconnect(pool.clone(), &source, database.clone()),
+        };
+
+        let operation = operation.clone();
+        let policy = policy.clone();
+        let retry = query.clone();
+
+        handle
+            .and_then(move |handle| operation(handle, query))
+            .then(move |result| -> BoxFuture<future::Loop<T, _>> {
+                let err = match result {
+                    Ok(value) => return Box::new(future::ok(future::Loop::Break(value))),
+                    Err(err) => err,
+                };
+
+                if !policy.should_retry(attempt, &err) {
+                    return Box::new(future::err(err));
+                }
+
+                let delay = policy.delay(attempt);
+                let query_id = retry.get_id();
+                warn!(
+                    "[retry] query {} failed, retrying in {:?}: {}",
+                    query_id, delay, err
+                );
+                if let Some(events) = &policy.events {
+                    events.query_retried(query_id, attempt + 1, delay, &err);
+                }
+                #[cfg(feature = "tracing")]
+                crate::trace::retry(query_id, attempt + 1, delay, &err);
+
+                let query = retry.renew_id();
+                Box::new(
+                    Delay::new(Instant::now() + delay)
+                        .map_err(Error::from)
+                        .map(move |_| future::Loop::Continue((None, query, attempt + 1))),
+                )
+            })
+    });
+
+    Box::new(fut)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io, str::FromStr, sync::Mutex};
+
+    use super::*;
+    use crate::{
+        errors::{codes, DriverError, ServerError},
+        pool::PoolBinding,
+        test_util::TestServer,
+        types::{Context, IntoOptions},
+    };
+
+    fn policy(retries: usize) -> RetryPolicy {
+        let options = Options::default()
+            .query_retries(retries)
+            .retry_backoff(Duration::from_millis(100))
+            .retry_backoff_max(Duration::from_millis(1000));
+        RetryPolicy::new(&options)
+    }
+
+    fn server_error(code: u32) -> Error {
+        Error::Server(ServerError {
+            code,
+            name: "DB::Exception".into(),
+            message: "".into(),
+            stack_trace: None,
+            nested: None,
+            handle: None,
+        })
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let policy = policy(2);
+        let reset: Error = io::Error::from(io::ErrorKind::ConnectionReset).into();
+
+        assert!(policy.should_retry(0, &reset));
+        assert!(policy.should_retry(1, &reset));
+        assert!(!policy.should_retry(2, &reset));
+
+        assert!(policy.should_retry(0, &server_error(codes::SOCKET_TIMEOUT)));
+        assert!(policy.should_retry(0, &Error::Driver(DriverError::Timeout)));
+        assert!(!policy.should_retry(0, &server_error(codes::SYNTAX_ERROR)));
+        assert!(!policy.should_retry(0, &Error::Driver(DriverError::UnexpectedPacket)));
+    }
+
+    #[test]
+    fn test_retry_classifier() {
+        let options = Options::default()
+            .query_retries(1)
+            .with_retry_classifier(|err| match err {
+                Error::Server(err) => err.code == codes::SYNTAX_ERROR,
+                _ => false,
+            });
+        let policy = RetryPolicy::new(&options);
+
+        assert!(policy.should_retry(0, &server_error(codes::SYNTAX_ERROR)));
+        assert!(!policy.should_retry(0, &server_error(codes::SOCKET_TIMEOUT)));
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = policy(10);
+        let bounds = [
+            (50, 100),
+            (100, 200),
+            (200, 400),
+            (400, 800),
+            (500, 1000),
+            (500, 1000),
+        ];
+
+        for (attempt, &(min, max)) in bounds.iter().enumerate() {
+            let delay = policy.delay(attempt);
+            assert!(delay >= Duration::from_millis(min), "{:?}", delay);
+            assert!(delay <= Duration::from_millis(max), "{:?}", delay);
+        }
+        assert!(policy.delay(100) <= Duration::from_millis(1000));
+    }
+
+    #[derive(Default)]
+    struct Retries(Mutex<Vec<(usize, Duration)>>);
+
+    impl PoolEvents for Retries {
+        fn query_retried(&self, _query_id: &str, attempt: usize, delay: Duration, _error: &Error) {
+            self.0.lock().unwrap().push((attempt, delay));
+        }
+    }
+
+    fn handle(options: Options) -> ClientHandle {
+        ClientHandle {
+            inner: None,
+            context: Context {
+                options: options.into_options_src(),
+                ..Context::default()
+            },
+            pool: PoolBinding::None,
+        }
+    }
+
+    fn query() -> Query {
+        Query::new("SELECT 1").ensure_id()
+    }
+
+    fn run<T: Send + 'static>(future: BoxFuture<T>) -> Result<T, Error> {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(future)
+    }
+
+    #[test]
+    fn test_with_retries() {
+        let events = Arc::new(Retries::default());
+        let options = Options::from_str("tcp://127.0.0.1:1?connection_timeout=100ms")
+            .unwrap()
+            .query_retries(2)
+            .retry_backoff(Duration::from_millis(10))
+            .with_event_handler(events.clone());
+
+        let calls = Arc::new(Mutex::new(0));
+        let counter = calls.clone();
+        let operation = move |_, _| -> BoxFuture<()> {
+            *counter.lock().unwrap() += 1;
+            let err = io::Error::from(io::ErrorKind::ConnectionReset);
+            Box::new(future::err(err.into()))
+        };
+
+        // The server is unreachable, so the retries fail to reconnect.
+        let err = run(with_retries(
+            handle(options.clone()),
+            query(),
+            true,
+            operation.clone(),
+        ))
+        .unwrap_err();
+        assert!(err.is_transient(), "{}", err);
+        assert_eq!(*calls.lock().unwrap(), 1);
+
+        let retries = events.0.lock().unwrap().clone();
+        assert_eq!(retries.iter().map(|r| r.0).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(retries.iter().all(|r| r.1 <= Duration::from_millis(20)));
+
+        // Statements that aren't idempotent fail right away.
+        events.0.lock().unwrap().clear();
+        run(with_retries(handle(options), query(), false, operation)).unwrap_err();
+        assert_eq!(*calls.lock().unwrap(), 2);
+        assert!(events.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_retry_renews_generated_id() {
+        let server = TestServer::new();
+        let options = server
+            .options()
+            .query_retries(2)
+            .retry_backoff(Duration::from_millis(1));
+
+        let ids = Arc::new(Mutex::new(Vec::new()));
+        let seen = ids.clone();
+        let operation = move |_, query: Query| -> BoxFuture<()> {
+            let mut ids = seen.lock().unwrap();
+            ids.push(query.get_id().to_string());
+            if ids.len() < 3 {
+                return Box::new(future::err(Error::Driver(DriverError::Timeout)));
+            }
+            Box::new(future::ok(()))
+        };
+
+        // The server may still run a timed out attempt, so a generated id
+        // isn't sent twice.
+        run(with_retries(
+            handle(options.clone()),
+            query(),
+            true,
+            operation.clone(),
+        ))
+        .unwrap();
+        let mut sent = ids.lock().unwrap().clone();
+        assert_eq!(sent.len(), 3);
+        sent.dedup();
+        assert_eq!(sent.len(), 3);
+
+        // An id set by the caller is kept.
+        ids.lock().unwrap().clear();
+        let query = Query::new("SELECT 1").id("fixed").ensure_id();
+        run(with_retries(handle(options), query, true, operation)).unwrap();
+        assert_eq!(*ids.lock().unwrap(), vec!["fixed"; 3]);
+    }
+
+    #[test]
+    fn test_session_is_not_retried() {
+        let events = Arc::new(Retries::default());
+        let options = Options::from_str("tcp://127.0.0.1:1?connection_timeout=100ms")
+            .unwrap()
+            .query_retries(2)
+            .with_event_handler(events.clone());
+        let handle = handle(options).with_session();
+
+        let operation = |_, _| -> BoxFuture<()> {
+            let err = io::Error::from(io::ErrorKind::ConnectionReset);
+            Box::new(future::err(err.into()))
+        };
+
+        run(with_retries(handle, query(), true, operation)).unwrap_err();
+        assert!(events.0.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,555 @@
+use std::{
+    cmp,
+    collections::HashMap,
+    io::{self, Cursor, Read, Write},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use chrono_tz::Tz;
+use futures::{future, Async, Poll};
+use tokio::io::{AsyncRead, AsyncWrite};
+use url::Url;
+
+use crate::{
+    binary::{protocol, Encoder, ReadEx},
+    client_info,
+    errors::{DriverError, Error, Result},
+    io::{ConnectFuture, Connector, Transport},
+    types::{Block, Options, ServerInfo},
+};
+
+/// In-memory stand-in for a ClickHouse server that answers the queries of
+/// its clients from a script, to test code built on the client without a
+/// running server. Clients connect with `TestServer::options`.
+///
+/// Queries are answered in the order of the script, queries beyond it
+/// return no rows. Inserts are accepted into the tables declared with
+/// `table` and kept, with `echo` they are what unscripted queries return.
+///
+/// ```
+/// # use futures::Future;
+/// # use clickhouse_rs::{test_util::TestServer, types::Block, Pool};
+/// let server = TestServer::new().respond(vec![Block::new().column("x", vec![1_u8])]);
+/// let pool = Pool::new(server.options());
+///
+/// let done = pool
+///     .get_handle()
+///     .and_then(|c| c.query("SELECT 1 AS x").fetch_all())
+///     .map(|(_, block)| assert_eq!(block.get::<u8, _>(0, "x").unwrap(), 1));
+/// tokio::run(done.map_err(|err| panic!("{}", err)));
+///
+/// assert_eq!(server.queries(), vec!["SELECT 1 AS x"]);
+/// ```
+#[derive(Clone)]
+pub struct TestServer {
+    state: Arc<Mutex<ServerState>>,
+}
+
+struct ServerState {
+    info: ServerInfo,
+    responses: Vec<Response>,
+    tables: Vec<(String, Block)>,
+    inserted: Vec<(String, Block)>,
+    queries: Vec<String>,
+    echo: bool,
+}
+
+enum Response {
+    Blocks(Vec<Block>),
+    Exception(u32, String),
+}
+
+impl Default for TestServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestServer {
+    pub fn new() -> Self {
+        let info = ServerInfo {
+            name: "ClickHouse".into(),
+            display_name: String::new(),
+            revision: client_info::CLICK_HOUSE_REVISION,
+            major_version: 1,
+            minor_version: 1,
+            patch_version: Some(0),
+            timezone: Tz::UTC,
+            settings: HashMap::new(),
+        };
+
+        let state = ServerState {
+            info,
+            responses: Vec::new(),
+            tables: Vec::new(),
+            inserted: Vec::new(),
+            queries: Vec::new(),
+            echo: false,
+        };
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// What the server tells about itself in the handshake.
+    pub fn with_server_info(self, info: ServerInfo) -> Self {
+        self.lock().info = info;
+        self
+    }
+
+    /// Answers the next query of the script with `blocks`.
+    pub fn respond(self, blocks: Vec<Block>) -> Self {
+        self.lock().responses.push(Response::Blocks(blocks));
+        self
+    }
+
+    /// Answers the next query of the script with a server exception.
+    pub fn fail(self, code: u32, message: impl Into<String>) -> Self {
+        self.lock()
+            .responses
+            .push(Response::Exception(code, message.into()));
+        self
+    }
+
+    /// Accepts inserts into `table`, whose columns are those of `header`.
+    pub fn table(self, name: &str, header: Block) -> Self {
+        self.lock().tables.push((name.to_string(), header));
+        self
+    }
+
+    /// Answers the queries beyond the script with the blocks inserted so
+    /// far, instead of no rows.
+    pub fn echo(self) -> Self {
+        self.lock().echo = true;
+        self
+    }
+
+    /// The SQL of the queries received so far, in order.
+    pub fn queries(&self) -> Vec<String> {
+        self.lock().queries.clone()
+    }
+
+    /// The data blocks inserted into `table` so far.
+    pub fn inserted(&self, table: &str) -> Vec<Block> {
+        self.lock()
+            .inserted
+            .iter()
+            .filter(|(name, _)| name == table)
+            .map(|(_, block)| block.clone())
+            .collect()
+    }
+
+    /// Options of clients connected to this server.
+    pub fn options(&self) -> Options {
+        Options::default().with_connector(Arc::new(self.clone()))
+    }
+
+    /// A new connection to this server.
+    pub fn transport(&self) -> MockTransport {
+        MockTransport {
+            server: self.clone(),
+            output: Vec::new(),
+            revision: 0,
+            compress: false,
+            state: ConnectionState::Idle,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ServerState> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl Connector for TestServer {
+    fn connect(&self, _addr: &Url) -> ConnectFuture {
+        Box::new(future::ok(Box::new(self.transport()) as Box<dyn Transport>))
+    }
+}
+
+/// Connection to a `TestServer`. Each write of the client is decoded as a
+/// sequence of whole packets and the answers are ready to be read right
+/// away; reads find nothing to read otherwise.
+pub struct MockTransport {
+    server: TestServer,
+    // Bytes sent to the client and not read yet
+    output: Vec<u8>,
+    // Protocol revision agreed in the handshake
+    revision: u64,
+    // Whether the blocks of the current query are compressed
+    compress: bool,
+    state: ConnectionState,
+}
+
+enum ConnectionState {
+    Idle,
+    // The external tables of the query are sent
+    Query(String),
+    // The data of an insert into the table is sent
+    Insert(String),
+}
+
+impl MockTransport {
+    fn receive(&mut self, data: &[u8]) -> Result<()> {
+        let mut reader = Cursor::new(data);
+        while (reader.position() as usize) < data.len() {
+            match reader.read_uvarint()? {
+                protocol::CLIENT_HELLO => self.hello(&mut reader)?,
+                protocol::CLIENT_QUERY => self.query(&mut reader)?,
+                protocol::CLIENT_DATA => self.data(&mut reader)?,
+                protocol::CLIENT_CANCEL => {
+                    self.state = ConnectionState::Idle;
+                    self.end_of_stream();
+                }
+                protocol::CLIENT_PING => {
+                    let mut encoder = Encoder::new();
+                    encoder.uvarint(protocol::SERVER_PONG);
+                    self.send(encoder);
+                }
+                packet => return Err(Error::Driver(DriverError::UnknownPacket { packet })),
+            }
+        }
+        Ok(())
+    }
+
+    fn hello<R: ReadEx>(&mut self, reader: &mut R) -> Result<()> {
+        reader.skip_string()?; // client name
+        reader.read_uvarint()?; // major version
+        reader.read_uvarint()?; // minor version
+        let client_revision = reader.read_uvarint()?;
+        reader.skip_string()?; // database
+        reader.skip_string()?; // user
+        reader.skip_string()?; // password
+
+        let info = self.server.lock().info.clone();
+        self.revision = cmp::min(info.revision, client_revision);
+
+        let mut encoder = Encoder::new();
+        encoder.uvarint(protocol::SERVER_HELLO);
+        encoder.string(&info.name);
+        encoder.uvarint(info.major_version);
+        encoder.uvarint(info.minor_version);
+        encoder.uvarint(info.revision);
+        encoder.string(info.timezone.name());
+        if self.revision >= protocol::DBMS_MIN_REVISION_WITH_SERVER_DISPLAY_NAME {
+            encoder.string(&info.display_name);
+        }
+        if self.revision >= protocol::DBMS_MIN_REVISION_WITH_VERSION_PATCH {
+            encoder.uvarint(info.patch_version.unwrap_or(0));
+        }
+        self.send(encoder);
+        Ok(())
+    }
+
+    fn query<R: ReadEx>(&mut self, reader: &mut R) -> Result<()> {
+        reader.skip_string()?; // query id
+
+        reader.read_uvarint()?; // query kind
+        reader.skip_string()?; // initial user
+        reader.skip_string()?; // initial query id
+        reader.skip_string()?; // address
+        reader.read_uvarint()?; // interface
+        reader.skip_string()?; // os user
+        reader.skip_string()?; // hostname
+        reader.skip_string()?; // client name
+        reader.read_uvarint()?; // major version
+        reader.read_uvarint()?; // minor version
+        reader.read_uvarint()?; // revision
+        if self.revision >= protocol::DBMS_MIN_REVISION_WITH_QUOTA_KEY_IN_CLIENT_INFO {
+            reader.skip_string()?;
+        }
+        if self.revision >= protocol::DBMS_MIN_REVISION_WITH_VERSION_PATCH {
+            reader.read_uvarint()?;
+        }
+        if self.revision >= protocol::DBMS_MIN_REVISION_WITH_OPENTELEMETRY
+            && reader.read_scalar::<u8>()? == 1
+        {
+            let mut trace = [0_u8; 24];
+            reader.read_bytes(&mut trace)?;
+            reader.skip_string()?;
+            reader.read_scalar::<u8>()?;
+        }
+
+        loop {
+            let name = reader.read_string()?;
+            if name.is_empty() {
+                break;
+            }
+            if self.revision >= protocol::DBMS_MIN_REVISION_WITH_SETTINGS_SERIALIZED_AS_STRINGS {
+                reader.read_scalar::<u8>()?; // is_important
+                reader.skip_string()?;
+            } else if name == "send_logs_level" {
+                reader.skip_string()?;
+            } else {
+                reader.read_uvarint()?;
+            }
+        }
+
+        if self.revision >= protocol::DBMS_MIN_REVISION_WITH_INTERSERVER_SECRET {
+            reader.skip_string()?;
+        }
+
+        reader.read_uvarint()?; // stage
+        self.compress = reader.read_uvarint()? == protocol::COMPRESS_ENABLE;
+        let sql = reader.read_string()?;
+
+        self.server.lock().queries.push(sql.clone());
+        self.state = ConnectionState::Query(sql);
+        Ok(())
+    }
+
+    fn data<R: Read + ReadEx>(&mut self, reader: &mut R) -> Result<()> {
+        reader.skip_string()?; // table name
+        let timezone = self.server.lock().info.timezone;
+        let block = Block::load(reader, timezone, None, self.compress)?;
+        let is_last = block.column_count() == 0;
+
+        match std::mem::replace(&mut self.state, ConnectionState::Idle) {
+            // External tables aren't looked at.
+            ConnectionState::Query(sql) if !is_last => self.state = ConnectionState::Query(sql),
+            ConnectionState::Query(sql) => self.run(&sql),
+            ConnectionState::Insert(table) if !is_last => {
+                self.server.lock().inserted.push((table.clone(), block));
+                self.state = ConnectionState::Insert(table);
+            }
+            ConnectionState::Insert(_) => self.end_of_stream(),
+            ConnectionState::Idle => return Err(Error::Driver(DriverError::UnexpectedPacket)),
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, sql: &str) {
+        let mut server = self.server.lock();
+
+        if let Some(table) = insert_table(sql) {
+            let header = server
+                .tables
+                .iter()
+                .find(|(name, _)| *name == table)
+                .map(|(_, header)| header.clone());
+            drop(server);
+            match header {
+                Some(header) => {
+                    if self.revision >= protocol::DBMS_MIN_REVISION_WITH_COLUMN_DEFAULTS_METADATA {
+                        let mut encoder = Encoder::new();
+                        encoder.uvarint(protocol::SERVER_TABLE_COLUMNS);
+                        encoder.string("");
+                        encoder.string("columns format version: 1\n0 columns:\n");
+                        self.send(encoder);
+                    }
+                    self.send_block(&header);
+                    self.state = ConnectionState::Insert(table);
+                }
+                None => self.send_exception(60, &format!("Table {} doesn't exist.", table)),
+            }
+            return;
+        }
+
+        let blocks = if server.responses.is_empty() {
+            if server.echo {
+                server
+                    .inserted
+                    .iter()
+                    .map(|(_, block)| block.clone())
+                    .collect()
+            } else {
+                Vec::new()
+            }
+        } else {
+            match server.responses.remove(0) {
+                Response::Blocks(blocks) => blocks,
+                Response::Exception(code, message) => {
+                    drop(server);
+                    self.send_exception(code, &message);
+                    return;
+                }
+            }
+        };
+        drop(server);
+
+        for block in &blocks {
+            self.send_block(block);
+        }
+        self.end_of_stream();
+    }
+
+    fn send_block(&mut self, block: &Block) {
+        let mut encoder = Encoder::new();
+        encoder.uvarint(protocol::SERVER_DATA);
+        encoder.string("");
+        block.write(&mut encoder, self.compress);
+        self.send(encoder);
+    }
+
+    fn send_exception(&mut self, code: u32, message: &str) {
+        let mut encoder = Encoder::new();
+        encoder.uvarint(protocol::SERVER_EXCEPTION);
+        encoder.write(code);
+        encoder.string("DB::Exception");
+        encoder.string(message);
+        encoder.string(""); // stack trace
+        encoder.write(0_u8); // nested
+        self.send(encoder);
+    }
+
+    fn end_of_stream(&mut self) {
+        let mut encoder = Encoder::new();
+        encoder.uvarint(protocol::SERVER_END_OF_STREAM);
+        self.send(encoder);
+    }
+
+    fn send(&mut self, encoder: Encoder) {
+        self.output.extend_from_slice(encoder.get_buffer_ref());
+    }
+}
+
+/// The table of an `INSERT INTO table ...` statement.
+fn insert_table(sql: &str) -> Option<String> {
+    let mut words = sql.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some(insert), Some(into), Some(table))
+            if insert.eq_ignore_ascii_case("INSERT") && into.eq_ignore_ascii_case("INTO") =>
+        {
+            let table = table.split('(').next().unwrap_or(table);
+            Some(table.trim_matches(|c| c == '`' || c == '"').to_string())
+        }
+        _ => None,
+    }
+}
+
+impl Read for MockTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.output.is_empty() {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        let n = cmp::min(buf.len(), self.output.len());
+        buf[..n].copy_from_slice(&self.output[..n]);
+        self.output.drain(..n);
+        Ok(n)
+    }
+}
+
+impl Write for MockTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.receive(buf)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncRead for MockTransport {}
+
+impl AsyncWrite for MockTransport {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        Ok(Async::Ready(()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::Future;
+    use tokio::runtime::current_thread;
+
+    use super::*;
+    use crate::{errors::ErrorKind, types::Block, ClientHandle, Pool};
+
+    fn run<F: Future>(future: F) -> std::result::Result<F::Item, F::Error> {
+        current_thread::block_on_all(future)
+    }
+
+    #[test]
+    fn test_scripted_query() {
+        let server = TestServer::new().respond(vec![
+            Block::new().column("name", vec!["a", "b"]),
+            Block::new().column("name", vec!["c"]),
+        ]);
+        let pool = Pool::new(server.options());
+
+        let (_, block) = run(pool
+            .get_handle()
+            .and_then(|c| c.query("SELECT name FROM t").fetch_all()))
+        .unwrap();
+        let names: Vec<String> = (0..3).map(|row| block.get(row, "name").unwrap()).collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+
+        let (_, block) = run(pool
+            .get_handle()
+            .and_then(|c| c.query("SELECT 1").fetch_all()))
+        .unwrap();
+        assert_eq!(block.row_count(), 0);
+        assert_eq!(server.queries(), vec!["SELECT name FROM t", "SELECT 1"]);
+    }
+
+    #[test]
+    fn test_echo_inserted_blocks() {
+        let header = Block::new().column("id", Vec::<u32>::new());
+        let server = TestServer::new().table("t", header).echo();
+        let pool = Pool::new(server.options().with_compression());
+
+        let block = Block::new().column("id", vec![1_u32, 2, 3]);
+        let done = pool
+            .get_handle()
+            .and_then(|c| c.insert("t", block))
+            .and_then(|c| c.query("SELECT id FROM t").fetch_all());
+        let (_, block) = run(done).unwrap();
+
+        let ids: Vec<u32> = (0..3).map(|row| block.get(row, "id").unwrap()).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(server.inserted("t").len(), 1);
+        assert_eq!(server.queries()[0], "INSERT INTO t (id) VALUES");
+    }
+
+    #[test]
+    fn test_scripted_exception() {
+        let server = TestServer::new().fail(60, "Table default.t doesn't exist.");
+        let pool = Pool::new(server.options());
+
+        let err = run(pool
+            .get_handle()
+            .and_then(|c| c.query("SELECT * FROM t").fetch_all()))
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Server);
+        match err {
+            Error::Server(err) => assert_eq!(err.code, 60),
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        let err = run(pool
+            .get_handle()
+            .and_then(|c| c.insert("t", Block::new().column("x", vec![1_u8]))))
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Server);
+
+        run(pool.get_handle().and_then(ClientHandle::ping)).unwrap();
+    }
+
+    #[test]
+    fn test_server_info() {
+        let info = ServerInfo {
+            name: "Mock".into(),
+            major_version: 21,
+            minor_version: 8,
+            timezone: Tz::Europe__Moscow,
+            ..TestServer::new().lock().info.clone()
+        };
+        let server = TestServer::new().with_server_info(info);
+
+        let c = run(Pool::new(server.options()).get_handle()).unwrap();
+        assert_eq!(c.server_info().name, "Mock");
+        assert_eq!(c.server_info().version().0, 21);
+        assert_eq!(c.server_info().timezone, Tz::Europe__Moscow);
+        // Settings aren't sent at the protocol revision of the client.
+        assert!(c.server_settings().is_empty());
+    }
+
+    #[test]
+    fn test_insert_table() {
+        assert_eq!(insert_table("INSERT INTO t (x) VALUES"), Some("t".into()));
+        assert_eq!(insert_table("insert into `t`(x) VALUES"), Some("t".into()));
+        assert_eq!(insert_table("SELECT 1"), None);
+    }
+}
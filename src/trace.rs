@@ -0,0 +1,227 @@
+//! Spans and events of the `tracing` feature.
+
+use std::time::Duration;
+
+use tracing::{field, Span};
+use url::Url;
+
+use crate::{errors::Error, types::QueryCounts};
+
+/// Span of a query, from sending it to the end of its response.
+///
+/// The SQL is recorded only if `sql_len` is set, truncated to that many
+/// bytes.
+pub(crate) fn query_span(
+    query_id: &str,
+    host: Option<&Url>,
+    sql: &str,
+    sql_len: Option<usize>,
+) -> Span {
+    let span = tracing::info_span!(
+        "clickhouse.query",
+        query_id,
+        host = field::Empty,
+        sql = field::Empty,
+        rows_read = field::Empty,
+        bytes_read = field::Empty,
+        rows_written = field::Empty,
+        bytes_written = field::Empty,
+        error = field::Empty,
+    );
+    if let Some(host) = host {
+        span.record("host", field::display(host));
+    }
+    if let Some(len) = sql_len {
+        span.record("sql", truncate(sql, len));
+    }
+    span
+}
+
+/// Records the outcome of the query of `span`.
+pub(crate) fn finish_query(span: &Span, counts: QueryCounts, error: Option<&Error>) {
+    span.record("rows_read", counts.rows_read);
+    span.record("bytes_read", counts.bytes_read);
+    span.record("rows_written", counts.rows_written);
+    span.record("bytes_written", counts.bytes_written);
+    span.record("error", error.is_some());
+    if let Some(err) = error {
+        tracing::warn!(parent: span, error = %err, "query failed");
+    }
+}
+
+/// Span of establishing a connection to `host`, handshake included.
+pub(crate) fn connection_span(host: &Url) -> Span {
+    tracing::info_span!("clickhouse.connect", host = %host, error = field::Empty)
+}
+
+/// Records the outcome of the connection of `span`.
+pub(crate) fn finish_connection(span: &Span, error: Option<&Error>) {
+    span.record("error", error.is_some());
+    if let Some(err) = error {
+        tracing::warn!(parent: span, error = %err, "connection failed");
+    }
+}
+
+/// A failed query is sent again after `delay`.
+pub(crate) fn retry(query_id: &str, attempt: usize, delay: Duration, error: &Error) {
+    tracing::warn!(
+        query_id,
+        attempt,
+        delay_ms = delay.as_millis() as u64,
+        error = %error,
+        "clickhouse.retry"
+    );
+}
+
+/// The connection to `host` failed its check and is opened again.
+pub(crate) fn reconnect(host: Option<&Url>) {
+    match host {
+        Some(host) => tracing::warn!(host = %host, "clickhouse.reconnect"),
+        None => tracing::warn!("clickhouse.reconnect"),
+    }
+}
+
+/// Cuts `sql` to at most `len` bytes, at a char boundary.
+fn truncate(sql: &str, len: usize) -> &str {
+    if sql.len() <= len {
+        return sql;
+    }
+    let mut end = len;
+    while !sql.is_char_boundary(end) {
+        end -= 1;
+    }
+    &sql[..end]
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        fmt,
+        str::FromStr,
+        sync::{Arc, Mutex},
+    };
+
+    use futures::Future;
+    use tokio::runtime::current_thread;
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Event, Metadata, Subscriber,
+    };
+
+    use super::*;
+    use crate::{test_misc::DATABASE_URL, types::Options, Pool};
+
+    type Fields = HashMap<String, String>;
+
+    /// Keeps the name and the fields of every span.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(String, Fields)>>>);
+
+    impl Recorder {
+        fn spans(&self, name: &str) -> Vec<Fields> {
+            let spans = self.0.lock().unwrap();
+            spans
+                .iter()
+                .filter(|(n, _)| n == name)
+                .map(|(_, fields)| fields.clone())
+                .collect()
+        }
+    }
+
+    struct Visitor<'a>(&'a mut Fields);
+
+    impl Visit for Visitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().into(), value.into());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.insert(field.name().into(), format!("{:?}", value));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes) -> Id {
+            let mut fields = Fields::new();
+            span.record(&mut Visitor(&mut fields));
+            let mut spans = self.0.lock().unwrap();
+            spans.push((span.metadata().name().into(), fields));
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record) {
+            let mut spans = self.0.lock().unwrap();
+            values.record(&mut Visitor(&mut spans[span.into_u64() as usize - 1].1));
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event) {}
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    fn run<F: Future>(recorder: &Recorder, future: F) -> std::result::Result<F::Item, F::Error> {
+        tracing::subscriber::with_default(recorder.clone(), || current_thread::block_on_all(future))
+    }
+
+    #[test]
+    fn test_query_span() {
+        let options = Options::from_str(DATABASE_URL.as_str()).unwrap();
+        let pool = Pool::new(options.trace_sql(Some(6)));
+        let recorder = Recorder::default();
+
+        let done = pool
+            .get_handle()
+            .and_then(|c| c.query("SELECT 1").with_id("q1").fetch_all());
+        run(&recorder, done).unwrap();
+
+        let connections = recorder.spans("clickhouse.connect");
+        assert_eq!(connections.len(), 1);
+        assert!(connections[0].contains_key("host"));
+        assert_eq!(connections[0]["error"], "false");
+
+        let queries = recorder.spans("clickhouse.query");
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0]["query_id"], "q1");
+        assert_eq!(queries[0]["host"], connections[0]["host"]);
+        assert_eq!(queries[0]["sql"], "SELECT");
+        assert_eq!(queries[0]["rows_read"], "1");
+        assert_eq!(queries[0]["error"], "false");
+    }
+
+    #[test]
+    fn test_failed_query_span() {
+        let pool = Pool::new(DATABASE_URL.as_str());
+        let recorder = Recorder::default();
+
+        let done = pool.get_handle().and_then(|c| {
+            c.query("SELECT * FROM clickhouse_test_missing_table")
+                .fetch_all()
+        });
+        run(&recorder, done).unwrap_err();
+
+        let queries = recorder.spans("clickhouse.query");
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0]["error"], "true");
+        // The SQL is only recorded if `trace_sql` is set.
+        assert!(!queries[0].contains_key("sql"));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("SELECT 1", 100), "SELECT 1");
+        assert_eq!(truncate("SELECT 1", 6), "SELECT");
+        assert_eq!(truncate("SELECT 'ё'", 9), "SELECT '");
+        assert_eq!(truncate("SELECT 'ё'", 10), "SELECT 'ё");
+        assert_eq!(truncate("SELECT 1", 0), "");
+    }
+}
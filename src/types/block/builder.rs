@@ -13,6 +13,11 @@ use crate::{
 
 pub trait RowBuilder {
     fn apply<K: ColumnType>(self, block: &mut Block<K>) -> Result<(), Error>;
+
+    /// Checks that the row can be applied to `block` without changing it.
+    fn check<K: ColumnType>(&self, _block: &Block<K>) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 pub struct RNil;
@@ -65,6 +70,11 @@ where
         put_param(self.key, self.value, block)?;
         self.tail.apply(block)
     }
+
+    fn check<K: ColumnType>(&self, block: &Block<K>) -> Result<(), Error> {
+        check_param(&self.key, &self.value, block)?;
+        self.tail.check(block)
+    }
 }
 
 impl RowBuilder for Vec<(String, Value)> {
@@ -74,6 +84,21 @@ impl RowBuilder for Vec<(String, Value)> {
         }
         Ok(())
     }
+
+    fn check<K: ColumnType>(&self, block: &Block<K>) -> Result<(), Error> {
+        for (k, v) in self {
+            check_param(k, v, block)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_param<K: ColumnType>(key: &str, value: &Value, block: &Block<K>) -> Result<(), Error> {
+    match key.get_index(&block.columns) {
+        Ok(col_index) => block.columns[col_index].check(value, block.overflow_mode),
+        Err(Error::FromSql(FromSqlError::UnknownColumn { .. })) if block.row_count() <= 1 => Ok(()),
+        Err(err) => Err(err),
+    }
 }
 
 fn put_param<K: ColumnType>(
@@ -83,7 +108,7 @@ fn put_param<K: ColumnType>(
 ) -> Result<(), Error> {
     let col_index = match key.as_ref().get_index(&block.columns) {
         Ok(col_index) => col_index,
-        Err(Error::FromSql(FromSqlError::OutOfRange)) => {
+        Err(Error::FromSql(FromSqlError::UnknownColumn { .. })) => {
             if block.row_count() <= 1 {
                 let sql_type = From::from(value.clone());
 
@@ -108,11 +133,11 @@ fn put_param<K: ColumnType>(
         Err(err) => return Err(err),
     };
 
-    block.columns[col_index].push(value);
-    Ok(())
+    let mode = block.overflow_mode;
+    block.columns[col_index].push(value, mode)
 }
 
-fn extract_timezone(value: &Value) -> Tz {
+pub(super) fn extract_timezone(value: &Value) -> Tz {
     match value {
         Value::Date(_, tz) => *tz,
         Value::DateTime(_, tz) => *tz,
@@ -133,9 +158,14 @@ mod test {
     use chrono::prelude::*;
     use chrono_tz::Tz::{self, UTC};
 
+    use std::sync::Arc;
+
     use crate::{
         row,
-        types::{Decimal, SqlType, Simple, DateTimeType},
+        types::{
+            column, decimal::NoBits, DateTimeType, Decimal, Enum16, Enum8, OverflowMode, Simple,
+            SqlType, Value,
+        },
     };
 
     use super::*;
@@ -203,4 +233,330 @@ mod test {
         assert_eq!(block.columns[14].sql_type(), SqlType::DateTime(DateTimeType::DateTime32));
         assert_eq!(block.columns[15].sql_type(), SqlType::Decimal(18, 4));
     }
+
+    #[test]
+    fn test_push_wrong_type() {
+        let mut block = Block::<Simple>::new().column("id", vec![1_u8]);
+
+        let err = block.push(row! { id: 300_i64 }).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Can't push Value::Int64 into column \"id\" of type UInt8."));
+        assert_eq!(block.row_count(), 1);
+
+        block.push(row! { id: 2_u8 }).unwrap();
+        assert_eq!(block.row_count(), 2);
+    }
+
+    #[test]
+    fn test_push_out_of_range() {
+        let data =
+            <dyn ColumnData>::from_type::<ArcColumnWrapper>(SqlType::Decimal(5, 2), Tz::Zulu, 0)
+                .unwrap();
+        let mut block = Block::<Simple>::new();
+        block.columns.push(column::new_column("price", data));
+
+        block
+            .push(row! { price: Decimal::of(999.99_f64, 2) })
+            .unwrap();
+        let err = block
+            .push(row! { price: Decimal::of(1000_f64, 2) })
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Value 1000.00 in row 1 of column \"price\" is out of range for Decimal(5, 2)"
+        );
+        assert_eq!(block.row_count(), 1);
+
+        let mut block = block.with_overflow_mode(OverflowMode::Saturating);
+        block
+            .push(row! { price: Decimal::of(1000_f64, 2) })
+            .unwrap();
+        assert_eq!(
+            block.get::<Decimal, _>(1, "price").unwrap(),
+            Decimal::of(999.99_f64, 2)
+        );
+
+        let mut block = Block::<Simple>::new();
+        block
+            .push(row! { at: Value::DateTime(0, Tz::UTC) })
+            .unwrap();
+        let late = Value::DateTime64(i64::from(u32::MAX) + 1, (0, Tz::UTC));
+        assert!(block.push(row! { at: late }).is_err());
+    }
+
+    #[test]
+    fn test_push_decimal32_bounds() {
+        let decimal = |underlying| Decimal {
+            underlying,
+            precision: 18,
+            scale: 4,
+            nobits: NoBits::N64,
+        };
+        let data =
+            <dyn ColumnData>::from_type::<ArcColumnWrapper>(SqlType::Decimal(9, 4), Tz::Zulu, 0)
+                .unwrap();
+        let mut block = Block::<Simple>::new();
+        block.columns.push(column::new_column("amount", data));
+
+        block.push(row! { amount: decimal(999_999_999) }).unwrap();
+        block.push(row! { amount: decimal(-999_999_999) }).unwrap();
+        let err = block
+            .push(row! { amount: decimal(1_000_000_000) })
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Value 100000.0000 in row 2 of column \"amount\" is out of range for Decimal(9, 4)"
+        );
+        let err = block
+            .push(row! { amount: decimal(i64::from(i32::MAX) + 1) })
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::ValueOutOfRange { row_index: 2, .. }),
+            "{:?}",
+            err
+        );
+        assert_eq!(block.row_count(), 2);
+
+        let stored: Vec<i32> = (0..2)
+            .map(|row| block.get::<Decimal, _>(row, "amount").unwrap().internal())
+            .collect();
+        assert_eq!(stored, vec![999_999_999, -999_999_999]);
+
+        let mut block = block.with_overflow_mode(OverflowMode::Wrapping);
+        block.push(row! { amount: decimal(5_000_000_000) }).unwrap();
+        let wrapped: Decimal = block.get(2, "amount").unwrap();
+        assert_eq!(wrapped.mantissa(), i128::from(5_000_000_000_i64 as i32));
+    }
+
+    #[test]
+    fn test_nullable_matrix() {
+        use crate::binary::Encoder;
+        use std::{
+            io::Cursor,
+            net::{Ipv4Addr, Ipv6Addr},
+        };
+
+        let uuid = uuid::Uuid::parse_str("936da01f-9abd-4d9d-80c7-02af85c822a8").unwrap();
+        let enum8 = vec![("a".to_string(), 1_i8), ("b".to_string(), 2)];
+        let enum16 = vec![("a".to_string(), 1_i16), ("b".to_string(), 1000)];
+        let cases = vec![
+            (SqlType::UInt8, Value::UInt8(1)),
+            (SqlType::UInt16, Value::UInt16(2)),
+            (SqlType::UInt32, Value::UInt32(3)),
+            (SqlType::UInt64, Value::UInt64(4)),
+            (SqlType::Int8, Value::Int8(-1)),
+            (SqlType::Int16, Value::Int16(-2)),
+            (SqlType::Int32, Value::Int32(-3)),
+            (SqlType::Int64, Value::Int64(-4)),
+            (SqlType::Float32, Value::Float32(0.5)),
+            (SqlType::Float64, Value::Float64(1.5)),
+            (SqlType::String, Value::from("text")),
+            (SqlType::FixedString(3), Value::from("abc")),
+            (SqlType::Date, Value::Date(18_000, Tz::Zulu)),
+            (
+                SqlType::DateTime(DateTimeType::DateTime32),
+                Value::DateTime(1_600_000_000, Tz::Zulu),
+            ),
+            (
+                SqlType::DateTime(DateTimeType::DateTime64(3, Tz::Zulu)),
+                Value::DateTime64(1_600_000_000_123, (3, Tz::Zulu)),
+            ),
+            (
+                SqlType::Decimal(9, 2),
+                Value::Decimal(Decimal::of(1.25_f64, 2)),
+            ),
+            (
+                SqlType::Decimal(18, 4),
+                Value::Decimal(Decimal::of(-2.5_f64, 4)),
+            ),
+            (
+                SqlType::Enum8(enum8.clone()),
+                Value::Enum8(enum8, Enum8::of(2)),
+            ),
+            (
+                SqlType::Enum16(enum16.clone()),
+                Value::Enum16(enum16, Enum16::of(1000)),
+            ),
+            (SqlType::Ipv4, Value::from(Ipv4Addr::new(10, 0, 0, 1))),
+            (SqlType::Ipv6, Value::from(Ipv6Addr::LOCALHOST)),
+            (SqlType::Uuid, Value::from(uuid)),
+        ];
+
+        for (sql_type, value) in cases {
+            let nullable = SqlType::Nullable(sql_type.clone().into());
+            let data =
+                <dyn ColumnData>::from_type::<ArcColumnWrapper>(nullable.clone(), Tz::Zulu, 0)
+                    .unwrap();
+            let mut block = Block::<Simple>::new();
+            block.columns.push(column::new_column("v", data));
+
+            let null = Value::Nullable(column::Either::Left(SqlType::String.into()));
+            let some = Value::Nullable(column::Either::Right(Box::new(value.clone())));
+            for v in [some, null, value.clone()] {
+                let row: Vec<(String, Value)> = vec![("v".into(), v)];
+                block
+                    .push(row)
+                    .unwrap_or_else(|err| panic!("{}: {}", nullable, err));
+            }
+
+            let mut encoder = Encoder::new();
+            block.write(&mut encoder, false);
+            let mut reader = Cursor::new(encoder.get_buffer_ref());
+            let block = Block::<Simple>::load(&mut reader, Tz::Zulu, None, false).unwrap();
+            assert_eq!(block.columns[0].sql_type(), nullable);
+
+            let read: Vec<Option<Value>> = (0..3).map(|row| block.get(row, "v").unwrap()).collect();
+            assert_eq!(
+                read,
+                vec![Some(value.clone()), None, Some(value.clone())],
+                "{}",
+                nullable
+            );
+
+            let data =
+                <dyn ColumnData>::from_type::<ArcColumnWrapper>(sql_type.clone(), Tz::Zulu, 0)
+                    .unwrap();
+            let mut block = Block::<Simple>::new();
+            block.columns.push(column::new_column("v", data));
+            block.push(vec![("v".to_string(), value)]).unwrap();
+            assert!(
+                block.get::<Option<Value>, _>(0, "v").is_err(),
+                "{}",
+                sql_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_nullable_columns_from_options() {
+        use std::net::{Ipv4Addr, Ipv6Addr};
+
+        let uuid = uuid::Uuid::parse_str("936da01f-9abd-4d9d-80c7-02af85c822a8").unwrap();
+        let date = UTC.ymd(2020, 1, 2);
+        let time = UTC.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap();
+        let block = Block::<Simple>::new()
+            .column("u8", vec![Some(1_u8), None])
+            .column("i64", vec![Some(-1_i64), None])
+            .column("f64", vec![Some(0.5_f64), None])
+            .column("str", vec![Some("a"), None])
+            .column("string", vec![Some("b".to_string()), None])
+            .column("bytes", vec![Some(vec![1_u8, 2]), None])
+            .column("date", vec![Some(date), None])
+            .column("time", vec![Some(time), None])
+            .column("decimal", vec![Some(Decimal::of(1.5_f64, 2)), None])
+            .column("enum8", vec![Some(Enum8::of(1)), None])
+            .column("enum16", vec![Some(Enum16::of(1)), None])
+            .column("ipv4", vec![Some(Ipv4Addr::LOCALHOST), None])
+            .column("ipv6", vec![Some(Ipv6Addr::LOCALHOST), None])
+            .column("uuid", vec![Some(uuid), None]);
+
+        for column in block.columns() {
+            assert!(
+                matches!(column.sql_type(), SqlType::Nullable(_)),
+                "{}",
+                column.name()
+            );
+            assert!(block
+                .get::<Option<Value>, _>(0, column.name())
+                .unwrap()
+                .is_some());
+            assert!(block
+                .get::<Option<Value>, _>(1, column.name())
+                .unwrap()
+                .is_none());
+        }
+        assert_eq!(block.get::<Option<u8>, _>(0, "u8").unwrap(), Some(1));
+        assert_eq!(block.get::<Option<&str>, _>(0, "str").unwrap(), Some("a"));
+        assert_eq!(
+            block.get::<Option<Vec<u8>>, _>(0, "bytes").unwrap(),
+            Some(vec![1, 2])
+        );
+        assert_eq!(
+            block.get::<Option<Date<Tz>>, _>(0, "date").unwrap(),
+            Some(date)
+        );
+        assert_eq!(
+            block.get::<Option<DateTime<Tz>>, _>(0, "time").unwrap(),
+            Some(time)
+        );
+        assert_eq!(
+            block.get::<Option<Decimal>, _>(0, "decimal").unwrap(),
+            Some(Decimal::of(1.5_f64, 2))
+        );
+        assert_eq!(
+            block.get::<Option<Enum16>, _>(0, "enum16").unwrap(),
+            Some(Enum16::of(1))
+        );
+        assert_eq!(
+            block.get::<Option<Ipv4Addr>, _>(0, "ipv4").unwrap(),
+            Some(Ipv4Addr::LOCALHOST)
+        );
+        assert_eq!(block.get::<Option<Ipv6Addr>, _>(1, "ipv6").unwrap(), None);
+        assert_eq!(
+            block.get::<Option<uuid::Uuid>, _>(0, "uuid").unwrap(),
+            Some(uuid)
+        );
+
+        let mut rows = Block::<Simple>::new();
+        for ip in [Some(Ipv4Addr::LOCALHOST), None] {
+            rows.push(
+                row! { ip: ip, uuid: ip.map(|_| uuid), decimal: ip.map(|_| Decimal::of(1, 4)) },
+            )
+            .unwrap();
+        }
+        assert_eq!(
+            rows.get::<Option<Ipv4Addr>, _>(0, "ip").unwrap(),
+            Some(Ipv4Addr::LOCALHOST)
+        );
+        assert_eq!(rows.get::<Option<uuid::Uuid>, _>(1, "uuid").unwrap(), None);
+        assert_eq!(
+            rows.get::<Option<Decimal>, _>(0, "decimal").unwrap(),
+            Some(Decimal::of(1, 4))
+        );
+    }
+
+    #[test]
+    fn test_push_compatible_types() {
+        let array = |values: Vec<Value>| Value::Array(SqlType::UInt32.into(), Arc::new(values));
+
+        let mut block = Block::<Simple>::new();
+        block
+            .push(row! {
+                opt: Some(1_u32),
+                arr: array(vec![Value::UInt32(1), Value::UInt32(2)]),
+                decimal: Decimal::of(1.0_f64, 2)
+            })
+            .unwrap();
+        block
+            .push(row! {
+                opt: 2_u32,
+                arr: array(Vec::new()),
+                decimal: Decimal::of(2.0_f64, 2)
+            })
+            .unwrap();
+        block
+            .push(row! {
+                opt: Option::<u32>::None,
+                arr: array(vec![Value::UInt32(3)]),
+                decimal: Decimal::of(3.0_f64, 2)
+            })
+            .unwrap();
+        assert_eq!(block.row_count(), 3);
+
+        assert!(block
+            .push(row! { opt: 1_u64, arr: array(Vec::new()), decimal: Decimal::of(1.0_f64, 2) })
+            .is_err());
+        assert!(block
+            .push(row! { opt: 1_u32, arr: array(vec![Value::Int8(1)]), decimal: Decimal::of(1.0_f64, 2) })
+            .is_err());
+        assert!(block
+            .push(row! { opt: 1_u32, arr: array(Vec::new()), decimal: Decimal::of(1.0_f64, 3) })
+            .is_err());
+
+        assert_eq!(block.row_count(), 3);
+        for column in block.columns() {
+            assert_eq!(column.len(), 3);
+        }
+    }
 }
@@ -9,19 +9,52 @@ use byteorder::{LittleEndian, WriteBytesExt};
 use clickhouse_rs_cityhash_sys::{city_hash_128, UInt128};
 use lz4::liblz4::LZ4_decompress_safe;
 
-use crate::{binary::ReadEx, errors::{Error, Result}};
+use crate::{
+    binary::ReadEx,
+    errors::{DriverError, Error, Result},
+};
 
 const DBMS_MAX_COMPRESSED_SIZE: u32 = 0x4000_0000; // 1GB
 
+/// Method byte of frames whose data is stored without compression.
+pub(crate) const METHOD_NONE: u8 = 0x02;
+/// Method byte of LZ4 compressed frames.
+pub(crate) const METHOD_LZ4: u8 = 0x82;
+
 pub(crate) struct CompressedReader<'a, R> {
     reader: &'a mut R,
+    compressed: Vec<u8>,
     cursor: io::Cursor<Vec<u8>>,
 }
 
+/// Compressed and decompressed chunk buffers kept between blocks, so that
+/// decoding another block doesn't allocate them again.
+#[derive(Default)]
+pub(crate) struct Buffers {
+    compressed: Vec<u8>,
+    decompressed: Vec<u8>,
+}
+
 pub(crate) fn make<R>(reader: &mut R) -> CompressedReader<R> {
+    with_buffers(reader, Buffers::default())
+}
+
+pub(crate) fn with_buffers<R>(reader: &mut R, buffers: Buffers) -> CompressedReader<'_, R> {
+    let mut decompressed = buffers.decompressed;
+    decompressed.clear();
     CompressedReader {
         reader,
-        cursor: io::Cursor::new(Vec::new()),
+        compressed: buffers.compressed,
+        cursor: io::Cursor::new(decompressed),
+    }
+}
+
+impl<'a, R> CompressedReader<'a, R> {
+    pub(crate) fn into_buffers(self) -> Buffers {
+        Buffers {
+            compressed: self.compressed,
+            decompressed: self.cursor.into_inner(),
+        }
     }
 }
 
@@ -36,12 +69,10 @@ where
     }
 
     fn fill(&mut self) -> Result<()> {
-        let cursor = mem::replace(&mut self.cursor, io::Cursor::new(Vec::new()));
-        let buffer = cursor.into_inner();
-
-        let tmp = decompress_buffer(&mut self.reader, buffer)?;
-        self.cursor = io::Cursor::new(tmp);
-        Ok(())
+        let mut data = mem::take(self.cursor.get_mut());
+        let result = decompress_buffer(&mut self.reader, &mut self.compressed, &mut data);
+        self.cursor = io::Cursor::new(data);
+        result
     }
 }
 
@@ -58,17 +89,21 @@ where
     }
 }
 
-fn decompress_buffer<R>(reader: &mut R, mut buffer: Vec<u8>) -> Result<Vec<u8>>
+/// Reads a compressed chunk into `buffer` and decompresses it into `data`,
+/// growing both only if they are too small.
+fn decompress_buffer<R>(reader: &mut R, buffer: &mut Vec<u8>, data: &mut Vec<u8>) -> Result<()>
 where
     R: ReadEx,
 {
+    data.clear();
+
     let h = UInt128 {
         lo: reader.read_scalar()?,
         hi: reader.read_scalar()?,
     };
 
     let method: u8 = reader.read_scalar()?;
-    if method != 0x82 {
+    if method != METHOD_LZ4 && method != METHOD_NONE {
         let message: String = format!("unsupported compression method {}", method);
         return Err(raise_error(message));
     }
@@ -79,25 +114,33 @@ where
     if compressed > DBMS_MAX_COMPRESSED_SIZE {
         return Err(raise_error("compressed data too big".to_string()));
     }
+    if compressed < 9 || (method == METHOD_NONE && original != compressed - 9) {
+        return Err(raise_error("invalid compressed data size".to_string()));
+    }
 
     buffer.resize(compressed as usize, 0_u8);
     {
-        let mut cursor = io::Cursor::new(&mut buffer);
-        cursor.write_u8(0x82)?;
+        let mut cursor = io::Cursor::new(&mut buffer[..]);
+        cursor.write_u8(method)?;
         cursor.write_u32::<LittleEndian>(compressed)?;
         cursor.write_u32::<LittleEndian>(original)?;
     }
     reader.read_bytes(&mut buffer[9..])?;
 
-    if h != city_hash_128(&buffer) {
+    if h != city_hash_128(&buffer[..]) {
         return Err(raise_error("data was corrupted".to_string()));
     }
 
-    let data = vec![0_u8; original as usize];
+    if method == METHOD_NONE {
+        data.extend_from_slice(&buffer[9..]);
+        return Ok(());
+    }
+
+    data.reserve(original as usize);
     let status = unsafe {
         LZ4_decompress_safe(
-            (buffer.as_mut_ptr() as *const c_char).add(9),
-            data.as_ptr() as *mut c_char,
+            (buffer.as_ptr() as *const c_char).add(9),
+            data.as_mut_ptr() as *mut c_char,
             (compressed - 9) as c_int,
             original as c_int,
         )
@@ -107,11 +150,13 @@ where
         return Err(raise_error("can't decompress data".to_string()));
     }
 
-    Ok(data)
+    // LZ4 has initialized exactly `status` bytes of the spare capacity.
+    unsafe { data.set_len(status as usize) };
+    Ok(())
 }
 
 fn raise_error(message: String) -> Error {
-    message.into()
+    Error::Driver(DriverError::CorruptedData(message))
 }
 
 #[cfg(test)]
@@ -132,7 +177,9 @@ mod test {
         ];
 
         let mut cursor = io::Cursor::new(&source[..]);
-        let actual = decompress_buffer(&mut cursor, Vec::new()).unwrap();
+        let mut buffer = Vec::new();
+        let mut actual = vec![42_u8];
+        decompress_buffer(&mut cursor, &mut buffer, &mut actual).unwrap();
 
         assert_eq!(actual, expected);
     }
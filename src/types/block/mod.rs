@@ -1,8 +1,10 @@
 use std::{
-    cmp, fmt,
-    io::{Cursor, Read},
-    os::raw::c_char,
+    any, cmp, fmt,
+    io::{Cursor, Read, Write},
     marker::PhantomData,
+    mem, ops,
+    os::raw::c_char,
+    sync::Arc,
 };
 
 use byteorder::{LittleEndian, WriteBytesExt};
@@ -12,10 +14,12 @@ use lz4::liblz4::{LZ4_compressBound, LZ4_compress_default};
 
 use crate::{
     binary::{protocol, Encoder, ReadEx},
-    errors::{Error, FromSqlError, Result},
+    errors::{DriverError, Error, FromSqlError, Result, SchemaProblem},
     types::{
-        column::{self, ArcColumnWrapper, Column, ColumnFrom},
-        FromSql, ColumnType, Simple,
+        column::{self, ArcColumnWrapper, Column, ColumnData, ColumnFrom},
+        overflow::in_column,
+        params::escape_literal,
+        ColumnType, FromSql, OverflowMode, RoundingMode, Simple, SqlType, Value,
     },
 };
 
@@ -25,6 +29,7 @@ pub use self::{
     block_info::BlockInfo,
     builder::{RCons, RNil, RowBuilder},
     row::{Row, Rows},
+    sort::SortColumn,
 };
 use crate::types::Complex;
 
@@ -33,13 +38,17 @@ mod builder;
 mod chunk_iterator;
 mod compressed;
 mod row;
-
-const INSERT_BLOCK_SIZE: usize = 1_048_576;
+mod sort;
 
 const DEFAULT_CAPACITY: usize = 100;
 
 pub trait ColumnIdx {
     fn get_index<K: ColumnType>(&self, columns: &[Column<K>]) -> Result<usize>;
+
+    /// Same as `get_index`, but compares column names ignoring ASCII case.
+    fn get_index_ignore_case<K: ColumnType>(&self, columns: &[Column<K>]) -> Result<usize> {
+        self.get_index(columns)
+    }
 }
 
 /// Represents Clickhouse Block
@@ -48,6 +57,11 @@ pub struct Block<K: ColumnType = Simple> {
     info: BlockInfo,
     columns: Vec<Column<K>>,
     capacity: usize,
+    overflow_mode: OverflowMode,
+    rounding_mode: RoundingMode,
+    ignore_case: bool,
+    totals: Option<Box<Block>>,
+    extremes: Option<Box<Block>>,
 }
 
 impl<L: ColumnType, R: ColumnType> PartialEq<Block<R>> for Block<L> {
@@ -72,6 +86,11 @@ impl<K: ColumnType> Clone for Block<K> {
             info: self.info,
             columns: self.columns.iter().map(|c| (*c).clone()).collect(),
             capacity: self.capacity,
+            overflow_mode: self.overflow_mode,
+            rounding_mode: self.rounding_mode,
+            ignore_case: self.ignore_case,
+            totals: self.totals.clone(),
+            extremes: self.extremes.clone(),
         }
     }
 }
@@ -91,13 +110,34 @@ impl ColumnIdx for usize {
 
 impl<'a> ColumnIdx for &'a str {
     fn get_index<K: ColumnType>(&self, columns: &[Column<K>]) -> Result<usize> {
-        match columns
-            .iter()
-            .enumerate()
-            .find(|(_, column)| column.name() == *self)
-        {
-            None => Err(Error::FromSql(FromSqlError::OutOfRange)),
-            Some((index, _)) => Ok(index),
+        match columns.iter().position(|column| column.name() == *self) {
+            None => Err(unknown_column(self, columns)),
+            Some(index) => Ok(index),
+        }
+    }
+
+    fn get_index_ignore_case<K: ColumnType>(&self, columns: &[Column<K>]) -> Result<usize> {
+        let matches: Vec<usize> = (0..columns.len())
+            .filter(|&index| columns[index].name().eq_ignore_ascii_case(self))
+            .collect();
+
+        match matches.as_slice() {
+            [] => Err(unknown_column(self, columns)),
+            [index] => Ok(*index),
+            [first, rest @ ..]
+                if rest
+                    .iter()
+                    .all(|&index| columns[index].name() == columns[*first].name()) =>
+            {
+                Ok(*first)
+            }
+            _ => Err(Error::FromSql(FromSqlError::AmbiguousColumn {
+                name: self.to_string(),
+                matches: matches
+                    .iter()
+                    .map(|&index| columns[index].name().to_string())
+                    .collect(),
+            })),
         }
     }
 }
@@ -106,6 +146,56 @@ impl ColumnIdx for String {
     fn get_index<K: ColumnType>(&self, columns: &[Column<K>]) -> Result<usize> {
         self.as_str().get_index(columns)
     }
+
+    fn get_index_ignore_case<K: ColumnType>(&self, columns: &[Column<K>]) -> Result<usize> {
+        self.as_str().get_index_ignore_case(columns)
+    }
+}
+
+fn unknown_column<K: ColumnType>(name: &str, columns: &[Column<K>]) -> Error {
+    Error::FromSql(FromSqlError::UnknownColumn {
+        name: name.to_string(),
+        available: columns
+            .iter()
+            .map(|column| column.name().to_string())
+            .collect(),
+    })
+}
+
+/// Gathers the values at `indices` of `column`, pushing them one by one into
+/// a new column of the same type if its data can't gather them itself.
+fn take_column<K: ColumnType>(column: &Column<K>, indices: &[usize]) -> Result<Column<Simple>> {
+    let data = match column.data.take(indices) {
+        Some(data) => Arc::from(data),
+        None => {
+            let values: Vec<Value> = indices
+                .iter()
+                .map(|&index| Value::from(column.at(index)))
+                .collect();
+            let timezone = values
+                .iter()
+                .map(builder::extract_timezone)
+                .find(|&tz| tz != Tz::Zulu)
+                .unwrap_or(Tz::Zulu);
+
+            let mut data = <dyn ColumnData>::from_type::<ArcColumnWrapper>(
+                column.sql_type(),
+                timezone,
+                values.len(),
+            )?;
+            let inner = Arc::get_mut(&mut data).unwrap();
+            for value in values {
+                inner.push(value);
+            }
+            data
+        }
+    };
+
+    Ok(Column {
+        name: column.name().to_string(),
+        data,
+        _marker: PhantomData,
+    })
 }
 
 impl Block {
@@ -115,6 +205,11 @@ impl Block {
             info: Default::default(),
             columns: vec![],
             capacity: DEFAULT_CAPACITY,
+            overflow_mode: OverflowMode::default(),
+            rounding_mode: RoundingMode::default(),
+            ignore_case: false,
+            totals: None,
+            extremes: None,
         }
     }
 
@@ -124,33 +219,177 @@ impl Block {
             info: Default::default(),
             columns: vec![],
             capacity,
+            overflow_mode: OverflowMode::default(),
+            rounding_mode: RoundingMode::default(),
+            ignore_case: false,
+            totals: None,
+            extremes: None,
+        }
+    }
+
+    /// Reads a block in the `Native` format, as written by `write_native`,
+    /// `clickhouse-client --format Native` or the server, with `tz` as the
+    /// timezone of `Date` and `DateTime` columns that don't name one.
+    ///
+    /// `revision` is the protocol revision the block was written with, `0`
+    /// for the `Native` file format: blocks of a revision above `0` start
+    /// with the block info, from revision 54454 on every column is followed
+    /// by a flag which must tell the default serialization.
+    ///
+    /// A stream of blocks is read by calling this until the reader is empty.
+    ///
+    /// ```
+    /// # use chrono_tz::Tz;
+    /// # use clickhouse_rs::types::Block;
+    /// # fn example() -> clickhouse_rs::errors::Result<()> {
+    /// let block = Block::new().column("x", vec![1_u8, 2]);
+    ///
+    /// let mut file = Vec::new();
+    /// block.write_native(&mut file, 0)?;
+    /// assert_eq!(Block::read_native(&mut &file[..], Tz::UTC, 0)?, block);
+    /// # Ok(())
+    /// # }
+    /// # example().unwrap();
+    /// ```
+    pub fn read_native<R: Read>(reader: &mut R, tz: Tz, revision: u64) -> Result<Self> {
+        let mut block = Block::new();
+        if revision > 0 {
+            block.info = BlockInfo::read(reader)?;
+        }
+
+        let num_columns = reader.read_uvarint()?;
+        let num_rows = reader.read_uvarint()? as usize;
+
+        for _ in 0..num_columns {
+            let name = reader.read_string()?;
+            let type_name = reader.read_string()?;
+
+            if revision >= protocol::DBMS_MIN_REVISION_WITH_CUSTOM_SERIALIZATION {
+                let has_custom: u8 = reader.read_scalar()?;
+                if has_custom != 0 {
+                    let message = format!(
+                        "Custom serialization of column \"{}\" is not supported.",
+                        name
+                    );
+                    return Err(message.into());
+                }
+            }
+
+            let data = <dyn column::ColumnData>::load_data::<ArcColumnWrapper, _>(
+                reader, &type_name, num_rows, tz, None,
+            )?;
+            block.append_column(Column {
+                name,
+                data,
+                _marker: PhantomData,
+            });
         }
+
+        Ok(block)
     }
 
-    pub(crate) fn load<R>(reader: &mut R, tz: Tz, compress: bool) -> Result<Self>
+    /// Loads a block, interpreting every `Date`/`DateTime` column in
+    /// `tz_override` (if given) instead of the server or column timezone.
+    pub(crate) fn load<R>(
+        reader: &mut R,
+        tz: Tz,
+        tz_override: Option<Tz>,
+        compress: bool,
+    ) -> Result<Self>
     where
         R: Read + ReadEx,
     {
         if compress {
             let mut cr = compressed::make(reader);
-            Self::raw_load(&mut cr, tz)
+            Self::raw_load(&mut cr, tz, tz_override, LoadMode::Fresh)
+        } else {
+            Self::raw_load(reader, tz, tz_override, LoadMode::Fresh)
+        }
+    }
+
+    /// Same as `load`, but defers decoding the values of every column until
+    /// they are accessed, see `Column::read_lazy`.
+    pub(crate) fn load_lazy<R>(
+        reader: &mut R,
+        tz: Tz,
+        tz_override: Option<Tz>,
+        compress: bool,
+    ) -> Result<Self>
+    where
+        R: Read,
+    {
+        if compress {
+            let mut cr = compressed::make(reader);
+            Self::raw_load(&mut cr, tz, tz_override, LoadMode::Lazy)
         } else {
-            Self::raw_load(reader, tz)
+            Self::raw_load(reader, tz, tz_override, LoadMode::Lazy)
         }
     }
 
-    fn raw_load<R>(reader: &mut R, tz: Tz) -> Result<Block<Simple>>
+    /// Same as `load`, but decodes columns into the storage of the columns
+    /// of the previous block in `recycled` that nothing else refers to
+    /// anymore, e.g. because its consumer already dropped it, and reuses the
+    /// decompression buffers. The new block is kept in `recycled` afterwards.
+    pub(crate) fn load_reusing<R>(
+        reader: &mut R,
+        tz: Tz,
+        tz_override: Option<Tz>,
+        compress: bool,
+        recycled: &mut RecycledBlock,
+    ) -> Result<Self>
     where
-        R: ReadEx,
+        R: Read,
+    {
+        let block = if compress {
+            let buffers = mem::take(&mut recycled.buffers);
+            let mut cr = compressed::with_buffers(reader, buffers);
+            let block = Self::raw_load(
+                &mut cr,
+                tz,
+                tz_override,
+                LoadMode::Reusing(&mut recycled.block),
+            );
+            recycled.buffers = cr.into_buffers();
+            block?
+        } else {
+            Self::raw_load(
+                reader,
+                tz,
+                tz_override,
+                LoadMode::Reusing(&mut recycled.block),
+            )?
+        };
+
+        recycled.block = block.clone();
+        Ok(block)
+    }
+
+    fn raw_load<R>(
+        reader: &mut R,
+        tz: Tz,
+        tz_override: Option<Tz>,
+        mut mode: LoadMode<'_>,
+    ) -> Result<Block<Simple>>
+    where
+        R: Read,
     {
         let mut block = Block::new();
         block.info = BlockInfo::read(reader)?;
 
         let num_columns = reader.read_uvarint()?;
-        let num_rows = reader.read_uvarint()?;
-
-        for _ in 0..num_columns {
-            let column = Column::read(reader, num_rows as usize, tz)?;
+        let num_rows = reader.read_uvarint()? as usize;
+
+        for index in 0..num_columns as usize {
+            let column = match &mut mode {
+                LoadMode::Reusing(recycled) => match recycled.columns.get_mut(index) {
+                    Some(previous) => {
+                        Column::read_reusing(reader, num_rows, tz, tz_override, previous)?
+                    }
+                    None => Column::read(reader, num_rows, tz, tz_override)?,
+                },
+                LoadMode::Lazy => Column::read_lazy(reader, num_rows, tz, tz_override)?,
+                LoadMode::Fresh => Column::read(reader, num_rows, tz, tz_override)?,
+            };
             block.append_column(column);
         }
 
@@ -158,6 +397,23 @@ impl Block {
     }
 }
 
+/// Where `Block::raw_load` decodes the values of the columns into.
+enum LoadMode<'a> {
+    /// New storage for every column.
+    Fresh,
+    /// The storage of the columns of a previous block, where possible.
+    Reusing(&'a mut Block),
+    /// Nowhere until they are accessed.
+    Lazy,
+}
+
+/// State kept between the data blocks of a query decoded with block reuse.
+#[derive(Default)]
+pub(crate) struct RecycledBlock {
+    block: Block,
+    buffers: compressed::Buffers,
+}
+
 impl<K: ColumnType> Block<K> {
     /// Return the number of rows in the current block.
     pub fn row_count(&self) -> usize {
@@ -172,29 +428,156 @@ impl<K: ColumnType> Block<K> {
         self.columns.len()
     }
 
+    /// Size of the values in the `Native` format, the bytes the block takes
+    /// on the wire uncompressed, without column names and types.
+    pub(crate) fn data_size(&self) -> usize {
+        let mut encoder = Encoder::new();
+        for column in &self.columns {
+            column.data.save(&mut encoder, 0, self.row_count());
+        }
+        encoder.get_buffer().len()
+    }
+
     /// This method returns a slice of columns.
     pub fn columns(&self) -> &[Column<K>] {
         &self.columns
     }
 
+    /// Checks that all columns have the same number of rows, which a row
+    /// pushed without a value for some of the columns breaks. Fails with
+    /// `DriverError::ColumnLength` for the first column shorter than the
+    /// longest one. Inserting a block runs this check too.
+    ///
+    /// ```rust
+    /// # use clickhouse_rs::{row, errors::{DriverError, Error}, types::Block};
+    /// let mut block = Block::new();
+    /// block.push(row! { id: 1_u32, name: "a" })?;
+    /// block.push(row! { id: 2_u32 })?;
+    ///
+    /// match block.validate() {
+    ///     Err(Error::Driver(DriverError::ColumnLength { name, rows: 1, expected: 2 })) => {
+    ///         assert_eq!(name, "name")
+    ///     }
+    ///     res => panic!("unexpected result {:?}", res),
+    /// }
+    /// # Ok::<(), Error>(())
+    /// ```
+    pub fn validate(&self) -> Result<()> {
+        let expected = self.columns.iter().map(Column::len).max().unwrap_or(0);
+        match self.columns.iter().find(|column| column.len() != expected) {
+            None => Ok(()),
+            Some(column) => Err(Error::Driver(DriverError::ColumnLength {
+                name: column.name().to_string(),
+                rows: column.len(),
+                expected,
+            })),
+        }
+    }
+
+    /// Returns the name and the type of every column, available even if the
+    /// block has no rows.
+    pub fn schema(&self) -> Vec<(&str, SqlType)> {
+        self.columns
+            .iter()
+            .map(|column| (column.name(), column.sql_type()))
+            .collect()
+    }
+
     fn append_column(&mut self, column: Column<K>) {
+        if let Err(err) = self.try_append_column(column) {
+            panic!("all columns in block must have same count of rows: {}", err)
+        }
+    }
+
+    fn try_append_column(&mut self, column: Column<K>) -> Result<()> {
         let column_len = column.len();
 
         if !self.columns.is_empty() && self.row_count() != column_len {
-            panic!("all columns in block must have same count of rows.")
+            return Err(Error::Driver(DriverError::ColumnLength {
+                name: column.name,
+                rows: column_len,
+                expected: self.row_count(),
+            }));
         }
 
         self.columns.push(column);
+        Ok(())
     }
 
     /// Get the value of a particular cell of the block.
+    ///
+    /// `String` and `FixedString` values can be borrowed as `&str` or `&[u8]`
+    /// without copying; ask for a `String` or `Vec<u8>` to get an owned copy.
+    /// A value that can't be converted fails with `FromSqlError::Column`.
+    /// A name finds the first column called so, see `columns_named`.
     pub fn get<'a, T, I>(&'a self, row: usize, col: I) -> Result<T>
     where
         T: FromSql<'a>,
         I: ColumnIdx + Copy,
     {
-        let column_index = col.get_index(self.columns())?;
-        T::from_sql(self.columns[column_index].at(row))
+        let column_index = self.column_index(col)?;
+        let column = &self.columns[column_index];
+        T::from_sql(column.at(row)).map_err(|err| match err {
+            Error::FromSql(source) => Error::FromSql(FromSqlError::Column {
+                column: column.name().to_string(),
+                column_index,
+                sql_type: column.sql_type().to_string().into_owned(),
+                rust_type: any::type_name::<T>().into(),
+                row_index: row,
+                source: Box::new(source),
+            }),
+            err => err,
+        })
+    }
+
+    /// Sets how values that don't fit the type of their column are handled
+    /// by the columns added and the rows pushed afterwards, and when the
+    /// block is inserted into a table with narrower types.
+    ///
+    /// By default they are rejected with `Error::ValueOutOfRange`, or make
+    /// `column` panic.
+    ///
+    /// ```
+    /// # use chrono::prelude::*;
+    /// # use chrono_tz::Tz;
+    /// # use clickhouse_rs::types::{Block, OverflowMode};
+    /// let dates = vec![Tz::UTC.ymd(1969, 12, 31)];
+    /// assert!(Block::new().try_add_column("d", dates.clone()).is_err());
+    ///
+    /// let block = Block::new()
+    ///     .with_overflow_mode(OverflowMode::Saturating)
+    ///     .column("d", dates);
+    /// assert_eq!(block.get::<Date<Tz>, _>(0, "d").unwrap(), Tz::UTC.ymd(1970, 1, 1));
+    /// ```
+    pub fn with_overflow_mode(mut self, mode: OverflowMode) -> Self {
+        self.overflow_mode = mode;
+        self
+    }
+
+    /// Sets how the sub-second digits of times are handled when the block is
+    /// inserted into a table whose `DateTime64` columns have a lower
+    /// precision. By default they are truncated.
+    ///
+    /// ```
+    /// # use chrono::prelude::*;
+    /// # use chrono_tz::Tz;
+    /// # use clickhouse_rs::types::{Block, RoundingMode};
+    /// let times = vec![Tz::UTC.timestamp(0, 999_999_999)];
+    /// let block = Block::new()
+    ///     .with_rounding_mode(RoundingMode::Checked)
+    ///     .column("t", times);
+    /// ```
+    pub fn with_rounding_mode(mut self, mode: RoundingMode) -> Self {
+        self.rounding_mode = mode;
+        self
+    }
+
+    /// Makes `get`, `get_column` and `Row::get` find columns by name
+    /// ignoring ASCII case, e.g. `total` finds a column `Total`. A name that
+    /// matches several columns fails with `FromSqlError::AmbiguousColumn`.
+    pub fn with_case_insensitive_columns(mut self, enabled: bool) -> Self {
+        self.ignore_case = enabled;
+        self
     }
 
     /// Add new column into this block
@@ -205,18 +588,72 @@ impl<K: ColumnType> Block<K> {
         self.column(name, values)
     }
 
+    /// Add new column into this block, fails if its length differs from the
+    /// columns already added.
+    ///
+    /// `values` can be a `Vec`, a slice of numbers, strings or options of
+    /// them, or data built directly, e.g. a `VectorColumnData` or a
+    /// `StringColumnData` collected from an iterator.
+    pub fn try_add_column<S>(mut self, name: &str, values: S) -> Result<Self>
+    where
+        S: ColumnFrom,
+    {
+        let data = S::column_from_checked::<ArcColumnWrapper>(values, self.overflow_mode)
+            .map_err(|err| in_column(err, name))?;
+        self.try_append_column(column::new_column(name, data))?;
+        Ok(self)
+    }
+
     /// Add new column into this block
+    ///
+    /// Slices and collected column data are copied in bulk, without building
+    /// a `Value` per row:
+    ///
+    /// ```
+    /// # use clickhouse_rs::types::{Block, StringColumnData, VectorColumnData};
+    /// let ids: &[u64] = &[1, 2, 3];
+    /// let block = Block::new()
+    ///     .column("id", ids)
+    ///     .column("score", &[Some(0.5_f64), None, Some(1.5)][..])
+    ///     .column("name", (1..=3).map(|i| format!("n{}", i)).collect::<StringColumnData>())
+    ///     .column("rank", (0..3_u8).collect::<VectorColumnData<u8>>());
+    /// assert_eq!(block.row_count(), 3);
+    /// ```
     pub fn column<S>(mut self, name: &str, values: S) -> Self
         where
             S: ColumnFrom,
     {
-        let data = S::column_from::<ArcColumnWrapper>(values);
+        let data = match S::column_from_checked::<ArcColumnWrapper>(values, self.overflow_mode) {
+            Ok(data) => data,
+            Err(err) => panic!("{}", in_column(err, name)),
+        };
         let column = column::new_column(name, data);
 
         self.append_column(column);
         self
     }
 
+    /// Formats the rows as the list of tuples of an `INSERT ... VALUES`
+    /// query, e.g. `(1, 'a\\'b'), (2, NULL)`.
+    ///
+    /// Values are written as escaped ClickHouse literals of their column
+    /// type: strings are quoted, arrays are written as `[...]`, dates as
+    /// `toDate('...')` calls. This is meant for small inserts; `insert`
+    /// sends the block in the native format, which is much cheaper for the
+    /// server to parse.
+    pub fn to_values_clause(&self) -> String {
+        let mut rows = Vec::with_capacity(self.row_count());
+        for row in 0..self.row_count() {
+            let values: Vec<String> = self
+                .columns
+                .iter()
+                .map(|column| escape_literal(&Value::from(column.at(row))))
+                .collect();
+            rows.push(format!("({})", values.join(", ")));
+        }
+        rows.join(", ")
+    }
+
     /// Returns true if the block contains no elements.
     pub fn is_empty(&self) -> bool {
         self.columns.is_empty()
@@ -232,7 +669,14 @@ impl<K: ColumnType> Block<K> {
     }
 
     /// This method is a convenient way to pass row into a block.
+    ///
+    /// The row is rejected as a whole, leaving the block untouched, if any of
+    /// its values doesn't match the type of the column it goes to.
     pub fn push<B: RowBuilder>(&mut self, row: B) -> Result<()> {
+        if let Some(name) = self.duplicate_names().into_iter().next() {
+            return Err(Error::Driver(DriverError::DuplicateColumn { name }));
+        }
+        row.check(self)?;
         row.apply(self)
     }
 
@@ -241,64 +685,366 @@ impl<K: ColumnType> Block<K> {
         where
             I: ColumnIdx + Copy,
     {
-        let column_index = col.get_index(self.columns())?;
+        let column_index = self.column_index(col)?;
         let column = &self.columns[column_index];
         Ok(column)
     }
 
+    /// Returns the indexes of all columns called `name`, compared as
+    /// `with_case_insensitive_columns` says. A query like `SELECT a, a` returns
+    /// several columns with the same name, looking them up by name always
+    /// finds the first one.
+    pub fn columns_named(&self, name: &str) -> Vec<usize> {
+        (0..self.columns.len())
+            .filter(|&index| {
+                let column_name = self.columns[index].name();
+                if self.ignore_case {
+                    column_name.eq_ignore_ascii_case(name)
+                } else {
+                    column_name == name
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the names given to more than one column, in order.
+    fn duplicate_names(&self) -> Vec<String> {
+        let mut duplicates: Vec<String> = Vec::new();
+        for (index, column) in self.columns.iter().enumerate() {
+            let name = column.name();
+            if self.columns[..index].iter().any(|c| c.name() == name)
+                && !duplicates.iter().any(|d| d == name)
+            {
+                duplicates.push(name.to_string());
+            }
+        }
+        duplicates
+    }
+
+    fn column_index<I: ColumnIdx>(&self, col: I) -> Result<usize> {
+        if self.ignore_case {
+            col.get_index_ignore_case(&self.columns)
+        } else {
+            col.get_index(&self.columns)
+        }
+    }
+
+    /// Returns a block of the rows at `indices`, in that order, a row can be
+    /// taken more than once. The values are copied column by column, `NULL`s
+    /// and arrays included, and keep the types of their columns.
+    ///
+    /// ```rust
+    /// # use clickhouse_rs::types::Block;
+    /// let block = Block::new()
+    ///     .column("id", vec![1_u32, 2, 3])
+    ///     .column("tags", vec![vec!["a"], vec![], vec!["b", "c"]]);
+    ///
+    /// let taken = block.take(&[2, 0])?;
+    /// assert_eq!(taken.get::<u32, _>(0, "id")?, 3);
+    /// assert_eq!(taken.get::<Vec<&str>, _>(0, "tags")?, vec!["b", "c"]);
+    ///
+    /// let kept = block.filter(&[false, true, true])?;
+    /// assert_eq!(kept.row_count(), 2);
+    /// assert_eq!(block.head(1)?.get::<u32, _>(0, "id")?, 1);
+    /// # Ok::<(), clickhouse_rs::errors::Error>(())
+    /// ```
+    pub fn take(&self, indices: &[usize]) -> Result<Block> {
+        let rows = self.row_count();
+        if let Some(&index) = indices.iter().find(|&&index| index >= rows) {
+            return Err(Error::Driver(DriverError::RowOutOfRange { index, rows }));
+        }
+
+        let mut columns = Vec::with_capacity(self.columns.len());
+        for column in &self.columns {
+            columns.push(take_column(column, indices)?);
+        }
+
+        Ok(Block {
+            info: Default::default(),
+            columns,
+            capacity: cmp::max(indices.len(), DEFAULT_CAPACITY),
+            overflow_mode: self.overflow_mode,
+            rounding_mode: self.rounding_mode,
+            ignore_case: self.ignore_case,
+            totals: None,
+            extremes: None,
+        })
+    }
+
+    /// Returns a block of the rows sorted by `keys`, the first key first.
+    /// Numbers, strings, dates, decimals, enums (by value) and IP addresses
+    /// can be sorted by, other columns fail with
+    /// `DriverError::UnsortableColumn`. Rows equal on every key keep their
+    /// order.
+    ///
+    /// ```rust
+    /// # use clickhouse_rs::types::{Block, SortColumn};
+    /// let block = Block::new()
+    ///     .column("team", vec!["b", "a", "b", "a"])
+    ///     .column("score", vec![Some(1_u32), None, Some(3), Some(2)]);
+    ///
+    /// let sorted = block.sort_by(&[SortColumn::asc("team"), SortColumn::desc("score").nulls_first(true)])?;
+    /// let scores: Vec<Option<u32>> = (0..4).map(|row| sorted.get(row, "score")).collect::<Result<_, _>>()?;
+    /// assert_eq!(scores, vec![None, Some(2), Some(3), Some(1)]);
+    /// # Ok::<(), clickhouse_rs::errors::Error>(())
+    /// ```
+    pub fn sort_by(&self, keys: &[SortColumn]) -> Result<Block> {
+        let rows = sort::sorted_rows(self, keys)?;
+        self.take(&rows)
+    }
+
+    /// Returns a block of the rows for which `mask` is `true`, the mask has
+    /// an entry for every row.
+    pub fn filter(&self, mask: &[bool]) -> Result<Block> {
+        let rows = self.row_count();
+        if mask.len() != rows {
+            return Err(Error::Driver(DriverError::MaskLength {
+                mask: mask.len(),
+                rows,
+            }));
+        }
+
+        let indices: Vec<usize> = (0..rows).filter(|&index| mask[index]).collect();
+        self.take(&indices)
+    }
+
+    /// Returns a block of the rows in `range`.
+    pub fn slice(&self, range: ops::Range<usize>) -> Result<Block> {
+        let rows = self.row_count();
+        if range.start > range.end || range.end > rows {
+            let index = cmp::max(range.start, range.end);
+            return Err(Error::Driver(DriverError::RowOutOfRange { index, rows }));
+        }
+
+        let indices: Vec<usize> = range.collect();
+        self.take(&indices)
+    }
+
+    /// Returns a block of the first `n` rows, or of all rows if there are
+    /// fewer.
+    pub fn head(&self, n: usize) -> Result<Block> {
+        self.slice(0..cmp::min(n, self.row_count()))
+    }
+
     pub(crate) fn chunks(&self, n: usize) -> ChunkIterator<K> {
         ChunkIterator::new(n, self)
     }
+
+    /// Returns the totals row of a `WITH TOTALS` query, if the server sent one.
+    pub fn totals(&self) -> Option<&Block> {
+        self.totals.as_deref()
+    }
+
+    /// Returns the rows with the minimum and the maximum values of each
+    /// column if the query ran with the `extremes` setting enabled.
+    pub fn extremes(&self) -> Option<(Block, Block)> {
+        let extremes = self.extremes.as_ref()?;
+        let mut rows = extremes.chunks(1);
+        Some((rows.next()?, rows.next()?))
+    }
+
+    /// Deserializes the totals row of a `WITH TOTALS` query by column names,
+    /// or returns `None` if the server sent no totals.
+    ///
+    /// ```
+    /// # use clickhouse_rs::types::Block;
+    /// # fn example(block: Block) -> clickhouse_rs::errors::Result<()> {
+    /// let totals: Option<std::collections::HashMap<String, u64>> = block.totals_row()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn totals_row<T>(&self) -> Result<Option<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        match self.totals() {
+            Some(totals) if !totals.is_empty() => crate::types::de::from_row(totals, 0).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Deserializes the rows with the minimum and the maximum values of each
+    /// column by column names, or returns `None` if the query didn't run
+    /// with the `extremes` setting enabled.
+    ///
+    /// ```
+    /// # use clickhouse_rs::types::Block;
+    /// # fn example(block: Block) -> clickhouse_rs::errors::Result<()> {
+    /// use std::collections::HashMap;
+    ///
+    /// if let Some((min, max)) = block.extremes_for::<HashMap<String, f64>>()? {
+    ///     println!("price axis from {} to {}", min["price"], max["price"]);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn extremes_for<T>(&self) -> Result<Option<(T, T)>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        match self.extremes.as_deref() {
+            Some(extremes) if extremes.row_count() == 2 => {
+                let min = crate::types::de::from_row(extremes, 0)?;
+                let max = crate::types::de::from_row(extremes, 1)?;
+                Ok(Some((min, max)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub(crate) fn set_totals(&mut self, totals: Option<Block>) {
+        self.totals = totals.map(Box::new);
+    }
+
+    pub(crate) fn set_extremes(&mut self, extremes: Option<Block>) {
+        self.extremes = extremes.map(Box::new);
+    }
 }
 
 impl Block {
-    pub(crate) fn cast_to(self, header: &Block) -> Result<Self> {
-        let info = self.info;
-        let mut columns = self.columns;
-        columns.reverse();
+    /// Converts the columns to the types of the columns of `header` with the
+    /// same name, in their order if `reorder` is set. Every column missing,
+    /// extra, out of order or of a type that can't be converted is reported
+    /// in a single `Error::SchemaMismatch`.
+    pub(crate) fn cast_to(self, header: &Block, reorder: bool) -> Result<Self> {
+        self.validate()?;
+        let duplicates = self.duplicate_names();
+        let mut columns: Vec<_> = self.columns.into_iter().map(Some).collect();
+        let mut new_columns = Vec::with_capacity(header.column_count());
+        let mut problems: Vec<_> = duplicates
+            .iter()
+            .map(|name| SchemaProblem::DuplicateColumn { name: name.clone() })
+            .collect();
+        let mut last_index = 0;
 
-        if header.column_count() != columns.len() {
-            return Err(Error::FromSql(FromSqlError::OutOfRange));
+        for column in header.columns() {
+            let name = column.name();
+            let found = columns
+                .iter()
+                .position(|c| c.as_ref().is_some_and(|c| c.name() == name));
+            let index = match found {
+                Some(index) => index,
+                None => {
+                    problems.push(SchemaProblem::MissingColumn {
+                        name: name.to_string(),
+                        sql_type: column.sql_type().to_string().into_owned(),
+                    });
+                    continue;
+                }
+            };
+
+            if index < last_index && !reorder {
+                problems.push(SchemaProblem::OutOfOrder {
+                    name: name.to_string(),
+                });
+            }
+            last_index = index;
+
+            let old_column = columns[index].take().unwrap();
+            match old_column.fit_to(column.sql_type(), self.overflow_mode, self.rounding_mode) {
+                Ok(new_column) => new_columns.push(new_column),
+                Err(Error::FromSql(FromSqlError::InvalidType { src, dst })) => {
+                    problems.push(SchemaProblem::TypeMismatch {
+                        name: name.to_string(),
+                        expected: dst.into_owned(),
+                        actual: src.into_owned(),
+                    })
+                }
+                Err(err) => return Err(err),
+            }
         }
 
-        let mut new_columns = Vec::with_capacity(columns.len());
-        for column in header.columns() {
-            let dst_type = column.sql_type();
-            let old_column = columns.pop().unwrap();
-            let new_column = old_column.cast_to(dst_type)?;
-            new_columns.push(new_column);
+        for column in columns.into_iter().flatten() {
+            if duplicates.iter().any(|name| name == column.name()) {
+                continue;
+            }
+            problems.push(SchemaProblem::ExtraColumn {
+                name: column.name().to_string(),
+            });
+        }
+
+        if !problems.is_empty() {
+            return Err(Error::SchemaMismatch { problems });
         }
 
         Ok(Block {
-            info,
+            info: self.info,
             columns: new_columns,
             capacity: self.capacity,
+            overflow_mode: self.overflow_mode,
+            rounding_mode: self.rounding_mode,
+            ignore_case: self.ignore_case,
+            totals: None,
+            extremes: None,
         })
     }
 
+    /// Writes the block in the `Native` format, see `read_native` for
+    /// `revision`. With revision `0` the output is the same as that of
+    /// `clickhouse-client --format Native`, except that `DateTime` columns
+    /// never name their timezone.
+    pub fn write_native<W: Write>(&self, writer: &mut W, revision: u64) -> Result<()> {
+        let mut encoder = Encoder::new();
+        if revision > 0 {
+            self.info.write(&mut encoder);
+        }
+
+        encoder.uvarint(self.column_count() as u64);
+        encoder.uvarint(self.row_count() as u64);
+
+        for column in &self.columns {
+            encoder.string(column.name());
+            encoder.string(column.sql_type().to_string().as_ref());
+            if revision >= protocol::DBMS_MIN_REVISION_WITH_CUSTOM_SERIALIZATION {
+                encoder.write(0_u8);
+            }
+            column.data.save(&mut encoder, 0, column.len());
+        }
+
+        writer.write_all(encoder.get_buffer_ref())?;
+        Ok(())
+    }
+
     pub(crate) fn write(&self, encoder: &mut Encoder, compress: bool) {
-        if compress {
+        self.write_framed(encoder, if compress { Some(0) } else { None })
+    }
+
+    /// Writes the block, with `compression` in a compressed frame: LZ4
+    /// compressed if the block has at least that many bytes, stored as is
+    /// otherwise. The server accepts both kinds of frames on a connection
+    /// with compression enabled.
+    fn write_framed(&self, encoder: &mut Encoder, compression: Option<usize>) {
+        if let Some(min_bytes) = compression {
             let mut tmp_encoder = Encoder::new();
             self.write(&mut tmp_encoder, false);
             let tmp = tmp_encoder.get_buffer();
 
             let mut buf = Vec::new();
-            let size;
-            unsafe {
-                buf.resize(9 + LZ4_compressBound(tmp.len() as i32) as usize, 0_u8);
-                size = LZ4_compress_default(
-                    tmp.as_ptr() as *const c_char,
-                    (buf.as_mut_ptr() as *mut c_char).add(9),
-                    tmp.len() as i32,
-                    buf.len() as i32,
-                );
-            }
-            buf.resize(9 + size as usize, 0_u8);
+            let method = if tmp.len() < min_bytes {
+                buf.resize(9, 0_u8);
+                buf.extend_from_slice(&tmp);
+                compressed::METHOD_NONE
+            } else {
+                let size;
+                unsafe {
+                    buf.resize(9 + LZ4_compressBound(tmp.len() as i32) as usize, 0_u8);
+                    size = LZ4_compress_default(
+                        tmp.as_ptr() as *const c_char,
+                        (buf.as_mut_ptr() as *mut c_char).add(9),
+                        tmp.len() as i32,
+                        buf.len() as i32,
+                    );
+                }
+                buf.resize(9 + size as usize, 0_u8);
+                compressed::METHOD_LZ4
+            };
 
             let buf_len = buf.len() as u32;
             {
                 let mut cursor = Cursor::new(&mut buf);
-                cursor.write_u8(0x82).unwrap();
+                cursor.write_u8(method).unwrap();
                 cursor.write_u32::<LittleEndian>(buf_len).unwrap();
                 cursor.write_u32::<LittleEndian>(tmp.len() as u32).unwrap();
             }
@@ -318,16 +1064,48 @@ impl Block {
         }
     }
 
-    pub(crate) fn send_data(&self, encoder: &mut Encoder, compress: bool) {
-        encoder.uvarint(protocol::CLIENT_DATA);
-        encoder.string(""); // temporary table
-        for chunk in self.chunks(INSERT_BLOCK_SIZE) {
-            chunk.write(encoder, compress);
+    /// Sends the block as data, see `send_table`.
+    pub(crate) fn send_data(
+        &self,
+        encoder: &mut Encoder,
+        compression: Option<usize>,
+        block_rows: usize,
+    ) {
+        self.send_table("", encoder, compression, block_rows);
+    }
+
+    /// Sends the block as data of the temporary (external) table `name`,
+    /// compressed if `compression` holds the size from which blocks are, in
+    /// blocks of at most `block_rows` rows.
+    pub(crate) fn send_table(
+        &self,
+        name: &str,
+        encoder: &mut Encoder,
+        compression: Option<usize>,
+        block_rows: usize,
+    ) {
+        // A block without columns terminates the data, so an empty table has
+        // to be sent with its columns.
+        if self.row_count() == 0 {
+            encoder.uvarint(protocol::CLIENT_DATA);
+            encoder.string(name);
+            self.write_framed(encoder, compression);
+            return;
+        }
+
+        for chunk in self.chunks(cmp::max(block_rows, 1)) {
+            encoder.uvarint(protocol::CLIENT_DATA);
+            encoder.string(name);
+            chunk.write_framed(encoder, compression);
         }
     }
 
     pub(crate) fn concat(blocks: &[Self]) -> Block<Complex> {
-        let first = blocks.first().expect("blocks should not be empty.");
+        // Statements like `CREATE TABLE` don't return a single block with columns.
+        let first = match blocks.first() {
+            Some(first) => first,
+            None => return Block::default(),
+        };
 
         for block in blocks {
             assert_eq!(
@@ -348,6 +1126,11 @@ impl Block {
             info: first.info,
             columns,
             capacity: blocks.iter().map(|b| b.capacity).sum(),
+            overflow_mode: first.overflow_mode,
+            rounding_mode: first.rounding_mode,
+            ignore_case: first.ignore_case,
+            totals: None,
+            extremes: None,
         }
     }
 }
@@ -417,8 +1200,293 @@ fn text_cells<K: ColumnType>(data: &Column<K>) -> Vec<String> {
 
 #[cfg(test)]
 mod test {
+    use crate::types::{Enum8, StringColumnData, VectorColumnData};
+
     use super::*;
 
+    #[test]
+    fn test_enum_labels() {
+        let mut encoder = Encoder::new();
+        BlockInfo::default().write(&mut encoder);
+        encoder.uvarint(2);
+        encoder.uvarint(3);
+        encoder.string("e");
+        encoder.string("Enum16('a' = 1, 'b' = 1000)");
+        for value in &[1000_i16, 1, 1000] {
+            encoder.write(*value);
+        }
+        encoder.string("n");
+        encoder.string("Int16");
+        for value in &[1000_i16, 1, 1000] {
+            encoder.write(*value);
+        }
+
+        let mut cursor = Cursor::new(encoder.get_buffer_ref());
+        let block = Block::<Simple>::load(&mut cursor, Tz::Zulu, None, false).unwrap();
+        assert_eq!(
+            block.columns()[0].enum_labels().unwrap(),
+            vec!["b", "a", "b"]
+        );
+        assert!(block.columns()[1].enum_labels().is_err());
+
+        let blocks = Block::concat(&[block.clone(), block]);
+        assert_eq!(
+            blocks.columns()[0].enum_labels().unwrap(),
+            vec!["b", "a", "b", "b", "a", "b"]
+        );
+    }
+
+    crate::clickhouse_enum! {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        enum Level {
+            Low = "low",
+            High = "high",
+        }
+    }
+
+    #[test]
+    fn test_enum_by_label() {
+        let mut encoder = Encoder::new();
+        BlockInfo::default().write(&mut encoder);
+        encoder.uvarint(2);
+        encoder.uvarint(3);
+        encoder.string("level");
+        encoder.string("Enum8('low' = 1, 'high' = 2, 'mid' = 3)");
+        for value in &[2_i8, 1, 7] {
+            encoder.write(*value);
+        }
+        encoder.string("opt");
+        encoder.string("Nullable(Enum16('low' = 1, 'high' = 1000))");
+        encoder.write_bytes(&[0, 1, 0]);
+        for value in &[1000_i16, 0, 1] {
+            encoder.write(*value);
+        }
+
+        let mut cursor = Cursor::new(encoder.get_buffer_ref());
+        let mut block = Block::<Simple>::load(&mut cursor, Tz::Zulu, None, false).unwrap();
+
+        assert_eq!(block.get::<&str, _>(0, "level").unwrap(), "high");
+        assert_eq!(block.get::<String, _>(1, "level").unwrap(), "low");
+        assert_eq!(block.get::<Level, _>(0, "level").unwrap(), Level::High);
+        assert_eq!(
+            block.get::<Option<Level>, _>(0, "opt").unwrap(),
+            Some(Level::High)
+        );
+        assert_eq!(block.get::<Option<Level>, _>(1, "opt").unwrap(), None);
+        assert_eq!(block.get::<Option<&str>, _>(2, "opt").unwrap(), Some("low"));
+
+        // 7 is not a value of the enum.
+        let err = block.get::<&str, _>(2, "level").unwrap_err();
+        assert!(err.to_string().contains("column 'level'"), "{}", err);
+        assert!(err.to_string().contains("at row 2"), "{}", err);
+        match err {
+            Error::FromSql(FromSqlError::Column { source, .. }) => match *source {
+                FromSqlError::UnknownEnumValue { value } => assert_eq!(value, "7"),
+                source => panic!("unexpected source {:?}", source),
+            },
+            err => panic!("unexpected error {:?}", err),
+        }
+        let row = block.rows().nth(1).unwrap();
+        assert_eq!(row.get::<Level, _>("level").unwrap(), Level::Low);
+
+        let row =
+            |level: Value, opt: Value| vec![("level".to_string(), level), ("opt".to_string(), opt)];
+        block
+            .push(row(Level::Low.into(), Some("high").into()))
+            .unwrap();
+        block
+            .push(row("mid".into(), Option::<&str>::None.into()))
+            .unwrap();
+        assert_eq!(block.row_count(), 5);
+        assert_eq!(block.get::<Enum8, _>(3, "level").unwrap(), Enum8::of(1));
+        assert_eq!(block.get::<&str, _>(4, "level").unwrap(), "mid");
+        assert_eq!(
+            block.get::<Option<Level>, _>(3, "opt").unwrap(),
+            Some(Level::High)
+        );
+        assert!(block.get::<Level, _>(4, "level").is_err());
+
+        let err = block
+            .push(row("none".into(), Some("low").into()))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Value none in row 5 of column \"level\" is out of range for Enum8('low' = 1, 'high' = 2, 'mid' = 3)"
+        );
+        assert_eq!(block.row_count(), 5);
+    }
+
+    #[test]
+    fn test_cast_labels_to_enum() {
+        let values = vec![("low".to_string(), 1_i8), ("high".to_string(), 2)];
+        let header = header(SqlType::Enum8(values.clone()));
+
+        let block = Block::new().column("v", vec!["high", "low"]);
+        let casted = block.cast_to(&header, false).unwrap();
+        assert_eq!(casted.columns()[0].sql_type(), SqlType::Enum8(values));
+        assert_eq!(casted.get::<Enum8, _>(0, "v").unwrap(), Enum8::of(2));
+        assert_eq!(casted.get::<Level, _>(1, "v").unwrap(), Level::Low);
+
+        let block = Block::new().column("v", vec!["high", "mid"]);
+        match block.cast_to(&header, false).unwrap_err() {
+            Error::ValueOutOfRange {
+                row_index, value, ..
+            } => {
+                assert_eq!(row_index, 1);
+                assert_eq!(value, "mid");
+            }
+            err => panic!("{:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_datetime_columns_in_different_timezones() {
+        let mut encoder = Encoder::new();
+        BlockInfo::default().write(&mut encoder);
+        encoder.uvarint(3);
+        encoder.uvarint(1);
+        encoder.string("utc");
+        encoder.string("DateTime('UTC')");
+        encoder.write(1_000_u32);
+        encoder.string("moscow");
+        encoder.string("DateTime64(3, 'Europe/Moscow')");
+        encoder.write(1_000_000_i64);
+        encoder.string("server");
+        encoder.string("DateTime");
+        encoder.write(1_000_u32);
+        let source = encoder.get_buffer();
+
+        let load = |tz_override| {
+            let fresh = Block::load(
+                &mut Cursor::new(&source[..]),
+                Tz::Asia__Tokyo,
+                tz_override,
+                false,
+            )
+            .unwrap();
+            let lazy = Block::load_lazy(
+                &mut Cursor::new(&source[..]),
+                Tz::Asia__Tokyo,
+                tz_override,
+                false,
+            )
+            .unwrap();
+            assert_eq!(fresh, lazy);
+            fresh
+        };
+
+        let timezone = |block: &Block, name| match block.get::<Value, _>(0, name).unwrap() {
+            Value::DateTime(1_000, tz) | Value::DateTime64(1_000_000, (3, tz)) => tz,
+            value => panic!("unexpected value {:?}", value),
+        };
+
+        let block = load(None);
+        assert_eq!(timezone(&block, "utc"), Tz::UTC);
+        assert_eq!(timezone(&block, "moscow"), Tz::Europe__Moscow);
+        assert_eq!(timezone(&block, "server"), Tz::Asia__Tokyo);
+
+        let block = load(Some(Tz::Europe__London));
+        for name in ["utc", "moscow", "server"] {
+            assert_eq!(timezone(&block, name), Tz::Europe__London);
+        }
+    }
+
+    #[test]
+    fn test_get_error_context() {
+        let block = Block::<Simple>::new()
+            .column("n", vec![1_u8, 2])
+            .column("x", vec![Some(1_i64), None]);
+
+        let err = block.get::<u32, _>(1, "x").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "From SQL error: `column 'x' (Nullable(Int64)) cannot be read as u32 at row 1`"
+        );
+        match err {
+            Error::FromSql(FromSqlError::Column {
+                column_index: 1,
+                source,
+                ..
+            }) => assert!(matches!(*source, FromSqlError::InvalidType { .. })),
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        let err = block.get::<Option<i64>, _>(1, "y").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "From SQL error: `column 'y' not found, the columns are: n, x`"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_column_names() {
+        let block = Block::<Simple>::new()
+            .column("a", vec![1_u32, 2])
+            .column("a", vec![3_u32, 4])
+            .column("b", vec![5_u32, 6])
+            .column("A", vec![7_u32, 8]);
+
+        let mut encoder = Encoder::new();
+        block.write(&mut encoder, false);
+        let mut reader = Cursor::new(encoder.get_buffer_ref());
+        let mut block = Block::load(&mut reader, Tz::Zulu, None, false).unwrap();
+
+        assert_eq!(block.column_count(), 4);
+        assert_eq!(block.get::<u32, _>(1, "a").unwrap(), 2);
+        assert_eq!(block.get::<u32, _>(1, 1).unwrap(), 4);
+        assert_eq!(block.columns_named("a"), vec![0, 1]);
+        assert!(block.columns_named("c").is_empty());
+
+        let row = block.rows().nth(1).unwrap();
+        assert_eq!(row.get::<u32, _>("a").unwrap(), 2);
+        assert_eq!(row.get::<u32, _>(1).unwrap(), 4);
+
+        let row: Vec<(String, Value)> = vec![("b".into(), 9_u32.into())];
+        match block.push(row) {
+            Err(Error::Driver(DriverError::DuplicateColumn { name })) => assert_eq!(name, "a"),
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert_eq!(block.row_count(), 2);
+
+        let block = block.with_case_insensitive_columns(true);
+        assert_eq!(block.columns_named("a"), vec![0, 1, 3]);
+        assert!(block.get::<u32, _>(0, "a").is_err());
+
+        let block = Block::<Simple>::new()
+            .column("a", vec![1_u32])
+            .column("a", vec![2_u32])
+            .with_case_insensitive_columns(true);
+        assert_eq!(block.get::<u32, _>(0, "A").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_case_insensitive_columns() {
+        let block = Block::<Simple>::new()
+            .column("Id", vec![1_u32, 2])
+            .column("Name", vec!["a", "b"]);
+
+        assert!(block.get::<u32, _>(0, "id").is_err());
+
+        let block = block.with_case_insensitive_columns(true);
+        assert_eq!(block.get::<u32, _>(1, "ID").unwrap(), 2);
+        assert_eq!(block.get::<&str, _>(0, "name").unwrap(), "a");
+        assert_eq!(block.get_column("NAME").unwrap().name(), "Name");
+
+        let row = block.rows().next().unwrap();
+        assert_eq!(row.get::<u32, _>("id").unwrap(), 1);
+
+        let block = block.column("ID", vec![3_u32, 4]);
+        match block.get::<u32, _>(0, "id") {
+            Err(Error::FromSql(FromSqlError::AmbiguousColumn { name, matches })) => {
+                assert_eq!(name, "id");
+                assert_eq!(matches, vec!["Id".to_string(), "ID".to_string()]);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert!(block.get::<u32, _>(0, "ID").is_err());
+        assert!(block.get::<u32, _>(0, "missing").is_err());
+    }
+
     #[test]
     fn test_write_default() {
         let expected = [1_u8, 0, 2, 255, 255, 255, 255, 0, 0, 0];
@@ -455,7 +1523,7 @@ mod test {
         ];
 
         let mut cursor = Cursor::new(&source[..]);
-        let actual = Block::load(&mut cursor, Tz::UTC, true).unwrap();
+        let actual = Block::load(&mut cursor, Tz::UTC, None, true).unwrap();
 
         assert_eq!(actual, expected);
     }
@@ -464,12 +1532,172 @@ mod test {
     fn test_read_empty_block() {
         let source = [1, 0, 2, 255, 255, 255, 255, 0, 0, 0];
         let mut cursor = Cursor::new(&source[..]);
-        match Block::<Simple>::load(&mut cursor, Tz::Zulu, false) {
+        match Block::<Simple>::load(&mut cursor, Tz::Zulu, None, false) {
             Ok(block) => assert!(block.is_empty()),
             Err(_) => unreachable!(),
         }
     }
 
+    #[test]
+    fn test_read_block_without_rows() {
+        let types = [
+            "UInt32",
+            "Float64",
+            "String",
+            "FixedString(3)",
+            "Nullable(UInt8)",
+            "Array(UInt32)",
+            "Array(Array(String))",
+            "Array(Nullable(Int16))",
+            "Date",
+            "DateTime",
+            "DateTime64(3, 'UTC')",
+            "Decimal(9, 2)",
+            "Enum8('a' = 1, 'b' = 2)",
+            "UUID",
+            "IPv4",
+            "IPv6",
+        ];
+
+        let mut encoder = Encoder::new();
+        BlockInfo::default().write(&mut encoder);
+        encoder.uvarint(types.len() as u64);
+        encoder.uvarint(0);
+        for (i, type_name) in types.iter().enumerate() {
+            encoder.string(format!("c{}", i));
+            encoder.string(type_name);
+        }
+
+        let mut cursor = Cursor::new(encoder.get_buffer_ref());
+        let block = Block::<Simple>::load(&mut cursor, Tz::Zulu, None, false).unwrap();
+
+        assert_eq!(block.column_count(), types.len());
+        assert_eq!(block.row_count(), 0);
+        for (column, type_name) in block.columns().iter().zip(types.iter()) {
+            assert_eq!(column.len(), 0);
+            assert_eq!(column.sql_type().to_string(), *type_name);
+        }
+    }
+
+    #[test]
+    fn test_write_and_read_block_without_rows() {
+        let block = Block::<Simple>::new()
+            .column("id", Vec::<u32>::new())
+            .column("tags", Vec::<Vec<String>>::new());
+
+        let mut encoder = Encoder::new();
+        block.write(&mut encoder, false);
+
+        let mut cursor = Cursor::new(encoder.get_buffer_ref());
+        let actual = Block::load(&mut cursor, Tz::Zulu, None, false).unwrap();
+
+        assert_eq!(actual.column_count(), 2);
+        assert_eq!(actual.row_count(), 0);
+        assert_eq!(block, actual);
+    }
+
+    #[test]
+    fn test_concat_without_blocks() {
+        let actual = Block::<Simple>::concat(&[]);
+        assert!(actual.is_empty());
+        assert_eq!(actual.row_count(), 0);
+    }
+
+    #[test]
+    fn test_totals_and_extremes() {
+        let mut block = Block::<Simple>::new().column("x", vec![1_u32, 2, 3]);
+        assert!(block.totals().is_none());
+        assert!(block.extremes().is_none());
+
+        block.set_totals(Some(Block::new().column("x", vec![6_u32])));
+        block.set_extremes(Some(Block::new().column("x", vec![1_u32, 3])));
+
+        let totals = block.totals().unwrap();
+        assert_eq!(totals.get::<u32, _>(0, "x").unwrap(), 6);
+
+        let (min, max) = block.extremes().unwrap();
+        assert_eq!(min.row_count(), 1);
+        assert_eq!(min.get::<u32, _>(0, "x").unwrap(), 1);
+        assert_eq!(max.get::<u32, _>(0, "x").unwrap(), 3);
+    }
+
+    #[test]
+    fn test_send_table() {
+        let block = Block::<Simple>::new().column("id", vec![1_u64, 2]);
+
+        let mut encoder = Encoder::new();
+        block.send_table("ids", &mut encoder, None, 1_048_576);
+
+        let mut expected = Encoder::new();
+        expected.uvarint(protocol::CLIENT_DATA);
+        expected.string("ids");
+        block.write(&mut expected, false);
+
+        assert_eq!(encoder.get_buffer_ref(), expected.get_buffer_ref());
+    }
+
+    #[test]
+    fn test_send_data_in_blocks() {
+        let block = Block::<Simple>::new()
+            .column("n", vec![1_u32, 2, 3, 4, 5])
+            .column(
+                "a",
+                vec![vec![1_u8], vec![2, 3], vec![], vec![4, 5, 6], vec![7]],
+            )
+            .column("s", vec![Some("a"), None, Some("c"), None, Some("e")]);
+
+        let mut encoder = Encoder::new();
+        block.send_data(&mut encoder, None, 2);
+
+        let mut cursor = Cursor::new(encoder.get_buffer_ref());
+        let mut blocks = Vec::new();
+        while (cursor.position() as usize) < encoder.get_buffer_ref().len() {
+            assert_eq!(cursor.read_uvarint().unwrap(), protocol::CLIENT_DATA);
+            assert_eq!(cursor.read_string().unwrap(), "");
+            blocks.push(Block::<Simple>::load(&mut cursor, Tz::Zulu, None, false).unwrap());
+        }
+
+        let rows: Vec<_> = blocks.iter().map(Block::row_count).collect();
+        assert_eq!(rows, [2, 2, 1]);
+        assert_eq!(blocks[1].get::<Vec<u8>, _>(1, "a").unwrap(), vec![4, 5, 6]);
+        assert_eq!(Block::concat(&blocks), block.chunks(5).next().unwrap());
+    }
+
+    #[test]
+    fn test_send_empty_table() {
+        let block = Block::<Simple>::new().column("id", Vec::<u64>::new());
+
+        let mut encoder = Encoder::new();
+        block.send_table("ids", &mut encoder, None, 1_048_576);
+
+        let mut cursor = Cursor::new(encoder.get_buffer_ref());
+        assert_eq!(cursor.read_uvarint().unwrap(), protocol::CLIENT_DATA);
+        assert_eq!(cursor.read_string().unwrap(), "ids");
+        let actual = Block::<Simple>::load(&mut cursor, Tz::Zulu, None, false).unwrap();
+        assert_eq!(actual.column_count(), 1);
+        assert_eq!(actual.row_count(), 0);
+    }
+
+    #[test]
+    fn test_schema() {
+        let mut encoder = Encoder::new();
+        BlockInfo::default().write(&mut encoder);
+        encoder.uvarint(2);
+        encoder.uvarint(0);
+        encoder.string("a");
+        encoder.string("UInt8");
+        encoder.string("b");
+        encoder.string("String");
+
+        let mut cursor = Cursor::new(encoder.get_buffer_ref());
+        let block = Block::<Simple>::load(&mut cursor, Tz::Zulu, None, false).unwrap();
+
+        assert_eq!(
+            block.schema(),
+            vec![("a", SqlType::UInt8), ("b", SqlType::String)]
+        );
+    }
+
     #[test]
     fn test_empty() {
         assert!(Block::<Simple>::default().is_empty())
@@ -560,8 +1788,703 @@ mod test {
         block.write(&mut encoder, false);
 
         let mut reader = Cursor::new(encoder.get_buffer_ref());
-        let rblock = Block::load(&mut reader, Tz::Zulu, false).unwrap();
+        let rblock = Block::load(&mut reader, Tz::Zulu, None, false).unwrap();
 
         assert_eq!(block, rblock);
     }
+
+    /// A block of `rows` rows of a single column in the `Native` format.
+    fn native_block(name: &str, type_name: &str, rows: u64, data: &[u8]) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.uvarint(1);
+        encoder.uvarint(rows);
+        encoder.string(name);
+        encoder.string(type_name);
+        encoder.write_bytes(data);
+        encoder.get_buffer()
+    }
+
+    #[test]
+    fn test_read_native() {
+        // SELECT 1 AS x, 'a' AS s FORMAT Native
+        let source = [
+            2_u8, 1, 1, b'x', 5, b'U', b'I', b'n', b't', b'8', 1, 1, b's', 6, b'S', b't', b'r',
+            b'i', b'n', b'g', 1, b'a',
+        ];
+
+        let block = Block::read_native(&mut &source[..], Tz::UTC, 0).unwrap();
+        assert_eq!(
+            block,
+            Block::<Simple>::new()
+                .column("x", vec![1_u8])
+                .column("s", vec!["a"])
+        );
+
+        let mut target = Vec::new();
+        block.write_native(&mut target, 0).unwrap();
+        assert_eq!(target, source);
+    }
+
+    #[test]
+    fn test_native_round_trip() {
+        let columns: Vec<(&str, Vec<u8>)> = vec![
+            ("UInt8", vec![1, 255]),
+            ("UInt16", vec![1, 0, 255, 255]),
+            ("UInt32", vec![1, 0, 0, 0, 255, 255, 255, 255]),
+            (
+                "UInt64",
+                [1_u64.to_le_bytes(), u64::MAX.to_le_bytes()].concat(),
+            ),
+            ("Int8", vec![1, 0x80]),
+            ("Int16", vec![1, 0, 0, 0x80]),
+            (
+                "Int32",
+                [1_i32.to_le_bytes(), i32::MIN.to_le_bytes()].concat(),
+            ),
+            (
+                "Int64",
+                [1_i64.to_le_bytes(), i64::MIN.to_le_bytes()].concat(),
+            ),
+            (
+                "Float32",
+                [0.5_f32.to_le_bytes(), (-2.0_f32).to_le_bytes()].concat(),
+            ),
+            (
+                "Float64",
+                [0.5_f64.to_le_bytes(), (-2.0_f64).to_le_bytes()].concat(),
+            ),
+            ("String", vec![1, b'a', 0]),
+            ("FixedString(2)", vec![b'a', b'b', b'c', 0]),
+            (
+                "Date",
+                [18262_u16.to_le_bytes(), 0_u16.to_le_bytes()].concat(),
+            ),
+            (
+                "DateTime",
+                [1_577_836_800_u32.to_le_bytes(), 0_u32.to_le_bytes()].concat(),
+            ),
+            (
+                "DateTime64(3, 'Europe/Moscow')",
+                [1_577_836_800_123_i64.to_le_bytes(), 0_i64.to_le_bytes()].concat(),
+            ),
+            (
+                "Decimal(9, 2)",
+                [12345_i32.to_le_bytes(), (-1_i32).to_le_bytes()].concat(),
+            ),
+            (
+                "Decimal(18, 4)",
+                [12345_i64.to_le_bytes(), (-1_i64).to_le_bytes()].concat(),
+            ),
+            ("Enum8('a' = 1, 'b\\'c' = -2)", vec![1, 0xfe]),
+            (
+                "Enum16('a' = 1, 'b' = 1000)",
+                [1_i16.to_le_bytes(), 1000_i16.to_le_bytes()].concat(),
+            ),
+            ("IPv4", vec![1, 0, 0, 127, 8, 8, 8, 8]),
+            ("IPv6", (0..32).collect()),
+            ("UUID", (0..32).collect()),
+            ("Nullable(String)", vec![0, 1, 1, b'a', 0]),
+            ("Nullable(Nothing)", vec![1, 1, 0, 0]),
+            (
+                "Array(UInt8)",
+                [1_u64, 3]
+                    .iter()
+                    .flat_map(|offset| offset.to_le_bytes().to_vec())
+                    .chain(vec![1, 2, 3])
+                    .collect(),
+            ),
+            (
+                "Array(Array(Nullable(Int8)))",
+                [1_u64, 2, 1, 3]
+                    .iter()
+                    .flat_map(|offset| offset.to_le_bytes().to_vec())
+                    .chain(vec![0, 1, 0, 7, 0, 9])
+                    .collect(),
+            ),
+        ];
+
+        for (type_name, data) in columns {
+            let source = native_block("c", type_name, 2, &data);
+            let mut reader = &source[..];
+            let block = Block::read_native(&mut reader, Tz::UTC, 0).unwrap();
+            assert!(reader.is_empty(), "{}", type_name);
+            assert_eq!(block.row_count(), 2, "{}", type_name);
+
+            let mut target = Vec::new();
+            block.write_native(&mut target, 0).unwrap();
+            assert_eq!(target, source, "{}", type_name);
+        }
+    }
+
+    #[test]
+    fn test_native_revisions() {
+        let block = Block::<Simple>::new()
+            .column("x", vec![1_u32, 2])
+            .column("s", vec![Some("a"), None]);
+
+        for &revision in &[
+            0,
+            54213,
+            protocol::DBMS_MIN_REVISION_WITH_CUSTOM_SERIALIZATION,
+        ] {
+            let mut target = Vec::new();
+            block.write_native(&mut target, revision).unwrap();
+            assert_eq!(
+                Block::read_native(&mut &target[..], Tz::UTC, revision).unwrap(),
+                block
+            );
+        }
+
+        // The protocol writes blocks of the client revision.
+        let mut encoder = Encoder::new();
+        block.write(&mut encoder, false);
+        let mut target = Vec::new();
+        block.write_native(&mut target, 54213).unwrap();
+        assert_eq!(encoder.get_buffer(), target);
+
+        // Columns with a custom serialization are rejected.
+        let mut source = native_block("c", "UInt8", 1, &[1, 7]);
+        source.splice(0..0, vec![1, 0, 2, 0xff, 0xff, 0xff, 0xff, 0]);
+        let revision = protocol::DBMS_MIN_REVISION_WITH_CUSTOM_SERIALIZATION;
+        assert!(Block::read_native(&mut &source[..], Tz::UTC, revision).is_err());
+    }
+
+    #[test]
+    fn test_compression_threshold() {
+        let small = Block::<Simple>::new().column("x", vec![1_u32, 2]);
+        let large = Block::<Simple>::new().column("x", (0..1000_u32).collect::<Vec<_>>());
+
+        for block in &[small, large] {
+            let mut encoder = Encoder::new();
+            block.send_data(&mut encoder, Some(1000), 1_048_576);
+            let source = encoder.get_buffer();
+
+            // Packet type, empty table name, checksum, then the method.
+            let method = source[2 + 16];
+            let expected = if block.row_count() == 2 {
+                compressed::METHOD_NONE
+            } else {
+                compressed::METHOD_LZ4
+            };
+            assert_eq!(method, expected);
+
+            let mut reader = Cursor::new(&source[2..]);
+            let read = Block::load(&mut reader, Tz::Zulu, None, true).unwrap();
+            assert_eq!(&read, block);
+        }
+    }
+
+    #[test]
+    fn test_load_lazy() {
+        let block = Block::<Simple>::new()
+            .column("n", vec![1_u64, 2, 3])
+            .column("s", vec![Some("one"), None, Some("three")])
+            .column("a", vec![vec![1_i32, 2], vec![], vec![3]])
+            .column("f", vec![0.5_f64, 1.5, 2.5]);
+
+        for &compress in &[false, true] {
+            let mut encoder = Encoder::new();
+            block.write(&mut encoder, compress);
+            let source = encoder.get_buffer();
+
+            let mut reader = Cursor::new(&source[..]);
+            let lazy = Block::load_lazy(&mut reader, Tz::Zulu, None, compress).unwrap();
+            assert_eq!(reader.position() as usize, source.len());
+
+            assert_eq!(lazy.schema(), block.schema());
+            assert_eq!(lazy.row_count(), 3);
+            assert_eq!(lazy.get::<Option<&str>, _>(2, "s").unwrap(), Some("three"));
+            assert_eq!(lazy, block);
+
+            let mut truncated = Cursor::new(&source[..source.len() - 1]);
+            assert!(Block::load_lazy(&mut truncated, Tz::Zulu, None, compress).is_err());
+        }
+    }
+
+    #[test]
+    fn test_column_major_block() {
+        let mut ids = VectorColumnData::with_capacity(3);
+        for id in 1..=3_u32 {
+            ids.append(id);
+        }
+
+        let mut names = StringColumnData::with_capacity(3);
+        names.append_iter(vec!["one", "two"]);
+        names.append("three");
+
+        let block = Block::<Simple>::new()
+            .try_add_column("id", ids)
+            .unwrap()
+            .try_add_column("name", names)
+            .unwrap()
+            .try_add_column("score", VectorColumnData::from(vec![0.5_f64, 1.5, 2.5]))
+            .unwrap();
+
+        let expected = Block::<Simple>::new()
+            .column("id", vec![1_u32, 2, 3])
+            .column("name", vec!["one", "two", "three"])
+            .column("score", vec![0.5_f64, 1.5, 2.5]);
+        assert_eq!(block, expected);
+    }
+
+    #[test]
+    fn test_to_values_clause() {
+        use chrono::TimeZone;
+
+        let block = Block::<Simple>::new()
+            .column("id", vec![1_u32, 2])
+            .column("name", vec!["o'brien", "back\\slash"])
+            .column("tags", vec![vec!["a".to_string(), "b".to_string()], vec![]])
+            .column(
+                "day",
+                vec![Tz::UTC.ymd(2020, 1, 2), Tz::UTC.ymd(1999, 12, 31)],
+            )
+            .column("score", vec![Some(1.5_f64), None]);
+
+        assert_eq!(
+            block.to_values_clause(),
+            "(1, 'o\\'brien', ['a', 'b'], toDate('2020-01-02'), 1.5), \
+             (2, 'back\\\\slash', [], toDate('1999-12-31'), NULL)"
+        );
+        assert_eq!(Block::<Simple>::new().to_values_clause(), "");
+    }
+
+    #[test]
+    fn test_add_column_length_mismatch() {
+        let block = Block::<Simple>::new().column("id", vec![1_u32, 2, 3]);
+
+        let err = block
+            .try_add_column("name", vec!["one", "two"])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Driver error: `Column `name` has 2 rows but the block has 3.`"
+        );
+    }
+
+    fn header(sql_type: SqlType) -> Block {
+        let data =
+            <dyn column::ColumnData>::from_type::<ArcColumnWrapper>(sql_type, Tz::Zulu, 0).unwrap();
+        let mut header = Block::new();
+        header.append_column(column::new_column("v", data));
+        header
+    }
+
+    #[test]
+    fn test_cast_schema_mismatch() {
+        let mut header = Block::new();
+        for (name, sql_type) in [
+            ("a", SqlType::UInt32),
+            ("b", SqlType::Nullable(SqlType::String.into())),
+            ("c", SqlType::Float64),
+            ("d", SqlType::String),
+        ] {
+            let data =
+                <dyn column::ColumnData>::from_type::<ArcColumnWrapper>(sql_type, Tz::Zulu, 0)
+                    .unwrap();
+            header.append_column(column::new_column(name, data));
+        }
+
+        let block = Block::new()
+            .column("c", vec![1.5_f64])
+            .column("a", vec![1_u32])
+            .column("b", vec!["x"])
+            .column("e", vec![1_u8]);
+
+        match block.clone().cast_to(&header, false).unwrap_err() {
+            Error::SchemaMismatch { problems } => assert_eq!(
+                problems,
+                vec![
+                    SchemaProblem::TypeMismatch {
+                        name: "b".into(),
+                        expected: "Nullable(String)".into(),
+                        actual: "String".into(),
+                    },
+                    SchemaProblem::OutOfOrder { name: "c".into() },
+                    SchemaProblem::MissingColumn {
+                        name: "d".into(),
+                        sql_type: "String".into(),
+                    },
+                    SchemaProblem::ExtraColumn { name: "e".into() },
+                ]
+            ),
+            err => panic!("{:?}", err),
+        }
+
+        let block = Block::new()
+            .column("d", vec!["y"])
+            .column("c", vec![1.5_f64])
+            .column("b", vec![Some("x")])
+            .column("a", vec![1_u32]);
+        assert!(block.clone().cast_to(&header, false).is_err());
+
+        let reordered = block.cast_to(&header, true).unwrap();
+        let names: Vec<_> = reordered.columns().iter().map(|c| c.name()).collect();
+        assert_eq!(names, ["a", "b", "c", "d"]);
+        assert_eq!(reordered.get::<u32, _>(0, "a").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_cast_ip_strings() {
+        use std::net::{Ipv4Addr, Ipv6Addr};
+
+        let mut header = Block::new();
+        for (name, sql_type) in [
+            ("v4", SqlType::Ipv4),
+            ("v6", SqlType::Nullable(SqlType::Ipv6.into())),
+        ] {
+            let data =
+                <dyn column::ColumnData>::from_type::<ArcColumnWrapper>(sql_type, Tz::Zulu, 0)
+                    .unwrap();
+            header.append_column(column::new_column(name, data));
+        }
+
+        let block = Block::new()
+            .column("v4", vec!["10.0.0.1", "127.0.0.1"])
+            .column("v6", vec![Some("::1"), None]);
+        let casted = block.cast_to(&header, false).unwrap();
+        assert_eq!(
+            casted.get::<Ipv4Addr, _>(0, "v4").unwrap(),
+            Ipv4Addr::new(10, 0, 0, 1)
+        );
+        assert_eq!(
+            casted.get::<Option<Ipv6Addr>, _>(0, "v6").unwrap(),
+            Some(Ipv6Addr::LOCALHOST)
+        );
+        assert_eq!(casted.get::<Option<Ipv6Addr>, _>(1, "v6").unwrap(), None);
+
+        let block = Block::new()
+            .column("v4", vec!["10.0.0.1", "10.0.0.256"])
+            .column("v6", vec![Some("::1"), None]);
+        match block.cast_to(&header, false).unwrap_err() {
+            Error::Other(message) => assert_eq!(
+                message,
+                "value `10.0.0.256` of column `v4` at row 1 is not a valid IPv4 address."
+            ),
+            err => panic!("{:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_cast_duplicate_columns() {
+        let block = Block::new()
+            .column("v", vec![1_u32])
+            .column("v", vec![2_u32])
+            .column("w", vec![3_u32]);
+
+        match block.cast_to(&header(SqlType::UInt32), false).unwrap_err() {
+            Error::SchemaMismatch { problems } => {
+                assert_eq!(problems.len(), 2, "{:?}", problems);
+                assert_eq!(
+                    problems[0].to_string(),
+                    "column \"v\" is given more than once"
+                );
+                assert_eq!(problems[1].to_string(), "column \"w\" is not in the table");
+            }
+            err => panic!("{:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_cast_out_of_range_decimal() {
+        use crate::types::{Decimal, OverflowMode};
+
+        let values = vec![Decimal::of(1.5_f64, 2), Decimal::of(-1234.56_f64, 2)];
+        let block = Block::new().column("v", values);
+        let header = header(SqlType::Decimal(5, 2));
+
+        match block.clone().cast_to(&header, false).unwrap_err() {
+            Error::ValueOutOfRange {
+                column,
+                row_index,
+                value,
+                target_type,
+            } => {
+                assert_eq!(column, "v");
+                assert_eq!(row_index, 1);
+                assert_eq!(value, "-1234.56");
+                assert_eq!(target_type, "Decimal(5, 2)");
+            }
+            err => panic!("{:?}", err),
+        }
+
+        let saturated = block
+            .clone()
+            .with_overflow_mode(OverflowMode::Saturating)
+            .cast_to(&header, false)
+            .unwrap();
+        assert_eq!(saturated.columns()[0].sql_type(), SqlType::Decimal(5, 2));
+        assert_eq!(
+            saturated.get::<Decimal, _>(0, "v").unwrap(),
+            Decimal::of(1.5_f64, 2)
+        );
+        assert_eq!(
+            saturated.get::<Decimal, _>(1, "v").unwrap(),
+            Decimal::of(-999.99_f64, 2)
+        );
+
+        assert!(block
+            .with_overflow_mode(OverflowMode::Wrapping)
+            .cast_to(&header, false)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_cast_out_of_range_datetime() {
+        use crate::types::{DateTimeType, OverflowMode};
+        use chrono::prelude::*;
+
+        let times = vec![
+            Some(Tz::UTC.with_ymd_and_hms(2200, 1, 1, 0, 0, 0).unwrap()),
+            None,
+        ];
+        let block = Block::new().column("v", times);
+        let header = header(SqlType::Nullable(
+            SqlType::DateTime(DateTimeType::DateTime32).into(),
+        ));
+
+        let err = block.clone().cast_to(&header, false).unwrap_err();
+        assert!(
+            matches!(err, Error::ValueOutOfRange { row_index: 0, .. }),
+            "{:?}",
+            err
+        );
+
+        let saturated = block
+            .with_overflow_mode(OverflowMode::Saturating)
+            .cast_to(&header, false)
+            .unwrap();
+        let times: Vec<Option<DateTime<Tz>>> =
+            (0..2).map(|row| saturated.get(row, "v").unwrap()).collect();
+        assert_eq!(
+            times,
+            vec![
+                Some(Tz::UTC.timestamp_opt(i64::from(u32::MAX), 0).unwrap()),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cast_datetime64_rounding() {
+        use crate::types::{DateTimeType, RoundingMode};
+        use chrono::prelude::*;
+        use std::convert::TryInto;
+
+        let time = Tz::UTC.timestamp_opt(1_546_300_800, 123_856_789).unwrap();
+        let block = Block::new().column("v", vec![time]);
+        let millis_header = header(SqlType::DateTime(DateTimeType::DateTime64(3, Tz::UTC)));
+        // Chrono columns are converted when saved, so the ticks are read
+        // back from what would be sent to the server.
+        let millis = |block: &Block| {
+            let mut encoder = Encoder::new();
+            block.get_column("v").unwrap().data.save(&mut encoder, 0, 1);
+            i64::from_le_bytes(encoder.get_buffer()[..].try_into().unwrap())
+        };
+
+        let truncated = block.clone().cast_to(&millis_header, false).unwrap();
+        assert_eq!(millis(&truncated), 1_546_300_800_123);
+
+        let rounded = block
+            .clone()
+            .with_rounding_mode(RoundingMode::HalfUp)
+            .cast_to(&millis_header, false)
+            .unwrap();
+        assert_eq!(millis(&rounded), 1_546_300_800_124);
+
+        let err = block
+            .with_rounding_mode(RoundingMode::Checked)
+            .cast_to(&millis_header, false)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Value 2019-01-01 00:00:00.123856789 in row 0 of column \"v\" is out of range for DateTime64(3, 'UTC')"
+        );
+
+        let exact = Block::new()
+            .with_rounding_mode(RoundingMode::Checked)
+            .column(
+                "v",
+                vec![Some(Tz::UTC.timestamp_opt(1, 5_000_000).unwrap()), None],
+            );
+        let nullable_header = header(SqlType::Nullable(
+            SqlType::DateTime(DateTimeType::DateTime64(3, Tz::UTC)).into(),
+        ));
+        let cast = exact.cast_to(&nullable_header, false).unwrap();
+        let times: Vec<Option<DateTime<Tz>>> =
+            (0..2).map(|row| cast.get(row, "v").unwrap()).collect();
+        assert_eq!(
+            times,
+            vec![Some(Tz::UTC.timestamp_opt(1, 5_000_000).unwrap()), None]
+        );
+    }
+
+    #[test]
+    fn test_date_column_out_of_range() {
+        use chrono::prelude::*;
+
+        let dates = vec![Tz::UTC.ymd(2000, 1, 1), Tz::UTC.ymd(2150, 1, 1)];
+        let err = Block::<Simple>::new()
+            .try_add_column("d", dates)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Value 2150-01-01 in row 1 of column \"d\" is out of range for Date"
+        );
+
+        let nested = vec![vec![], vec![Tz::UTC.ymd(1960, 1, 1)]];
+        assert!(Block::<Simple>::new().try_add_column("d", nested).is_err());
+    }
+
+    #[test]
+    fn test_take_and_filter() {
+        use chrono::prelude::*;
+
+        let dates = vec![
+            Tz::Asia__Tokyo.ymd(2020, 1, 1),
+            Tz::Asia__Tokyo.ymd(2020, 1, 2),
+            Tz::Asia__Tokyo.ymd(2020, 1, 3),
+        ];
+        let block = Block::new()
+            .column("id", vec![1_u32, 2, 3])
+            .column("name", vec!["a", "b", "c"])
+            .column("score", vec![Some(1.5_f64), None, Some(3.5)])
+            .column("tags", vec![vec!["x", "z"], vec![], vec!["y"]])
+            .column("day", dates.clone());
+
+        let taken = block.take(&[2, 0, 2]).unwrap();
+        assert_eq!(taken.row_count(), 3);
+        assert_eq!(taken.schema(), block.schema());
+        let ids: Vec<u32> = (0..3).map(|row| taken.get(row, "id").unwrap()).collect();
+        assert_eq!(ids, vec![3, 1, 3]);
+        assert_eq!(taken.get::<&str, _>(1, "name").unwrap(), "a");
+        assert_eq!(taken.get::<Option<f64>, _>(0, "score").unwrap(), Some(3.5));
+        assert_eq!(
+            taken.get::<Vec<&str>, _>(1, "tags").unwrap(),
+            vec!["x", "z"]
+        );
+        assert_eq!(taken.get::<Date<Tz>, _>(0, "day").unwrap(), dates[2]);
+        assert_eq!(
+            taken.get::<Value, _>(0, "day").unwrap(),
+            Value::from(dates[2])
+        );
+
+        let kept = block.filter(&[false, true, true]).unwrap();
+        assert_eq!(kept, block.take(&[1, 2]).unwrap());
+        assert_eq!(
+            kept.get_column("score").unwrap().nulls(),
+            Some(&[1_u8, 0][..])
+        );
+        assert_eq!(
+            kept.get_column("tags")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .offsets(),
+            &[0, 1]
+        );
+
+        assert_eq!(block.slice(1..3).unwrap(), kept);
+        assert_eq!(block.head(10).unwrap(), block);
+        assert_eq!(block.head(0).unwrap().row_count(), 0);
+        assert_eq!(block.filter(&[false; 3]).unwrap().column_count(), 5);
+
+        match block.filter(&[true]).unwrap_err() {
+            Error::Driver(DriverError::MaskLength { mask: 1, rows: 3 }) => {}
+            err => panic!("unexpected error {:?}", err),
+        }
+        match block.take(&[0, 3]).unwrap_err() {
+            Error::Driver(DriverError::RowOutOfRange { index: 3, rows: 3 }) => {}
+            err => panic!("unexpected error {:?}", err),
+        }
+        assert!(block.slice(2..4).is_err());
+    }
+
+    #[test]
+    fn test_validate_column_lengths() {
+        let mut block = Block::new();
+        block
+            .push(vec![
+                ("id".to_string(), Value::UInt32(1)),
+                ("name".to_string(), Value::from("a")),
+            ])
+            .unwrap();
+        assert!(block.validate().is_ok());
+
+        block
+            .push(vec![("id".to_string(), Value::UInt32(2))])
+            .unwrap();
+        let header = Block::new()
+            .column("id", Vec::<u32>::new())
+            .column("name", Vec::<&str>::new());
+        for err in [
+            block.validate().unwrap_err(),
+            block.cast_to(&header, false).unwrap_err(),
+        ] {
+            match err {
+                Error::Driver(DriverError::ColumnLength {
+                    name,
+                    rows,
+                    expected,
+                }) => {
+                    assert_eq!((name.as_str(), rows, expected), ("name", 1, 2));
+                }
+                err => panic!("unexpected error {:?}", err),
+            }
+        }
+
+        assert!(Block::new().validate().is_ok());
+    }
+
+    #[test]
+    fn test_sort_by() {
+        use crate::types::{Decimal, SortColumn};
+
+        let block = Block::new()
+            .column("team", vec!["b", "a", "b", "a", "a"])
+            .column(
+                "score",
+                vec![Some(1.5_f64), None, Some(3.0), Some(2.0), None],
+            )
+            .column("id", vec![1_u32, 2, 3, 4, 5])
+            .column(
+                "price",
+                [1.5_f64, 0.25, 10.0, -1.0, 0.5]
+                    .iter()
+                    .map(|&p| Decimal::of(p, 2))
+                    .collect::<Vec<_>>(),
+            )
+            .column("tags", vec![vec![1_u8], vec![], vec![2], vec![3], vec![]]);
+
+        let ids = |block: &Block| -> Vec<u32> {
+            (0..block.row_count())
+                .map(|row| block.get(row, "id").unwrap())
+                .collect()
+        };
+
+        let keys = [SortColumn::asc("team"), SortColumn::asc("score")];
+        assert_eq!(ids(&block.sort_by(&keys).unwrap()), vec![4, 2, 5, 1, 3]);
+
+        let keys = [
+            SortColumn::asc("team"),
+            SortColumn::desc("score").nulls_first(true),
+        ];
+        assert_eq!(ids(&block.sort_by(&keys).unwrap()), vec![2, 5, 4, 3, 1]);
+
+        let keys = [SortColumn::desc("score")];
+        assert_eq!(ids(&block.sort_by(&keys).unwrap()), vec![3, 4, 1, 2, 5]);
+
+        let sorted = block.sort_by(&[SortColumn::asc("price")]).unwrap();
+        assert_eq!(ids(&sorted), vec![4, 2, 5, 1, 3]);
+        assert_eq!(sorted.get::<Vec<u8>, _>(0, "tags").unwrap(), vec![3]);
+
+        assert_eq!(block.sort_by(&[]).unwrap(), block);
+        assert!(block.sort_by(&[SortColumn::asc("missing")]).is_err());
+        match block.sort_by(&[SortColumn::asc("tags")]).unwrap_err() {
+            Error::Driver(DriverError::UnsortableColumn { name, sql_type }) => {
+                assert_eq!((name.as_str(), sql_type.as_str()), ("tags", "Array(UInt8)"));
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
 }
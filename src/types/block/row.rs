@@ -14,6 +14,9 @@ pub struct Row<'a, K: ColumnType> {
 
 impl<'a, K: ColumnType> Row<'a, K> {
     /// Get the value of a particular cell of the row.
+    ///
+    /// `String` and `FixedString` values can be borrowed as `&str` or `&[u8]`
+    /// without copying; ask for a `String` or `Vec<u8>` to get an owned copy.
     pub fn get<T, I>(&'a self, col: I) -> Result<T>
     where
         T: FromSql<'a>,
@@ -85,14 +88,8 @@ impl<'a, K: ColumnType> BlockRef<'a, K> {
 
     fn get_column<I: ColumnIdx + Copy>(&self, col: I) -> Result<&Column<K>> {
         match self {
-            BlockRef::Borrowed(block) => {
-                let column_index = col.get_index(block.columns())?;
-                Ok(&block.columns[column_index])
-            }
-            BlockRef::Owned(block) => {
-                let column_index = col.get_index(block.columns())?;
-                Ok(&block.columns[column_index])
-            }
+            BlockRef::Borrowed(block) => block.get_column(col),
+            BlockRef::Owned(block) => block.get_column(col),
         }
     }
 }
@@ -0,0 +1,183 @@
+use std::cmp::Ordering;
+
+use crate::{
+    errors::{DriverError, Error, Result},
+    types::{column::Either, Block, Column, ColumnType, SqlType, ValueRef},
+};
+
+/// A sort key of `Block::sort_by`: the column to sort by, its direction and
+/// where its `NULL`s go.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SortColumn {
+    pub name: String,
+    pub descending: bool,
+    /// Puts `NULL`s before every other value instead of after, whatever the
+    /// direction is.
+    pub nulls_first: bool,
+}
+
+impl SortColumn {
+    /// Sorts by `name` in ascending order with `NULL`s last.
+    pub fn asc(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            descending: false,
+            nulls_first: false,
+        }
+    }
+
+    /// Sorts by `name` in descending order with `NULL`s last.
+    pub fn desc(name: &str) -> Self {
+        Self {
+            descending: true,
+            ..Self::asc(name)
+        }
+    }
+
+    /// Puts `NULL`s first if `enabled`.
+    pub fn nulls_first(self, enabled: bool) -> Self {
+        Self {
+            nulls_first: enabled,
+            ..self
+        }
+    }
+
+    fn compare(&self, a: &Option<ValueRef>, b: &Option<ValueRef>) -> Ordering {
+        match (a, b) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) if self.nulls_first => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) if self.nulls_first => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a), Some(b)) if self.descending => compare_values(b, a),
+            (Some(a), Some(b)) => compare_values(a, b),
+        }
+    }
+}
+
+/// Returns the order of the rows of `block` sorted by `keys`, rows equal on
+/// every key keep their order.
+pub(super) fn sorted_rows<K: ColumnType>(
+    block: &Block<K>,
+    keys: &[SortColumn],
+) -> Result<Vec<usize>> {
+    let mut values = Vec::with_capacity(keys.len());
+    for key in keys {
+        let column = &block.columns[block.column_index(key.name.as_str())?];
+        values.push(key_values(column)?);
+    }
+
+    let mut rows: Vec<usize> = (0..block.row_count()).collect();
+    rows.sort_by(|&a, &b| {
+        keys.iter()
+            .zip(&values)
+            .map(|(key, values)| key.compare(&values[a], &values[b]))
+            .find(|&ordering| ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    });
+    Ok(rows)
+}
+
+/// Reads the values of a sort key, `None` for its `NULL`s.
+fn key_values<K: ColumnType>(column: &Column<K>) -> Result<Vec<Option<ValueRef<'_>>>> {
+    let sql_type = column.sql_type();
+    let inner_type = match &sql_type {
+        SqlType::Nullable(inner) => *inner,
+        sql_type => sql_type,
+    };
+
+    match inner_type {
+        SqlType::UInt8
+        | SqlType::UInt16
+        | SqlType::UInt32
+        | SqlType::UInt64
+        | SqlType::Int8
+        | SqlType::Int16
+        | SqlType::Int32
+        | SqlType::Int64
+        | SqlType::Float32
+        | SqlType::Float64
+        | SqlType::String
+        | SqlType::FixedString(_)
+        | SqlType::Date
+        | SqlType::DateTime(_)
+        | SqlType::Decimal(..)
+        | SqlType::Enum8(_)
+        | SqlType::Enum16(_)
+        | SqlType::Ipv4
+        | SqlType::Ipv6 => {}
+        _ => {
+            return Err(Error::Driver(DriverError::UnsortableColumn {
+                name: column.name().to_string(),
+                sql_type: sql_type.to_string().into_owned(),
+            }))
+        }
+    }
+
+    Ok((0..column.len())
+        .map(|index| match column.at(index) {
+            ValueRef::Nullable(Either::Left(_)) => None,
+            ValueRef::Nullable(Either::Right(value)) => Some(*value),
+            value => Some(value),
+        })
+        .collect())
+}
+
+/// Compares two values of a column of one of the types `key_values` accepts.
+/// `NaN` is greater than any other number.
+fn compare_values(a: &ValueRef, b: &ValueRef) -> Ordering {
+    match (a, b) {
+        (ValueRef::UInt8(a), ValueRef::UInt8(b)) => a.cmp(b),
+        (ValueRef::UInt16(a), ValueRef::UInt16(b)) => a.cmp(b),
+        (ValueRef::UInt32(a), ValueRef::UInt32(b)) => a.cmp(b),
+        (ValueRef::UInt64(a), ValueRef::UInt64(b)) => a.cmp(b),
+        (ValueRef::Int8(a), ValueRef::Int8(b)) => a.cmp(b),
+        (ValueRef::Int16(a), ValueRef::Int16(b)) => a.cmp(b),
+        (ValueRef::Int32(a), ValueRef::Int32(b)) => a.cmp(b),
+        (ValueRef::Int64(a), ValueRef::Int64(b)) => a.cmp(b),
+        (ValueRef::Float32(a), ValueRef::Float32(b)) => a
+            .partial_cmp(b)
+            .unwrap_or_else(|| a.is_nan().cmp(&b.is_nan())),
+        (ValueRef::Float64(a), ValueRef::Float64(b)) => a
+            .partial_cmp(b)
+            .unwrap_or_else(|| a.is_nan().cmp(&b.is_nan())),
+        (ValueRef::String(a), ValueRef::String(b)) => a.cmp(b),
+        (ValueRef::Date(a, _), ValueRef::Date(b, _)) => a.cmp(b),
+        (ValueRef::DateTime(a, _), ValueRef::DateTime(b, _)) => a.cmp(b),
+        (ValueRef::DateTime64(a, _), ValueRef::DateTime64(b, _)) => a.cmp(b),
+        (ValueRef::Decimal(a), ValueRef::Decimal(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        (ValueRef::Enum8(_, a), ValueRef::Enum8(_, b)) => a.0.cmp(&b.0),
+        (ValueRef::Enum16(_, a), ValueRef::Enum16(_, b)) => a.0.cmp(&b.0),
+        (ValueRef::Ipv4(a), ValueRef::Ipv4(b)) => a.cmp(b),
+        (ValueRef::Ipv6(a), ValueRef::Ipv6(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::Decimal;
+
+    #[test]
+    fn test_compare_values() {
+        let nan = ValueRef::Float64(f64::NAN);
+        assert_eq!(
+            compare_values(&ValueRef::Float64(1.0), &nan),
+            Ordering::Less
+        );
+        assert_eq!(compare_values(&nan, &nan), Ordering::Equal);
+
+        let a = ValueRef::Decimal(Decimal::of(1.5_f64, 1));
+        let b = ValueRef::Decimal(Decimal::of(1.25_f64, 2));
+        assert_eq!(compare_values(&a, &b), Ordering::Greater);
+
+        let key = SortColumn::desc("x");
+        let one = Some(ValueRef::UInt8(1));
+        assert_eq!(key.compare(&None, &one), Ordering::Greater);
+        assert_eq!(
+            key.clone().nulls_first(true).compare(&None, &one),
+            Ordering::Less
+        );
+    }
+}
@@ -2,7 +2,7 @@ use crate::{
     binary::{protocol, Encoder},
     client_info,
     errors::Result,
-    types::{Block, Context, Query, Simple, Options},
+    types::{query::TraceContext, Block, Context, Options, Query, Simple},
 };
 
 /// Represents clickhouse commands.
@@ -11,41 +11,46 @@ pub(crate) enum Cmd {
     Ping,
     SendQuery(Query, Context),
     SendData(Block, Context),
+    Cancel,
     Union(Box<Cmd>, Box<Cmd>),
 }
 
 impl Cmd {
-    /// Returns the packed command as a byte vector.
+    /// Returns the packed command as a byte vector, data split into blocks
+    /// of `insert_block_size` rows if set instead of
+    /// `Options::max_insert_block_size`.
     #[inline]
-    pub(crate) fn get_packed_command(&self) -> Result<Vec<u8>> {
-        encode_command(self)
+    pub(crate) fn get_packed_command(&self, insert_block_size: Option<usize>) -> Result<Vec<u8>> {
+        encode_command(self, insert_block_size)
     }
 }
 
-#[derive(Debug, PartialOrd, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialOrd, PartialEq)]
 enum SettingsBinaryFormat {
     Old,
     Strings,
 }
 
-fn encode_command(cmd: &Cmd) -> Result<Vec<u8>> {
+fn encode_command(cmd: &Cmd, insert_block_size: Option<usize>) -> Result<Vec<u8>> {
     match cmd {
         Cmd::Hello(context) => encode_hello(context),
         Cmd::Ping => encode_ping(),
         Cmd::SendQuery(query, context) => encode_query(query, context),
-        Cmd::SendData(block, context) => encode_data(&block, context),
-        Cmd::Union(first, second) => encode_union(first.as_ref(), second.as_ref()),
+        Cmd::SendData(block, context) => encode_data(block, context, insert_block_size),
+        Cmd::Cancel => encode_cancel(),
+        Cmd::Union(first, second) => {
+            encode_union(first.as_ref(), second.as_ref(), insert_block_size)
+        }
     }
 }
 
 fn encode_hello(context: &Context) -> Result<Vec<u8>> {
-    trace!("[hello]        -> {}", client_info::description());
+    let options = context.options.get()?;
+    trace!("[hello]        -> {}", client_info::description(&options));
 
     let mut encoder = Encoder::new();
     encoder.uvarint(protocol::CLIENT_HELLO);
-    client_info::write(&mut encoder);
-
-    let options = context.options.get()?;
+    client_info::write(&mut encoder, &options);
 
     encoder.string(&options.database);
     encoder.string(&options.username);
@@ -62,12 +67,20 @@ fn encode_ping() -> Result<Vec<u8>> {
     Ok(encoder.get_buffer())
 }
 
+fn encode_cancel() -> Result<Vec<u8>> {
+    trace!("[cancel]       -> cancel");
+
+    let mut encoder = Encoder::new();
+    encoder.uvarint(protocol::CLIENT_CANCEL);
+    Ok(encoder.get_buffer())
+}
+
 fn encode_query(query: &Query, context: &Context) -> Result<Vec<u8>> {
     trace!("[send query] {}", query.get_sql());
 
     let mut encoder = Encoder::new();
     encoder.uvarint(protocol::CLIENT_QUERY);
-    encoder.string("");
+    encoder.string(query.get_id());
 
     {
         let hostname = &context.hostname;
@@ -79,21 +92,34 @@ fn encode_query(query: &Query, context: &Context) -> Result<Vec<u8>> {
         encoder.string(hostname);
         encoder.string(hostname);
     }
-    client_info::write(&mut encoder);
+    let options = context.options.get()?;
 
-    if context.server_info.revision >= protocol::DBMS_MIN_REVISION_WITH_QUOTA_KEY_IN_CLIENT_INFO {
-        encoder.string("");
+    client_info::write(&mut encoder, &options);
+
+    let revision = context.server_info.protocol_revision();
+    if revision >= protocol::DBMS_MIN_REVISION_WITH_QUOTA_KEY_IN_CLIENT_INFO {
+        encoder.string(query.get_quota_key().unwrap_or(&options.quota_key));
     }
 
-    let options = context.options.get()?;
+    if revision >= protocol::DBMS_MIN_REVISION_WITH_VERSION_PATCH {
+        encoder.uvarint(client_info::CLICK_HOUSE_REVISION);
+    }
 
-    let settings_format = if context.server_info.revision >= protocol::DBMS_MIN_REVISION_WITH_SETTINGS_SERIALIZED_AS_STRINGS {
-        SettingsBinaryFormat::Strings
-    } else {
-        SettingsBinaryFormat::Old
-    };
+    if revision >= protocol::DBMS_MIN_REVISION_WITH_OPENTELEMETRY {
+        encode_trace_context(&mut encoder, query.get_trace_context());
+    }
 
-    serialize_settings(&mut encoder, &options, settings_format);
+    let settings_format =
+        if revision >= protocol::DBMS_MIN_REVISION_WITH_SETTINGS_SERIALIZED_AS_STRINGS {
+            SettingsBinaryFormat::Strings
+        } else {
+            SettingsBinaryFormat::Old
+        };
+
+    serialize_settings(&mut encoder, &options, query, settings_format);
+    if revision >= protocol::DBMS_MIN_REVISION_WITH_INTERSERVER_SECRET {
+        encoder.string(""); // interserver secret
+    }
     encoder.uvarint(protocol::STATE_COMPLETE);
 
     encoder.uvarint(if options.compression {
@@ -105,12 +131,51 @@ fn encode_query(query: &Query, context: &Context) -> Result<Vec<u8>> {
     let options = context.options.get()?;
 
     encoder.string(&query.get_sql());
-    Block::<Simple>::default().send_data(&mut encoder, options.compression);
+    for (name, block) in query.get_external_tables() {
+        block.send_table(
+            name,
+            &mut encoder,
+            compression(&options),
+            options.max_insert_block_size,
+        );
+    }
+    Block::<Simple>::default().send_data(
+        &mut encoder,
+        compression(&options),
+        options.max_insert_block_size,
+    );
 
     Ok(encoder.get_buffer())
 }
 
-fn serialize_settings(encoder: &mut Encoder, options: &Options, format: SettingsBinaryFormat) {
+fn encode_trace_context(encoder: &mut Encoder, trace_context: Option<TraceContext>) {
+    match trace_context {
+        Some(trace) if trace.trace_id != 0 => {
+            encoder.write(1_u8);
+            // A UUID, the high half first.
+            encoder.write((trace.trace_id >> 64) as u64);
+            encoder.write(trace.trace_id as u64);
+            encoder.write(trace.span_id);
+            encoder.string(""); // tracestate
+            encoder.write(trace.flags);
+        }
+        _ => encoder.write(0_u8),
+    }
+}
+
+fn serialize_settings(
+    encoder: &mut Encoder,
+    options: &Options,
+    query: &Query,
+    format: SettingsBinaryFormat,
+) {
+    if let Some(level) = query.get_logs_level() {
+        encoder.string("send_logs_level");
+        if format >= SettingsBinaryFormat::Strings {
+            encoder.write(0_u8); // is_important
+        }
+        encoder.string(level.as_setting());
+    }
 
     if let Some(level) = options.readonly {
         encoder.string("readonly");
@@ -120,6 +185,28 @@ fn serialize_settings(encoder: &mut Encoder, options: &Options, format: Settings
         serialize_uint(encoder, level as u64, format);
     }
 
+    if let Some(rows) = query.get_max_block_size() {
+        encoder.string("max_block_size");
+        if format >= SettingsBinaryFormat::Strings {
+            encoder.write(0_u8); // is_important
+        }
+        serialize_uint(encoder, rows as u64, format);
+    }
+
+    let flags = [
+        ("async_insert", query.get_async_insert()),
+        ("wait_for_async_insert", query.get_wait_for_async_insert()),
+    ];
+    for (name, value) in flags.iter() {
+        if let Some(value) = value {
+            encoder.string(name);
+            if format >= SettingsBinaryFormat::Strings {
+                encoder.write(0_u8); // is_important
+            }
+            serialize_uint(encoder, *value as u64, format);
+        }
+    }
+
     encoder.string(""); // settings
 }
 
@@ -132,15 +219,252 @@ fn serialize_uint(encoder: &mut Encoder, value: u64, format: SettingsBinaryForma
     encoder.uvarint(value);
 }
 
-fn encode_data(block: &Block, context: &Context) -> Result<Vec<u8>> {
+fn encode_data(
+    block: &Block,
+    context: &Context,
+    insert_block_size: Option<usize>,
+) -> Result<Vec<u8>> {
     let mut encoder = Encoder::new();
     let options = context.options.get()?;
-    block.send_data(&mut encoder, options.compression);
+    let block_rows = insert_block_size.unwrap_or(options.max_insert_block_size);
+    block.send_data(&mut encoder, compression(&options), block_rows);
     Ok(encoder.get_buffer())
 }
 
-fn encode_union(first: &Cmd, second: &Cmd) -> Result<Vec<u8>> {
-    let mut result = encode_command(first)?;
-    result.extend((encode_command(second)?).iter());
+/// The size from which data blocks are compressed, `None` if compression
+/// is off.
+fn compression(options: &Options) -> Option<usize> {
+    if options.compression {
+        Some(options.compression_min_block_bytes)
+    } else {
+        None
+    }
+}
+
+fn encode_union(first: &Cmd, second: &Cmd, insert_block_size: Option<usize>) -> Result<Vec<u8>> {
+    let mut result = encode_command(first, insert_block_size)?;
+    result.extend((encode_command(second, insert_block_size)?).iter());
     Ok(result)
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::{
+        binary::ReadEx,
+        types::{IntoOptions, ServerInfo},
+    };
+
+    use super::*;
+
+    /// Reads the client name, version and quota key of an encoded query.
+    fn read_client_info(packet: &[u8]) -> (String, u64, u64, String) {
+        skip_client_info(&mut Cursor::new(packet))
+    }
+
+    /// Reads an encoded query up to and including the quota key.
+    fn skip_client_info(reader: &mut Cursor<&[u8]>) -> (String, u64, u64, String) {
+        assert_eq!(reader.read_uvarint().unwrap(), protocol::CLIENT_QUERY);
+        reader.skip_string().unwrap(); // query id
+        assert_eq!(reader.read_uvarint().unwrap(), 1); // initial query
+        for _ in 0..3 {
+            reader.skip_string().unwrap(); // initial user, query id and address
+        }
+        assert_eq!(reader.read_uvarint().unwrap(), 1); // tcp
+        reader.skip_string().unwrap(); // os user
+        reader.skip_string().unwrap(); // hostname
+
+        let name = reader.read_string().unwrap();
+        let major = reader.read_uvarint().unwrap();
+        let minor = reader.read_uvarint().unwrap();
+        assert_eq!(
+            reader.read_uvarint().unwrap(),
+            client_info::CLICK_HOUSE_REVISION
+        );
+        let quota_key = reader.read_string().unwrap();
+        (name, major, minor, quota_key)
+    }
+
+    fn context(options: Options) -> Context {
+        Context {
+            server_info: ServerInfo {
+                revision: client_info::CLICK_HOUSE_REVISION,
+                ..ServerInfo::default()
+            },
+            options: options.into_options_src(),
+            ..Context::default()
+        }
+    }
+
+    #[test]
+    fn test_hello_client_name() {
+        let options = Options::default()
+            .client_name("my-service/1.2.3")
+            .client_version(1, 2)
+            .database("metrics")
+            .username("reader");
+        let packet = encode_hello(&context(options)).unwrap();
+
+        let mut reader = Cursor::new(&packet[..]);
+        assert_eq!(reader.read_uvarint().unwrap(), protocol::CLIENT_HELLO);
+        assert_eq!(reader.read_string().unwrap(), "my-service/1.2.3");
+        assert_eq!(reader.read_uvarint().unwrap(), 1);
+        assert_eq!(reader.read_uvarint().unwrap(), 2);
+        assert_eq!(
+            reader.read_uvarint().unwrap(),
+            client_info::CLICK_HOUSE_REVISION
+        );
+        assert_eq!(reader.read_string().unwrap(), "metrics");
+        assert_eq!(reader.read_string().unwrap(), "reader");
+    }
+
+    #[test]
+    fn test_client_info() {
+        let query = Query::new("SELECT 1");
+
+        let packet = encode_query(&query, &context(Options::default())).unwrap();
+        assert_eq!(
+            read_client_info(&packet),
+            ("Rust SQLDriver".into(), 1, 1, "".into())
+        );
+
+        let options = Options::default()
+            .client_name("billing")
+            .client_version(2, 7)
+            .quota_key("tenant-1");
+        let packet = encode_query(&query, &context(options.clone())).unwrap();
+        assert_eq!(
+            read_client_info(&packet),
+            ("billing".into(), 2, 7, "tenant-1".into())
+        );
+
+        let query = query.quota_key("tenant-2");
+        let packet = encode_query(&query, &context(options)).unwrap();
+        assert_eq!(read_client_info(&packet).3, "tenant-2");
+    }
+
+    #[test]
+    fn test_async_insert_settings() {
+        let query = Query::new("INSERT INTO t (a) VALUES")
+            .async_insert(true)
+            .wait_for_async_insert(false);
+        let options = Options::default();
+
+        let mut encoder = Encoder::new();
+        serialize_settings(&mut encoder, &options, &query, SettingsBinaryFormat::Old);
+        let mut expected = Encoder::new();
+        expected.string("async_insert");
+        expected.uvarint(1);
+        expected.string("wait_for_async_insert");
+        expected.uvarint(0);
+        expected.string("");
+        assert_eq!(encoder.get_buffer(), expected.get_buffer());
+
+        let mut encoder = Encoder::new();
+        serialize_settings(
+            &mut encoder,
+            &options,
+            &query,
+            SettingsBinaryFormat::Strings,
+        );
+        let mut expected = Encoder::new();
+        expected.string("async_insert");
+        expected.write(0_u8);
+        expected.string("1");
+        expected.string("wait_for_async_insert");
+        expected.write(0_u8);
+        expected.string("0");
+        expected.string("");
+        assert_eq!(encoder.get_buffer(), expected.get_buffer());
+
+        let mut encoder = Encoder::new();
+        serialize_settings(
+            &mut encoder,
+            &options,
+            &Query::new("SELECT 1"),
+            SettingsBinaryFormat::Old,
+        );
+        let mut expected = Encoder::new();
+        expected.string("");
+        assert_eq!(encoder.get_buffer(), expected.get_buffer());
+    }
+
+    #[test]
+    fn test_max_block_size_setting() {
+        let query = Query::new("SELECT number FROM numbers(10)").max_block_size(1000);
+        let options = Options::default();
+
+        let mut encoder = Encoder::new();
+        serialize_settings(&mut encoder, &options, &query, SettingsBinaryFormat::Old);
+        let mut expected = Encoder::new();
+        expected.string("max_block_size");
+        expected.uvarint(1000);
+        expected.string("");
+        assert_eq!(encoder.get_buffer(), expected.get_buffer());
+
+        let mut encoder = Encoder::new();
+        serialize_settings(
+            &mut encoder,
+            &options,
+            &query,
+            SettingsBinaryFormat::Strings,
+        );
+        let mut expected = Encoder::new();
+        expected.string("max_block_size");
+        expected.write(0_u8);
+        expected.string("1000");
+        expected.string("");
+        assert_eq!(encoder.get_buffer(), expected.get_buffer());
+    }
+
+    #[test]
+    fn test_trace_context() {
+        let mut encoder = Encoder::new();
+        let trace = TraceContext {
+            trace_id: 0x0af7651916cd43dd8448eb211c80319c,
+            span_id: 0xb7ad6b7169203331,
+            flags: 1,
+        };
+        encode_trace_context(&mut encoder, Some(trace));
+        encode_trace_context(&mut encoder, None);
+
+        let mut expected = vec![1_u8];
+        expected.extend(&0x0af7651916cd43dd_u64.to_le_bytes());
+        expected.extend(&0x8448eb211c80319c_u64.to_le_bytes());
+        expected.extend(&0xb7ad6b7169203331_u64.to_le_bytes());
+        expected.extend(&[0, 1, 0]);
+        assert_eq!(encoder.get_buffer(), expected);
+
+        let query = Query::new("SELECT 1").with_trace_context(trace.trace_id, trace.span_id, 1);
+        let packet = encode_query(&query, &context(Options::default())).unwrap();
+        let mut reader = Cursor::new(&packet[..]);
+        skip_client_info(&mut reader);
+        assert_eq!(
+            reader.read_uvarint().unwrap(),
+            client_info::CLICK_HOUSE_REVISION
+        ); // patch
+        assert_eq!(reader.read_scalar::<u8>().unwrap(), 1);
+        assert_eq!(reader.read_scalar::<u64>().unwrap(), 0x0af7651916cd43dd);
+        assert_eq!(reader.read_scalar::<u64>().unwrap(), 0x8448eb211c80319c);
+        assert_eq!(reader.read_scalar::<u64>().unwrap(), 0xb7ad6b7169203331);
+        assert_eq!(reader.read_string().unwrap(), "");
+        assert_eq!(reader.read_scalar::<u8>().unwrap(), 1);
+        assert_eq!(reader.read_string().unwrap(), ""); // no settings
+        assert_eq!(reader.read_string().unwrap(), ""); // interserver secret
+        assert_eq!(reader.read_uvarint().unwrap(), protocol::STATE_COMPLETE);
+
+        // Not sent to servers older than the trace context.
+        let old = Context {
+            server_info: ServerInfo {
+                revision: protocol::DBMS_MIN_REVISION_WITH_OPENTELEMETRY - 1,
+                ..ServerInfo::default()
+            },
+            options: Options::default().into_options_src(),
+            ..Context::default()
+        };
+        let with_trace = encode_query(&query, &old).unwrap();
+        let without = encode_query(&query.with_trace_context(0, 0, 0), &old).unwrap();
+        assert_eq!(with_trace, without);
+    }
+}
@@ -2,37 +2,139 @@ use crate::{
     binary::{Encoder, ReadEx},
     errors::Result,
     types::{
-        column::{column_data::{BoxColumnData, ArcColumnData}, list::List, ArcColumnWrapper, ColumnData},
+        column::{
+            column_data::{ArcColumnData, BoxColumnData},
+            is_assignable,
+            list::List,
+            ArcColumnWrapper, ColumnData, ColumnFrom, ColumnWrapper,
+        },
         SqlType, Value, ValueRef,
     },
 };
 use chrono_tz::Tz;
-use std::sync::Arc;
+use std::{ops::Range, sync::Arc};
 
-pub(crate) struct ArrayColumnData {
+/// Data of an `Array` column: the values of all arrays in one flat inner
+/// column, and for each row the offset in it where its array ends.
+///
+/// Reading the arrays through `offsets` and `inner` avoids building a
+/// `Vec` for every row:
+///
+/// ```rust
+/// # use clickhouse_rs::types::{Block, Simple};
+/// let block = Block::<Simple>::new().column("xs", vec![vec![1_u32, 2], vec![], vec![3]]);
+/// let array = block.get_column("xs").unwrap().as_array().unwrap();
+/// assert_eq!(array.offsets(), &[2, 2, 3]);
+/// assert_eq!(array.row_range(2), 2..3);
+/// assert_eq!(array.inner().len(), 3);
+/// ```
+///
+/// It can also be built one array at a time and added to a block with
+/// `Block::column`:
+///
+/// ```rust
+/// # use clickhouse_rs::types::{ArrayColumnData, Block, Simple, SqlType};
+/// let mut data = ArrayColumnData::new(SqlType::UInt32).unwrap();
+/// data.push_array(&[1_u32, 2]).unwrap();
+/// data.push_array(&[3_u32]).unwrap();
+/// assert_eq!(data.offsets(), &[2, 3]);
+///
+/// let block = Block::<Simple>::new().column("xs", data);
+/// assert_eq!(block.get::<Vec<u32>, _>(0, "xs").unwrap(), vec![1, 2]);
+/// ```
+pub struct ArrayColumnData {
     pub(crate) inner: ArcColumnData,
     pub(crate) offsets: List<u64>,
 }
 
 impl ArrayColumnData {
+    /// Creates empty data of an `Array(inner_type)` column.
+    pub fn new(inner_type: SqlType) -> Result<Self> {
+        Ok(ArrayColumnData {
+            inner: <dyn ColumnData>::from_type::<ArcColumnWrapper>(inner_type, Tz::Zulu, 0)?,
+            offsets: List::with_capacity(0),
+        })
+    }
+
+    /// Appends the array of one row, its offset is computed from the
+    /// length of `values`. Fails without changing the data if a value
+    /// doesn't match the inner type.
+    pub fn push_array<T: Clone + Into<Value>>(&mut self, values: &[T]) -> Result<()> {
+        let inner_type = self.inner.sql_type();
+        let values: Vec<Value> = values.iter().cloned().map(Into::into).collect();
+
+        if let Some(value) = values.iter().find(|v| !is_assignable(&inner_type, v)) {
+            let message = format!(
+                "Can't push Value::{} into an array of {}.",
+                SqlType::from(value.clone()),
+                inner_type
+            );
+            return Err(message.into());
+        }
+
+        let prev = self.offsets.as_slice().last().copied().unwrap_or(0);
+        self.offsets.push(prev + values.len() as u64);
+
+        if Arc::get_mut(&mut self.inner).is_none() {
+            self.inner = Arc::from(self.inner.clone_instance());
+        }
+        let inner = Arc::get_mut(&mut self.inner).unwrap();
+        for value in values {
+            inner.push(value);
+        }
+        Ok(())
+    }
+
     pub(crate) fn load<R: ReadEx>(
         reader: &mut R,
         type_name: &str,
         rows: usize,
         tz: Tz,
+        tz_override: Option<Tz>,
     ) -> Result<Self> {
-        let mut offsets = List::with_capacity(rows);
-        offsets.resize(rows, 0_u64);
-        reader.read_bytes(offsets.as_mut())?;
+        let offsets = List::<u64>::load(reader, rows)?;
 
         let size = match rows {
             0 => 0,
             _ => offsets.at(rows - 1) as usize,
         };
-        let inner = ColumnData::load_data::<ArcColumnWrapper, _>(reader, type_name, size, tz)?;
+        let inner = ColumnData::load_nested::<ArcColumnWrapper, _>(
+            reader,
+            type_name,
+            size,
+            tz,
+            tz_override,
+        )?;
 
         Ok(ArrayColumnData { inner, offsets })
     }
+
+    /// Returns for each row the offset in `inner` where its array ends,
+    /// the array of a row starts where the one of the previous row ends.
+    pub fn offsets(&self) -> &[u64] {
+        self.offsets.as_slice()
+    }
+
+    /// Returns the flat column of the values of all arrays.
+    pub fn inner(&self) -> &dyn ColumnData {
+        self.inner.as_ref()
+    }
+
+    /// Returns the positions in `inner` of the values of the array of `row`.
+    pub fn row_range(&self, row: usize) -> Range<usize> {
+        let offsets = self.offsets();
+        let start = match row {
+            0 => 0,
+            _ => offsets[row - 1] as usize,
+        };
+        start..offsets[row] as usize
+    }
+}
+
+impl ColumnFrom for ArrayColumnData {
+    fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
+        W::wrap(source)
+    }
 }
 
 impl ColumnData for ArrayColumnData {
@@ -42,14 +144,20 @@ impl ColumnData for ArrayColumnData {
     }
 
     fn save(&self, encoder: &mut Encoder, start: usize, end: usize) {
-        let mut offset = 0_u64;
+        // Offsets are written relative to the first saved row.
+        let base = if start == 0 {
+            0
+        } else {
+            self.offsets.at(start - 1)
+        };
+        let mut offset = base;
 
         for i in start..end {
             offset = self.offsets.at(i);
-            encoder.write(offset);
+            encoder.write(offset - base);
         }
 
-        self.inner.save(encoder, 0, offset as usize);
+        self.inner.save(encoder, base as usize, offset as usize);
     }
 
     fn len(&self) -> usize {
@@ -77,21 +185,14 @@ impl ColumnData for ArrayColumnData {
 
     fn at(&self, index: usize) -> ValueRef {
         let sql_type = self.inner.sql_type();
-
-        let start = if index > 0 {
-            self.offsets.at(index - 1) as usize
-        } else {
-            0_usize
-        };
-        let end = self.offsets.at(index) as usize;
-        let mut vs = Vec::with_capacity(end);
-        for i in start..end {
-            let v = self.inner.at(i);
-            vs.push(v);
-        }
+        let vs: Vec<_> = self.row_range(index).map(|i| self.inner.at(i)).collect();
         ValueRef::Array(sql_type.into(), Arc::new(vs))
     }
 
+    fn as_array(&self) -> Option<&ArrayColumnData> {
+        Some(self)
+    }
+
     fn clone_instance(&self) -> BoxColumnData {
         Box::new(Self {
             inner: self.inner.clone(),
@@ -99,6 +200,20 @@ impl ColumnData for ArrayColumnData {
         })
     }
 
+    fn take(&self, indices: &[usize]) -> Option<BoxColumnData> {
+        let mut offsets = List::with_capacity(indices.len());
+        let mut inner_indices = Vec::new();
+        for &index in indices {
+            inner_indices.extend(self.row_range(index));
+            offsets.push(inner_indices.len() as u64);
+        }
+        let inner = self.inner.take(&inner_indices)?;
+        Some(Box::new(Self {
+            inner: inner.into(),
+            offsets,
+        }))
+    }
+
     unsafe fn get_internal(&self, pointers: &[*mut *const u8], level: u8) -> Result<()> {
         if level == self.sql_type().level() {
             *pointers[0] = self.offsets.as_ptr() as *const u8;
@@ -139,8 +254,87 @@ mod test {
         block.write(&mut encoder, false);
 
         let mut reader = Cursor::new(encoder.get_buffer_ref());
-        let rblock = Block::load(&mut reader, Tz::Zulu, false).unwrap();
+        let rblock = Block::load(&mut reader, Tz::Zulu, None, false).unwrap();
 
         assert_eq!(block, rblock);
     }
+
+    #[test]
+    fn test_offsets() {
+        let rows = vec![vec![1_u32, 2, 3], vec![], vec![4], vec![5, 6], vec![]];
+        let block = Block::<Simple>::new().column("vals", rows.clone());
+
+        let mut encoder = Encoder::new();
+        block.write(&mut encoder, false);
+        let mut reader = Cursor::new(encoder.get_buffer_ref());
+        let rblock = Block::load(&mut reader, Tz::Zulu, None, false).unwrap();
+
+        let array = rblock.get_column("vals").unwrap().as_array().unwrap();
+        assert_eq!(array.offsets(), &[3, 3, 4, 6, 6]);
+        assert_eq!(array.inner().len(), 6);
+        assert_eq!(array.inner().sql_type(), SqlType::UInt32);
+
+        for (row, values) in rows.iter().enumerate() {
+            let range = array.row_range(row);
+            assert_eq!(range.len(), values.len());
+            let flat: Vec<u32> = range.map(|i| array.inner().at(i).into()).collect();
+            assert_eq!(&flat, values);
+        }
+
+        assert!(rblock.get_column("vals").unwrap().as_array().is_some());
+        let block = Block::<Simple>::new().column("n", vec![1_u32]);
+        assert!(block.get_column("n").unwrap().as_array().is_none());
+    }
+
+    #[test]
+    fn test_write_and_read_empty_arrays() {
+        let block = Block::<Simple>::new().column("vals", vec![Vec::<u32>::new(), Vec::new()]);
+
+        let mut encoder = Encoder::new();
+        block.write(&mut encoder, false);
+
+        let mut reader = Cursor::new(encoder.get_buffer_ref());
+        let rblock = Block::load(&mut reader, Tz::Zulu, None, false).unwrap();
+
+        assert_eq!(rblock.row_count(), 2);
+        assert_eq!(block, rblock);
+    }
+
+    #[test]
+    fn test_push_array() {
+        let rows = vec![vec![1_u32, 2], vec![3], vec![], vec![4, 5, 6]];
+
+        let mut data = ArrayColumnData::new(SqlType::UInt32).unwrap();
+        for row in &rows {
+            data.push_array(row).unwrap();
+        }
+        assert_eq!(data.offsets(), &[2, 3, 3, 6]);
+
+        let err = data.push_array(&[7_u64]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Other error: `Can't push Value::UInt64 into an array of UInt32.`"
+        );
+        assert_eq!(data.offsets(), &[2, 3, 3, 6]);
+        assert_eq!(data.inner().len(), 6);
+
+        let block = Block::<Simple>::new().column("vals", data);
+        assert_eq!(block, Block::<Simple>::new().column("vals", rows.clone()));
+
+        let mut encoder = Encoder::new();
+        block.write(&mut encoder, false);
+        let mut reader = Cursor::new(encoder.get_buffer_ref());
+        let rblock = Block::load(&mut reader, Tz::Zulu, None, false).unwrap();
+
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(&rblock.get::<Vec<u32>, _>(i, "vals").unwrap(), row);
+        }
+
+        let mut strings = ArrayColumnData::new(SqlType::String).unwrap();
+        strings.push_array(&["a", "b"]).unwrap();
+        strings.push_array::<&str>(&[]).unwrap();
+        let block = Block::<Simple>::new().column("s", strings);
+        assert_eq!(block.get::<Vec<&str>, _>(0, "s").unwrap(), vec!["a", "b"]);
+        assert!(block.get::<Vec<&str>, _>(1, "s").unwrap().is_empty());
+    }
 }
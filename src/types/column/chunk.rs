@@ -53,4 +53,27 @@ impl ColumnData for ChunkColumnData {
     fn clone_instance(&self) -> BoxColumnData {
         unimplemented!()
     }
+
+    fn take(&self, indices: &[usize]) -> Option<BoxColumnData> {
+        let indices: Vec<usize> = indices
+            .iter()
+            .map(|&index| index + self.range.start)
+            .collect();
+        self.data.take(&indices)
+    }
+
+    fn nulls(&self) -> Option<&[u8]> {
+        self.data.nulls().map(|nulls| &nulls[self.range.clone()])
+    }
+
+    fn is_null(&self, index: usize) -> bool {
+        self.data.is_null(index + self.range.start)
+    }
+
+    fn null_count(&self) -> usize {
+        self.range
+            .clone()
+            .filter(|&index| self.data.is_null(index))
+            .count()
+    }
 }
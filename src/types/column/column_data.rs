@@ -1,9 +1,12 @@
-use std::{convert, sync::Arc};
+use std::{cmp::Ordering, convert, io, sync::Arc};
 
 use crate::{
     binary::Encoder,
     errors::{Error, FromSqlError, Result},
-    types::{SqlType, Value, ValueRef},
+    types::{
+        column::{ArrayColumnData, Either},
+        SqlType, Value, ValueRef,
+    },
 };
 
 pub(crate) type ArcColumnData = Arc<dyn ColumnData + Send + Sync>;
@@ -26,6 +29,107 @@ pub trait ColumnData {
     fn cast_to(&self, _this: &ArcColumnData, _target: &SqlType) -> Option<ArcColumnData> {
         None
     }
+
+    /// Returns the data of an `Array` column.
+    fn as_array(&self) -> Option<&ArrayColumnData> {
+        None
+    }
+
+    /// Returns the label of the value at `index` of an `Enum8` or `Enum16`
+    /// column, `None` for other columns and for unknown values.
+    fn enum_label(&self, _index: usize) -> Option<&str> {
+        None
+    }
+
+    /// Returns `true` if `reload` can decode values into the storage of
+    /// this column.
+    fn can_reload(&self) -> bool {
+        false
+    }
+
+    /// Replaces the values of the column with `size` values of the same type
+    /// read from `reader`, reusing the allocated storage.
+    fn reload(&mut self, _reader: &mut dyn io::Read, _size: usize) -> Result<()> {
+        Err(Error::FromSql(FromSqlError::UnsupportedOperation))
+    }
+
+    /// Returns the null map of a `Nullable` column, a non-zero byte for each
+    /// `NULL` value.
+    fn nulls(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// Returns a column of the values at `indices`, gathered from the storage
+    /// of this one, or `None` if it has to be rebuilt value by value. The
+    /// indices are in range.
+    fn take(&self, _indices: &[usize]) -> Option<BoxColumnData> {
+        None
+    }
+
+    /// Returns `true` if the value at `index` is `NULL`.
+    fn is_null(&self, index: usize) -> bool {
+        self.nulls().is_some_and(|nulls| nulls[index] != 0)
+    }
+
+    /// Returns the number of `NULL` values in the column.
+    fn null_count(&self) -> usize {
+        self.nulls().map_or(0, |nulls| {
+            nulls.iter().filter(|&&is_null| is_null != 0).count()
+        })
+    }
+
+    /// Returns the smallest non-null numeric value of the column.
+    fn min_value(&self) -> Option<ValueRef<'_>> {
+        scan_extremum(self, Ordering::Less)
+    }
+
+    /// Returns the largest non-null numeric value of the column.
+    fn max_value(&self) -> Option<ValueRef<'_>> {
+        scan_extremum(self, Ordering::Greater)
+    }
+}
+
+fn scan_extremum<C>(column: &C, wanted: Ordering) -> Option<ValueRef<'_>>
+where
+    C: ColumnData + ?Sized,
+{
+    let mut result: Option<ValueRef> = None;
+
+    for i in 0..column.len() {
+        let value = match column.at(i) {
+            ValueRef::Nullable(Either::Left(_)) => continue,
+            ValueRef::Nullable(Either::Right(inner)) => *inner,
+            value => value,
+        };
+
+        // Skips NaNs as well as values of non-numeric types.
+        if numeric_cmp(&value, &value).is_none() {
+            continue;
+        }
+
+        result = match result {
+            Some(current) if numeric_cmp(&value, &current) != Some(wanted) => Some(current),
+            _ => Some(value),
+        };
+    }
+
+    result
+}
+
+pub(crate) fn numeric_cmp(a: &ValueRef, b: &ValueRef) -> Option<Ordering> {
+    match (a, b) {
+        (ValueRef::UInt8(a), ValueRef::UInt8(b)) => a.partial_cmp(b),
+        (ValueRef::UInt16(a), ValueRef::UInt16(b)) => a.partial_cmp(b),
+        (ValueRef::UInt32(a), ValueRef::UInt32(b)) => a.partial_cmp(b),
+        (ValueRef::UInt64(a), ValueRef::UInt64(b)) => a.partial_cmp(b),
+        (ValueRef::Int8(a), ValueRef::Int8(b)) => a.partial_cmp(b),
+        (ValueRef::Int16(a), ValueRef::Int16(b)) => a.partial_cmp(b),
+        (ValueRef::Int32(a), ValueRef::Int32(b)) => a.partial_cmp(b),
+        (ValueRef::Int64(a), ValueRef::Int64(b)) => a.partial_cmp(b),
+        (ValueRef::Float32(a), ValueRef::Float32(b)) => a.partial_cmp(b),
+        (ValueRef::Float64(a), ValueRef::Float64(b)) => a.partial_cmp(b),
+        _ => None,
+    }
 }
 
 pub(crate) trait ColumnDataExt {
@@ -1,4 +1,8 @@
-use std::iter;
+use std::{
+    cmp::Ordering,
+    iter,
+    sync::{Arc, OnceLock},
+};
 
 use crate::{
     binary::Encoder,
@@ -6,11 +10,19 @@ use crate::{
     types::{SqlType, Value, ValueRef},
 };
 
-use super::column_data::{ArcColumnData, BoxColumnData, ColumnData};
+use super::{
+    column_data::{numeric_cmp, ArcColumnData, BoxColumnData, ColumnData},
+    list::List,
+    ArrayColumnData,
+};
 
 pub struct ConcatColumnData {
     data: Vec<ArcColumnData>,
     index: Vec<usize>,
+    /// The chunks of an `Array` column merged into one, built on first use.
+    array: OnceLock<Option<ArrayColumnData>>,
+    /// The null maps of the chunks one after the other, built on first use.
+    nulls: OnceLock<Option<Vec<u8>>>,
 }
 
 impl ConcatColumnData {
@@ -18,7 +30,58 @@ impl ConcatColumnData {
         Self::check_columns(&data);
 
         let index = build_index(data.iter().map(len_of_column_data));
-        Self { data, index }
+        Self {
+            data,
+            index,
+            array: OnceLock::new(),
+            nulls: OnceLock::new(),
+        }
+    }
+
+    /// Merges the arrays of the chunks: their inner columns are concatenated
+    /// and the offsets of each chunk are shifted by the length of the inner
+    /// columns before it.
+    fn merge_arrays(&self) -> Option<ArrayColumnData> {
+        let arrays = self
+            .data
+            .iter()
+            .map(|chunk| chunk.as_array())
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut offsets = List::with_capacity(self.len());
+        let mut base = 0;
+        for array in &arrays {
+            for &offset in array.offsets() {
+                offsets.push(base + offset);
+            }
+            base += array.offsets().last().copied().unwrap_or(0);
+        }
+
+        let inner = arrays.iter().map(|array| array.inner.clone()).collect();
+        Some(ArrayColumnData {
+            inner: Arc::new(ConcatColumnData::concat(inner)),
+            offsets,
+        })
+    }
+
+    fn merge_nulls(&self) -> Option<Vec<u8>> {
+        let mut nulls = Vec::with_capacity(self.len());
+        for chunk in &self.data {
+            nulls.extend_from_slice(chunk.nulls()?);
+        }
+        Some(nulls)
+    }
+
+    fn extremum(&self, wanted: Ordering) -> Option<ValueRef<'_>> {
+        let chunks = self.data.iter().filter_map(|chunk| match wanted {
+            Ordering::Less => chunk.min_value(),
+            _ => chunk.max_value(),
+        });
+
+        chunks.fold(None, |result, value| match result {
+            Some(current) if numeric_cmp(&value, &current) != Some(wanted) => Some(current),
+            _ => Some(value),
+        })
     }
 
     fn check_columns(data: &[ArcColumnData]) {
@@ -66,6 +129,59 @@ impl ColumnData for ConcatColumnData {
         unimplemented!()
     }
 
+    fn enum_label(&self, index: usize) -> Option<&str> {
+        let chunk_index = find_chunk(&self.index, index);
+        self.data[chunk_index].enum_label(index - self.index[chunk_index])
+    }
+
+    fn as_array(&self) -> Option<&ArrayColumnData> {
+        match self.data.as_slice() {
+            [chunk] => chunk.as_array(),
+            _ => self.array.get_or_init(|| self.merge_arrays()).as_ref(),
+        }
+    }
+
+    /// Gathers the values from their chunk if all `indices` point into the
+    /// same one. Values spread over several chunks are gathered value by
+    /// value, since the chunks can't be appended to each other.
+    fn take(&self, indices: &[usize]) -> Option<BoxColumnData> {
+        let chunk_index = match indices.first() {
+            None => 0,
+            Some(&first) => find_chunk(&self.index, first),
+        };
+        let (start, end) = (self.index[chunk_index], self.index[chunk_index + 1]);
+        if indices.iter().any(|&index| index < start || index >= end) {
+            return None;
+        }
+
+        let local: Vec<usize> = indices.iter().map(|&index| index - start).collect();
+        self.data[chunk_index].take(&local)
+    }
+
+    fn nulls(&self) -> Option<&[u8]> {
+        match self.data.as_slice() {
+            [chunk] => chunk.nulls(),
+            _ => self.nulls.get_or_init(|| self.merge_nulls()).as_deref(),
+        }
+    }
+
+    fn is_null(&self, index: usize) -> bool {
+        let chunk_index = find_chunk(&self.index, index);
+        self.data[chunk_index].is_null(index - self.index[chunk_index])
+    }
+
+    fn null_count(&self) -> usize {
+        self.data.iter().map(|chunk| chunk.null_count()).sum()
+    }
+
+    fn min_value(&self) -> Option<ValueRef<'_>> {
+        self.extremum(Ordering::Less)
+    }
+
+    fn max_value(&self) -> Option<ValueRef<'_>> {
+        self.extremum(Ordering::Greater)
+    }
+
     unsafe fn get_internal(&self, pointers: &[*mut *const u8], level: u8) -> Result<()> {
         if level == 0xff {
             *pointers[0] = &self.data as *const Vec<ArcColumnData> as *mut u8;
@@ -124,9 +240,7 @@ fn len_of_column_data(x: &ArcColumnData) -> usize {
 mod test {
     use std::sync::Arc;
 
-    use crate::types::column::{
-        column_data::ColumnDataExt, numeric::VectorColumnData, string::StringColumnData,
-    };
+    use crate::types::column::{numeric::VectorColumnData, string::StringColumnData};
 
     use super::*;
 
@@ -212,10 +326,57 @@ mod test {
         assert_eq!(actual.len(), 4);
     }
 
+    #[test]
+    fn test_concat_min_max() {
+        let mut data = VectorColumnData::<u32>::with_capacity(1);
+        data.append(7_u32);
+        let xs: Vec<ArcColumnData> = vec![make_num_column(), Arc::new(data)];
+        let actual = ConcatColumnData::concat(xs);
+
+        assert_eq!(actual.min_value(), Some(ValueRef::UInt32(1)));
+        assert_eq!(actual.max_value(), Some(ValueRef::UInt32(7)));
+        assert_eq!(actual.null_count(), 0);
+    }
+
+    #[test]
+    fn test_concat_arrays() {
+        let xs = vec![
+            make_array_column(&[&[1, 2], &[]]),
+            make_array_column(&[&[3], &[4, 5]]),
+        ];
+        let actual = ConcatColumnData::concat(xs);
+
+        let array = actual.as_array().unwrap();
+        assert_eq!(array.offsets(), &[2, 2, 3, 5]);
+        assert_eq!(array.row_range(3), 3..5);
+        assert_eq!(array.inner().at(3), ValueRef::UInt32(4));
+    }
+
+    #[test]
+    fn test_concat_take() {
+        let xs = vec![make_num_column(), make_num_column()];
+        let actual = ConcatColumnData::concat(xs);
+
+        let taken = actual.take(&[3, 2]).unwrap();
+        assert_eq!(taken.at(0), ValueRef::UInt32(2));
+        assert_eq!(taken.at(1), ValueRef::UInt32(1));
+
+        // Values of different chunks are gathered value by value.
+        assert!(actual.take(&[0, 3]).is_none());
+    }
+
+    fn make_array_column(arrays: &[&[u32]]) -> ArcColumnData {
+        let mut data = ArrayColumnData::new(SqlType::UInt32).unwrap();
+        for array in arrays {
+            data.push_array(array).unwrap();
+        }
+        Arc::new(data)
+    }
+
     fn make_string_column() -> ArcColumnData {
         let mut data = StringColumnData::with_capacity(1);
-        data.append("13298a5f-6a10-4fbe-9644-807f7ebf82cc".to_string());
-        data.append("df0e62bb-c0db-4728-a558-821f8e8da38c".to_string());
+        data.append("13298a5f-6a10-4fbe-9644-807f7ebf82cc");
+        data.append("df0e62bb-c0db-4728-a558-821f8e8da38c");
         Arc::new(data)
     }
 
@@ -11,10 +11,13 @@ use crate::{
         column_data::{BoxColumnData, ColumnData},
         list::List,
         nullable::NullableColumnData,
-        numeric::save_data,
         ArcColumnWrapper, ColumnFrom, ColumnWrapper, Either,
     },
-    types::{DateConverter, Marshal, SqlType, StatBuffer, Unmarshal, Value, ValueRef},
+    types::{
+        overflow::{date_days, fit_days, fit_seconds, out_of_range},
+        DateConverter, DateTimeType, Marshal, OverflowMode, SqlType, StatBuffer, Unmarshal, Value,
+        ValueRef,
+    },
 };
 
 pub struct DateColumnData<T>
@@ -59,29 +62,39 @@ where
         size: usize,
         tz: Tz,
     ) -> Result<DateColumnData<T>> {
-        let mut data = List::with_capacity(size);
-        unsafe {
-            data.set_len(size);
-        }
-        reader.read_bytes(data.as_mut())?;
+        let data = List::load(reader, size)?;
         Ok(DateColumnData { data, tz })
     }
 }
 
 impl ColumnFrom for Vec<Date<Tz>> {
     fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
+        Self::column_from_checked::<W>(source, OverflowMode::Wrapping).unwrap()
+    }
+
+    fn column_from_checked<W: ColumnWrapper>(
+        source: Self,
+        mode: OverflowMode,
+    ) -> Result<W::Wrapper> {
         let mut data = List::<u16>::with_capacity(source.len());
-        for s in source {
-            data.push(u16::get_days(s));
+        for (index, s) in source.into_iter().enumerate() {
+            data.push(date_to_days(index, s.naive_local(), mode)?);
         }
 
         let column: DateColumnData<u16> = DateColumnData { data, tz: Tz::Zulu };
-        W::wrap(column)
+        Ok(W::wrap(column))
     }
 }
 
 impl ColumnFrom for Vec<Vec<Date<Tz>>> {
     fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
+        Self::column_from_checked::<W>(source, OverflowMode::Wrapping).unwrap()
+    }
+
+    fn column_from_checked<W: ColumnWrapper>(
+        source: Self,
+        mode: OverflowMode,
+    ) -> Result<W::Wrapper> {
         let fake: Vec<Date<Tz>> = Vec::with_capacity(source.len());
         let inner = Vec::column_from::<ArcColumnWrapper>(fake);
         let sql_type = inner.sql_type();
@@ -91,22 +104,29 @@ impl ColumnFrom for Vec<Vec<Date<Tz>>> {
             offsets: List::with_capacity(source.len()),
         };
 
-        for vs in source {
+        for (index, vs) in source.into_iter().enumerate() {
             let mut inner = Vec::with_capacity(vs.len());
             for v in vs {
-                let days = u16::get_days(v);
+                let days = date_to_days(index, v.naive_local(), mode)?;
                 let value: Value = Value::Date(days, v.timezone());
                 inner.push(value);
             }
             data.push(Value::Array(sql_type.clone().into(), Arc::new(inner)));
         }
 
-        W::wrap(data)
+        Ok(W::wrap(data))
     }
 }
 
 impl ColumnFrom for Vec<Vec<DateTime<Tz>>> {
     fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
+        Self::column_from_checked::<W>(source, OverflowMode::Wrapping).unwrap()
+    }
+
+    fn column_from_checked<W: ColumnWrapper>(
+        source: Self,
+        mode: OverflowMode,
+    ) -> Result<W::Wrapper> {
         let fake: Vec<DateTime<Tz>> = Vec::with_capacity(source.len());
         let inner = Vec::column_from::<ArcColumnWrapper>(fake);
         let sql_type = inner.sql_type();
@@ -116,21 +136,38 @@ impl ColumnFrom for Vec<Vec<DateTime<Tz>>> {
             offsets: List::with_capacity(source.len()),
         };
 
-        for vs in source {
+        for (index, vs) in source.into_iter().enumerate() {
             let mut inner = Vec::with_capacity(vs.len());
             for v in vs {
-                let value: Value = Value::DateTime(v.timestamp() as u32, v.timezone());
+                let seconds = match fit_seconds(v.timestamp(), mode) {
+                    Some(seconds) => seconds,
+                    None => {
+                        return Err(out_of_range(
+                            index,
+                            v,
+                            &SqlType::DateTime(DateTimeType::DateTime32),
+                        ))
+                    }
+                };
+                let value: Value = Value::DateTime(seconds, v.timezone());
                 inner.push(value);
             }
             data.push(Value::Array(sql_type.clone().into(), Arc::new(inner)));
         }
 
-        W::wrap(data)
+        Ok(W::wrap(data))
     }
 }
 
 impl ColumnFrom for Vec<Option<Date<Tz>>> {
     fn column_from<W: ColumnWrapper>(source: Self) -> <W as ColumnWrapper>::Wrapper {
+        Self::column_from_checked::<W>(source, OverflowMode::Wrapping).unwrap()
+    }
+
+    fn column_from_checked<W: ColumnWrapper>(
+        source: Self,
+        mode: OverflowMode,
+    ) -> Result<W::Wrapper> {
         let fake: Vec<Date<Tz>> = Vec::with_capacity(source.len());
         let inner = Vec::column_from::<ArcColumnWrapper>(fake);
 
@@ -139,18 +176,26 @@ impl ColumnFrom for Vec<Option<Date<Tz>>> {
             nulls: Vec::with_capacity(source.len()),
         };
 
-        for value in source {
+        for (index, value) in source.into_iter().enumerate() {
             match value {
                 None => data.push(Value::Nullable(Either::Left(SqlType::Date.into()))),
                 Some(d) => {
-                    let days = u16::get_days(d);
+                    let days = date_to_days(index, d.naive_local(), mode)?;
                     let value = Value::Date(days, d.timezone());
                     data.push(Value::Nullable(Either::Right(Box::new(value))))
                 }
             }
         }
 
-        W::wrap(data)
+        Ok(W::wrap(data))
+    }
+}
+
+/// Converts the date in row `index` to the days of a `Date` column.
+fn date_to_days(index: usize, date: NaiveDate, mode: OverflowMode) -> Result<u16> {
+    match fit_days(date_days(date), mode) {
+        Some(days) => Ok(days),
+        None => Err(out_of_range(index, date, &SqlType::Date)),
     }
 }
 
@@ -174,7 +219,7 @@ where
     }
 
     fn save(&self, encoder: &mut Encoder, start: usize, end: usize) {
-        save_data::<T>(self.data.as_ref(), encoder, start, end);
+        self.data.save(encoder, start, end);
     }
 
     fn len(&self) -> usize {
@@ -24,13 +24,8 @@ impl DateTime64ColumnData {
         precision: u32,
         tz: Tz,
     ) -> Result<DateTime64ColumnData> {
-        let mut data = List::with_capacity(size);
-        unsafe {
-            data.set_len(size);
-        }
-        reader.read_bytes(data.as_mut())?;
         Ok(DateTime64ColumnData {
-            data,
+            data: List::load(reader, size)?,
             params: (precision, tz),
         })
     }
@@ -49,8 +44,8 @@ impl ColumnData for DateTime64ColumnData {
         SqlType::DateTime(DateTimeType::DateTime64(precision, tz))
     }
 
-    fn save(&self, _encoder: &mut Encoder, _start: usize, _end: usize) {
-        unimplemented!()
+    fn save(&self, encoder: &mut Encoder, start: usize, end: usize) {
+        self.data.save(encoder, start, end)
     }
 
     fn len(&self) -> usize {
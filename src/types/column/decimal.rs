@@ -45,12 +45,14 @@ impl DecimalColumnData {
         nobits: NoBits,
         size: usize,
         tz: Tz,
+        tz_override: Option<Tz>,
     ) -> Result<Self> {
         let type_name = match nobits {
             NoBits::N32 => "Int32",
             NoBits::N64 => "Int64",
         };
-        let inner = ColumnData::load_data::<BoxColumnWrapper, _>(reader, type_name, size, tz)?;
+        let inner =
+            ColumnData::load_data::<BoxColumnWrapper, _>(reader, type_name, size, tz, tz_override)?;
 
         Ok(DecimalColumnData {
             inner,
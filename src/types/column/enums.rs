@@ -1,4 +1,9 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, OnceLock},
+};
+
 use chrono_tz::Tz;
 
 use crate::{
@@ -19,6 +24,8 @@ use crate::{
 pub(crate) struct Enum16ColumnData {
     pub(crate) enum_values: Vec<(String, i16)>,
     pub(crate) inner: Box<dyn ColumnData + Send + Sync>,
+    // Positions of the values in `enum_values`, built on first lookup
+    pub(crate) labels: OnceLock<HashMap<i16, usize>>,
 }
 
 pub(crate) struct Enum16Adapter<K: ColumnType> {
@@ -37,15 +44,40 @@ impl Enum16ColumnData {
         enum_values: Vec<(String, i16)>,
         size: usize,
         tz: Tz,
+        tz_override: Option<Tz>,
     ) -> Result<Self> {
         let type_name = "Int16";
 
-        let inner = ColumnData::load_data::<BoxColumnWrapper, _>(reader, type_name, size, tz)?;
+        let inner =
+            ColumnData::load_data::<BoxColumnWrapper, _>(reader, type_name, size, tz, tz_override)?;
 
-        Ok(Enum16ColumnData { enum_values, inner })
+        Ok(Enum16ColumnData {
+            enum_values,
+            inner,
+            labels: OnceLock::new(),
+        })
     }
 }
 
+/// Returns the label of `value`, the map of labels is built on the first
+/// call and shared by all later ones.
+fn find_label<'a, T: Copy + Eq + Hash>(
+    labels: &OnceLock<HashMap<T, usize>>,
+    enum_values: &'a [(String, T)],
+    value: T,
+) -> Option<&'a str> {
+    let labels = labels.get_or_init(|| {
+        enum_values
+            .iter()
+            .enumerate()
+            .map(|(position, (_, value))| (*value, position))
+            .collect()
+    });
+    labels
+        .get(&value)
+        .map(|&position| enum_values[position].0.as_str())
+}
+
 impl ColumnData for Enum16ColumnData {
     fn sql_type(&self) -> SqlType {
         SqlType::Enum16(self.enum_values.clone())
@@ -69,15 +101,21 @@ impl ColumnData for Enum16ColumnData {
 
     fn at(&self, index: usize) -> ValueRef {
         let enum_value = i16::from(self.inner.at(index));
-        ValueRef::Enum16(self.enum_values.clone(), Enum16(enum_value))
+        ValueRef::Enum16(&self.enum_values, Enum16(enum_value))
     }
 
     fn clone_instance(&self) -> BoxColumnData {
         Box::new(Self {
             inner: self.inner.clone_instance(),
             enum_values: self.enum_values.clone(),
+            labels: OnceLock::new(),
         })
     }
+
+    fn enum_label(&self, index: usize) -> Option<&str> {
+        let value = i16::from(self.inner.at(index));
+        find_label(&self.labels, &self.enum_values, value)
+    }
 }
 
 impl<K: ColumnType> ColumnData for Enum16Adapter<K> {
@@ -154,7 +192,7 @@ impl<K: ColumnType> ColumnData for NullableEnum16Adapter<K> {
         match value {
             None => ValueRef::Nullable(Either::Left(self.sql_type().into())),
             Some(v) => {
-                let inner = ValueRef::Enum16(self.enum_values.clone(), v);
+                let inner = ValueRef::Enum16(&self.enum_values, v);
                 ValueRef::Nullable(Either::Right(Box::new(inner)))
             }
         }
@@ -177,6 +215,7 @@ impl ColumnFrom for Vec<Enum16> {
         let column = Enum16ColumnData {
             inner,
             enum_values: vec![],
+            labels: OnceLock::new(),
         };
 
         W::wrap(column)
@@ -202,6 +241,7 @@ impl ColumnFrom for Vec<Option<Enum16>> {
         let inner = Enum16ColumnData {
             enum_values: vec![],
             inner,
+            labels: OnceLock::new(),
         };
 
         W::wrap(NullableColumnData {
@@ -214,6 +254,8 @@ impl ColumnFrom for Vec<Option<Enum16>> {
 pub(crate) struct Enum8ColumnData {
     pub(crate) enum_values: Vec<(String, i8)>,
     pub(crate) inner: Box<dyn ColumnData + Send + Sync>,
+    // Positions of the values in `enum_values`, built on first lookup
+    pub(crate) labels: OnceLock<HashMap<i8, usize>>,
 }
 
 pub(crate) struct Enum8Adapter<K: ColumnType> {
@@ -232,12 +274,18 @@ impl Enum8ColumnData {
         enum_values: Vec<(String, i8)>,
         size: usize,
         tz: Tz,
+        tz_override: Option<Tz>,
     ) -> Result<Self> {
         let type_name = "Int8";
 
-        let inner = ColumnData::load_data::<BoxColumnWrapper, _>(reader, type_name, size, tz)?;
+        let inner =
+            ColumnData::load_data::<BoxColumnWrapper, _>(reader, type_name, size, tz, tz_override)?;
 
-        Ok(Enum8ColumnData { enum_values, inner })
+        Ok(Enum8ColumnData {
+            enum_values,
+            inner,
+            labels: OnceLock::new(),
+        })
     }
 }
 
@@ -264,15 +312,21 @@ impl ColumnData for Enum8ColumnData {
 
     fn at(&self, index: usize) -> ValueRef {
         let enum_value = i8::from(self.inner.at(index));
-        ValueRef::Enum8(self.enum_values.clone(), Enum8(enum_value))
+        ValueRef::Enum8(&self.enum_values, Enum8(enum_value))
     }
 
     fn clone_instance(&self) -> BoxColumnData {
         Box::new(Self {
             inner: self.inner.clone_instance(),
             enum_values: self.enum_values.clone(),
+            labels: OnceLock::new(),
         })
     }
+
+    fn enum_label(&self, index: usize) -> Option<&str> {
+        let value = i8::from(self.inner.at(index));
+        find_label(&self.labels, &self.enum_values, value)
+    }
 }
 
 impl<K: ColumnType> ColumnData for Enum8Adapter<K> {
@@ -350,7 +404,7 @@ impl<K: ColumnType> ColumnData for NullableEnum8Adapter<K> {
         match value {
             None => ValueRef::Nullable(Either::Left(self.sql_type().into())),
             Some(v) => {
-                let inner = ValueRef::Enum8(self.enum_values.clone(), v);
+                let inner = ValueRef::Enum8(&self.enum_values, v);
                 ValueRef::Nullable(Either::Right(Box::new(inner)))
             }
         }
@@ -373,6 +427,7 @@ impl ColumnFrom for Vec<Enum8> {
         let column = Enum8ColumnData {
             inner,
             enum_values: vec![],
+            labels: OnceLock::new(),
         };
 
         W::wrap(column)
@@ -398,6 +453,7 @@ impl ColumnFrom for Vec<Option<Enum8>> {
         let inner = Enum8ColumnData {
             enum_values: vec![],
             inner,
+            labels: OnceLock::new(),
         };
 
         W::wrap(NullableColumnData {
@@ -406,3 +462,30 @@ impl ColumnFrom for Vec<Option<Enum8>> {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_enum_label() {
+        let column = Enum8ColumnData::load(
+            &mut Cursor::new(&[1_u8, 0xfe, 1, 3][..]),
+            vec![("a".to_string(), 1), ("b".to_string(), -2)],
+            4,
+            Tz::Zulu,
+            None,
+        )
+        .unwrap();
+        assert!(column.labels.get().is_none());
+
+        assert_eq!(column.enum_label(0), Some("a"));
+        let labels = column.labels.get().unwrap() as *const _;
+        assert_eq!(column.enum_label(1), Some("b"));
+        assert_eq!(column.enum_label(2), Some("a"));
+        assert_eq!(column.enum_label(3), None);
+        assert_eq!(column.labels.get().unwrap() as *const _, labels);
+    }
+}
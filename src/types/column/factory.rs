@@ -1,10 +1,16 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{Arc, OnceLock},
+};
+
 use chrono_tz::Tz;
 
 use combine::{
-    any,
+    any, attempt, count_min_max,
     error::StringStreamError,
     many, many1, none_of, optional,
-    parser::char::{digit, spaces, string},
+    parser::char::{digit, hex_digit, spaces, string},
     sep_by1, token, Parser,
 };
 
@@ -13,13 +19,23 @@ use crate::{
     errors::Result,
     types::{
         column::{
+            array::ArrayColumnData,
+            column_data::ColumnData,
+            date::DateColumnData,
             datetime64::DateTime64ColumnData,
-            array::ArrayColumnData, column_data::ColumnData, date::DateColumnData,
-            decimal::{DecimalColumnData}, fixed_string::FixedStringColumnData, list::List,
-            nullable::NullableColumnData, numeric::VectorColumnData, string::StringColumnData,
-            BoxColumnWrapper, ArcColumnWrapper, ColumnWrapper, SqlType,
-            ip::{IpColumnData, Ipv4, Ipv6, Uuid},
+            decimal::DecimalColumnData,
             enums::{Enum16ColumnData, Enum8ColumnData},
+            fixed_string::FixedStringColumnData,
+            ip::{IpColumnData, Ipv4, Ipv6, Uuid},
+            json::JsonColumnData,
+            list::List,
+            low_cardinality::{LowCardinalityColumnData, SHARED_DICTIONARIES_WITH_ADDITIONAL_KEYS},
+            nothing::NothingColumnData,
+            nullable::NullableColumnData,
+            numeric::VectorColumnData,
+            string::StringColumnData,
+            tuple::TupleColumnData,
+            ArcColumnWrapper, BoxColumnWrapper, ColumnWrapper, SqlType,
         },
         DateTimeType, decimal::NoBits,
     },
@@ -42,12 +58,31 @@ macro_rules! match_str {
 }
 
 impl dyn ColumnData {
-    #[allow(clippy::cognitive_complexity)]
+    /// Loads `size` values of `type_name`. `DateTime` and `DateTime64` columns
+    /// use the timezone of their own type if it declares one and `tz`, the
+    /// server timezone, otherwise; `tz_override` takes precedence over both.
     pub(crate) fn load_data<W: ColumnWrapper, T: ReadEx>(
         reader: &mut T,
         type_name: &str,
         size: usize,
         tz: Tz,
+        tz_override: Option<Tz>,
+    ) -> Result<W::Wrapper> {
+        if size > 0 {
+            read_state_prefix(reader, type_name)?;
+        }
+        <dyn ColumnData>::load_nested::<W, T>(reader, type_name, size, tz, tz_override)
+    }
+
+    /// Same as `load_data` for a column nested in another one, its state
+    /// prefix has been read along with the one of the outer column.
+    #[allow(clippy::cognitive_complexity)]
+    pub(crate) fn load_nested<W: ColumnWrapper, T: ReadEx>(
+        reader: &mut T,
+        type_name: &str,
+        size: usize,
+        tz: Tz,
+        tz_override: Option<Tz>,
     ) -> Result<W::Wrapper> {
         Ok(match_str!(type_name, {
             "UInt8" => W::wrap(VectorColumnData::<u8>::load(reader, size)?),
@@ -61,32 +96,55 @@ impl dyn ColumnData {
             "Float32" | "Float" => W::wrap(VectorColumnData::<f32>::load(reader, size)?),
             "Float64" | "Double" => W::wrap(VectorColumnData::<f64>::load(reader, size)?),
             "String" | "Char" | "Varchar" | "Text" | "TinyText" | "MediumText" | "LongText" | "Blob" | "TinyBlob" | "MediumBlob" | "LongBlob" => W::wrap(StringColumnData::load(reader, size)?),
-            "Date" => W::wrap(DateColumnData::<u16>::load(reader, size, tz)?),
-            "DateTime" | "Timestamp" => W::wrap(DateColumnData::<u32>::load(reader, size, tz)?),
+            "Date" => W::wrap(DateColumnData::<u16>::load(reader, size, tz_override.unwrap_or(tz))?),
+            "DateTime" | "Timestamp" => W::wrap(DateColumnData::<u32>::load(reader, size, tz_override.unwrap_or(tz))?),
             "IPv4" => W::wrap(IpColumnData::<Ipv4>::load(reader, size)?),
             "IPv6" => W::wrap(IpColumnData::<Ipv6>::load(reader, size)?),
             "UUID" => W::wrap(IpColumnData::<Uuid>::load(reader, size)?),
+            "Nothing" => W::wrap(NothingColumnData::load(reader, size)?),
+            "Object('json')" => W::wrap(JsonColumnData::load(reader, size, tz, tz_override)?),
             _ => {
                 if let Some(inner_type) = parse_nullable_type(type_name) {
-                    W::wrap(NullableColumnData::load(reader, inner_type, size, tz)?)
+                    W::wrap(NullableColumnData::load(reader, inner_type, size, tz, tz_override)?)
                 } else if let Some(str_len) = parse_fixed_string(type_name) {
                     W::wrap(FixedStringColumnData::load(reader, size, str_len)?)
                 } else if let Some(inner_type) = parse_array_type(type_name) {
-                    W::wrap(ArrayColumnData::load(reader, inner_type, size, tz)?)
-                } else if let Some((precision, scale, nobits)) = parse_decimal(type_name) {
-                    W::wrap(DecimalColumnData::load(
-                        reader, precision, scale, nobits, size, tz,
-                    )?)
-                } else if let Some(items) = parse_enum8(type_name) {
-                    W::wrap(Enum8ColumnData::load(reader, items, size, tz)?)
-                } else if let Some(items) = parse_enum16(type_name) {
-                    W::wrap(Enum16ColumnData::load(reader, items, size, tz)?)
-                } else if let Some((precision, timezone)) = parse_date_time64(type_name) {
-                    let column_timezone = get_timezone(&timezone, tz)?;
-                    W::wrap(DateTime64ColumnData::load(reader, size, precision, column_timezone)?)
+                    W::wrap(ArrayColumnData::load(reader, inner_type, size, tz, tz_override)?)
+                } else if let Some(elements) = parse_tuple_type(type_name) {
+                    W::wrap(TupleColumnData::load(reader, elements, size, tz, tz_override)?)
+                } else if let Some(inner_type) = parse_low_cardinality_type(type_name) {
+                    match parse_nullable_type(inner_type) {
+                        Some(value_type) => {
+                            let (data, nulls) =
+                                LowCardinalityColumnData::load(reader, value_type, size, tz, tz_override)?;
+                            W::wrap(NullableColumnData { inner: Arc::new(data), nulls })
+                        }
+                        None => W::wrap(LowCardinalityColumnData::load(reader, inner_type, size, tz, tz_override)?.0),
+                    }
                 } else {
-                    let message = format!("Unsupported column type \"{}\".", type_name);
-                    return Err(message.into());
+                    match cached_type(type_name) {
+                        Some(ParsedType::Decimal(precision, scale, nobits)) => W::wrap(DecimalColumnData::load(
+                            reader, precision, scale, nobits, size, tz, tz_override,
+                        )?),
+                        Some(ParsedType::Enum8(items)) => {
+                            W::wrap(Enum8ColumnData::load(reader, items, size, tz, tz_override)?)
+                        }
+                        Some(ParsedType::Enum16(items)) => {
+                            W::wrap(Enum16ColumnData::load(reader, items, size, tz, tz_override)?)
+                        }
+                        Some(ParsedType::DateTime(timezone)) => {
+                            let column_timezone = get_timezone(&timezone, tz, tz_override)?;
+                            W::wrap(DateColumnData::<u32>::load(reader, size, column_timezone)?)
+                        }
+                        Some(ParsedType::DateTime64(precision, timezone)) => {
+                            let column_timezone = get_timezone(&timezone, tz, tz_override)?;
+                            W::wrap(DateTime64ColumnData::load(reader, size, precision, column_timezone)?)
+                        }
+                        None => {
+                            let message = format!("Unsupported column type \"{}\".", type_name);
+                            return Err(message.into());
+                        }
+                    }
                 }
             }
         }))
@@ -116,6 +174,8 @@ impl dyn ColumnData {
             SqlType::Ipv4 => W::wrap(IpColumnData::<Ipv4>::with_capacity(capacity)),
             SqlType::Ipv6 => W::wrap(IpColumnData::<Ipv6>::with_capacity(capacity)),
             SqlType::Uuid => W::wrap(IpColumnData::<Uuid>::with_capacity(capacity)),
+            SqlType::Nothing => W::wrap(NothingColumnData::with_capacity(capacity)),
+            SqlType::Json => W::wrap(JsonColumnData::with_capacity(capacity)),
 
             SqlType::Date => W::wrap(DateColumnData::<u16>::with_capacity(capacity, timezone)),
             SqlType::DateTime(DateTimeType::DateTime64(precision, timezone)) => W::wrap(
@@ -147,6 +207,19 @@ impl dyn ColumnData {
                     nobits,
                 })
             }
+            SqlType::Tuple(elements) => {
+                let mut names = Vec::with_capacity(elements.len());
+                let mut inner = Vec::with_capacity(elements.len());
+                for (name, element_type) in elements {
+                    names.push(name);
+                    inner.push(<dyn ColumnData>::from_type::<ArcColumnWrapper>(
+                        element_type.clone(),
+                        timezone,
+                        capacity,
+                    )?);
+                }
+                W::wrap(TupleColumnData::new(names, inner))
+            }
             SqlType::Enum8(enum_values) => W::wrap(Enum8ColumnData {
                 enum_values,
                 inner: ColumnData::from_type::<BoxColumnWrapper>(
@@ -154,6 +227,7 @@ impl dyn ColumnData {
                     timezone,
                     capacity,
                 )?,
+                labels: OnceLock::new(),
             }),
             SqlType::Enum16(enum_values) => W::wrap(Enum16ColumnData {
                 enum_values,
@@ -162,11 +236,224 @@ impl dyn ColumnData {
                     timezone,
                     capacity,
                 )?,
+                labels: OnceLock::new(),
             }),
         })
     }
 }
 
+/// Reads the serialization state prefix of `type_name`. It precedes the
+/// values of a column, and for an `Array`, `Nullable` or `Tuple` the
+/// prefixes of all nested columns come before any of their values.
+fn read_state_prefix<T: ReadEx>(reader: &mut T, type_name: &str) -> Result<()> {
+    if parse_low_cardinality_type(type_name).is_some() {
+        let version: u64 = reader.read_scalar()?;
+        if version != SHARED_DICTIONARIES_WITH_ADDITIONAL_KEYS {
+            let message = format!(
+                "Unsupported LowCardinality keys serialization version {}.",
+                version
+            );
+            return Err(message.into());
+        }
+    } else if let Some(inner_type) = parse_nullable_type(type_name) {
+        read_state_prefix(reader, inner_type)?;
+    } else if let Some(inner_type) = parse_array_type(type_name) {
+        read_state_prefix(reader, inner_type)?;
+    } else if let Some(elements) = parse_tuple_type(type_name) {
+        for (_, element_type) in elements {
+            read_state_prefix(reader, element_type)?;
+        }
+    }
+    Ok(())
+}
+
+/// How the values of a column are laid out on the wire, enough to find
+/// where a column ends without decoding it.
+pub(crate) enum Layout {
+    /// Values of the given number of bytes each.
+    Fixed(usize),
+    /// Values prefixed with their length.
+    String,
+    /// One null flag byte per value, then the values.
+    Nullable(Box<Layout>),
+    /// One end offset per array, then the values of all arrays.
+    Array(Box<Layout>),
+    /// The values of each element one after another.
+    Tuple(Vec<Layout>),
+}
+
+/// Returns the layout of the values of `type_name`, if it is known without
+/// decoding them.
+pub(crate) fn data_layout(type_name: &str) -> Option<Layout> {
+    Some(match_str!(type_name, {
+        "UInt8" | "Int8" | "TinyInt" | "Nothing" => Layout::Fixed(1),
+        "UInt16" | "Int16" | "SmallInt" | "Date" => Layout::Fixed(2),
+        "UInt32" | "Int32" | "Int" | "Integer" | "Float32" | "Float" | "DateTime" | "Timestamp" | "IPv4" => Layout::Fixed(4),
+        "UInt64" | "Int64" | "BigInt" | "Float64" | "Double" => Layout::Fixed(8),
+        "IPv6" | "UUID" => Layout::Fixed(16),
+        "String" | "Char" | "Varchar" | "Text" | "TinyText" | "MediumText" | "LongText" | "Blob" | "TinyBlob" | "MediumBlob" | "LongBlob" => Layout::String,
+        _ => {
+            if let Some(inner_type) = parse_nullable_type(type_name) {
+                Layout::Nullable(Box::new(data_layout(inner_type)?))
+            } else if let Some(str_len) = parse_fixed_string(type_name) {
+                Layout::Fixed(str_len)
+            } else if let Some(inner_type) = parse_array_type(type_name) {
+                Layout::Array(Box::new(data_layout(inner_type)?))
+            } else if let Some(elements) = parse_tuple_type(type_name) {
+                let layouts = elements.into_iter().map(|(_, element_type)| data_layout(element_type));
+                Layout::Tuple(layouts.collect::<Option<_>>()?)
+            } else {
+                match cached_type(type_name)? {
+                    ParsedType::Decimal(_, _, NoBits::N32) => Layout::Fixed(4),
+                    ParsedType::Decimal(_, _, NoBits::N64) => Layout::Fixed(8),
+                    ParsedType::Enum8(_) => Layout::Fixed(1),
+                    ParsedType::Enum16(_) => Layout::Fixed(2),
+                    ParsedType::DateTime(_) => Layout::Fixed(4),
+                    ParsedType::DateTime64(..) => Layout::Fixed(8),
+                }
+            }
+        }
+    }))
+}
+
+/// Parameterized types that take a full parse to recognize, e.g. enums with
+/// their list of items.
+#[derive(Clone)]
+enum ParsedType {
+    Decimal(u8, u8, NoBits),
+    Enum8(Vec<(String, i8)>),
+    Enum16(Vec<(String, i16)>),
+    DateTime(Option<String>),
+    DateTime64(u32, Option<String>),
+}
+
+/// Upper bound of the number of type names kept by `cached_type`.
+const TYPE_CACHE_CAPACITY: usize = 1024;
+
+thread_local! {
+    static TYPE_CACHE: RefCell<HashMap<String, ParsedType>> = RefCell::new(HashMap::new());
+}
+
+/// Parses `type_name`, reusing the result of a previous parse of the same
+/// name on this thread. Every block of a result repeats the column types of
+/// the previous one, so only the first block pays for the parsing.
+fn cached_type(type_name: &str) -> Option<ParsedType> {
+    TYPE_CACHE.with(|cache| {
+        if let Some(parsed) = cache.borrow().get(type_name) {
+            return Some(parsed.clone());
+        }
+
+        let parsed = parse_type(type_name)?;
+        let mut cache = cache.borrow_mut();
+        if cache.len() >= TYPE_CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(type_name.to_string(), parsed.clone());
+        Some(parsed)
+    })
+}
+
+fn parse_type(type_name: &str) -> Option<ParsedType> {
+    if let Some((precision, scale, nobits)) = parse_decimal(type_name) {
+        Some(ParsedType::Decimal(precision, scale, nobits))
+    } else if let Some(items) = parse_enum8(type_name) {
+        Some(ParsedType::Enum8(items))
+    } else if let Some(items) = parse_enum16(type_name) {
+        Some(ParsedType::Enum16(items))
+    } else if let Some(timezone) = parse_datetime_tz(type_name) {
+        Some(ParsedType::DateTime(timezone))
+    } else {
+        parse_date_time64(type_name)
+            .map(|(precision, timezone)| ParsedType::DateTime64(precision, timezone))
+    }
+}
+
+/// Splits `Tuple(a Int8, b String)` or `Tuple(Int8, String)` into its
+/// elements and their names, if the tuple is named.
+pub(crate) fn parse_tuple_type(source: &str) -> Option<Vec<(Option<String>, &str)>> {
+    if !source.starts_with("Tuple(") || !source.ends_with(')') {
+        return None;
+    }
+    let body = &source[6..source.len() - 1];
+
+    let mut elements = Vec::new();
+    let mut depth = 0_usize;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (index, ch) in body.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == q {
+                quote = None;
+            }
+            continue;
+        }
+
+        match ch {
+            '\'' | '`' => quote = Some(ch),
+            '(' => depth += 1,
+            ')' => depth = depth.checked_sub(1)?,
+            ',' if depth == 0 => {
+                elements.push(parse_tuple_element(&body[start..index])?);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if quote.is_some() || depth != 0 {
+        return None;
+    }
+    elements.push(parse_tuple_element(&body[start..])?);
+    Some(elements)
+}
+
+/// Parses a single `name Type` or `Type` element, the name may be
+/// back-quoted.
+fn parse_tuple_element(source: &str) -> Option<(Option<String>, &str)> {
+    let source = source.trim();
+    if source.is_empty() {
+        return None;
+    }
+
+    if let Some(quoted) = source.strip_prefix('`') {
+        let mut name = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((index, ch)) = chars.next() {
+            match ch {
+                '\\' => name.push(chars.next()?.1),
+                '`' => {
+                    let element_type = quoted[index + 1..].trim();
+                    return if element_type.is_empty() {
+                        None
+                    } else {
+                        Some((Some(name), element_type))
+                    };
+                }
+                _ => name.push(ch),
+            }
+        }
+        return None;
+    }
+
+    // A type name is followed by its parameters, a name by a space.
+    match source.find([' ', '(']) {
+        Some(split) if source[split..].starts_with(' ') => {
+            let element_type = source[split + 1..].trim();
+            if element_type.is_empty() {
+                return None;
+            }
+            Some((Some(source[..split].to_string()), element_type))
+        }
+        _ => Some((None, source)),
+    }
+}
+
 fn parse_fixed_string(source: &str) -> Option<usize> {
     if !source.starts_with("FixedString") {
         return None;
@@ -193,6 +480,14 @@ fn parse_nullable_type(source: &str) -> Option<&str> {
     Some(inner_type)
 }
 
+fn parse_low_cardinality_type(source: &str) -> Option<&str> {
+    if !source.starts_with("LowCardinality(") || !source.ends_with(')') {
+        return None;
+    }
+
+    Some(&source[15..source.len() - 1])
+}
+
 fn parse_array_type(source: &str) -> Option<&str> {
     if !source.starts_with("Array") {
         return None;
@@ -219,12 +514,12 @@ fn parse_decimal(source: &str) -> Option<(u8, u8, NoBits)> {
 
     for (idx, byte) in source.as_bytes().iter().enumerate() {
         if *byte == b'(' {
-            match &source.as_bytes()[..idx] {
-                b"Decimal" => {}
-                b"Decimal32" => {
+            match source[..idx].trim_end() {
+                "Decimal" => {}
+                "Decimal32" => {
                     nobits = Some(NoBits::N32);
                 }
-                b"Decimal64" => {
+                "Decimal64" => {
                     nobits = Some(NoBits::N64);
                 }
                 _ => return None,
@@ -245,6 +540,7 @@ fn parse_decimal(source: &str) -> Option<(u8, u8, NoBits)> {
         Some(_) => {
             scale = std::str::from_utf8(&source.as_bytes()[params_indexes.0 + 1..params_indexes.1])
                 .unwrap()
+                .trim()
                 .parse()
                 .ok()
         }
@@ -326,8 +622,7 @@ fn parse_enum(size: EnumSize, input: &str) -> Option<Vec<(String, i16)>> {
                 .map_err(|_| StringStreamError::UnexpectedParse)
         });
 
-    let word_syms = token('\\').with(any()).or(none_of("'".chars()));
-    let word = token('\'').with(many(word_syms)).skip(token('\''));
+    let word = quoted_literal();
 
     let pair = spaces()
         .with(word)
@@ -352,6 +647,35 @@ fn parse_enum(size: EnumSize, input: &str) -> Option<Vec<(String, i16)>> {
     }
 }
 
+/// Parses a single-quoted literal of a type name, e.g. an enum item or a
+/// timezone, unescaping it by the rules of ClickHouse string literals.
+fn quoted_literal<'a>() -> impl Parser<&'a str, Output = String> {
+    let hex = token('x')
+        .with(count_min_max::<String, _, _>(2, 2, hex_digit()))
+        .map(|digits| char::from(u8::from_str_radix(&digits, 16).unwrap()));
+    let escaped = token('\\').with(attempt(hex).or(any().map(unescape_char)));
+
+    token('\'')
+        .with(many(escaped.or(none_of("'".chars()))))
+        .skip(token('\''))
+}
+
+/// Returns the character a `\` escape sequence stands for.
+fn unescape_char(ch: char) -> char {
+    match ch {
+        'a' => '\x07',
+        'b' => '\x08',
+        'e' => '\x1b',
+        'f' => '\x0c',
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        'v' => '\x0b',
+        '0' => '\0',
+        _ => ch,
+    }
+}
+
 fn parse_date_time64(source: &str) -> Option<(u32, Option<String>)> {
     let integer = many1::<String, _, _>(digit()).and_then(|digits| {
         digits
@@ -359,10 +683,7 @@ fn parse_date_time64(source: &str) -> Option<(u32, Option<String>)> {
             .map_err(|_| StringStreamError::UnexpectedParse)
     });
 
-    let word_syms = token('\\').with(any()).or(none_of("'".chars()));
-    let word = token('\'')
-        .with(many::<String, _, _>(word_syms))
-        .skip(token('\''));
+    let word = quoted_literal();
 
     let timezone = optional(spaces().skip(token(',')).skip(spaces()).with(word));
 
@@ -387,16 +708,41 @@ fn parse_date_time64(source: &str) -> Option<(u32, Option<String>)> {
     }
 }
 
-fn get_timezone(timezone: &Option<String>, tz: Tz) -> Result<Tz> {
-    match timezone {
-        None => Ok(tz),
-        Some(t) => Ok(t.parse()?),
+/// Parses `DateTime` and `DateTime('Tz')`, returning the timezone literal if
+/// the type declares one.
+fn parse_datetime_tz(source: &str) -> Option<Option<String>> {
+    let word = quoted_literal();
+
+    let timezone = token('(')
+        .skip(spaces())
+        .with(word)
+        .skip(spaces())
+        .skip(token(')'));
+
+    let mut parser = spaces()
+        .with(string("DateTime"))
+        .skip(spaces())
+        .with(optional(timezone))
+        .skip(spaces());
+
+    match parser.parse(source) {
+        Ok((timezone, "")) => Some(timezone),
+        _ => None,
+    }
+}
+
+fn get_timezone(timezone: &Option<String>, tz: Tz, tz_override: Option<Tz>) -> Result<Tz> {
+    match (tz_override, timezone) {
+        (Some(o), _) => Ok(o),
+        (None, None) => Ok(tz),
+        (None, Some(t)) => Ok(t.parse()?),
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::types::{column::column_data::BoxColumnData, ValueRef};
 
     #[test]
     fn test_parse_decimal() {
@@ -409,6 +755,203 @@ mod test {
         assert_eq!(parse_decimal("Decimal(0)"), None);
         assert_eq!(parse_decimal("Decimal(1, 2, 3)"), None);
         assert_eq!(parse_decimal("Decimal64(9)"), Some((18, 9, NoBits::N64)));
+        assert_eq!(parse_decimal("Decimal32(4)"), Some((9, 4, NoBits::N32)));
+        assert_eq!(
+            parse_decimal("Decimal ( 9 , 4 )"),
+            Some((9, 4, NoBits::N32))
+        );
+        assert_eq!(parse_decimal("Decimal32( 4 )"), Some((9, 4, NoBits::N32)));
+        assert_eq!(parse_decimal("Decimal128(4)"), None);
+    }
+
+    #[test]
+    fn test_load_date_time64_with_timezone_override() {
+        let type_name = "DateTime64(3, 'Europe/Moscow')";
+        let source = 1_000_i64.to_le_bytes();
+
+        let mut cursor = std::io::Cursor::new(&source[..]);
+        let column: BoxColumnData = <dyn ColumnData>::load_data::<BoxColumnWrapper, _>(
+            &mut cursor,
+            type_name,
+            1,
+            Tz::Zulu,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            column.sql_type(),
+            SqlType::DateTime(DateTimeType::DateTime64(3, Tz::Europe__Moscow))
+        );
+
+        let mut cursor = std::io::Cursor::new(&source[..]);
+        let column: BoxColumnData = <dyn ColumnData>::load_data::<BoxColumnWrapper, _>(
+            &mut cursor,
+            type_name,
+            1,
+            Tz::Zulu,
+            Some(Tz::UTC),
+        )
+        .unwrap();
+        assert_eq!(
+            column.sql_type(),
+            SqlType::DateTime(DateTimeType::DateTime64(3, Tz::UTC))
+        );
+    }
+
+    #[test]
+    fn test_parse_datetime_tz() {
+        assert_eq!(parse_datetime_tz("DateTime"), Some(None));
+        assert_eq!(
+            parse_datetime_tz("DateTime('UTC')"),
+            Some(Some("UTC".to_string()))
+        );
+        assert_eq!(
+            parse_datetime_tz(" DateTime ( 'Europe/London' ) "),
+            Some(Some("Europe/London".to_string()))
+        );
+        assert_eq!(parse_datetime_tz("DateTime64(3)"), None);
+        assert_eq!(parse_datetime_tz("DateTime(UTC)"), None);
+    }
+
+    #[test]
+    fn test_load_datetime_with_timezone() {
+        let source = 1_000_u32.to_le_bytes();
+        let load = |type_name: &str, tz_override: Option<Tz>| -> Result<Tz> {
+            let mut cursor = std::io::Cursor::new(&source[..]);
+            let column: BoxColumnData = <dyn ColumnData>::load_data::<BoxColumnWrapper, _>(
+                &mut cursor,
+                type_name,
+                1,
+                Tz::Zulu,
+                tz_override,
+            )?;
+            match column.at(0) {
+                ValueRef::DateTime(1_000, tz) => Ok(tz),
+                value => panic!("unexpected value {:?}", value),
+            }
+        };
+
+        assert_eq!(load("DateTime", None).unwrap(), Tz::Zulu);
+        assert_eq!(load("DateTime('UTC')", None).unwrap(), Tz::UTC);
+        assert_eq!(
+            load("DateTime('Europe/London')", None).unwrap(),
+            Tz::Europe__London
+        );
+        assert_eq!(
+            load("DateTime('Europe/London')", Some(Tz::UTC)).unwrap(),
+            Tz::UTC
+        );
+        assert!(load("DateTime('Mars/Olympus')", None).is_err());
+    }
+
+    #[test]
+    fn test_sql_type_round_trip() {
+        let enum_items = vec![
+            ("a".to_string(), 1),
+            ("it's".to_string(), 2),
+            ("back\\slash".to_string(), -3),
+        ];
+        let types = vec![
+            SqlType::UInt8,
+            SqlType::UInt16,
+            SqlType::UInt32,
+            SqlType::UInt64,
+            SqlType::Int8,
+            SqlType::Int16,
+            SqlType::Int32,
+            SqlType::Int64,
+            SqlType::String,
+            SqlType::FixedString(16),
+            SqlType::Float32,
+            SqlType::Float64,
+            SqlType::Date,
+            SqlType::DateTime(DateTimeType::DateTime32),
+            SqlType::DateTime(DateTimeType::DateTime64(3, Tz::Europe__Moscow)),
+            SqlType::Ipv4,
+            SqlType::Ipv6,
+            SqlType::Uuid,
+            SqlType::Nothing,
+            SqlType::Nullable(SqlType::Int32.into()),
+            SqlType::Nullable(SqlType::Nothing.into()),
+            SqlType::Json,
+            SqlType::Array(SqlType::String.into()),
+            SqlType::Decimal(9, 2),
+            SqlType::Decimal(18, 4),
+            SqlType::Enum8(enum_items.clone()),
+            SqlType::Enum16(
+                enum_items
+                    .iter()
+                    .map(|(k, v)| (k.clone(), *v as i16 * 1000))
+                    .collect(),
+            ),
+            SqlType::Array(SqlType::Nullable(SqlType::Decimal(18, 4).into()).into()),
+            SqlType::Array(SqlType::Array(SqlType::Enum8(enum_items).into()).into()),
+        ];
+
+        for sql_type in types {
+            let type_name = sql_type.to_string();
+            let mut cursor = std::io::Cursor::new(&[][..]);
+            let column: BoxColumnData = <dyn ColumnData>::load_data::<BoxColumnWrapper, _>(
+                &mut cursor,
+                &type_name,
+                0,
+                Tz::Zulu,
+                None,
+            )
+            .unwrap_or_else(|err| panic!("failed to parse {}: {}", type_name, err));
+            assert_eq!(column.sql_type(), sql_type, "{}", type_name);
+        }
+
+        assert_eq!(
+            SqlType::Array(SqlType::Nullable(SqlType::Decimal(18, 4).into()).into()).to_string(),
+            "Array(Nullable(Decimal(18, 4)))"
+        );
+        assert_eq!(
+            SqlType::Enum8(vec![("a".to_string(), 1), ("b".to_string(), 2)]).to_string(),
+            "Enum8('a' = 1, 'b' = 2)"
+        );
+    }
+
+    #[test]
+    fn test_cached_type() {
+        let type_name = "Enum8('a' = 1, 'b' = 2)";
+        for _ in 0..2 {
+            match cached_type(type_name) {
+                Some(ParsedType::Enum8(items)) => {
+                    assert_eq!(items, vec![("a".to_string(), 1), ("b".to_string(), 2)])
+                }
+                _ => panic!("{} is not parsed as Enum8", type_name),
+            }
+        }
+        assert!(TYPE_CACHE.with(|cache| cache.borrow().contains_key(type_name)));
+
+        assert!(cached_type("Enum8('a' = 1").is_none());
+        assert!(!TYPE_CACHE.with(|cache| cache.borrow().contains_key("Enum8('a' = 1")));
+    }
+
+    #[test]
+    fn test_parse_enum_escapes() {
+        let type_name =
+            r"Enum8('a\'b' = 1, 'a\\b' = 2, 'a,b' = 3, 'a\nb\t' = 4, '\x41\0' = 5, '\q' = 6)";
+        let items = parse_enum8(type_name).unwrap();
+        assert_eq!(
+            items,
+            vec![
+                ("a'b".to_string(), 1),
+                ("a\\b".to_string(), 2),
+                ("a,b".to_string(), 3),
+                ("a\nb\t".to_string(), 4),
+                ("A\0".to_string(), 5),
+                ("q".to_string(), 6),
+            ]
+        );
+
+        let quoted = SqlType::Enum8(items.clone()).to_string();
+        assert_eq!(
+            quoted,
+            r"Enum8('a\'b' = 1, 'a\\b' = 2, 'a,b' = 3, 'a\nb\t' = 4, 'A\0' = 5, 'q' = 6)"
+        );
+        assert_eq!(parse_enum8(&quoted), Some(items));
     }
 
     #[test]
@@ -416,6 +959,23 @@ mod test {
         assert_eq!(parse_array_type("Array(UInt8)"), Some("UInt8"));
     }
 
+    #[test]
+    fn test_parse_tuple_type() {
+        assert_eq!(
+            parse_tuple_type("Tuple(a UInt32, `b c` Nullable(String))"),
+            Some(vec![
+                (Some("a".to_string()), "UInt32"),
+                (Some("b c".to_string()), "Nullable(String)"),
+            ])
+        );
+        assert_eq!(
+            parse_tuple_type("Tuple(UInt8, Array(Tuple(String, Int8)))"),
+            Some(vec![(None, "UInt8"), (None, "Array(Tuple(String, Int8))")])
+        );
+        assert_eq!(parse_tuple_type("Tuple(UInt8"), None);
+        assert_eq!(parse_tuple_type("Array(UInt8)"), None);
+    }
+
     #[test]
     fn test_parse_nullable_type() {
         assert_eq!(parse_nullable_type("Nullable(Int8)"), Some("Int8"));
@@ -2,8 +2,12 @@ use std::cmp;
 
 use crate::{
     binary::{Encoder, ReadEx},
-    errors::Result,
-    types::{column::column_data::BoxColumnData, from_sql::*, Column, SqlType, Value, ValueRef, ColumnType},
+    errors::{Error, Result},
+    types::{
+        column::{column_data::BoxColumnData, Either},
+        from_sql::*,
+        Column, ColumnType, SqlType, Value, ValueRef,
+    },
 };
 
 use super::column_data::ColumnData;
@@ -32,15 +36,52 @@ impl FixedStringColumnData {
     }
 
     pub(crate) fn load<T: ReadEx>(reader: &mut T, size: usize, str_len: usize) -> Result<Self> {
-        let mut instance = Self::with_capacity(size, str_len);
+        // Values are stored back to back as on the wire, read them at once.
+        let mut buffer = vec![0_u8; size * str_len];
+        reader.read_bytes(&mut buffer)?;
+        Ok(Self { buffer, str_len })
+    }
+
+    /// Returns the raw `str_len` bytes of the value, including the padding.
+    pub(crate) fn as_bytes(&self, index: usize) -> &[u8] {
+        let shift = index * self.str_len;
+        &self.buffer[shift..shift + self.str_len]
+    }
+}
 
-        for _ in 0..size {
-            let old_len = instance.buffer.len();
-            instance.buffer.resize(old_len + str_len, 0_u8);
-            reader.read_bytes(&mut instance.buffer[old_len..old_len + str_len])?;
+/// Strips the null bytes `FixedString` values are padded with.
+pub(crate) fn trim_nulls(bytes: &[u8]) -> &[u8] {
+    let len = bytes.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    &bytes[..len]
+}
+
+/// Checks that every value of `column` fits into `FixedString(str_len)`.
+pub(crate) fn check_str_len<K: ColumnType>(column: &Column<K>, str_len: usize) -> Result<()> {
+    for index in 0..column.len() {
+        let len = match column.at(index) {
+            ValueRef::Nullable(Either::Left(_)) => continue,
+            ValueRef::Nullable(Either::Right(inner)) => value_len(&inner)?,
+            value => value_len(&value)?,
+        };
+
+        if len > str_len {
+            let message = format!(
+                "value of column `{}` at row {} is {} bytes long and doesn't fit into FixedString({}).",
+                column.name(),
+                index,
+                len,
+                str_len
+            );
+            return Err(Error::Other(message.into()));
         }
+    }
+    Ok(())
+}
 
-        Ok(instance)
+fn value_len(value: &ValueRef) -> Result<usize> {
+    match value {
+        ValueRef::Array(SqlType::UInt8, vs) => Ok(vs.len()),
+        _ => Ok(value.as_bytes()?.len()),
     }
 }
 
@@ -64,13 +105,11 @@ impl ColumnData for FixedStringColumnData {
         let l = cmp::min(bs.len(), self.str_len);
         let old_len = self.buffer.len();
         self.buffer.extend_from_slice(&bs.as_bytes()[0..l]);
-        self.buffer.resize(old_len + (self.str_len - l), 0_u8);
+        self.buffer.resize(old_len + self.str_len, 0_u8);
     }
 
     fn at(&self, index: usize) -> ValueRef {
-        let shift = index * self.str_len;
-        let str_ref = &self.buffer[shift..shift + self.str_len];
-        ValueRef::String(str_ref)
+        ValueRef::String(self.as_bytes(index))
     }
 
     fn clone_instance(&self) -> BoxColumnData {
@@ -181,3 +220,66 @@ impl<K: ColumnType> ColumnData for NullableFixedStringAdapter<K> {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use chrono_tz::Tz;
+
+    use crate::types::{Block, Simple};
+
+    use super::*;
+
+    fn write_and_read(values: Vec<&'static str>) -> Result<Column<Simple>> {
+        let size = values.len();
+        let block = Block::<Simple>::new().column("s", values);
+        let column = block.columns()[0]
+            .clone()
+            .cast_to(SqlType::FixedString(4))?;
+
+        let mut encoder = Encoder::new();
+        column.write(&mut encoder);
+
+        let mut reader = Cursor::new(encoder.get_buffer_ref());
+        Column::read(&mut reader, size, Tz::Zulu, None)
+    }
+
+    #[test]
+    fn test_shorter_value_is_padded() {
+        let column = write_and_read(vec!["ab"]).unwrap();
+
+        assert_eq!(column.sql_type(), SqlType::FixedString(4));
+        assert_eq!(column.fixed_string_bytes(0).unwrap(), b"ab\0\0");
+        assert_eq!(column.fixed_string_trimmed(0).unwrap(), "ab");
+    }
+
+    #[test]
+    fn test_value_of_exact_length() {
+        let column = write_and_read(vec!["abcd"]).unwrap();
+
+        assert_eq!(column.fixed_string_bytes(0).unwrap(), b"abcd");
+        assert_eq!(column.fixed_string_trimmed(0).unwrap(), "abcd");
+    }
+
+    #[test]
+    fn test_overflowing_value() {
+        assert!(write_and_read(vec!["ab", "abcde"]).is_err());
+    }
+
+    #[test]
+    fn test_overflowing_binary_value() {
+        let block = Block::<Simple>::new().column("s", vec![vec![1_u8, 2], vec![1, 2, 3]]);
+        let column = &block.columns()[0];
+
+        assert!(column.clone().cast_to(SqlType::FixedString(3)).is_ok());
+        assert!(column.clone().cast_to(SqlType::FixedString(2)).is_err());
+    }
+
+    #[test]
+    fn test_trim_nulls() {
+        assert_eq!(trim_nulls(b"a\0b\0\0"), b"a\0b");
+        assert_eq!(trim_nulls(b"\0\0"), b"");
+        assert_eq!(trim_nulls(b""), b"");
+    }
+}
@@ -6,10 +6,14 @@ use std::{
 
 use crate::{
     binary::{Encoder, ReadEx},
-    errors::Result,
+    errors::{Error, Result},
     types::{
-        column::{column_data::BoxColumnData, nullable::NullableColumnData, ColumnWrapper},
-        SqlType, Value, ValueRef
+        column::{
+            column_data::{ArcColumnData, BoxColumnData},
+            nullable::NullableColumnData,
+            ColumnWrapper, Either,
+        },
+        Column, ColumnType, SqlType, Value, ValueRef,
     },
 };
 
@@ -22,6 +26,12 @@ pub(crate) trait IpVersion: Copy + Sync + Send + 'static {
     fn get(inner: &[u8], index: usize) -> ValueRef;
 }
 
+/// Addresses that `String` values can be parsed into on insert.
+pub(crate) trait IpParse: IpVersion {
+    /// Appends the bytes of the address `source`, `false` if it isn't one.
+    fn parse_into(inner: &mut Vec<u8>, source: &str) -> bool;
+}
+
 #[derive(Copy, Clone)]
 pub(crate) struct Ipv4;
 
@@ -115,6 +125,80 @@ impl IpVersion for Uuid {
     }
 }
 
+impl IpParse for Ipv4 {
+    fn parse_into(inner: &mut Vec<u8>, source: &str) -> bool {
+        match source.parse::<Ipv4Addr>() {
+            Ok(ip) => {
+                inner.extend(&ip.octets());
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+impl IpParse for Ipv6 {
+    fn parse_into(inner: &mut Vec<u8>, source: &str) -> bool {
+        match source.parse::<Ipv6Addr>() {
+            Ok(ip) => {
+                inner.extend(&ip.octets());
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// Parses the addresses of a `String` or `Nullable(String)` column into the
+/// data of an `IPv4` or `IPv6` column, failing on the first value that isn't
+/// one.
+pub(crate) fn parse_ip_column<V: IpParse, K: ColumnType>(
+    column: &Column<K>,
+) -> Result<ArcColumnData> {
+    let nullable = matches!(column.sql_type(), SqlType::Nullable(_));
+    let mut inner = Vec::with_capacity(column.len() * V::size());
+    let mut nulls = Vec::new();
+
+    for index in 0..column.len() {
+        let value = match column.at(index) {
+            ValueRef::Nullable(Either::Left(_)) => {
+                inner.resize(inner.len() + V::size(), 0);
+                nulls.push(1);
+                continue;
+            }
+            ValueRef::Nullable(Either::Right(value)) => *value,
+            value => value,
+        };
+        if nullable {
+            nulls.push(0);
+        }
+
+        if !value
+            .as_str()
+            .is_ok_and(|source| V::parse_into(&mut inner, source))
+        {
+            let message = format!(
+                "value `{}` of column `{}` at row {} is not a valid {} address.",
+                value,
+                column.name(),
+                index,
+                V::sql_type()
+            );
+            return Err(Error::Other(message.into()));
+        }
+    }
+
+    let data = Arc::new(IpColumnData::<V> {
+        inner,
+        phantom: PhantomData,
+    });
+    if nullable {
+        Ok(Arc::new(NullableColumnData { inner: data, nulls }))
+    } else {
+        Ok(data)
+    }
+}
+
 impl ColumnFrom for Vec<Ipv4Addr> {
     fn column_from<W: ColumnWrapper>(data: Self) -> W::Wrapper {
         let mut inner = Vec::with_capacity(data.len());
@@ -255,7 +339,7 @@ pub(crate) struct IpColumnData<V: IpVersion> {
 impl<V: IpVersion> IpColumnData<V> {
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            inner: vec![0; capacity * V::size()],
+            inner: Vec::with_capacity(capacity * V::size()),
             phantom: PhantomData,
         }
     }
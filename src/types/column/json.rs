@@ -0,0 +1,376 @@
+use std::fmt::Write;
+
+use chrono_tz::Tz;
+
+#[cfg(feature = "json")]
+use crate::types::column::{ColumnFrom, ColumnWrapper};
+use crate::{
+    binary::{Encoder, ReadEx},
+    errors::Result,
+    types::{
+        column::{
+            column_data::{BoxColumnData, ColumnData},
+            factory::parse_tuple_type,
+            string::StringColumnData,
+            BoxColumnWrapper, Either,
+        },
+        SqlType, Value, ValueRef,
+    },
+};
+
+/// Serialization kinds of `Object('json')` columns.
+const KIND_TUPLE: u8 = 0;
+const KIND_STRING: u8 = 1;
+
+/// Placeholder subcolumn the server sends for objects without any key.
+const DUMMY_SUBCOLUMN: &str = "_dummy";
+
+/// Column of the experimental `Object('json')` type.
+///
+/// The server sends such a column as a tuple of dynamic subcolumns, one per
+/// path seen in the block, with nested objects as nested tuples. They are
+/// reassembled into one JSON object per row, kept as text and read as a
+/// `String` or `&str`. Keys missing in a row come back with the default
+/// value of their subcolumn, as the server stores them.
+///
+/// Values are inserted as JSON text, which the server parses itself. Only
+/// top-level `Object('json')` columns are supported.
+pub(crate) struct JsonColumnData {
+    inner: BoxColumnData,
+}
+
+/// Decoded subcolumns of an object, in the order of the tuple elements.
+enum Subcolumn {
+    Value(BoxColumnData),
+    Object(Vec<(String, Subcolumn)>),
+}
+
+impl JsonColumnData {
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Box::new(StringColumnData::with_capacity(capacity)),
+        }
+    }
+
+    pub(crate) fn load<R: ReadEx>(
+        reader: &mut R,
+        size: usize,
+        tz: Tz,
+        tz_override: Option<Tz>,
+    ) -> Result<Self> {
+        if size == 0 {
+            return Ok(Self::with_capacity(0));
+        }
+
+        let kind: u8 = reader.read_scalar()?;
+        match kind {
+            KIND_STRING => Ok(Self {
+                inner: Box::new(StringColumnData::load(reader, size)?),
+            }),
+            KIND_TUPLE => {
+                let type_name = reader.read_string()?;
+                let object = load_object(reader, &type_name, size, tz, tz_override)?;
+
+                let mut inner = StringColumnData::with_capacity(size);
+                let mut buffer = String::new();
+                for row in 0..size {
+                    buffer.clear();
+                    write_object(&mut buffer, &object, row);
+                    inner.append(&buffer);
+                }
+                Ok(Self {
+                    inner: Box::new(inner),
+                })
+            }
+            _ => Err(format!("Unsupported Object('json') serialization kind {}.", kind).into()),
+        }
+    }
+}
+
+/// JSON values inserted into an `Object('json')` column.
+#[cfg(feature = "json")]
+impl ColumnFrom for Vec<serde_json::Value> {
+    fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
+        let mut inner = StringColumnData::with_capacity(source.len());
+        inner.append_iter(source.iter().map(serde_json::Value::to_string));
+        W::wrap(JsonColumnData {
+            inner: Box::new(inner),
+        })
+    }
+}
+
+fn load_object<R: ReadEx>(
+    reader: &mut R,
+    type_name: &str,
+    size: usize,
+    tz: Tz,
+    tz_override: Option<Tz>,
+) -> Result<Subcolumn> {
+    let elements = match parse_tuple_elements(type_name) {
+        Some(elements) => elements,
+        None => {
+            let message = format!("Unsupported Object('json') subcolumns \"{}\".", type_name);
+            return Err(message.into());
+        }
+    };
+
+    let mut fields = Vec::with_capacity(elements.len());
+    for (name, element_type) in elements {
+        let subcolumn = if element_type.starts_with("Tuple(") {
+            load_object(reader, element_type, size, tz, tz_override)?
+        } else {
+            Subcolumn::Value(<dyn ColumnData>::load_data::<BoxColumnWrapper, _>(
+                reader,
+                element_type,
+                size,
+                tz,
+                tz_override,
+            )?)
+        };
+        fields.push((name, subcolumn));
+    }
+
+    Ok(Subcolumn::Object(fields))
+}
+
+/// Splits `Tuple(a Int8, b String)` into its named elements.
+fn parse_tuple_elements(source: &str) -> Option<Vec<(String, &str)>> {
+    parse_tuple_type(source)?
+        .into_iter()
+        .map(|(name, element_type)| Some((name?, element_type)))
+        .collect()
+}
+
+fn write_object(buffer: &mut String, subcolumn: &Subcolumn, row: usize) {
+    match subcolumn {
+        Subcolumn::Value(column) => write_value(buffer, column.at(row)),
+        Subcolumn::Object(fields) => {
+            buffer.push('{');
+            let mut first = true;
+            for (name, field) in fields {
+                if name == DUMMY_SUBCOLUMN {
+                    continue;
+                }
+                if !first {
+                    buffer.push(',');
+                }
+                first = false;
+                write_string(buffer, name);
+                buffer.push(':');
+                write_object(buffer, field, row);
+            }
+            buffer.push('}');
+        }
+    }
+}
+
+fn write_value(buffer: &mut String, value: ValueRef<'_>) {
+    match value {
+        ValueRef::UInt8(_)
+        | ValueRef::UInt16(_)
+        | ValueRef::UInt32(_)
+        | ValueRef::UInt64(_)
+        | ValueRef::Int8(_)
+        | ValueRef::Int16(_)
+        | ValueRef::Int32(_)
+        | ValueRef::Int64(_) => write!(buffer, "{}", value).unwrap(),
+        ValueRef::Float32(v) if v.is_finite() => write!(buffer, "{}", v).unwrap(),
+        ValueRef::Float64(v) if v.is_finite() => write!(buffer, "{}", v).unwrap(),
+        ValueRef::Float32(_) | ValueRef::Float64(_) => buffer.push_str("null"),
+        ValueRef::String(v) => write_string(buffer, &String::from_utf8_lossy(v)),
+        ValueRef::Nullable(Either::Left(_)) => buffer.push_str("null"),
+        ValueRef::Nullable(Either::Right(inner)) => write_value(buffer, *inner),
+        ValueRef::Array(_, values) => {
+            buffer.push('[');
+            for (index, value) in values.iter().enumerate() {
+                if index > 0 {
+                    buffer.push(',');
+                }
+                write_value(buffer, value.clone());
+            }
+            buffer.push(']');
+        }
+        _ => write_string(buffer, &value.to_string()),
+    }
+}
+
+fn write_string(buffer: &mut String, value: &str) {
+    buffer.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => buffer.push_str("\\\""),
+            '\\' => buffer.push_str("\\\\"),
+            '\n' => buffer.push_str("\\n"),
+            '\r' => buffer.push_str("\\r"),
+            '\t' => buffer.push_str("\\t"),
+            c if c < ' ' => write!(buffer, "\\u{:04x}", c as u32).unwrap(),
+            c => buffer.push(c),
+        }
+    }
+    buffer.push('"');
+}
+
+impl ColumnData for JsonColumnData {
+    fn sql_type(&self) -> SqlType {
+        SqlType::Json
+    }
+
+    fn save(&self, encoder: &mut Encoder, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        encoder.write(KIND_STRING);
+        self.inner.save(encoder, start, end);
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn push(&mut self, value: Value) {
+        self.inner.push(value);
+    }
+
+    fn at(&self, index: usize) -> ValueRef<'_> {
+        self.inner.at(index)
+    }
+
+    fn clone_instance(&self) -> BoxColumnData {
+        Box::new(Self {
+            inner: self.inner.clone_instance(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn load(source: &[u8], size: usize) -> BoxColumnData {
+        let mut cursor = Cursor::new(source);
+        let column = <dyn ColumnData>::load_data::<BoxColumnWrapper, _>(
+            &mut cursor,
+            "Object('json')",
+            size,
+            Tz::Zulu,
+            None,
+        )
+        .unwrap();
+        assert_eq!(cursor.position() as usize, source.len());
+        column
+    }
+
+    #[test]
+    fn test_load_object() {
+        let mut encoder = Encoder::new();
+        encoder.write(KIND_TUPLE);
+        encoder.string("Tuple(a Int8, b String)");
+        encoder.write(1_i8);
+        encoder.string("x");
+
+        let column = load(&encoder.get_buffer(), 1);
+        assert_eq!(column.sql_type(), SqlType::Json);
+        assert_eq!(column.at(0), ValueRef::from(r#"{"a":1,"b":"x"}"#));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_load_json_value() {
+        use crate::types::FromSql;
+
+        let mut encoder = Encoder::new();
+        encoder.write(KIND_TUPLE);
+        encoder.string("Tuple(a Int8, b String)");
+        encoder.write(1_i8);
+        encoder.string("x");
+
+        let column = load(&encoder.get_buffer(), 1);
+        let value = serde_json::Value::from_sql(column.at(0)).unwrap();
+        assert_eq!(value, serde_json::json!({"a": 1, "b": "x"}));
+        // The text stays readable as a string.
+        assert_eq!(
+            <&str>::from_sql(column.at(0)).unwrap(),
+            r#"{"a":1,"b":"x"}"#
+        );
+
+        serde_json::Value::from_sql(ValueRef::from("{")).unwrap_err();
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_values_column() {
+        use crate::types::Block;
+
+        let values = vec![
+            serde_json::json!({"a": 1}),
+            serde_json::json!({"b": [true, null]}),
+        ];
+        let block = Block::new().column("j", values.clone());
+        assert_eq!(block.columns()[0].sql_type(), SqlType::Json);
+
+        let read: Vec<serde_json::Value> = (0..2).map(|row| block.get(row, "j").unwrap()).collect();
+        assert_eq!(read, values);
+    }
+
+    #[test]
+    fn test_load_nested_object() {
+        let mut encoder = Encoder::new();
+        encoder.write(KIND_TUPLE);
+        encoder.string("Tuple(`a b` Array(Int8), c Tuple(d Nullable(Float64), `e\\`` String))");
+        // `a b`: offsets, then the values
+        encoder.write(1_u64);
+        encoder.write(3_u64);
+        encoder.write(1_i8);
+        encoder.write(2_i8);
+        encoder.write(3_i8);
+        // c.d: null map, then the values
+        encoder.write(0_u8);
+        encoder.write(1_u8);
+        encoder.write(0.5_f64);
+        encoder.write(0_f64);
+        // c.e`
+        encoder.string("say \"hi\"\n");
+        encoder.string("");
+
+        let column = load(&encoder.get_buffer(), 2);
+        assert_eq!(
+            column.at(0),
+            ValueRef::from(r#"{"a b":[1],"c":{"d":0.5,"e`":"say \"hi\"\n"}}"#)
+        );
+        assert_eq!(
+            column.at(1),
+            ValueRef::from(r#"{"a b":[2,3],"c":{"d":null,"e`":""}}"#)
+        );
+    }
+
+    #[test]
+    fn test_save_as_string() {
+        let mut column = JsonColumnData::with_capacity(1);
+        column.push(Value::from(r#"{"a":1}"#));
+
+        let mut encoder = Encoder::new();
+        column.save(&mut encoder, 0, 1);
+        let source = encoder.get_buffer();
+        assert_eq!(source[0], KIND_STRING);
+
+        let column = load(&source, 1);
+        assert_eq!(column.at(0), ValueRef::from(r#"{"a":1}"#));
+    }
+
+    #[test]
+    fn test_parse_tuple_elements() {
+        assert_eq!(
+            parse_tuple_elements("Tuple(a Int8, `b.c` Enum8('x,y' = 1), d Tuple(e String))"),
+            Some(vec![
+                ("a".to_string(), "Int8"),
+                ("b.c".to_string(), "Enum8('x,y' = 1)"),
+                ("d".to_string(), "Tuple(e String)"),
+            ])
+        );
+        assert_eq!(parse_tuple_elements("Tuple(Int8)"), None);
+        assert_eq!(parse_tuple_elements("Tuple(a Int8"), None);
+        assert_eq!(parse_tuple_elements("Array(Int8)"), None);
+    }
+}
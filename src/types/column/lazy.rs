@@ -0,0 +1,281 @@
+use std::{
+    mem,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use chrono_tz::Tz;
+
+use crate::{
+    binary::{put_uvarint, Encoder, ReadEx},
+    errors::Result,
+    types::{
+        column::{
+            column_data::{ArcColumnData, BoxColumnData, ColumnData},
+            factory::{data_layout, Layout},
+            ArcColumnWrapper, ArrayColumnData,
+        },
+        SqlType, Value, ValueRef,
+    },
+};
+
+/// Column whose values are kept as received and only decoded when they are
+/// accessed for the first time.
+///
+/// Reading such a column costs a walk over the length prefixes of its
+/// values and a copy of its bytes. The decoded column is cached, so every
+/// later access is as fast as for an eagerly decoded one.
+pub(crate) struct LazyColumnData {
+    type_name: String,
+    sql_type: SqlType,
+    size: usize,
+    tz: Tz,
+    tz_override: Option<Tz>,
+    source: Mutex<Vec<u8>>,
+    decoded: OnceLock<ArcColumnData>,
+}
+
+impl LazyColumnData {
+    /// Reads the bytes of `size` values of `type_name`, or returns `None`
+    /// without reading anything if the type has to be decoded to know where
+    /// its values end.
+    pub(crate) fn load<R: ReadEx>(
+        reader: &mut R,
+        type_name: &str,
+        size: usize,
+        tz: Tz,
+        tz_override: Option<Tz>,
+    ) -> Result<Option<Self>> {
+        let layout = match data_layout(type_name) {
+            None => return Ok(None),
+            Some(layout) => layout,
+        };
+
+        // An empty column is cheap to build and checks the type up front, as
+        // eager decoding does.
+        let empty = <dyn ColumnData>::load_data::<ArcColumnWrapper, _>(
+            &mut &[][..],
+            type_name,
+            0,
+            tz,
+            tz_override,
+        )?;
+
+        let mut source = Vec::new();
+        copy_values(reader, &layout, size, &mut source)?;
+
+        Ok(Some(Self {
+            type_name: type_name.to_string(),
+            sql_type: empty.sql_type(),
+            size,
+            tz,
+            tz_override,
+            source: Mutex::new(source),
+            decoded: OnceLock::new(),
+        }))
+    }
+
+    /// Returns `true` once the values have been decoded.
+    #[cfg(test)]
+    pub(crate) fn is_decoded(&self) -> bool {
+        self.decoded.get().is_some()
+    }
+
+    fn decoded(&self) -> &ArcColumnData {
+        self.decoded.get_or_init(|| {
+            let source = mem::take(&mut *self.source.lock().unwrap());
+            <dyn ColumnData>::load_data::<ArcColumnWrapper, _>(
+                &mut &source[..],
+                &self.type_name,
+                self.size,
+                self.tz,
+                self.tz_override,
+            )
+            .expect("the column has been read up to its end already")
+        })
+    }
+
+    fn decoded_mut(&mut self) -> &mut (dyn ColumnData + Send + Sync) {
+        self.decoded();
+        let data = self.decoded.get_mut().unwrap();
+        if Arc::get_mut(data).is_none() {
+            *data = Arc::from(data.clone_instance());
+        }
+        Arc::get_mut(data).unwrap()
+    }
+}
+
+/// Appends the bytes of `size` values laid out as `layout` to `target`.
+fn copy_values<R: ReadEx>(
+    reader: &mut R,
+    layout: &Layout,
+    size: usize,
+    target: &mut Vec<u8>,
+) -> Result<()> {
+    match layout {
+        Layout::Fixed(width) => copy_bytes(reader, size * width, target),
+        Layout::String => {
+            let mut prefix = [0_u8; 10];
+            for _ in 0..size {
+                let len = reader.read_uvarint()?;
+                let prefix_len = put_uvarint(&mut prefix[..], len);
+                target.extend_from_slice(&prefix[..prefix_len]);
+                copy_bytes(reader, len as usize, target)?;
+            }
+            Ok(())
+        }
+        Layout::Nullable(inner) => {
+            copy_bytes(reader, size, target)?;
+            copy_values(reader, inner, size, target)
+        }
+        Layout::Array(inner) => {
+            copy_bytes(reader, size * mem::size_of::<u64>(), target)?;
+            let total = match size {
+                0 => 0,
+                _ => {
+                    let mut last = [0_u8; 8];
+                    last.copy_from_slice(&target[target.len() - 8..]);
+                    u64::from_le_bytes(last) as usize
+                }
+            };
+            copy_values(reader, inner, total, target)
+        }
+        Layout::Tuple(elements) => {
+            for element in elements {
+                copy_values(reader, element, size, target)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn copy_bytes<R: ReadEx>(reader: &mut R, len: usize, target: &mut Vec<u8>) -> Result<()> {
+    let start = target.len();
+    target.resize(start + len, 0);
+    reader.read_bytes(&mut target[start..])
+}
+
+impl ColumnData for LazyColumnData {
+    fn sql_type(&self) -> SqlType {
+        self.sql_type.clone()
+    }
+
+    fn save(&self, encoder: &mut Encoder, start: usize, end: usize) {
+        self.decoded().save(encoder, start, end)
+    }
+
+    fn len(&self) -> usize {
+        match self.decoded.get() {
+            None => self.size,
+            Some(data) => data.len(),
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        self.decoded_mut().push(value)
+    }
+
+    fn at(&self, index: usize) -> ValueRef<'_> {
+        self.decoded().at(index)
+    }
+
+    fn clone_instance(&self) -> BoxColumnData {
+        self.decoded().clone_instance()
+    }
+
+    unsafe fn get_internal(&self, pointers: &[*mut *const u8], level: u8) -> Result<()> {
+        self.decoded().get_internal(pointers, level)
+    }
+
+    fn cast_to(&self, _this: &ArcColumnData, target: &SqlType) -> Option<ArcColumnData> {
+        let data = self.decoded();
+        data.cast_to(data, target)
+    }
+
+    fn as_array(&self) -> Option<&ArrayColumnData> {
+        self.decoded().as_array()
+    }
+
+    fn enum_label(&self, index: usize) -> Option<&str> {
+        self.decoded().enum_label(index)
+    }
+
+    fn nulls(&self) -> Option<&[u8]> {
+        self.decoded().nulls()
+    }
+
+    fn take(&self, indices: &[usize]) -> Option<BoxColumnData> {
+        self.decoded().take(indices)
+    }
+
+    fn is_null(&self, index: usize) -> bool {
+        self.decoded().is_null(index)
+    }
+
+    fn null_count(&self) -> usize {
+        self.decoded().null_count()
+    }
+
+    fn min_value(&self) -> Option<ValueRef<'_>> {
+        self.decoded().min_value()
+    }
+
+    fn max_value(&self) -> Option<ValueRef<'_>> {
+        self.decoded().max_value()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn load(source: &[u8], type_name: &str, size: usize) -> (Option<LazyColumnData>, usize) {
+        let mut cursor = Cursor::new(source);
+        let column = LazyColumnData::load(&mut cursor, type_name, size, Tz::Zulu, None).unwrap();
+        (column, cursor.position() as usize)
+    }
+
+    #[test]
+    fn test_decode_on_first_access() {
+        let mut encoder = Encoder::new();
+        // offsets
+        encoder.write(2_u64);
+        encoder.write(3_u64);
+        // null map
+        encoder.write(0_u8);
+        encoder.write(1_u8);
+        encoder.write(0_u8);
+        encoder.string("a");
+        encoder.string("");
+        encoder.string("c".repeat(200));
+        let source = encoder.get_buffer();
+
+        let (column, read) = load(&source, "Array(Nullable(String))", 2);
+        let column = column.unwrap();
+        assert_eq!(read, source.len());
+        assert!(!column.is_decoded());
+        assert_eq!(column.len(), 2);
+        assert_eq!(column.sql_type().to_string(), "Array(Nullable(String))");
+        assert!(!column.is_decoded());
+
+        let eager = <dyn ColumnData>::load_data::<ArcColumnWrapper, _>(
+            &mut Cursor::new(&source[..]),
+            "Array(Nullable(String))",
+            2,
+            Tz::Zulu,
+            None,
+        )
+        .unwrap();
+        assert_eq!(column.at(0), eager.at(0));
+        assert_eq!(column.at(1), eager.at(1));
+        assert!(column.is_decoded());
+    }
+
+    #[test]
+    fn test_undecodable_layout() {
+        let (column, read) = load(&[1, 0], "Object('json')", 1);
+        assert!(column.is_none());
+        assert_eq!(read, 0);
+    }
+}
@@ -1,6 +1,10 @@
 use std::{fmt, mem, slice};
 
-use crate::types::{Marshal, StatBuffer, Unmarshal};
+use crate::{
+    binary::{Encoder, ReadEx},
+    errors::Result,
+    types::{Marshal, StatBuffer, Unmarshal},
+};
 
 #[derive(Clone)]
 pub struct List<T>
@@ -22,10 +26,18 @@ where
         self.data[index]
     }
 
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
     pub fn push(&mut self, value: T) {
         self.data.push(value);
     }
 
+    pub fn extend_from_slice(&mut self, values: &[T]) {
+        self.data.extend_from_slice(values);
+    }
+
     #[cfg(test)]
     pub fn new() -> List<T> {
         List { data: Vec::new() }
@@ -37,12 +49,53 @@ where
         }
     }
 
-    pub fn resize(&mut self, new_len: usize, value: T) {
-        self.data.resize(new_len, value);
+    pub fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional);
     }
 
-    pub(super) unsafe fn set_len(&mut self, new_len: usize) {
-        self.data.set_len(new_len);
+    /// Reads `size` values stored back to back in little-endian order, as
+    /// ClickHouse sends numeric columns, with a single `read_bytes` call.
+    pub(crate) fn load<R: ReadEx>(reader: &mut R, size: usize) -> Result<List<T>> {
+        let mut list = List::with_capacity(size);
+        list.reload(reader, size)?;
+        Ok(list)
+    }
+
+    /// Same as `load`, but reuses the storage of this list. The list is left
+    /// empty if reading fails.
+    pub(crate) fn reload<R: ReadEx>(&mut self, reader: &mut R, size: usize) -> Result<()> {
+        self.data.clear();
+        // All-zero bytes are a valid value of every numeric type.
+        self.data.resize(size, unsafe { mem::zeroed() });
+        if let Err(err) = reader.read_bytes(self.as_mut()) {
+            self.data.clear();
+            return Err(err);
+        }
+
+        if cfg!(target_endian = "big") {
+            for value in self.data.iter_mut() {
+                let bytes = unsafe {
+                    slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>())
+                };
+                *value = T::unmarshal(bytes);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the values in `start..end` in little-endian order.
+    pub(crate) fn save(&self, encoder: &mut Encoder, start: usize, end: usize) {
+        if cfg!(target_endian = "big") {
+            let mut buffer = T::buffer();
+            for value in &self.data[start..end] {
+                value.marshal(buffer.as_mut());
+                encoder.write_bytes(buffer.as_ref());
+            }
+        } else {
+            let size = mem::size_of::<T>();
+            let bytes: &[u8] = self.as_ref();
+            encoder.write_bytes(&bytes[start * size..end * size]);
+        }
     }
 
     pub(super) unsafe fn as_ptr(&self) -> *const T {
@@ -98,6 +151,25 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_load_and_save() {
+        let source = [1_u8, 0, 0, 0, 0, 0, 0xf0, 0x3f, 0, 0, 0, 0, 0, 0, 0, 0xc0];
+        let mut reader = std::io::Cursor::new(&source[..]);
+        let list = List::<f64>::load(&mut reader, 2).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.at(0), f64::from_bits(0x3ff0_0000_0000_0001));
+        assert_eq!(list.at(1), -2.0);
+
+        let mut encoder = Encoder::new();
+        list.save(&mut encoder, 0, 2);
+        assert_eq!(encoder.get_buffer(), source.to_vec());
+
+        let mut list = List::<u32>::with_capacity(1);
+        let mut reader = std::io::Cursor::new(&source[..6]);
+        assert!(list.reload(&mut reader, 2).is_err());
+        assert_eq!(list.len(), 0);
+    }
+
     #[test]
     fn test_push_and_get() {
         let mut list = List::<f64>::new();
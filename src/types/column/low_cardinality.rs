@@ -0,0 +1,336 @@
+use std::sync::Arc;
+
+use chrono_tz::Tz;
+
+use crate::{
+    binary::{Encoder, ReadEx},
+    errors::Result,
+    types::{
+        column::{
+            column_data::{ArcColumnData, BoxColumnData},
+            BoxColumnWrapper, ColumnData,
+        },
+        SqlType, Value, ValueRef,
+    },
+};
+
+/// The only keys serialization version of `LowCardinality` columns, written
+/// in the state prefix of the column.
+pub(crate) const SHARED_DICTIONARIES_WITH_ADDITIONAL_KEYS: u64 = 1;
+
+const KEY_TYPE_MASK: u64 = 0xff;
+const NEED_GLOBAL_DICTIONARY: u64 = 1 << 8;
+const HAS_ADDITIONAL_KEYS: u64 = 1 << 9;
+
+/// Data of a `LowCardinality(T)` column: a dictionary of distinct values
+/// and for each row the position of its value in it. The column reads and
+/// writes like a column of `T`.
+///
+/// `LowCardinality(Nullable(T))` is loaded as a `Nullable` column of
+/// `LowCardinality(T)` data, the first value of a dictionary stands for
+/// `NULL`.
+pub(crate) struct LowCardinalityColumnData {
+    dictionary: ArcColumnData,
+    keys: Vec<usize>,
+    tz: Tz,
+}
+
+impl LowCardinalityColumnData {
+    /// Loads `size` values of `LowCardinality(type_name)`, the state prefix
+    /// of the column has been read already. Returns the data and a null map
+    /// of the rows that refer to the first value of their dictionary.
+    pub(crate) fn load<R: ReadEx>(
+        reader: &mut R,
+        type_name: &str,
+        size: usize,
+        tz: Tz,
+        tz_override: Option<Tz>,
+    ) -> Result<(Self, Vec<u8>)> {
+        let mut dictionary = <dyn ColumnData>::load_nested::<BoxColumnWrapper, _>(
+            reader,
+            type_name,
+            0,
+            tz,
+            tz_override,
+        )?;
+        let mut keys = Vec::with_capacity(size);
+        let mut nulls = Vec::with_capacity(size);
+
+        // The values may come in several granules, each with its own
+        // dictionary, appended to the one of the previous granules.
+        while keys.len() < size {
+            let index_type: u64 = reader.read_scalar()?;
+            if index_type & NEED_GLOBAL_DICTIONARY != 0 {
+                return Err(
+                    "LowCardinality columns with a global dictionary are not supported.".into(),
+                );
+            }
+
+            let base = dictionary.len();
+            if index_type & HAS_ADDITIONAL_KEYS != 0 {
+                let num_keys: u64 = reader.read_scalar()?;
+                let additional = <dyn ColumnData>::load_nested::<BoxColumnWrapper, _>(
+                    reader,
+                    type_name,
+                    num_keys as usize,
+                    tz,
+                    tz_override,
+                )?;
+                if base == 0 {
+                    dictionary = additional;
+                } else {
+                    for i in 0..additional.len() {
+                        dictionary.push(additional.at(i).into());
+                    }
+                }
+            }
+
+            let num_rows = reader.read_scalar::<u64>()? as usize;
+            if num_rows == 0 || num_rows > size - keys.len() {
+                let message = format!(
+                    "LowCardinality granule of {} rows doesn't fit the {} rows left in the column.",
+                    num_rows,
+                    size - keys.len()
+                );
+                return Err(message.into());
+            }
+
+            for _ in 0..num_rows {
+                let key = match index_type & KEY_TYPE_MASK {
+                    0 => reader.read_scalar::<u8>()? as usize,
+                    1 => reader.read_scalar::<u16>()? as usize,
+                    2 => reader.read_scalar::<u32>()? as usize,
+                    3 => reader.read_scalar::<u64>()? as usize,
+                    key_type => {
+                        let message = format!("Unknown LowCardinality key type {}.", key_type);
+                        return Err(message.into());
+                    }
+                };
+                if base + key >= dictionary.len() {
+                    let message = format!(
+                        "LowCardinality key {} is out of the dictionary of {} values.",
+                        key,
+                        dictionary.len() - base
+                    );
+                    return Err(message.into());
+                }
+                keys.push(base + key);
+                nulls.push((key == 0) as u8);
+            }
+        }
+
+        let data = LowCardinalityColumnData {
+            dictionary: dictionary.into(),
+            keys,
+            tz: tz_override.unwrap_or(tz),
+        };
+        Ok((data, nulls))
+    }
+
+    /// Returns the values of `keys` as a plain column of the dictionary type.
+    fn materialize(&self, keys: &[usize]) -> BoxColumnData {
+        if let Some(column) = self.dictionary.take(keys) {
+            return column;
+        }
+
+        let mut column = <dyn ColumnData>::from_type::<BoxColumnWrapper>(
+            self.dictionary.sql_type(),
+            self.tz,
+            keys.len(),
+        )
+        .unwrap();
+        for &key in keys {
+            column.push(self.dictionary.at(key).into());
+        }
+        column
+    }
+}
+
+impl ColumnData for LowCardinalityColumnData {
+    fn sql_type(&self) -> SqlType {
+        self.dictionary.sql_type()
+    }
+
+    fn save(&self, encoder: &mut Encoder, start: usize, end: usize) {
+        let column = self.materialize(&self.keys[start..end]);
+        column.save(encoder, 0, end - start);
+    }
+
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn push(&mut self, value: Value) {
+        if Arc::get_mut(&mut self.dictionary).is_none() {
+            self.dictionary = Arc::from(self.dictionary.clone_instance());
+        }
+        let dictionary = Arc::get_mut(&mut self.dictionary).unwrap();
+        dictionary.push(value);
+        self.keys.push(dictionary.len() - 1);
+    }
+
+    fn at(&self, index: usize) -> ValueRef<'_> {
+        self.dictionary.at(self.keys[index])
+    }
+
+    fn clone_instance(&self) -> BoxColumnData {
+        Box::new(Self {
+            dictionary: self.dictionary.clone(),
+            keys: self.keys.clone(),
+            tz: self.tz,
+        })
+    }
+
+    fn take(&self, indices: &[usize]) -> Option<BoxColumnData> {
+        let keys = indices.iter().map(|&index| self.keys[index]).collect();
+        Some(Box::new(Self {
+            dictionary: self.dictionary.clone(),
+            keys,
+            tz: self.tz,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::types::column::{ArcColumnWrapper, Either};
+
+    fn write_granule(encoder: &mut Encoder, dictionary: &[&str], keys: &[u8]) {
+        encoder.write(HAS_ADDITIONAL_KEYS);
+        encoder.write(dictionary.len() as u64);
+        for value in dictionary {
+            encoder.string(value);
+        }
+        encoder.write(keys.len() as u64);
+        for &key in keys {
+            encoder.write(key);
+        }
+    }
+
+    fn strings(column: &dyn ColumnData, range: std::ops::Range<usize>) -> Vec<&str> {
+        range.map(|i| column.at(i).as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_load_low_cardinality_string() {
+        let mut encoder = Encoder::new();
+        encoder.write(SHARED_DICTIONARIES_WITH_ADDITIONAL_KEYS);
+        write_granule(&mut encoder, &["", "red", "green"], &[1, 2, 2, 0, 1]);
+        let source = encoder.get_buffer();
+
+        let column = <dyn ColumnData>::load_data::<ArcColumnWrapper, _>(
+            &mut Cursor::new(&source[..]),
+            "LowCardinality(String)",
+            5,
+            Tz::Zulu,
+            None,
+        )
+        .unwrap();
+        assert_eq!(column.sql_type(), SqlType::String);
+        assert_eq!(
+            strings(column.as_ref(), 0..5),
+            vec!["red", "green", "green", "", "red"]
+        );
+
+        let mut encoder = Encoder::new();
+        column.save(&mut encoder, 1, 3);
+        let mut expected = Encoder::new();
+        expected.string("green");
+        expected.string("green");
+        assert_eq!(encoder.get_buffer(), expected.get_buffer());
+    }
+
+    #[test]
+    fn test_load_low_cardinality_nullable_string() {
+        let mut encoder = Encoder::new();
+        encoder.write(SHARED_DICTIONARIES_WITH_ADDITIONAL_KEYS);
+        write_granule(&mut encoder, &["", "", "x"], &[2, 0, 1]);
+        let source = encoder.get_buffer();
+
+        let column = <dyn ColumnData>::load_data::<ArcColumnWrapper, _>(
+            &mut Cursor::new(&source[..]),
+            "LowCardinality(Nullable(String))",
+            3,
+            Tz::Zulu,
+            None,
+        )
+        .unwrap();
+        assert_eq!(column.sql_type(), SqlType::Nullable(&SqlType::String));
+        assert_eq!(column.nulls(), Some(&[0_u8, 1, 0][..]));
+        assert_eq!(
+            column.at(0),
+            ValueRef::Nullable(Either::Right(Box::new(ValueRef::from("x"))))
+        );
+        assert_eq!(
+            column.at(2),
+            ValueRef::Nullable(Either::Right(Box::new(ValueRef::from(""))))
+        );
+    }
+
+    #[test]
+    fn test_load_array_of_low_cardinality_strings() {
+        let rows: Vec<Vec<&str>> = vec![
+            vec!["rust", "db"],
+            vec![],
+            vec!["db", "olap", "rust"],
+            vec!["olap"],
+            vec![],
+        ];
+        let dictionary = ["", "rust", "db", "olap"];
+
+        let mut encoder = Encoder::new();
+        // The state prefix of the inner column precedes the array offsets.
+        encoder.write(SHARED_DICTIONARIES_WITH_ADDITIONAL_KEYS);
+        let mut offset = 0_u64;
+        for row in &rows {
+            offset += row.len() as u64;
+            encoder.write(offset);
+        }
+        // One dictionary shared by the values of all arrays.
+        let keys: Vec<u8> = rows
+            .iter()
+            .flatten()
+            .map(|tag| dictionary.iter().position(|value| value == tag).unwrap() as u8)
+            .collect();
+        write_granule(&mut encoder, &dictionary, &keys);
+        let source = encoder.get_buffer();
+
+        let column = <dyn ColumnData>::load_data::<ArcColumnWrapper, _>(
+            &mut Cursor::new(&source[..]),
+            "Array(LowCardinality(String))",
+            rows.len(),
+            Tz::Zulu,
+            None,
+        )
+        .unwrap();
+        assert_eq!(column.sql_type().to_string(), "Array(String)");
+
+        let array = column.as_array().unwrap();
+        assert_eq!(array.offsets(), &[2, 2, 5, 6, 6]);
+        for (index, row) in rows.iter().enumerate() {
+            assert_eq!(&strings(array.inner(), array.row_range(index)), row);
+        }
+    }
+
+    #[test]
+    fn test_load_low_cardinality_in_several_granules() {
+        let mut encoder = Encoder::new();
+        encoder.write(SHARED_DICTIONARIES_WITH_ADDITIONAL_KEYS);
+        write_granule(&mut encoder, &["", "a"], &[1, 1]);
+        write_granule(&mut encoder, &["", "b", "c"], &[2, 0, 1]);
+        let source = encoder.get_buffer();
+
+        let column = <dyn ColumnData>::load_data::<ArcColumnWrapper, _>(
+            &mut Cursor::new(&source[..]),
+            "LowCardinality(String)",
+            5,
+            Tz::Zulu,
+            None,
+        )
+        .unwrap();
+        assert_eq!(strings(column.as_ref(), 0..5), vec!["a", "a", "c", "", "b"]);
+    }
+}
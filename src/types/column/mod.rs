@@ -1,4 +1,4 @@
-use std::{fmt, ops, sync::Arc, marker, net::{Ipv4Addr, Ipv6Addr}};
+use std::{fmt, io, marker, ops, str, sync::Arc};
 
 use chrono_tz::Tz;
 
@@ -10,21 +10,31 @@ use crate::{
     errors::{Error, FromSqlError, Result},
     types::{
         column::{
+            chrono_datetime::get_date_slice,
             column_data::ArcColumnData,
             decimal::{DecimalAdapter, NullableDecimalAdapter},
-            fixed_string::{FixedStringAdapter, NullableFixedStringAdapter},
-            ip::{IpColumnData, Ipv4, Ipv6},
-            string::StringAdapter,
+            fixed_string::{
+                check_str_len, trim_nulls, FixedStringAdapter, NullableFixedStringAdapter,
+            },
+            ip::{parse_ip_column, Ipv4, Ipv6},
             iter::Iterable,
+            string::StringAdapter,
         },
         decimal::NoBits,
-        SqlType, Value, ValueRef,
+        overflow::{
+            fit_value, in_column, is_enum_by_label, is_narrowing, out_of_range, round_ticks,
+            rounded_precision,
+        },
+        DateTimeType, FromSql, OverflowMode, RoundingMode, SqlType, Value, ValueRef,
     },
 };
 
-use self::chunk::ChunkColumnData;
 pub(crate) use self::string_pool::StringPool;
-pub use self::{column_data::ColumnData, concat::ConcatColumnData, numeric::VectorColumnData};
+pub use self::{
+    array::ArrayColumnData, column_data::ColumnData, concat::ConcatColumnData,
+    numeric::VectorColumnData, string::StringColumnData,
+};
+use self::{chunk::ChunkColumnData, lazy::LazyColumnData};
 
 mod array;
 mod chunk;
@@ -39,11 +49,16 @@ mod factory;
 pub(crate) mod fixed_string;
 mod ip;
 pub(crate) mod iter;
+mod json;
+mod lazy;
 mod list;
+mod low_cardinality;
+mod nothing;
 mod nullable;
 mod numeric;
 mod string;
 mod string_pool;
+mod tuple;
 
 /// Represents Clickhouse Column
 pub struct Column<K: ColumnType> {
@@ -54,6 +69,18 @@ pub struct Column<K: ColumnType> {
 
 pub trait ColumnFrom {
     fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper;
+
+    /// Same as `column_from`, with the values that don't fit the column
+    /// type handled as `mode` says. `column_from` wraps them.
+    fn column_from_checked<W: ColumnWrapper>(
+        source: Self,
+        _mode: OverflowMode,
+    ) -> Result<W::Wrapper>
+    where
+        Self: Sized,
+    {
+        Ok(Self::column_from::<W>(source))
+    }
 }
 
 pub trait ColumnType: Send + Copy + Sync + 'static {}
@@ -180,13 +207,154 @@ impl<K: ColumnType> Column<K> {
     pub fn iter<'a, T: Iterable<'a, K>>(&'a self) -> Result<T::Iter> {
         <T as Iterable<'a, K>>::iter(self, self.sql_type())
     }
+
+    /// Returns the offsets and the flat values of an `Array` column, `None`
+    /// for other columns.
+    pub fn as_array(&self) -> Option<&ArrayColumnData> {
+        self.data.as_array()
+    }
+
+    /// Returns the labels of the values of an `Enum8` or `Enum16` column.
+    ///
+    /// Labels are looked up in a map built once per column, so this is the
+    /// fast way to convert a whole enum column to strings.
+    pub fn enum_labels(&self) -> Result<Vec<&str>> {
+        match self.sql_type() {
+            SqlType::Enum8(_) | SqlType::Enum16(_) => {}
+            sql_type => {
+                return Err(Error::FromSql(FromSqlError::InvalidType {
+                    src: sql_type.to_string(),
+                    dst: SqlType::String.to_string(),
+                }))
+            }
+        }
+
+        (0..self.len())
+            .map(|index| {
+                self.data
+                    .enum_label(index)
+                    .ok_or(Error::FromSql(FromSqlError::OutOfRange))
+            })
+            .collect()
+    }
+
+    /// Returns `true` for a `Nullable` column.
+    pub fn is_nullable(&self) -> bool {
+        matches!(self.sql_type(), SqlType::Nullable(_))
+    }
+
+    /// Returns the null map of a `Nullable` column, a non-zero byte for each
+    /// `NULL` value, without reading the values. `None` for other columns.
+    ///
+    /// ```rust
+    /// # use clickhouse_rs::types::Block;
+    /// let block = Block::new().column("x", vec![Some(1_u8), None, Some(3)]);
+    /// let column = block.get_column("x")?;
+    ///
+    /// assert!(column.is_nullable());
+    /// assert_eq!(column.nulls(), Some(&[0_u8, 1, 0][..]));
+    /// assert_eq!(column.null_count(), 1);
+    /// # Ok::<(), clickhouse_rs::errors::Error>(())
+    /// ```
+    pub fn nulls(&self) -> Option<&[u8]> {
+        self.data.nulls()
+    }
+
+    /// Returns `true` if the value at `index` is `NULL`, without reading it.
+    pub fn is_null(&self, index: usize) -> bool {
+        self.data.is_null(index)
+    }
+
+    /// Returns `true` if every value of the column is `NULL`. Only the null
+    /// map of a `Nullable` column is looked at.
+    pub fn is_all_null(&self) -> bool {
+        match self.data.nulls() {
+            Some(nulls) => nulls.iter().all(|&is_null| is_null != 0),
+            None => self.null_count() == self.len(),
+        }
+    }
+
+    /// Iterates over the values that are not `NULL` with the indexes of their
+    /// rows. The values behind the `NULL`s of a `Nullable` column are skipped
+    /// without being read, so `T` is the type of the inner column.
+    ///
+    /// ```rust
+    /// # use clickhouse_rs::types::Block;
+    /// let block = Block::new().column("x", vec![None, Some(2_u32), None, Some(4)]);
+    /// let column = block.get_column("x")?;
+    ///
+    /// let present = column.iter_non_null::<u32>().collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(present, vec![(1, 2), (3, 4)]);
+    /// # Ok::<(), clickhouse_rs::errors::Error>(())
+    /// ```
+    pub fn iter_non_null<'a, T>(&'a self) -> impl Iterator<Item = Result<(usize, T)>> + 'a
+    where
+        T: FromSql<'a> + 'a,
+    {
+        (0..self.len())
+            .filter(move |&index| !self.data.is_null(index))
+            .map(move |index| {
+                let value = match self.data.at(index) {
+                    ValueRef::Nullable(Either::Right(value)) => *value,
+                    value => value,
+                };
+                T::from_sql(value).map(|value| (index, value))
+            })
+    }
+
+    /// Returns the number of `NULL` values in the column.
+    pub fn null_count(&self) -> usize {
+        self.data.null_count()
+    }
+
+    /// Returns the smallest non-null value of a numeric column or `None` if
+    /// there is no such value.
+    pub fn min_value(&self) -> Option<Value> {
+        self.data.min_value().map(Value::from)
+    }
+
+    /// Returns the largest non-null value of a numeric column or `None` if
+    /// there is no such value.
+    pub fn max_value(&self) -> Option<Value> {
+        self.data.max_value().map(Value::from)
+    }
+
+    /// Returns the raw `N` bytes of a `FixedString(N)` value, including the
+    /// null bytes shorter values are padded with.
+    pub fn fixed_string_bytes(&self, index: usize) -> Result<&[u8]> {
+        match self.sql_type() {
+            SqlType::FixedString(_) => self.at(index).as_bytes(),
+            sql_type => Err(Error::FromSql(FromSqlError::InvalidType {
+                src: sql_type.to_string(),
+                dst: "FixedString".into(),
+            })),
+        }
+    }
+
+    /// Returns a `FixedString(N)` value as a string with the trailing null
+    /// bytes stripped.
+    pub fn fixed_string_trimmed(&self, index: usize) -> Result<&str> {
+        let bytes = trim_nulls(self.fixed_string_bytes(index)?);
+        Ok(str::from_utf8(bytes)?)
+    }
 }
 
 impl<K: ColumnType> Column<K> {
-    pub(crate) fn read<R: ReadEx>(reader: &mut R, size: usize, tz: Tz) -> Result<Column<K>> {
+    pub(crate) fn read<R: ReadEx>(
+        reader: &mut R,
+        size: usize,
+        tz: Tz,
+        tz_override: Option<Tz>,
+    ) -> Result<Column<K>> {
         let name = reader.read_string()?;
         let type_name = reader.read_string()?;
-        let data = ColumnData::load_data::<ArcColumnWrapper, _>(reader, &type_name, size, tz)?;
+        let data = ColumnData::load_data::<ArcColumnWrapper, _>(
+            reader,
+            &type_name,
+            size,
+            tz,
+            tz_override,
+        )?;
         let column = Self {
             name,
             data,
@@ -195,11 +363,80 @@ impl<K: ColumnType> Column<K> {
         Ok(column)
     }
 
+    /// Same as `read`, but decodes into the storage of `recycled` if it has
+    /// the same type and isn't referenced from anywhere else.
+    pub(crate) fn read_reusing<R: io::Read>(
+        reader: &mut R,
+        size: usize,
+        tz: Tz,
+        tz_override: Option<Tz>,
+        recycled: &mut Column<K>,
+    ) -> Result<Column<K>> {
+        let name = reader.read_string()?;
+        let type_name = reader.read_string()?;
+
+        if let Some(data) = Arc::get_mut(&mut recycled.data) {
+            if data.can_reload() && data.sql_type().to_string() == type_name {
+                data.reload(reader, size)?;
+                return Ok(Self {
+                    name,
+                    data: recycled.data.clone(),
+                    _marker: marker::PhantomData,
+                });
+            }
+        }
+
+        let data = <dyn ColumnData>::load_data::<ArcColumnWrapper, _>(
+            reader,
+            &type_name,
+            size,
+            tz,
+            tz_override,
+        )?;
+        Ok(Self {
+            name,
+            data,
+            _marker: marker::PhantomData,
+        })
+    }
+
+    /// Same as `read`, but defers decoding the values until they are first
+    /// accessed, see `LazyColumnData`. Columns of types whose values can't
+    /// be walked over without decoding them are decoded right away.
+    pub(crate) fn read_lazy<R: ReadEx>(
+        reader: &mut R,
+        size: usize,
+        tz: Tz,
+        tz_override: Option<Tz>,
+    ) -> Result<Column<K>> {
+        let name = reader.read_string()?;
+        let type_name = reader.read_string()?;
+
+        let data: ArcColumnData =
+            match LazyColumnData::load(reader, &type_name, size, tz, tz_override)? {
+                Some(lazy) => Arc::new(lazy),
+                None => <dyn ColumnData>::load_data::<ArcColumnWrapper, _>(
+                    reader,
+                    &type_name,
+                    size,
+                    tz,
+                    tz_override,
+                )?,
+            };
+        Ok(Self {
+            name,
+            data,
+            _marker: marker::PhantomData,
+        })
+    }
+
+    /// Returns the name of the column.
     #[inline(always)]
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    /// Returns the type of the column as parsed from the block header.
     #[inline(always)]
     pub fn sql_type(&self) -> SqlType {
         self.data.sql_type()
@@ -240,6 +477,7 @@ impl<K: ColumnType> Column<K> {
 
         match (dst_type.clone(), src_type.clone()) {
             (SqlType::FixedString(str_len), SqlType::String) => {
+                check_str_len(&self, str_len)?;
                 let name = self.name().to_owned();
                 let adapter = FixedStringAdapter {
                     column: self,
@@ -255,6 +493,7 @@ impl<K: ColumnType> Column<K> {
                 SqlType::Nullable(SqlType::FixedString(str_len)),
                 SqlType::Nullable(SqlType::String),
             ) => {
+                check_str_len(&self, *str_len)?;
                 let name = self.name().to_owned();
                 let adapter = NullableFixedStringAdapter {
                     column: self,
@@ -368,46 +607,20 @@ impl<K: ColumnType> Column<K> {
                     _marker: marker::PhantomData,
                 })
             }
-            (SqlType::Ipv4, SqlType::String) => {
-                let name = self.name().to_owned();
-
-                let n = self.len();
-                let mut inner = Vec::with_capacity(n);
-                for i in 0..n {
-                    let source = self.at(i).as_str().unwrap();
-                    let ip: Ipv4Addr = source.parse().unwrap();
-                    inner.extend(&ip.octets());
-                }
-
-                let data = Arc::new(IpColumnData::<Ipv4> {
-                    inner,
-                    phantom: marker::PhantomData,
-                });
-
+            (SqlType::Ipv4, SqlType::String)
+            | (SqlType::Nullable(SqlType::Ipv4), SqlType::Nullable(SqlType::String)) => {
+                let data = parse_ip_column::<Ipv4, _>(&self)?;
                 Ok(Column {
-                    name,
+                    name: self.name,
                     data,
                     _marker: marker::PhantomData,
                 })
             }
-            (SqlType::Ipv6, SqlType::String) => {
-                let name = self.name().to_owned();
-
-                let n = self.len();
-                let mut inner = Vec::with_capacity(n);
-                for i in 0..n {
-                    let source = self.at(i).as_str().unwrap();
-                    let ip: Ipv6Addr = source.parse().unwrap();
-                    inner.extend(&ip.octets());
-                }
-
-                let data = Arc::new(IpColumnData::<Ipv6> {
-                    inner,
-                    phantom: marker::PhantomData,
-                });
-
+            (SqlType::Ipv6, SqlType::String)
+            | (SqlType::Nullable(SqlType::Ipv6), SqlType::Nullable(SqlType::String)) => {
+                let data = parse_ip_column::<Ipv6, _>(&self)?;
                 Ok(Column {
-                    name,
+                    name: self.name,
                     data,
                     _marker: marker::PhantomData,
                 })
@@ -430,7 +643,170 @@ impl<K: ColumnType> Column<K> {
         }
     }
 
-    pub(crate) fn push(&mut self, value: Value) {
+    /// Same as `cast_to`, with the values that don't fit `dst_type`
+    /// handled as `mode` says and sub-second digits as `rounding` says.
+    pub(crate) fn fit_to(
+        self,
+        dst_type: SqlType,
+        mode: OverflowMode,
+        rounding: RoundingMode,
+    ) -> Result<Self> {
+        if let Some(precision) = rounded_precision(&self.sql_type(), &dst_type) {
+            // `DateTime<Tz>` columns are truncated on the fly when saved.
+            let is_chrono =
+                self.data.as_array().is_none() && get_date_slice(self.data.as_ref()).is_ok();
+            if rounding != RoundingMode::Truncate || !is_chrono {
+                return self.round_to(dst_type, precision, rounding);
+            }
+        }
+
+        // Enum labels have to be looked up whatever the mode is.
+        let by_label = is_enum_by_label(&self.sql_type(), &dst_type);
+        if !by_label
+            && (mode == OverflowMode::Wrapping || !is_narrowing(&self.sql_type(), &dst_type))
+        {
+            return self.cast_to(dst_type);
+        }
+
+        let mut values = Vec::new();
+        for index in 0..self.len() {
+            let value = self.exact_value(index)?;
+            match fit_value(value.clone(), &dst_type, mode) {
+                None => return Err(in_column(out_of_range(index, value, &dst_type), &self.name)),
+                Some(value) if by_label || mode == OverflowMode::Saturating => values.push(value),
+                Some(_) => {}
+            }
+        }
+
+        if !by_label && mode == OverflowMode::Checked {
+            return self.cast_to(dst_type);
+        }
+
+        let mut data =
+            <dyn ColumnData>::from_type::<ArcColumnWrapper>(dst_type, Tz::Zulu, values.len())?;
+        let inner = Arc::get_mut(&mut data).unwrap();
+        for value in values {
+            inner.push(value);
+        }
+        Ok(Column {
+            name: self.name,
+            data,
+            _marker: marker::PhantomData,
+        })
+    }
+
+    /// Same as `cast_to` for a `DateTime64` destination of `precision` that
+    /// holds fewer sub-second digits than the column.
+    fn round_to(self, dst_type: SqlType, precision: u32, mode: RoundingMode) -> Result<Self> {
+        let mut data = <dyn ColumnData>::from_type::<ArcColumnWrapper>(
+            dst_type.clone(),
+            Tz::Zulu,
+            self.len(),
+        )?;
+        let inner = Arc::get_mut(&mut data).unwrap();
+        let nullable = matches!(dst_type, SqlType::Nullable(_));
+
+        for index in 0..self.len() {
+            let value = match self.nanos_at(index)? {
+                None => Value::Nullable(Either::Left(dst_type.clone().into())),
+                Some((nanos, tz)) => match round_ticks(nanos, precision, mode) {
+                    None => {
+                        let value = Value::DateTime64(nanos, (9, tz));
+                        return Err(in_column(out_of_range(index, value, &dst_type), &self.name));
+                    }
+                    Some(ticks) if nullable => {
+                        let value = Value::DateTime64(ticks, (precision, tz));
+                        Value::Nullable(Either::Right(Box::new(value)))
+                    }
+                    Some(ticks) => Value::DateTime64(ticks, (precision, tz)),
+                },
+            };
+            inner.push(value);
+        }
+
+        Ok(Column {
+            name: self.name,
+            data,
+            _marker: marker::PhantomData,
+        })
+    }
+
+    /// Returns the time in row `index` of a `DateTime64` or `DateTime<Tz>`
+    /// column in nanoseconds since the epoch, `None` for `NULL`.
+    fn nanos_at(&self, index: usize) -> Result<Option<(i64, Tz)>> {
+        if self.data.is_null(index) {
+            return Ok(None);
+        }
+        if let Ok(times) = get_date_slice(self.data.as_ref()) {
+            let time = times[index];
+            let nanos = time
+                .timestamp_nanos_opt()
+                .ok_or(Error::FromSql(FromSqlError::OutOfRange))?;
+            return Ok(Some((nanos, time.timezone())));
+        }
+
+        let value = match Value::from(self.at(index)) {
+            Value::Nullable(Either::Right(value)) => *value,
+            value => value,
+        };
+        match value {
+            Value::DateTime64(ticks, (precision, tz)) => {
+                Ok(Some((ticks * 10_i64.pow(9 - precision), tz)))
+            }
+            value => Err(Error::FromSql(FromSqlError::InvalidType {
+                src: SqlType::from(value).to_string(),
+                dst: "DateTime64".into(),
+            })),
+        }
+    }
+
+    /// Returns the value in row `index` without the loss of range of
+    /// `ValueRef::DateTime` for `DateTime<Tz>` columns.
+    fn exact_value(&self, index: usize) -> Result<Value> {
+        let value = Value::from(self.at(index));
+        let sql_type = self.sql_type();
+        let is_chrono = |sql_type: &SqlType| *sql_type == SqlType::DateTime(DateTimeType::Chrono);
+        match (&sql_type, &value) {
+            (_, Value::Nullable(Either::Left(_))) => Ok(value),
+            (SqlType::Nullable(inner), _) if is_chrono(inner) => {
+                let time = get_date_slice(self.data.as_ref())?[index];
+                let exact = Value::DateTime64(time.timestamp(), (0, time.timezone()));
+                Ok(Value::Nullable(Either::Right(Box::new(exact))))
+            }
+            (sql_type, _) if is_chrono(sql_type) => {
+                let time = get_date_slice(self.data.as_ref())?[index];
+                Ok(Value::DateTime64(time.timestamp(), (0, time.timezone())))
+            }
+            _ => Ok(value),
+        }
+    }
+
+    /// Fails if `value` can't be pushed into the column, or doesn't fit its
+    /// type in `OverflowMode::Checked` mode.
+    pub(crate) fn check(&self, value: &Value, mode: OverflowMode) -> Result<()> {
+        let sql_type = self.sql_type();
+        if !is_assignable(&sql_type, value) {
+            let message = format!(
+                "Can't push Value::{} into column \"{}\" of type {}.",
+                SqlType::from(value.clone()),
+                self.name,
+                sql_type
+            );
+            return Err(message.into());
+        }
+        if fit_value(value.clone(), &sql_type, mode).is_none() {
+            return Err(in_column(
+                out_of_range(self.len(), value, &sql_type),
+                &self.name,
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn push(&mut self, value: Value, mode: OverflowMode) -> Result<()> {
+        self.check(&value, mode)?;
+        let value = fit_value(value, &self.sql_type(), mode).unwrap();
+
         loop {
             match Arc::get_mut(&mut self.data) {
                 None => {
@@ -438,7 +814,7 @@ impl<K: ColumnType> Column<K> {
                 }
                 Some(data) => {
                     data.push(value);
-                    break;
+                    return Ok(());
                 }
             }
         }
@@ -449,6 +825,48 @@ impl<K: ColumnType> Column<K> {
     }
 }
 
+/// Checks that `value` is of a type the column data of `sql_type` accepts.
+pub(crate) fn is_assignable(sql_type: &SqlType, value: &Value) -> bool {
+    match (sql_type, value) {
+        // A null of any type fits, the column stores its own default under it.
+        (SqlType::Nullable(_), Value::Nullable(Either::Left(_))) => true,
+        (SqlType::Nullable(inner), Value::Nullable(Either::Right(v))) => is_assignable(inner, v),
+        (SqlType::Nullable(inner), v) => is_assignable(inner, v),
+        (SqlType::Array(inner), Value::Array(_, vs)) => vs.iter().all(|v| is_assignable(inner, v)),
+        (SqlType::Tuple(elements), Value::Tuple(_, vs)) => {
+            elements.len() == vs.len()
+                && elements
+                    .iter()
+                    .zip(vs.iter())
+                    .all(|((_, t), v)| is_assignable(t, v))
+        }
+        (SqlType::Decimal(_, scale), Value::Decimal(v)) => *scale == v.scale,
+        (SqlType::DateTime(_), Value::DateTime(..))
+        | (SqlType::DateTime(_), Value::DateTime64(..))
+        | (SqlType::String, Value::String(_))
+        | (SqlType::FixedString(_), Value::String(_))
+        | (SqlType::Enum8(_), Value::Enum8(..))
+        | (SqlType::Enum16(_), Value::Enum16(..))
+        | (SqlType::Enum8(_), Value::String(_))
+        | (SqlType::Enum16(_), Value::String(_)) => true,
+        (SqlType::UInt8, Value::UInt8(_))
+        | (SqlType::UInt16, Value::UInt16(_))
+        | (SqlType::UInt32, Value::UInt32(_))
+        | (SqlType::UInt64, Value::UInt64(_))
+        | (SqlType::Int8, Value::Int8(_))
+        | (SqlType::Int16, Value::Int16(_))
+        | (SqlType::Int32, Value::Int32(_))
+        | (SqlType::Int64, Value::Int64(_))
+        | (SqlType::Float32, Value::Float32(_))
+        | (SqlType::Float64, Value::Float64(_))
+        | (SqlType::Date, Value::Date(..))
+        | (SqlType::Ipv4, Value::Ipv4(_))
+        | (SqlType::Ipv6, Value::Ipv6(_))
+        | (SqlType::Uuid, Value::Uuid(_)) => true,
+        _ => false,
+    }
+}
+
 pub(crate) fn new_column<K: ColumnType>(
     name: &str,
     data: Arc<(dyn ColumnData + Sync + Send + 'static)>,
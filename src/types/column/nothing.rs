@@ -0,0 +1,112 @@
+use crate::{
+    binary::{Encoder, ReadEx},
+    errors::Result,
+    types::{
+        column::{column_data::BoxColumnData, Either},
+        SqlType, Value, ValueRef,
+    },
+};
+
+use super::column_data::ColumnData;
+
+/// Column of the `Nothing` type, e.g. the inner column of `Nullable(Nothing)`
+/// for `SELECT NULL` or of `Array(Nothing)` for `SELECT []`.
+///
+/// Every value is `NULL`, only the row count is kept. On the wire each row
+/// takes a single placeholder byte.
+pub(crate) struct NothingColumnData {
+    size: usize,
+}
+
+impl NothingColumnData {
+    pub(crate) fn with_capacity(_capacity: usize) -> Self {
+        Self { size: 0 }
+    }
+
+    pub(crate) fn load<T: ReadEx>(reader: &mut T, size: usize) -> Result<Self> {
+        let mut placeholders = vec![0_u8; size];
+        reader.read_bytes(&mut placeholders)?;
+        Ok(Self { size })
+    }
+}
+
+impl ColumnData for NothingColumnData {
+    fn sql_type(&self) -> SqlType {
+        SqlType::Nothing
+    }
+
+    fn save(&self, encoder: &mut Encoder, start: usize, end: usize) {
+        encoder.write_bytes(&vec![0_u8; end - start]);
+    }
+
+    fn len(&self) -> usize {
+        self.size
+    }
+
+    fn push(&mut self, _value: Value) {
+        self.size += 1;
+    }
+
+    fn at(&self, index: usize) -> ValueRef<'_> {
+        assert!(
+            index < self.size,
+            "index {} out of range {}",
+            index,
+            self.size
+        );
+        ValueRef::Nullable(Either::Left(&SqlType::Nothing))
+    }
+
+    fn clone_instance(&self) -> BoxColumnData {
+        Box::new(Self { size: self.size })
+    }
+
+    fn is_null(&self, _index: usize) -> bool {
+        true
+    }
+
+    fn null_count(&self) -> usize {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use chrono_tz::Tz;
+
+    use crate::types::{column::column_data::BoxColumnData, column::BoxColumnWrapper};
+
+    use super::*;
+
+    #[test]
+    fn test_load_nullable_nothing() {
+        // Three null flags followed by three placeholder bytes.
+        let source = [1_u8, 1, 1, 0, 0, 0];
+        let mut cursor = Cursor::new(&source[..]);
+        let column: BoxColumnData = <dyn ColumnData>::load_data::<BoxColumnWrapper, _>(
+            &mut cursor,
+            "Nullable(Nothing)",
+            3,
+            Tz::Zulu,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(cursor.position(), 6);
+        assert_eq!(column.sql_type(), SqlType::Nullable(&SqlType::Nothing));
+        assert_eq!(column.len(), 3);
+        assert_eq!(column.null_count(), 3);
+        for i in 0..3 {
+            assert_eq!(
+                column.at(i),
+                ValueRef::Nullable(Either::Left(&SqlType::Nothing))
+            );
+        }
+
+        let mut encoder = Encoder::new();
+        column.save(&mut encoder, 0, 3);
+        assert_eq!(encoder.get_buffer(), source.to_vec());
+    }
+}
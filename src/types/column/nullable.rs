@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{io, sync::Arc};
 
 use crate::{
     binary::{Encoder, ReadEx},
@@ -22,12 +22,23 @@ impl NullableColumnData {
         type_name: &str,
         size: usize,
         tz: Tz,
+        tz_override: Option<Tz>,
     ) -> Result<Self> {
         let mut nulls = vec![0; size];
         reader.read_bytes(nulls.as_mut())?;
-        let inner = ColumnData::load_data::<ArcColumnWrapper, _>(reader, type_name, size, tz)?;
+        let inner = ColumnData::load_nested::<ArcColumnWrapper, _>(
+            reader,
+            type_name,
+            size,
+            tz,
+            tz_override,
+        )?;
         Ok(NullableColumnData { inner, nulls })
     }
+
+    pub(crate) fn null_count(&self) -> usize {
+        self.nulls.iter().filter(|&&is_null| is_null != 0).count()
+    }
 }
 
 impl ColumnData for NullableColumnData {
@@ -52,8 +63,8 @@ impl ColumnData for NullableColumnData {
 
         if let Value::Nullable(e) = value {
             match e {
-                Either::Left(sql_type) => {
-                    let default_value = Value::default(sql_type.clone());
+                Either::Left(_) => {
+                    let default_value = Value::default(inner_column.sql_type());
                     inner_column.push(default_value);
                     self.nulls.push(true as u8);
                 }
@@ -68,6 +79,14 @@ impl ColumnData for NullableColumnData {
         }
     }
 
+    fn nulls(&self) -> Option<&[u8]> {
+        Some(self.nulls.as_slice())
+    }
+
+    fn null_count(&self) -> usize {
+        NullableColumnData::null_count(self)
+    }
+
     fn at(&self, index: usize) -> ValueRef {
         if self.nulls[index] == 1 {
             let sql_type = self.inner.sql_type();
@@ -85,6 +104,15 @@ impl ColumnData for NullableColumnData {
         })
     }
 
+    fn take(&self, indices: &[usize]) -> Option<BoxColumnData> {
+        let inner = self.inner.take(indices)?;
+        let nulls = indices.iter().map(|&index| self.nulls[index]).collect();
+        Some(Box::new(Self {
+            inner: inner.into(),
+            nulls,
+        }))
+    }
+
     unsafe fn get_internal(&self, pointers: &[*mut *const u8], level: u8) -> Result<()> {
         if level == self.sql_type().level() {
             *pointers[0] = self.nulls.as_ptr();
@@ -95,6 +123,27 @@ impl ColumnData for NullableColumnData {
         }
     }
 
+    fn can_reload(&self) -> bool {
+        // The inner column may be shared with clones of this one.
+        Arc::strong_count(&self.inner) == 1
+            && Arc::weak_count(&self.inner) == 0
+            && self.inner.can_reload()
+    }
+
+    fn reload(&mut self, mut reader: &mut dyn io::Read, size: usize) -> Result<()> {
+        let inner = Arc::get_mut(&mut self.inner).unwrap();
+        self.nulls.clear();
+        self.nulls.resize(size, 0);
+        let result = match reader.read_bytes(self.nulls.as_mut()) {
+            Ok(()) => inner.reload(reader, size),
+            Err(err) => Err(err),
+        };
+        if result.is_err() {
+            self.nulls.clear();
+        }
+        result
+    }
+
     fn cast_to(&self, _this: &ArcColumnData, target: &SqlType) -> Option<ArcColumnData> {
         if let SqlType::Nullable(inner_target) = target {
             if let Some(inner) = self.inner.cast_to(&self.inner, inner_target) {
@@ -107,3 +156,91 @@ impl ColumnData for NullableColumnData {
         None
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::types::{Block, Simple, Value};
+
+    #[test]
+    fn test_null_count_and_min_max() {
+        let block =
+            Block::<Simple>::new().column("x", vec![Some(3_i32), None, Some(-1), None, Some(7)]);
+        let column = block.get_column("x").unwrap();
+
+        assert_eq!(column.null_count(), 2);
+        assert_eq!(column.min_value(), Some(Value::Int32(-1)));
+        assert_eq!(column.max_value(), Some(Value::Int32(7)));
+    }
+
+    #[test]
+    fn test_nulls() {
+        let block = Block::<Simple>::new()
+            .column("x", vec![Some("a"), None, None, Some("d")])
+            .column("y", vec![1_u8, 2, 3, 4]);
+
+        let x = block.get_column("x").unwrap();
+        assert!(x.is_nullable());
+        assert_eq!(x.nulls(), Some(&[0_u8, 1, 1, 0][..]));
+        assert_eq!(x.null_count(), 2);
+
+        let y = block.get_column("y").unwrap();
+        assert!(!y.is_nullable());
+        assert_eq!(y.nulls(), None);
+        assert_eq!(y.null_count(), 0);
+
+        let merged = Block::concat(std::slice::from_ref(&block));
+        assert_eq!(
+            merged.get_column("x").unwrap().nulls(),
+            Some(&[0_u8, 1, 1, 0][..])
+        );
+        let merged = Block::concat(&[block.clone(), block]);
+        let x = merged.get_column("x").unwrap();
+        assert_eq!(x.nulls(), Some(&[0_u8, 1, 1, 0, 0, 1, 1, 0][..]));
+        assert_eq!(x.null_count(), 4);
+    }
+
+    #[test]
+    fn test_iter_non_null_of_sparse_column() {
+        let values: Vec<Option<String>> = (0..100)
+            .map(|i| {
+                if i % 10 == 3 {
+                    Some(format!("v{}", i))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let block = Block::<Simple>::new().column("x", values);
+        let column = block.get_column("x").unwrap();
+
+        assert_eq!(column.null_count(), 90);
+        assert!(!column.is_all_null());
+        assert!(column.is_null(0));
+        assert!(!column.is_null(13));
+
+        let present: Vec<(usize, String)> =
+            column.iter_non_null().collect::<Result<_, _>>().unwrap();
+        let expected: Vec<(usize, String)> = (0..10)
+            .map(|i| (i * 10 + 3, format!("v{}", i * 10 + 3)))
+            .collect();
+        assert_eq!(present, expected);
+
+        let chunk = column.slice(10..30);
+        assert_eq!(chunk.null_count(), 18);
+        assert!(chunk.is_null(0));
+        assert!(!chunk.is_null(3));
+        assert_eq!(chunk.iter_non_null::<&str>().count(), 2);
+    }
+
+    #[test]
+    fn test_min_max_of_all_nulls() {
+        let block = Block::<Simple>::new().column("x", vec![None::<i32>, None]);
+        let column = block.get_column("x").unwrap();
+
+        assert_eq!(column.null_count(), 2);
+        assert!(column.is_all_null());
+        assert_eq!(column.iter_non_null::<i32>().count(), 0);
+        assert_eq!(column.min_value(), None);
+        assert_eq!(column.max_value(), None);
+    }
+}
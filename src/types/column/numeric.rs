@@ -1,4 +1,4 @@
-use std::{convert, mem, sync::Arc};
+use std::{cmp::Ordering, convert, io, iter::FromIterator, sync::Arc};
 
 use crate::{
     binary::{Encoder, ReadEx},
@@ -17,6 +17,8 @@ use super::{
     ColumnFrom,
 };
 
+/// Data of a numeric column, can be built directly and added to a block
+/// with `Block::column`.
 pub struct VectorColumnData<T>
 where
     T: StatBuffer
@@ -27,11 +29,51 @@ where
         + convert::From<Value>
         + Sync
         + HasSqlType
+        + PartialOrd
         + 'static,
 {
     pub(crate) data: List<T>,
 }
 
+impl<T> ColumnFrom for VectorColumnData<T>
+where
+    T: StatBuffer
+        + Unmarshal<T>
+        + Marshal
+        + Copy
+        + convert::Into<Value>
+        + convert::From<Value>
+        + Send
+        + Sync
+        + HasSqlType
+        + PartialOrd
+        + 'static,
+{
+    fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
+        W::wrap(source)
+    }
+}
+
+impl<T> From<Vec<T>> for VectorColumnData<T>
+where
+    T: StatBuffer
+        + Unmarshal<T>
+        + Marshal
+        + Copy
+        + convert::Into<Value>
+        + convert::From<Value>
+        + Sync
+        + HasSqlType
+        + PartialOrd
+        + 'static,
+{
+    fn from(source: Vec<T>) -> Self {
+        let mut data = VectorColumnData::with_capacity(source.len());
+        data.append_slice(&source);
+        data
+    }
+}
+
 impl<T> ColumnFrom for Vec<T>
 where
     T: StatBuffer
@@ -43,20 +85,60 @@ where
         + Send
         + Sync
         + HasSqlType
+        + PartialOrd
         + 'static,
 {
     fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
-        let mut data = List::with_capacity(source.len());
-        for s in source {
-            data.push(s);
-        }
-        W::wrap(VectorColumnData { data })
+        let mut data = VectorColumnData::with_capacity(source.len());
+        data.append_slice(&source);
+        W::wrap(data)
+    }
+}
+
+impl<T> ColumnFrom for &[T]
+where
+    T: StatBuffer
+        + Unmarshal<T>
+        + Marshal
+        + Copy
+        + convert::Into<Value>
+        + convert::From<Value>
+        + Send
+        + Sync
+        + HasSqlType
+        + PartialOrd
+        + 'static,
+{
+    fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
+        let mut data = VectorColumnData::with_capacity(source.len());
+        data.append_slice(source);
+        W::wrap(data)
+    }
+}
+
+impl<T> FromIterator<T> for VectorColumnData<T>
+where
+    T: StatBuffer
+        + Unmarshal<T>
+        + Marshal
+        + Copy
+        + convert::Into<Value>
+        + convert::From<Value>
+        + Send
+        + Sync
+        + HasSqlType
+        + PartialOrd
+        + 'static,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(source: I) -> Self {
+        let mut data = VectorColumnData::with_capacity(0);
+        data.append_iter(source);
+        data
     }
 }
 
 impl<T> ColumnFrom for Vec<Option<T>>
 where
-    Value: convert::From<T>,
     T: StatBuffer
         + Unmarshal<T>
         + Marshal
@@ -67,22 +149,65 @@ where
         + Sync
         + Default
         + HasSqlType
+        + PartialOrd
         + 'static,
 {
     fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
-        let fake: Vec<T> = Vec::with_capacity(source.len());
-        let inner = Vec::column_from::<ArcColumnWrapper>(fake);
+        W::wrap(nullable_from(source))
+    }
+}
 
-        let mut data = NullableColumnData {
-            inner,
-            nulls: Vec::with_capacity(source.len()),
-        };
+impl<T> ColumnFrom for &[Option<T>]
+where
+    T: StatBuffer
+        + Unmarshal<T>
+        + Marshal
+        + Copy
+        + convert::Into<Value>
+        + convert::From<Value>
+        + Send
+        + Sync
+        + Default
+        + HasSqlType
+        + PartialOrd
+        + 'static,
+{
+    fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
+        W::wrap(nullable_from(source.iter().copied()))
+    }
+}
 
-        for value in source {
-            data.push(value.into());
-        }
+/// Builds a `Nullable` column filling the values and the null map in a single
+/// pass, nulls are stored as `T::default()`.
+fn nullable_from<T, I>(source: I) -> NullableColumnData
+where
+    I: IntoIterator<Item = Option<T>>,
+    T: StatBuffer
+        + Unmarshal<T>
+        + Marshal
+        + Copy
+        + convert::Into<Value>
+        + convert::From<Value>
+        + Send
+        + Sync
+        + Default
+        + HasSqlType
+        + PartialOrd
+        + 'static,
+{
+    let source = source.into_iter();
+    let capacity = source.size_hint().0;
+    let mut values = List::with_capacity(capacity);
+    let mut nulls = Vec::with_capacity(capacity);
 
-        W::wrap(data)
+    for value in source {
+        nulls.push(value.is_none() as u8);
+        values.push(value.unwrap_or_default());
+    }
+
+    NullableColumnData {
+        inner: Arc::new(VectorColumnData { data: values }),
+        nulls,
     }
 }
 
@@ -98,6 +223,7 @@ where
         + Send
         + Sync
         + HasSqlType
+        + PartialOrd
         + 'static,
 {
     fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
@@ -130,6 +256,7 @@ where
         + Send
         + Sync
         + HasSqlType
+        + PartialOrd
         + 'static,
 {
     let mut inner = Vec::with_capacity(vs.len());
@@ -150,21 +277,80 @@ where
         + convert::From<Value>
         + Sync
         + HasSqlType
+        + PartialOrd
         + 'static,
 {
-    pub(crate) fn with_capacity(capacity: usize) -> VectorColumnData<T> {
+    /// Creates empty data with room for `capacity` values.
+    pub fn with_capacity(capacity: usize) -> VectorColumnData<T> {
         VectorColumnData {
             data: List::with_capacity(capacity),
         }
     }
 
+    /// Appends a single value.
+    pub fn append(&mut self, value: T) {
+        self.data.push(value);
+    }
+
+    /// Returns the number of values.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns `true` if there are no values.
+    pub fn is_empty(&self) -> bool {
+        self.data.len() == 0
+    }
+
     pub(crate) fn load<R: ReadEx>(reader: &mut R, size: usize) -> Result<VectorColumnData<T>> {
-        let mut data = List::with_capacity(size);
-        unsafe {
-            data.set_len(size);
+        Ok(Self {
+            data: List::load(reader, size)?,
+        })
+    }
+
+    /// Appends all values from `data` at once, growing the backing buffer at
+    /// most once instead of pushing values one by one.
+    pub fn append_slice(&mut self, data: &[T]) {
+        self.data.extend_from_slice(data);
+    }
+
+    /// Appends every value yielded by `source` without going through `Value`.
+    pub fn append_iter<I: IntoIterator<Item = T>>(&mut self, source: I) {
+        let source = source.into_iter();
+        self.data.reserve(source.size_hint().0);
+        for value in source {
+            self.data.push(value);
+        }
+    }
+
+    /// Returns the smallest value of the column, ignoring NaNs.
+    pub fn min(&self) -> Option<T> {
+        self.position(Ordering::Less)
+            .map(|index| self.data.at(index))
+    }
+
+    /// Returns the largest value of the column, ignoring NaNs.
+    pub fn max(&self) -> Option<T> {
+        self.position(Ordering::Greater)
+            .map(|index| self.data.at(index))
+    }
+
+    fn position(&self, wanted: Ordering) -> Option<usize> {
+        let mut result: Option<(usize, T)> = None;
+
+        for index in 0..self.data.len() {
+            let value = self.data.at(index);
+            if value.partial_cmp(&value).is_none() {
+                continue;
+            }
+
+            result = match result {
+                Some(current) if value.partial_cmp(&current.1) != Some(wanted) => Some(current),
+                _ => Some((index, value)),
+            };
         }
-        reader.read_bytes(data.as_mut())?;
-        Ok(Self { data })
+
+        result.map(|(index, _)| index)
     }
 }
 
@@ -179,6 +365,7 @@ where
         + Send
         + Sync
         + HasSqlType
+        + PartialOrd
         + 'static,
 {
     fn sql_type(&self) -> SqlType {
@@ -186,7 +373,7 @@ where
     }
 
     fn save(&self, encoder: &mut Encoder, start: usize, end: usize) {
-        save_data::<T>(self.data.as_ref(), encoder, start, end);
+        self.data.save(encoder, start, end);
     }
 
     fn len(&self) -> usize {
@@ -198,23 +385,7 @@ where
     }
 
     fn at(&self, index: usize) -> ValueRef {
-        let v: Value = self.data.at(index).into();
-        match v {
-            Value::UInt8(x) => ValueRef::UInt8(x),
-            Value::UInt16(x) => ValueRef::UInt16(x),
-            Value::UInt32(x) => ValueRef::UInt32(x),
-            Value::UInt64(x) => ValueRef::UInt64(x),
-
-            Value::Int8(x) => ValueRef::Int8(x),
-            Value::Int16(x) => ValueRef::Int16(x),
-            Value::Int32(x) => ValueRef::Int32(x),
-            Value::Int64(x) => ValueRef::Int64(x),
-
-            Value::Float32(x) => ValueRef::Float32(x),
-            Value::Float64(x) => ValueRef::Float64(x),
-
-            _ => panic!("can't convert value to value_ref."),
-        }
+        to_value_ref(self.data.at(index))
     }
 
     fn clone_instance(&self) -> BoxColumnData {
@@ -223,6 +394,34 @@ where
         })
     }
 
+    fn take(&self, indices: &[usize]) -> Option<BoxColumnData> {
+        let mut data = List::with_capacity(indices.len());
+        for &index in indices {
+            data.push(self.data.at(index));
+        }
+        Some(Box::new(Self { data }))
+    }
+
+    fn null_count(&self) -> usize {
+        0
+    }
+
+    fn min_value(&self) -> Option<ValueRef<'_>> {
+        self.min().map(to_value_ref)
+    }
+
+    fn max_value(&self) -> Option<ValueRef<'_>> {
+        self.max().map(to_value_ref)
+    }
+
+    fn can_reload(&self) -> bool {
+        true
+    }
+
+    fn reload(&mut self, mut reader: &mut dyn io::Read, size: usize) -> Result<()> {
+        self.data.reload(&mut reader, size)
+    }
+
     unsafe fn get_internal(&self, pointers: &[*mut *const u8], level: u8) -> Result<()> {
         assert_eq!(level, 0);
         *pointers[0] = self.data.as_ptr() as *const u8;
@@ -231,8 +430,80 @@ where
     }
 }
 
-pub(crate) fn save_data<T>(data: &[u8], encoder: &mut Encoder, start: usize, end: usize) {
-    let start_index = start * mem::size_of::<T>();
-    let end_index = end * mem::size_of::<T>();
-    encoder.write_bytes(&data[start_index..end_index]);
+fn to_value_ref<'a, T: convert::Into<Value>>(value: T) -> ValueRef<'a> {
+    let v: Value = value.into();
+    match v {
+        Value::UInt8(x) => ValueRef::UInt8(x),
+        Value::UInt16(x) => ValueRef::UInt16(x),
+        Value::UInt32(x) => ValueRef::UInt32(x),
+        Value::UInt64(x) => ValueRef::UInt64(x),
+
+        Value::Int8(x) => ValueRef::Int8(x),
+        Value::Int16(x) => ValueRef::Int16(x),
+        Value::Int32(x) => ValueRef::Int32(x),
+        Value::Int64(x) => ValueRef::Int64(x),
+
+        Value::Float32(x) => ValueRef::Float32(x),
+        Value::Float64(x) => ValueRef::Float64(x),
+
+        _ => panic!("can't convert value to value_ref."),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::column::Either;
+
+    use super::*;
+
+    #[test]
+    fn test_append_slice() {
+        let mut column = VectorColumnData::<u64>::with_capacity(4);
+        column.push(Value::UInt64(1));
+        column.append_slice(&[2, 3, 4]);
+
+        assert_eq!(column.len(), 4);
+        assert_eq!(column.at(0), ValueRef::UInt64(1));
+        assert_eq!(column.at(3), ValueRef::UInt64(4));
+    }
+
+    #[test]
+    fn test_column_from_slices() {
+        let values: &[u32] = &[1, 2, 3];
+        let column = <&[u32]>::column_from::<ArcColumnWrapper>(values);
+        assert_eq!(column.sql_type(), SqlType::UInt32);
+        assert_eq!(column.at(2), ValueRef::UInt32(3));
+
+        let values: &[Option<i16>] = &[Some(-1), None, Some(3)];
+        let column = <&[Option<i16>]>::column_from::<ArcColumnWrapper>(values);
+        assert_eq!(column.sql_type(), SqlType::Nullable(SqlType::Int16.into()));
+        assert_eq!(column.len(), 3);
+        assert_eq!(column.null_count(), 1);
+        assert_eq!(
+            column.at(0),
+            ValueRef::Nullable(Either::Right(Box::new(ValueRef::Int16(-1))))
+        );
+        assert_eq!(
+            column.at(1),
+            ValueRef::Nullable(Either::Left(SqlType::Int16.into()))
+        );
+
+        let column: VectorColumnData<u64> = (0..5).map(|i| i * 10).collect();
+        assert_eq!(column.len(), 5);
+        assert_eq!(column.at(4), ValueRef::UInt64(40));
+    }
+
+    #[test]
+    fn test_min_max() {
+        let mut column = VectorColumnData::<f64>::with_capacity(4);
+        column.append_slice(&[2.5, f64::NAN, -1.0, 8.0]);
+
+        assert_eq!(column.min(), Some(-1.0));
+        assert_eq!(column.max(), Some(8.0));
+        assert_eq!(column.null_count(), 0);
+
+        let empty = VectorColumnData::<u8>::with_capacity(0);
+        assert_eq!(empty.min(), None);
+        assert_eq!(empty.max_value(), None);
+    }
 }
@@ -1,4 +1,8 @@
-use std::{io::Write, string::ToString, sync::Arc};
+use std::{
+    io::{self, Write},
+    iter::FromIterator,
+    sync::Arc,
+};
 
 use crate::{
     binary::{Encoder, ReadEx},
@@ -6,7 +10,7 @@ use crate::{
     types::{
         column::{
             array::ArrayColumnData, list::List, nullable::NullableColumnData, ArcColumnWrapper,
-            ColumnWrapper, Either, StringPool,
+            ColumnWrapper, StringPool,
         },
         Column, FromSql, SqlType, Value, ValueRef, ColumnType,
     },
@@ -17,7 +21,9 @@ use super::{
     ColumnFrom,
 };
 
-pub(crate) struct StringColumnData {
+/// Data of a `String` column, can be built directly and added to a block
+/// with `Block::column`.
+pub struct StringColumnData {
     pool: StringPool,
 }
 
@@ -26,7 +32,8 @@ pub(crate) struct StringAdapter<K: ColumnType> {
 }
 
 impl StringColumnData {
-    pub(crate) fn with_capacity(capacity: usize) -> Self {
+    /// Creates empty data with room for `capacity` strings.
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
             pool: StringPool::with_capacity(capacity),
         }
@@ -41,6 +48,43 @@ impl StringColumnData {
 
         Ok(data)
     }
+
+    /// Appends a single string.
+    pub fn append<S: AsRef<[u8]>>(&mut self, value: S) {
+        let value = value.as_ref();
+        self.pool.allocate(value.len()).copy_from_slice(value);
+    }
+
+    /// Returns the number of strings.
+    pub fn len(&self) -> usize {
+        self.pool.len()
+    }
+
+    /// Returns `true` if there are no strings.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends every string yielded by `source`, copying the bytes straight
+    /// into the pool without going through `Value`.
+    pub fn append_iter<I>(&mut self, source: I)
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let iter = source.into_iter();
+        self.pool.reserve_strings(iter.size_hint().0);
+        for s in iter {
+            let s = s.as_ref();
+            self.pool.allocate(s.len()).copy_from_slice(s);
+        }
+    }
+}
+
+impl ColumnFrom for StringColumnData {
+    fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
+        W::wrap(source)
+    }
 }
 
 impl ColumnFrom for Vec<String> {
@@ -51,14 +95,37 @@ impl ColumnFrom for Vec<String> {
 
 impl<'a> ColumnFrom for Vec<&'a str> {
     fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
-        let data: Vec<_> = source.iter().map(ToString::to_string).collect();
-        W::wrap(StringColumnData { pool: data.into() })
+        let mut data = StringColumnData::with_capacity(source.len());
+        data.append_iter(source.iter().map(|s| s.as_bytes()));
+        W::wrap(data)
     }
 }
 
 impl<'a> ColumnFrom for Vec<&'a [u8]> {
-    fn column_from<W: ColumnWrapper>(data: Self) -> W::Wrapper {
-        W::wrap(StringColumnData { pool: data.into() })
+    fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
+        let mut data = StringColumnData::with_capacity(source.len());
+        data.append_iter(source);
+        W::wrap(data)
+    }
+}
+
+impl ColumnFrom for &[&str] {
+    fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
+        W::wrap(source.iter().collect::<StringColumnData>())
+    }
+}
+
+impl ColumnFrom for &[String] {
+    fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
+        W::wrap(source.iter().collect::<StringColumnData>())
+    }
+}
+
+impl<S: AsRef<[u8]>> FromIterator<S> for StringColumnData {
+    fn from_iter<I: IntoIterator<Item = S>>(source: I) -> Self {
+        let mut data = StringColumnData::with_capacity(0);
+        data.append_iter(source);
+        data
     }
 }
 
@@ -122,41 +189,58 @@ fn make_array_of_array<W: ColumnWrapper, S: StringSource>(
 
 impl ColumnFrom for Vec<Option<Vec<u8>>> {
     fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
-        make_opt_column::<W, Vec<u8>>(source)
+        W::wrap(make_opt_column(source))
     }
 }
 
 impl ColumnFrom for Vec<Option<&str>> {
     fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
-        make_opt_column::<W, &str>(source)
+        W::wrap(make_opt_column(source))
     }
 }
 
 impl ColumnFrom for Vec<Option<String>> {
     fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
-        make_opt_column::<W, String>(source)
+        W::wrap(make_opt_column(source))
     }
 }
 
-fn make_opt_column<W: ColumnWrapper, S: StringSource>(source: Vec<Option<S>>) -> W::Wrapper {
-    let inner = Arc::new(StringColumnData::with_capacity(source.len()));
+impl ColumnFrom for &[Option<&str>] {
+    fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
+        W::wrap(make_opt_column(source.iter().copied()))
+    }
+}
 
-    let mut data = NullableColumnData {
-        inner,
-        nulls: Vec::with_capacity(source.len()),
-    };
+impl ColumnFrom for &[Option<String>] {
+    fn column_from<W: ColumnWrapper>(source: Self) -> W::Wrapper {
+        W::wrap(make_opt_column(source.iter().map(Option::as_ref)))
+    }
+}
+
+/// Builds a `Nullable(String)` column filling the strings and the null map in
+/// a single pass, nulls are stored as empty strings.
+fn make_opt_column<I, S>(source: I) -> NullableColumnData
+where
+    I: IntoIterator<Item = Option<S>>,
+    S: AsRef<[u8]>,
+{
+    let source = source.into_iter();
+    let capacity = source.size_hint().0;
+    let mut inner = StringColumnData::with_capacity(capacity);
+    let mut nulls = Vec::with_capacity(capacity);
 
     for value in source {
-        let item = if let Some(v) = value {
-            let inner = v.to_value();
-            Value::Nullable(Either::Right(Box::new(inner)))
-        } else {
-            Value::Nullable(Either::Left(SqlType::String.into()))
-        };
-        data.push(item);
+        nulls.push(value.is_none() as u8);
+        match value {
+            Some(v) => inner.append(v),
+            None => inner.append(b""),
+        }
     }
 
-    W::wrap(data)
+    NullableColumnData {
+        inner: Arc::new(inner),
+        nulls,
+    }
 }
 
 impl ColumnData for StringColumnData {
@@ -192,6 +276,32 @@ impl ColumnData for StringColumnData {
         })
     }
 
+    fn take(&self, indices: &[usize]) -> Option<BoxColumnData> {
+        let mut data = Self::with_capacity(indices.len());
+        for &index in indices {
+            let s = self.pool.get(index);
+            let mut b = data.pool.allocate(s.len());
+            b.write_all(s).unwrap();
+        }
+        Some(Box::new(data))
+    }
+
+    fn can_reload(&self) -> bool {
+        true
+    }
+
+    fn reload(&mut self, mut reader: &mut dyn io::Read, size: usize) -> Result<()> {
+        self.pool.clear();
+        self.pool.reserve_strings(size);
+        for _ in 0..size {
+            if let Err(err) = reader.read_str_into_buffer(&mut self.pool) {
+                self.pool.clear();
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
     unsafe fn get_internal(&self, pointers: &[*mut *const u8], level: u8) -> Result<()> {
         assert_eq!(level, 0);
         *pointers[0] = &self.pool as *const StringPool as *const u8;
@@ -228,3 +338,47 @@ impl<K: ColumnType> ColumnData for StringAdapter<K> {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::types::column::Either;
+
+    use super::*;
+
+    #[test]
+    fn test_append_iter() {
+        let mut column = StringColumnData::with_capacity(3);
+        column.push(Value::from("foo"));
+        column.append_iter(vec!["bar", ""]);
+
+        assert_eq!(column.len(), 3);
+        assert_eq!(column.at(0), ValueRef::from("foo"));
+        assert_eq!(column.at(1), ValueRef::from("bar"));
+        assert_eq!(column.at(2), ValueRef::from(""));
+    }
+
+    #[test]
+    fn test_column_from_slices() {
+        let values: &[&str] = &["foo", "bar"];
+        let column = <&[&str]>::column_from::<ArcColumnWrapper>(values);
+        assert_eq!(column.sql_type(), SqlType::String);
+        assert_eq!(column.at(1), ValueRef::from("bar"));
+
+        let values = [Some("foo".to_string()), None];
+        let column = <&[Option<String>]>::column_from::<ArcColumnWrapper>(&values[..]);
+        assert_eq!(column.sql_type(), SqlType::Nullable(SqlType::String.into()));
+        assert_eq!(column.null_count(), 1);
+        assert_eq!(
+            column.at(0),
+            ValueRef::Nullable(Either::Right(Box::new(ValueRef::from("foo"))))
+        );
+        assert_eq!(
+            column.at(1),
+            ValueRef::Nullable(Either::Left(SqlType::String.into()))
+        );
+
+        let column: StringColumnData = (0..3).map(|i| format!("row-{}", i)).collect();
+        assert_eq!(column.len(), 3);
+        assert_eq!(column.at(2), ValueRef::from("row-2"));
+    }
+}
@@ -1,6 +1,8 @@
-use std::{io::Write, slice};
+use std::{cmp, io::Write, slice};
 
 const AVG_STR_SIZE: usize = 80;
+/// Bytes per string the buffer of strings read from the server starts with.
+const LOADED_STR_SIZE: usize = 16;
 
 #[derive(Copy, Clone)]
 struct StringPtr {
@@ -69,6 +71,44 @@ impl StringPool {
         self.try_allocate(size).unwrap()
     }
 
+    /// Same as `allocate`, but grows the last chunk instead of starting a new
+    /// one, so the strings of a column read from the server end up in a
+    /// single buffer sized after them rather than after `AVG_STR_SIZE`.
+    pub(crate) fn allocate_contiguous(&mut self, size: usize) -> &mut [u8] {
+        if self.free_space() < size || self.chunks.is_empty() {
+            let required = self.position + size;
+            match self.chunks.last_mut() {
+                Some(chunk) => {
+                    let len = cmp::max(chunk.len() * 2, required);
+                    chunk.resize(len, 0);
+                }
+                None => {
+                    self.position = 0;
+                    let len = cmp::max(self.capacity * LOADED_STR_SIZE, size);
+                    self.chunks.push(vec![0_u8; len]);
+                }
+            }
+        }
+
+        self.try_allocate(size).unwrap()
+    }
+
+    pub(crate) fn reserve_strings(&mut self, additional: usize) {
+        self.pointers.reserve(additional);
+    }
+
+    /// Removes all strings, keeping the largest chunk for the new ones.
+    pub(crate) fn clear(&mut self) {
+        let largest = (0..self.chunks.len()).max_by_key(|&i| self.chunks[i].len());
+        if let Some(index) = largest {
+            let chunk = self.chunks.swap_remove(index);
+            self.chunks.clear();
+            self.chunks.push(chunk);
+        }
+        self.pointers.clear();
+        self.position = 0;
+    }
+
     fn free_space(&self) -> usize {
         if let Some(buffer) = self.chunks.last() {
             return buffer.len() - self.position;
@@ -152,6 +192,21 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_allocate_contiguous() {
+        let mut pool = StringPool::with_capacity(10);
+        for i in 0..1000 {
+            let s = format!("text-{}", i);
+            pool.allocate_contiguous(s.len())
+                .copy_from_slice(s.as_bytes());
+        }
+
+        assert_eq!(pool.chunks.len(), 1);
+        for i in 0..1000 {
+            assert_eq!(pool.get(i), format!("text-{}", i).as_bytes());
+        }
+    }
+
     #[test]
     fn test_get() {
         let mut pool = StringPool::with_capacity(10);
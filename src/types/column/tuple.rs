@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use chrono_tz::Tz;
+
+use crate::{
+    binary::{Encoder, ReadEx},
+    errors::Result,
+    types::{
+        column::{
+            column_data::{ArcColumnData, BoxColumnData},
+            ArcColumnWrapper, ColumnData,
+        },
+        SqlType, Value, ValueRef,
+    },
+};
+
+/// Data of a `Tuple` column: one inner column per element.
+///
+/// The names of the elements of a named tuple such as
+/// `Tuple(a UInt32, b String)` are kept in its type, elements of unnamed
+/// tuples are only accessed by position.
+pub(crate) struct TupleColumnData {
+    pub(crate) sql_type: &'static SqlType,
+    pub(crate) inner: Vec<ArcColumnData>,
+}
+
+impl TupleColumnData {
+    pub(crate) fn load<R: ReadEx>(
+        reader: &mut R,
+        elements: Vec<(Option<String>, &str)>,
+        size: usize,
+        tz: Tz,
+        tz_override: Option<Tz>,
+    ) -> Result<Self> {
+        let mut names = Vec::with_capacity(elements.len());
+        let mut inner = Vec::with_capacity(elements.len());
+        for (name, type_name) in elements {
+            let column = <dyn ColumnData>::load_nested::<ArcColumnWrapper, _>(
+                reader,
+                type_name,
+                size,
+                tz,
+                tz_override,
+            )?;
+            names.push(name);
+            inner.push(column);
+        }
+        Ok(Self::new(names, inner))
+    }
+
+    pub(crate) fn new(names: Vec<Option<String>>, inner: Vec<ArcColumnData>) -> Self {
+        let elements = names
+            .into_iter()
+            .zip(inner.iter().map(|column| column.sql_type().into()))
+            .collect();
+        Self {
+            sql_type: SqlType::Tuple(elements).into(),
+            inner,
+        }
+    }
+}
+
+impl ColumnData for TupleColumnData {
+    fn sql_type(&self) -> SqlType {
+        self.sql_type.clone()
+    }
+
+    fn save(&self, encoder: &mut Encoder, start: usize, end: usize) {
+        for column in &self.inner {
+            column.save(encoder, start, end);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.inner.first().map_or(0, |column| column.len())
+    }
+
+    fn push(&mut self, value: Value) {
+        if let Value::Tuple(_, values) = value {
+            assert_eq!(
+                values.len(),
+                self.inner.len(),
+                "tuple should have {} elements",
+                self.inner.len()
+            );
+            for (column, value) in self.inner.iter_mut().zip(values.iter()) {
+                Arc::get_mut(column).unwrap().push(value.clone());
+            }
+        } else {
+            panic!("value should be a tuple ({:?})", value)
+        }
+    }
+
+    fn at(&self, index: usize) -> ValueRef<'_> {
+        let values = self.inner.iter().map(|column| column.at(index)).collect();
+        ValueRef::Tuple(self.sql_type, Arc::new(values))
+    }
+
+    fn clone_instance(&self) -> BoxColumnData {
+        Box::new(Self {
+            sql_type: self.sql_type,
+            inner: self.inner.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_load_named_tuple() {
+        let mut encoder = Encoder::new();
+        encoder.write(1_u32);
+        encoder.write(2_u32);
+        encoder.string("x");
+        encoder.string("y");
+        let source = encoder.get_buffer();
+
+        let column = <dyn ColumnData>::load_data::<ArcColumnWrapper, _>(
+            &mut Cursor::new(&source[..]),
+            "Tuple(a UInt32, `b c` String)",
+            2,
+            Tz::Zulu,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            column.sql_type().to_string(),
+            "Tuple(a UInt32, `b c` String)"
+        );
+        assert_eq!(column.sql_type().tuple_position("b c"), Some(1));
+
+        match column.at(1) {
+            ValueRef::Tuple(sql_type, values) => {
+                assert_eq!(sql_type, &column.sql_type());
+                assert_eq!(*values, vec![ValueRef::UInt32(2), ValueRef::from("y")]);
+            }
+            value => panic!("unexpected value {:?}", value),
+        }
+
+        let mut encoder = Encoder::new();
+        column.save(&mut encoder, 0, 2);
+        assert_eq!(encoder.get_buffer(), source);
+    }
+
+    #[test]
+    fn test_load_array_of_tuples() {
+        let rows: Vec<Vec<(&str, u64)>> = vec![vec![("a", 1), ("b", 2)], vec![], vec![("c", 3)]];
+
+        let mut encoder = Encoder::new();
+        let mut offset = 0_u64;
+        for row in &rows {
+            offset += row.len() as u64;
+            encoder.write(offset);
+        }
+        for (s, _) in rows.iter().flatten() {
+            encoder.string(s);
+        }
+        for (_, n) in rows.iter().flatten() {
+            encoder.write(*n);
+        }
+        let source = encoder.get_buffer();
+
+        let column = <dyn ColumnData>::load_data::<ArcColumnWrapper, _>(
+            &mut Cursor::new(&source[..]),
+            "Array(Tuple(String, UInt64))",
+            rows.len(),
+            Tz::Zulu,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            column.sql_type().to_string(),
+            "Array(Tuple(String, UInt64))"
+        );
+
+        for (index, row) in rows.iter().enumerate() {
+            let pairs: Vec<_> = match column.at(index) {
+                ValueRef::Array(_, values) => values
+                    .iter()
+                    .map(|value| match value {
+                        ValueRef::Tuple(_, elements) => match &elements[..] {
+                            [s, ValueRef::UInt64(n)] => (s.as_str().unwrap(), *n),
+                            elements => panic!("unexpected elements {:?}", elements),
+                        },
+                        value => panic!("unexpected value {:?}", value),
+                    })
+                    .collect(),
+                value => panic!("unexpected value {:?}", value),
+            };
+            assert_eq!(&pairs, row);
+        }
+
+        let mut encoder = Encoder::new();
+        column.save(&mut encoder, 0, rows.len());
+        assert_eq!(encoder.get_buffer(), source);
+    }
+
+    #[test]
+    fn test_unnamed_tuple_round_trip() {
+        let sql_type: &'static SqlType = SqlType::Tuple(vec![
+            (None, &SqlType::UInt8),
+            (None, SqlType::Nullable(&SqlType::String).into()),
+        ])
+        .into();
+        let mut column =
+            <dyn ColumnData>::from_type::<ArcColumnWrapper>(sql_type.clone(), Tz::Zulu, 2).unwrap();
+        let values = vec![Value::UInt8(7), Value::from(Some("z"))];
+        Arc::get_mut(&mut column)
+            .unwrap()
+            .push(Value::Tuple(sql_type, Arc::new(values)));
+        assert_eq!(
+            column.sql_type().to_string(),
+            "Tuple(UInt8, Nullable(String))"
+        );
+        assert_eq!(column.sql_type().tuple_position("0"), None);
+
+        let mut encoder = Encoder::new();
+        column.save(&mut encoder, 0, 1);
+        let loaded = <dyn ColumnData>::load_data::<ArcColumnWrapper, _>(
+            &mut Cursor::new(encoder.get_buffer_ref()),
+            "Tuple(UInt8, Nullable(String))",
+            1,
+            Tz::Zulu,
+            None,
+        )
+        .unwrap();
+        assert_eq!(loaded.sql_type(), *sql_type);
+        assert_eq!(loaded.at(0), column.at(0));
+    }
+}
@@ -1,7 +1,7 @@
 use chrono::{prelude::*, Date};
 use chrono_tz::Tz;
 
-use crate::types::{SqlType, Value, ValueRef, DateTimeType};
+use crate::types::{overflow::date_days, DateTimeType, SqlType, Value, ValueRef};
 
 pub trait DateConverter {
     fn to_date(&self, tz: Tz) -> ValueRef<'static>;
@@ -9,9 +9,7 @@ pub trait DateConverter {
     fn date_type() -> SqlType;
 
     fn get_days(date: Date<Tz>) -> u16 {
-        const UNIX_EPOCH_DAY: i64 = 719_163;
-        let gregorian_day = i64::from(date.num_days_from_ce());
-        (gregorian_day - UNIX_EPOCH_DAY) as u16
+        date_days(date.naive_local()) as u16
     }
 }
 
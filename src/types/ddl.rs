@@ -0,0 +1,85 @@
+use crate::types::{escape_identifier, SqlType};
+
+/// Renders a `CREATE TABLE` statement with the columns of `schema`, e.g.
+/// of `Block::schema`, followed by `engine`, e.g. `ENGINE = Memory`.
+///
+/// The table and column names are back-quoted, so a table of another
+/// database has to be created on a connection using that database. Columns
+/// of `SqlType::DateTime` are created in the server timezone, the type does
+/// not carry one; `DateTime64` columns keep theirs.
+///
+/// ```rust
+/// # use clickhouse_rs::types::{schema_to_ddl, Block};
+/// let block = Block::new()
+///     .column("id", vec![1_u32])
+///     .column("name", vec![Some("foo")]);
+///
+/// assert_eq!(
+///     schema_to_ddl("users", &block.schema(), "ENGINE = Memory"),
+///     "CREATE TABLE `users` (`id` UInt32, `name` Nullable(String)) ENGINE = Memory"
+/// );
+/// ```
+pub fn schema_to_ddl<S: AsRef<str>>(table: &str, schema: &[(S, SqlType)], engine: &str) -> String {
+    let columns: Vec<String> = schema
+        .iter()
+        .map(|(name, sql_type)| format!("{} {}", escape_identifier(name.as_ref()), sql_type))
+        .collect();
+
+    let mut ddl = format!(
+        "CREATE TABLE {} ({})",
+        escape_identifier(table),
+        columns.join(", ")
+    );
+    if !engine.is_empty() {
+        ddl.push(' ');
+        ddl.push_str(engine);
+    }
+    ddl
+}
+
+#[cfg(test)]
+mod test {
+    use chrono_tz::Tz;
+
+    use super::*;
+    use crate::types::DateTimeType;
+
+    #[test]
+    fn test_schema_to_ddl() {
+        let schema = vec![
+            ("id".to_string(), SqlType::UInt64),
+            (
+                "odd `name`".to_string(),
+                SqlType::Nullable(SqlType::String.into()),
+            ),
+            ("price".to_string(), SqlType::Decimal(18, 4)),
+            ("code".to_string(), SqlType::FixedString(3)),
+            (
+                "state".to_string(),
+                SqlType::Enum8(vec![("it's".to_string(), 1), ("a\\b".to_string(), -2)]),
+            ),
+            (
+                "at".to_string(),
+                SqlType::DateTime(DateTimeType::DateTime64(3, Tz::Europe__Moscow)),
+            ),
+            ("tags".to_string(), SqlType::Array(SqlType::String.into())),
+        ];
+
+        assert_eq!(
+            schema_to_ddl("db`t", &schema, "ENGINE = Memory"),
+            "CREATE TABLE `db\\`t` (\
+             `id` UInt64, \
+             `odd \\`name\\`` Nullable(String), \
+             `price` Decimal(18, 4), \
+             `code` FixedString(3), \
+             `state` Enum8('it\\'s' = 1, 'a\\\\b' = -2), \
+             `at` DateTime64(3, 'Europe/Moscow'), \
+             `tags` Array(String)\
+             ) ENGINE = Memory"
+        );
+        assert_eq!(
+            schema_to_ddl("t", &[("n", SqlType::UInt8)], ""),
+            "CREATE TABLE `t` (`n` UInt8)"
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use std::{fmt, str, sync::Arc};
+
+use serde::de::{
+    self,
+    value::{MapDeserializer, SeqDeserializer},
+    DeserializeOwned, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor,
+};
+
+use crate::{
+    errors::{Error, Result},
+    types::{column::Either, Block, ColumnType, SqlType, ValueRef},
+};
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Error::Other(message.to_string().into())
+    }
+}
+
+/// Deserializes the `row`th row of `block`: as a struct or a map by column
+/// names, as a tuple or a sequence in column order.
+pub(crate) fn from_row<T, K>(block: &Block<K>, row: usize) -> Result<T>
+where
+    T: DeserializeOwned,
+    K: ColumnType,
+{
+    T::deserialize(RowDeserializer { block, row })
+}
+
+struct RowDeserializer<'a, K: ColumnType> {
+    block: &'a Block<K>,
+    row: usize,
+}
+
+struct Cells<'a, K: ColumnType> {
+    block: &'a Block<K>,
+    row: usize,
+    column: usize,
+}
+
+impl<'a, K: ColumnType> Cells<'a, K> {
+    fn next_value(&mut self) -> ValueDeserializer<'a> {
+        let value = self.block.columns()[self.column].at(self.row);
+        self.column += 1;
+        ValueDeserializer(value)
+    }
+}
+
+impl<'de, 'a, K: ColumnType> Deserializer<'de> for RowDeserializer<'a, K> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_map(Cells {
+            block: self.block,
+            row: self.row,
+            column: 0,
+        })
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Cells {
+            block: self.block,
+            row: self.row,
+            column: 0,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+impl<'de, 'a, K: ColumnType> MapAccess<'de> for Cells<'a, K> {
+    type Error = Error;
+
+    fn next_key_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>> {
+        match self.block.columns().get(self.column) {
+            None => Ok(None),
+            Some(column) => {
+                let name = de::value::StrDeserializer::<Error>::new(column.name());
+                seed.deserialize(name).map(Some)
+            }
+        }
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value> {
+        seed.deserialize(self.next_value())
+    }
+}
+
+impl<'de, 'a, K: ColumnType> SeqAccess<'de> for Cells<'a, K> {
+    type Error = Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>> {
+        if self.column == self.block.column_count() {
+            return Ok(None);
+        }
+        seed.deserialize(self.next_value()).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.block.column_count() - self.column)
+    }
+}
+
+/// Numbers and strings are passed as they are, `NULL` as `None`, arrays and
+/// unnamed tuples as sequences and named tuples as maps by element names;
+/// every other value as its text, e.g. `2020-01-01` for a `Date`.
+struct ValueDeserializer<'a>(ValueRef<'a>);
+
+impl<'de, 'a> de::IntoDeserializer<'de, Error> for ValueDeserializer<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for ValueDeserializer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            ValueRef::UInt8(v) => visitor.visit_u8(v),
+            ValueRef::UInt16(v) => visitor.visit_u16(v),
+            ValueRef::UInt32(v) => visitor.visit_u32(v),
+            ValueRef::UInt64(v) => visitor.visit_u64(v),
+            ValueRef::Int8(v) => visitor.visit_i8(v),
+            ValueRef::Int16(v) => visitor.visit_i16(v),
+            ValueRef::Int32(v) => visitor.visit_i32(v),
+            ValueRef::Int64(v) => visitor.visit_i64(v),
+            ValueRef::Float32(v) => visitor.visit_f32(v),
+            ValueRef::Float64(v) => visitor.visit_f64(v),
+            ValueRef::String(v) => match str::from_utf8(v) {
+                Ok(s) => visitor.visit_str(s),
+                Err(_) => visitor.visit_bytes(v),
+            },
+            ValueRef::Nullable(Either::Left(_)) => visitor.visit_none(),
+            ValueRef::Nullable(Either::Right(inner)) => {
+                visitor.visit_some(ValueDeserializer(*inner))
+            }
+            ValueRef::Array(_, values) => {
+                let values = Arc::try_unwrap(values).unwrap_or_else(|values| (*values).clone());
+                let mut seq = SeqDeserializer::new(values.into_iter().map(ValueDeserializer));
+                let result = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(result)
+            }
+            ValueRef::Tuple(SqlType::Tuple(elements), values)
+                if elements.iter().all(|(name, _)| name.is_some()) =>
+            {
+                let values = Arc::try_unwrap(values).unwrap_or_else(|values| (*values).clone());
+                let entries = elements
+                    .iter()
+                    .map(|(name, _)| name.as_deref().unwrap_or_default())
+                    .zip(values.into_iter().map(ValueDeserializer));
+                let mut map = MapDeserializer::new(entries);
+                let result = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(result)
+            }
+            ValueRef::Tuple(_, values) => {
+                let values = Arc::try_unwrap(values).unwrap_or_else(|values| (*values).clone());
+                let mut seq = SeqDeserializer::new(values.into_iter().map(ValueDeserializer));
+                let result = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(result)
+            }
+            value => visitor.visit_string(value.to_string()),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            ValueRef::UInt8(v) => visitor.visit_bool(v != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            ValueRef::Decimal(v) => visitor.visit_f32(v.into()),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            ValueRef::Decimal(v) => visitor.visit_f64(v.into()),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            ValueRef::Nullable(_) => self.deserialize_any(visitor),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char str string bytes byte_buf
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use serde::de::{Deserialize, MapAccess, Visitor};
+
+    use super::*;
+    use crate::{
+        row,
+        types::{Simple, Value},
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct Totals {
+        sum: u64,
+        name: Option<String>,
+    }
+
+    // A hand-written equivalent of `#[derive(Deserialize)]`.
+    impl<'de> Deserialize<'de> for Totals {
+        fn deserialize<D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> std::result::Result<Self, D::Error> {
+            struct TotalsVisitor;
+
+            impl<'de> Visitor<'de> for TotalsVisitor {
+                type Value = Totals;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("struct Totals")
+                }
+
+                fn visit_map<A: MapAccess<'de>>(
+                    self,
+                    mut map: A,
+                ) -> std::result::Result<Totals, A::Error> {
+                    let mut sum = None;
+                    let mut name = None;
+                    while let Some(key) = map.next_key::<String>()? {
+                        match key.as_str() {
+                            "sum" => sum = Some(map.next_value()?),
+                            "name" => name = Some(map.next_value()?),
+                            _ => map.next_value::<de::IgnoredAny>().map(|_| ())?,
+                        }
+                    }
+                    Ok(Totals {
+                        sum: sum.ok_or_else(|| de::Error::missing_field("sum"))?,
+                        name: name.unwrap_or(None),
+                    })
+                }
+            }
+
+            deserializer.deserialize_struct("Totals", &["sum", "name"], TotalsVisitor)
+        }
+    }
+
+    #[test]
+    fn test_from_row() {
+        let block = Block::<Simple>::new()
+            .column("key", vec!["a", "b"])
+            .column("sum", vec![3_u64, 4])
+            .column("name", vec![Some("x"), None])
+            .column("values", vec![vec![1_i32, 2], vec![]]);
+
+        assert_eq!(
+            from_row::<Totals, _>(&block, 0).unwrap(),
+            Totals {
+                sum: 3,
+                name: Some("x".to_string())
+            }
+        );
+        assert_eq!(
+            from_row::<Totals, _>(&block, 1).unwrap(),
+            Totals { sum: 4, name: None }
+        );
+
+        let tuple: (String, u64, Option<String>, Vec<i32>) = from_row(&block, 0).unwrap();
+        assert_eq!(
+            tuple,
+            ("a".to_string(), 3, Some("x".to_string()), vec![1, 2])
+        );
+
+        let sums = Block::<Simple>::new().column("sum", vec![3_u64, 4]);
+        let map: HashMap<String, u64> = from_row(&sums, 1).unwrap();
+        assert_eq!(map["sum"], 4);
+    }
+
+    #[test]
+    fn test_from_row_named_tuple() {
+        let sql_type: &'static SqlType = SqlType::Tuple(vec![
+            (Some("sum".to_string()), &SqlType::UInt64),
+            (
+                Some("name".to_string()),
+                SqlType::Nullable(&SqlType::String).into(),
+            ),
+        ])
+        .into();
+        let totals = Value::Tuple(
+            sql_type,
+            Arc::new(vec![Value::UInt64(5), Value::from(Some("y"))]),
+        );
+        let mut block = Block::<Simple>::new();
+        block.push(row! { totals: totals }).unwrap();
+
+        let (totals,): (Totals,) = from_row(&block, 0).unwrap();
+        assert_eq!(
+            totals,
+            Totals {
+                sum: 5,
+                name: Some("y".to_string())
+            }
+        );
+        assert_eq!(
+            block.columns()[0].at(0).tuple_element("sum"),
+            Some(&ValueRef::UInt64(5))
+        );
+    }
+
+    #[test]
+    fn test_from_row_type_mismatch() {
+        let block = Block::<Simple>::new().column("sum", vec!["text"]);
+        assert!(from_row::<Totals, _>(&block, 0).is_err());
+    }
+}
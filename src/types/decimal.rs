@@ -1,4 +1,7 @@
-use std::{fmt, cmp::Ordering};
+use std::{
+    cmp::{self, Ordering},
+    fmt,
+};
 
 static FACTORS10: &[i64] = &[
     1,
@@ -125,6 +128,15 @@ impl PartialEq for Decimal {
     }
 }
 
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let scale = cmp::max(self.scale, other.scale);
+        let a = self.mantissa() * 10_i128.pow(u32::from(scale - self.scale));
+        let b = other.mantissa() * 10_i128.pow(u32::from(scale - other.scale));
+        Some(a.cmp(&b))
+    }
+}
+
 fn decimal2str(decimal: &Decimal) -> String {
     let mut r = format!("{}", decimal.underlying);
     while r.len() < decimal.scale() {
@@ -158,7 +170,14 @@ impl From<Decimal> for f32 {
 
 impl From<Decimal> for f64 {
     fn from(value: Decimal) -> Self {
-        value.underlying as f64 / FACTORS10[value.scale()] as f64
+        value.to_f64()
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+impl From<Decimal> for rust_decimal::Decimal {
+    fn from(value: Decimal) -> Self {
+        rust_decimal::Decimal::from_i128_with_scale(value.mantissa(), u32::from(value.scale))
     }
 }
 
@@ -215,6 +234,17 @@ impl Decimal {
         self.scale as usize
     }
 
+    /// Returns the unscaled value, e.g. `12345` for `123.45`. Together with
+    /// `scale` it represents the decimal without any loss.
+    pub fn mantissa(&self) -> i128 {
+        i128::from(self.underlying)
+    }
+
+    /// Converts the decimal to `f64`, applying the scale.
+    pub fn to_f64(&self) -> f64 {
+        self.underlying as f64 / FACTORS10[self.scale()] as f64
+    }
+
     pub(crate) fn set_scale(self, scale: u8) -> Self {
         let underlying = match scale.cmp(&self.scale) {
             Ordering::Less => {
@@ -308,6 +338,39 @@ mod test {
         assert_eq!(120_000, b.underlying);
     }
 
+    #[test]
+    fn test_mantissa_and_to_f64() {
+        let d = Decimal {
+            underlying: 12345,
+            precision: 10,
+            scale: 2,
+            nobits: NoBits::N64,
+        };
+
+        assert_eq!(d.mantissa(), 12345);
+        assert_eq!(d.scale(), 2);
+        assert!((d.to_f64() - 123.45).abs() < f64::EPSILON);
+        assert_eq!(format!("{}", d), "123.45");
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn test_into_rust_decimal() {
+        let d = Decimal {
+            underlying: 12345,
+            precision: 10,
+            scale: 2,
+            nobits: NoBits::N64,
+        };
+
+        let actual = rust_decimal::Decimal::from(d);
+        assert_eq!(actual, rust_decimal::Decimal::new(12345, 2));
+        assert_eq!(actual.to_string(), "123.45");
+
+        let negative = Decimal::of(-7, 3);
+        assert_eq!(rust_decimal::Decimal::from(negative).to_string(), "-7.000");
+    }
+
     #[test]
     fn test_decimal2str() {
         let d = Decimal::of(0.00001, 5);
@@ -1,6 +1,19 @@
 use std::fmt;
 
-// TODO Using strings as a keys
+/// A fieldless Rust enum stored in an `Enum8` or `Enum16` column by the
+/// labels of its variants, usually implemented with `clickhouse_enum!`.
+///
+/// Such enums are read with `Block::get` and `Row::get` from enum columns,
+/// and pushed into them as their label, which is resolved against the
+/// values of the column.
+pub trait ClickhouseEnum: Sized {
+    /// Returns the variant with `label`, if any.
+    fn from_label(label: &str) -> Option<Self>;
+
+    /// Returns the label of the variant.
+    fn label(&self) -> &'static str;
+}
+
 #[derive(Clone, Copy)]
 pub struct Enum8(pub(crate) i8);
 
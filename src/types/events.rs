@@ -0,0 +1,101 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use url::Url;
+
+use crate::errors::Error;
+
+/// Why a connection was closed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CloseReason {
+    /// The connection outlived `idle_timeout` or `max_lifetime`.
+    Expired,
+    /// The socket failed or timed out.
+    Broken,
+    /// The connection was dropped by its owner or not kept by the pool.
+    Released,
+}
+
+/// Rows and bytes a query read and wrote, passed to
+/// `PoolEvents::query_finished`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct QueryCounts {
+    pub rows_read: u64,
+    pub bytes_read: u64,
+    pub rows_written: u64,
+    pub bytes_written: u64,
+}
+
+/// A query that completed, passed to `PoolEvents::query_logged`.
+#[derive(Debug)]
+pub struct QueryLog<'a> {
+    /// Text of the query as sent to the server.
+    pub sql: &'a str,
+    pub query_id: &'a str,
+    /// Time from sending the query to its last packet.
+    pub duration: Duration,
+    /// Totals of the progress reported by the server.
+    pub rows_read: u64,
+    pub bytes_read: u64,
+    pub rows_written: u64,
+    pub bytes_written: u64,
+    /// Why the query failed, `None` if it succeeded.
+    pub error: Option<&'a Error>,
+}
+
+/// Hooks called on connection, checkout and query events, e.g. to export
+/// metrics.
+///
+/// Every method does nothing by default. The hooks are called from the
+/// tasks driving the connections, so they should return quickly.
+pub trait PoolEvents: Send + Sync {
+    /// A connection to `host` was established.
+    fn connection_opened(&self, _host: &Url) {}
+
+    /// A connection to `host` was closed.
+    fn connection_closed(&self, _host: Option<&Url>, _reason: CloseReason) {}
+
+    /// `Pool::get_handle` was called.
+    fn checkout_started(&self) {}
+
+    /// `Pool::get_handle` resolved after `wait`, with `error` if it failed.
+    fn checkout_finished(&self, _wait: Duration, _error: Option<&Error>) {}
+
+    /// A query was sent to the server.
+    fn query_started(&self, _query_id: &str) {}
+
+    /// A query completed after `duration`, with `error` if it failed.
+    ///
+    /// `counts` are the totals of the progress reported by the server.
+    fn query_finished(
+        &self,
+        _query_id: &str,
+        _counts: QueryCounts,
+        _duration: Duration,
+        _error: Option<&Error>,
+    ) {
+    }
+
+    /// A query completed, called right after `query_finished` with the
+    /// text of the query.
+    fn query_logged(&self, _log: &QueryLog) {}
+
+    /// A query that failed with `error` is sent again after `delay`;
+    /// `attempt` counts the retries, starting at 1.
+    fn query_retried(&self, _query_id: &str, _attempt: usize, _delay: Duration, _error: &Error) {}
+}
+
+/// Shared event handler of `Options`.
+#[derive(Clone)]
+pub(crate) struct EventHandler(pub(crate) Arc<dyn PoolEvents>);
+
+impl fmt::Debug for EventHandler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("EventHandler")
+    }
+}
+
+impl PartialEq for EventHandler {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::as_ptr(&self.0) as *const u8 == Arc::as_ptr(&other.0) as *const u8
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{
+    errors::{DriverError, Error, Result},
+    types::{FromSql, Row, Simple},
+};
+
+/// Conversion of a whole row, e.g. for `QueryResult::fetch_one`.
+///
+/// Implemented for tuples of up to 8 values of `FromSql` types, taken from
+/// the columns in their order.
+pub trait FromRow: Sized {
+    fn from_row(row: &Row<Simple>) -> Result<Self>;
+}
+
+fn check_columns(row: &Row<Simple>, expected: usize) -> Result<()> {
+    if row.len() != expected {
+        return Err(Error::Driver(DriverError::ColumnCount {
+            columns: row.len(),
+            expected,
+        }));
+    }
+    Ok(())
+}
+
+macro_rules! from_row_tuple {
+    ( $len:expr => $( $t:ident: $idx:tt ),* ) => {
+        impl<$( $t ),*> FromRow for ( $( $t, )* )
+        where
+            $( $t: for<'a> FromSql<'a>, )*
+        {
+            fn from_row(row: &Row<Simple>) -> Result<Self> {
+                check_columns(row, $len)?;
+                Ok(( $( row.get::<$t, _>($idx)?, )* ))
+            }
+        }
+    };
+}
+
+from_row_tuple!(1 => A: 0);
+from_row_tuple!(2 => A: 0, B: 1);
+from_row_tuple!(3 => A: 0, B: 1, C: 2);
+from_row_tuple!(4 => A: 0, B: 1, C: 2, D: 3);
+from_row_tuple!(5 => A: 0, B: 1, C: 2, D: 3, E: 4);
+from_row_tuple!(6 => A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+from_row_tuple!(7 => A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6);
+from_row_tuple!(8 => A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::Block;
+
+    #[test]
+    fn test_tuple_from_row() {
+        let block = Block::new()
+            .column("id", vec![1_u32, 2])
+            .column("name", vec!["foo", "bar"]);
+
+        let rows: Vec<(u32, String)> = block
+            .rows()
+            .map(|row| FromRow::from_row(&row))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(rows, vec![(1, "foo".to_string()), (2, "bar".to_string())]);
+
+        let row = block.rows().next().unwrap();
+        match <(u32,)>::from_row(&row) {
+            Err(Error::Driver(DriverError::ColumnCount {
+                columns: 2,
+                expected: 1,
+            })) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert!(<(u32, u32)>::from_row(&row).is_err());
+    }
+}
@@ -1,11 +1,17 @@
 use chrono::prelude::*;
 use chrono_tz::Tz;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::{
+    any,
+    net::{Ipv4Addr, Ipv6Addr},
+};
 
-use crate::types::{Enum16, Enum8};
+use crate::types::{ClickhouseEnum, Enum16, Enum8};
 use crate::{
     errors::{Error, FromSqlError},
-    types::{column::{Either, datetime64::to_datetime}, Decimal, SqlType, ValueRef},
+    types::{
+        column::{datetime64::to_datetime, Either},
+        Decimal, SqlType, Value, ValueRef,
+    },
 };
 
 pub type FromSqlResult<T> = Result<T, Error>;
@@ -32,6 +38,14 @@ macro_rules! from_sql_impl {
     };
 }
 
+/// Any value, e.g. to convert numbers of any width with `Value::as_i64`,
+/// `Value::as_u64` or `Value::as_f64`.
+impl<'a> FromSql<'a> for Value {
+    fn from_sql(value: ValueRef<'a>) -> FromSqlResult<Self> {
+        Ok(Value::from(value))
+    }
+}
+
 impl<'a> FromSql<'a> for Decimal {
     fn from_sql(value: ValueRef<'a>) -> FromSqlResult<Self> {
         match value {
@@ -47,6 +61,13 @@ impl<'a> FromSql<'a> for Decimal {
     }
 }
 
+#[cfg(feature = "rust_decimal")]
+impl<'a> FromSql<'a> for rust_decimal::Decimal {
+    fn from_sql(value: ValueRef<'a>) -> FromSqlResult<Self> {
+        Decimal::from_sql(value).map(rust_decimal::Decimal::from)
+    }
+}
+
 impl<'a> FromSql<'a> for Enum8 {
     fn from_sql(value: ValueRef<'a>) -> FromSqlResult<Self> {
         match value {
@@ -79,6 +100,17 @@ impl<'a> FromSql<'a> for Enum16 {
     }
 }
 
+impl<'a, T: ClickhouseEnum> FromSql<'a> for T {
+    fn from_sql(value: ValueRef<'a>) -> FromSqlResult<Self> {
+        let label = value.as_str()?;
+        T::from_label(label).ok_or_else(|| {
+            Error::FromSql(FromSqlError::UnknownEnumValue {
+                value: label.to_string(),
+            })
+        })
+    }
+}
+
 impl<'a> FromSql<'a> for &'a str {
     fn from_sql(value: ValueRef<'a>) -> FromSqlResult<&'a str> {
         value.as_str()
@@ -97,6 +129,15 @@ impl<'a> FromSql<'a> for String {
     }
 }
 
+/// Parses JSON text, e.g. of an `Object('json')` or a `String` column.
+#[cfg(feature = "json")]
+impl<'a> FromSql<'a> for serde_json::Value {
+    fn from_sql(value: ValueRef<'a>) -> FromSqlResult<Self> {
+        serde_json::from_str(value.as_str()?)
+            .map_err(|err| Error::Other(format!("Invalid JSON: {}", err).into()))
+    }
+}
+
 impl<'a> FromSql<'a> for Ipv4Addr {
     fn from_sql(value: ValueRef<'a>) -> FromSqlResult<Self> {
         match value {
@@ -232,6 +273,8 @@ from_sql_vec_impl! {
     f64: Float64
 }
 
+/// Reads a `Nullable` column, `None` for its nulls. Other columns fail with
+/// `FromSqlError::InvalidType`, even though they never hold a null.
 impl<'a, T> FromSql<'a> for Option<T>
 where
     T: FromSql<'a>,
@@ -249,7 +292,7 @@ where
                 let from = SqlType::from(value.clone()).to_string();
                 Err(Error::FromSql(FromSqlError::InvalidType {
                     src: from,
-                    dst: stringify!($t).into(),
+                    dst: any::type_name::<Self>().into(),
                 }))
             }
         }
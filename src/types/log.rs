@@ -0,0 +1,97 @@
+use chrono::prelude::*;
+use chrono_tz::Tz;
+
+use crate::{errors::Result, types::Block};
+
+/// Verbosity of the server logs requested with `QueryResult::with_logs`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Fatal,
+    Error,
+    Warning,
+    Information,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    /// Value of the `send_logs_level` setting.
+    pub(crate) fn as_setting(self) -> &'static str {
+        match self {
+            LogLevel::Fatal => "fatal",
+            LogLevel::Error => "error",
+            LogLevel::Warning => "warning",
+            LogLevel::Information => "information",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+
+    /// Maps a Poco priority (1 = fatal ... 8 = trace) onto a level. `critical`
+    /// and `notice` have no setting of their own and are folded into the
+    /// neighbouring levels.
+    fn from_priority(priority: i8) -> Self {
+        match priority {
+            i8::MIN..=1 => LogLevel::Fatal,
+            2 | 3 => LogLevel::Error,
+            4 => LogLevel::Warning,
+            5 | 6 => LogLevel::Information,
+            7 => LogLevel::Debug,
+            _ => LogLevel::Trace,
+        }
+    }
+}
+
+/// A log line the server sent while executing a query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogEntry {
+    pub time: DateTime<Tz>,
+    pub time_microseconds: u32,
+    pub host: String,
+    pub query_id: String,
+    pub thread_id: u64,
+    pub priority: LogLevel,
+    pub source: String,
+    pub text: String,
+}
+
+impl LogEntry {
+    pub(crate) fn from_block(block: &Block) -> Result<Vec<LogEntry>> {
+        let mut entries = Vec::with_capacity(block.row_count());
+
+        for row in 0..block.row_count() {
+            // Older servers report `thread_number` instead of `thread_id`.
+            let thread_id = match block.get::<u64, _>(row, "thread_id") {
+                Ok(thread_id) => thread_id,
+                Err(_) => u64::from(block.get::<u32, _>(row, "thread_number")?),
+            };
+
+            entries.push(LogEntry {
+                time: block.get(row, "event_time")?,
+                time_microseconds: block.get(row, "event_time_microseconds")?,
+                host: block.get(row, "host_name")?,
+                query_id: block.get(row, "query_id")?,
+                thread_id,
+                priority: LogLevel::from_priority(block.get(row, "priority")?),
+                source: block.get(row, "source")?,
+                text: block.get(row, "text")?,
+            });
+        }
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_priority() {
+        assert_eq!(LogLevel::from_priority(1), LogLevel::Fatal);
+        assert_eq!(LogLevel::from_priority(3), LogLevel::Error);
+        assert_eq!(LogLevel::from_priority(6), LogLevel::Information);
+        assert_eq!(LogLevel::from_priority(8), LogLevel::Trace);
+        assert_eq!(LogLevel::from_priority(9), LogLevel::Trace);
+    }
+}
@@ -1,27 +1,36 @@
-use std::{borrow::Cow, collections::HashMap, fmt, sync::Mutex};
+use std::{borrow::Cow, cmp, collections::HashMap, fmt, ops, sync::Mutex, time::Duration};
 
 use chrono::prelude::*;
 use chrono_tz::Tz;
 use hostname::get;
+use url::Url;
 
-use crate::errors::ServerError;
+use crate::{client_info, errors::ServerError};
 
 pub use self::{
-    block::{Block, RCons, RNil, Row, RowBuilder, Rows},
-    column::{Column, ColumnType, Complex, Simple, iter::Iterable},
+    block::{Block, RCons, RNil, Row, RowBuilder, Rows, SortColumn},
+    column::{
+        iter::Iterable, ArrayColumnData, Column, ColumnType, Complex, Simple, StringColumnData,
+        VectorColumnData,
+    },
+    ddl::schema_to_ddl,
     decimal::Decimal,
-    enums::{Enum16, Enum8},
+    enums::{ClickhouseEnum, Enum16, Enum8},
+    events::{CloseReason, PoolEvents, QueryCounts, QueryLog},
+    from_row::FromRow,
     from_sql::FromSql,
-    options::Options,
+    log::{LogEntry, LogLevel},
+    options::{LoadBalancing, Options},
+    overflow::{OverflowMode, RoundingMode},
+    packet_reader::PacketReader,
+    params::{escape_identifier, escape_literal, ToSqlLiteral},
     query::Query,
     query_result::QueryResult,
     value::Value,
 };
 
-#[cfg(feature = "ssl")]
-pub use self::options::Certificate;
-
 pub(crate) use self::{
+    block::RecycledBlock,
     cmd::Cmd,
     date_converter::DateConverter,
     marshal::Marshal,
@@ -34,13 +43,14 @@ pub(crate) use self::{
 };
 
 #[cfg(feature = "tls")]
-pub use self::options::Certificate;
+pub use self::options::{Certificate, Identity};
 
 pub(crate) mod column;
 mod marshal;
 mod stat_buffer;
 mod unmarshal;
 
+mod from_row;
 mod from_sql;
 mod value;
 mod value_ref;
@@ -49,39 +59,183 @@ mod block;
 mod cmd;
 
 mod date_converter;
+mod ddl;
+#[cfg(feature = "serde")]
+mod de;
+mod log;
+mod overflow;
+mod params;
 mod query;
 mod query_result;
 
 mod decimal;
 mod enums;
+mod events;
 mod options;
+mod packet_reader;
 
 pub(crate) mod either;
 
+/// Query execution progress reported by the server.
+///
+/// Values are increments since the previous `Progress` of the same query.
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
-pub(crate) struct Progress {
+pub struct Progress {
+    /// Rows read.
     pub rows: u64,
+    /// Bytes read (uncompressed).
     pub bytes: u64,
+    /// Approximate total number of rows to read.
     pub total_rows: u64,
+    /// Rows written (not reported by servers older than revision 54420).
+    pub written_rows: u64,
+    /// Bytes written (not reported by servers older than revision 54420).
+    pub written_bytes: u64,
+}
+
+impl ops::AddAssign for Progress {
+    fn add_assign(&mut self, other: Self) {
+        self.rows += other.rows;
+        self.bytes += other.bytes;
+        self.total_rows += other.total_rows;
+        self.written_rows += other.written_rows;
+        self.written_bytes += other.written_bytes;
+    }
 }
 
+/// Query execution statistics sent by the server after the result.
 #[derive(Copy, Clone, Default, Debug, PartialEq)]
-pub(crate) struct ProfileInfo {
+pub struct ProfileInfo {
+    /// Rows in the result.
     pub rows: u64,
+    /// Bytes in the result (uncompressed).
     pub bytes: u64,
+    /// Blocks in the result.
     pub blocks: u64,
+    /// Whether a `LIMIT` was applied.
     pub applied_limit: bool,
+    /// Rows the result would have without `LIMIT`, useful for pagination.
     pub rows_before_limit: u64,
+    /// Whether `rows_before_limit` was calculated.
     pub calculated_rows_before_limit: bool,
 }
 
+/// Statistics collected while a query or an insert was running.
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct QueryStats {
+    /// Profile info of the result, if the server sent one.
+    pub profile_info: Option<ProfileInfo>,
+    /// Sum of all `Progress` packets.
+    pub progress: Progress,
+}
+
+impl QueryStats {
+    pub(crate) fn update<S>(&mut self, packet: &Packet<S>) {
+        match packet {
+            Packet::Progress(progress) => self.progress += *progress,
+            Packet::ProfileInfo(info) => self.profile_info = Some(*info),
+            _ => {}
+        }
+    }
+}
+
+/// Rows and bytes written by an insert, see `ClientHandle::insert_with_result`.
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct InsertResult {
+    /// Rows written as the server reported them, or the rows sent if the
+    /// server doesn't report them.
+    pub rows_written: u64,
+    /// Bytes written as the server reported them, or the size of the data
+    /// sent if the server doesn't report them.
+    pub bytes_written: u64,
+}
+
+impl InsertResult {
+    pub(crate) fn new(sent: &Block, stats: &QueryStats) -> Self {
+        let progress = stats.progress;
+        if progress.written_rows > 0 {
+            return Self {
+                rows_written: progress.written_rows,
+                bytes_written: progress.written_bytes,
+            };
+        }
+
+        Self {
+            rows_written: sent.row_count() as u64,
+            bytes_written: sent.data_size() as u64,
+        }
+    }
+}
+
+/// Rows and bytes read and written by a statement, see
+/// `ClientHandle::execute_with_stats`.
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct ExecuteResult {
+    /// Rows written, as the server reported them. Servers older than
+    /// protocol revision 54420 don't report written rows.
+    pub written_rows: u64,
+    /// Bytes written, as the server reported them.
+    pub written_bytes: u64,
+    pub read_rows: u64,
+    /// Bytes read (uncompressed).
+    pub read_bytes: u64,
+    /// Time from sending the statement to the end of its response.
+    pub elapsed: Duration,
+}
+
+impl ExecuteResult {
+    pub(crate) fn new(stats: &QueryStats, elapsed: Duration) -> Self {
+        let progress = stats.progress;
+        Self {
+            written_rows: progress.written_rows,
+            written_bytes: progress.written_bytes,
+            read_rows: progress.rows,
+            read_bytes: progress.bytes,
+            elapsed,
+        }
+    }
+}
+
+/// What the server told about itself in the handshake.
 #[derive(Clone, PartialEq)]
-pub(crate) struct ServerInfo {
+pub struct ServerInfo {
+    /// Name of the server software, e.g. `ClickHouse`.
     pub name: String,
+    /// Name of the server to show to users. Only sent from protocol
+    /// revision 54372 on, empty for older servers.
+    pub display_name: String,
+    /// Protocol revision of the server.
     pub revision: u64,
     pub minor_version: u64,
     pub major_version: u64,
+    /// Patch version. Only sent from protocol revision 54401 on, `None` for
+    /// older servers.
+    pub patch_version: Option<u64>,
+    /// Default timezone of the server.
     pub timezone: Tz,
+    /// Settings the server sent in the handshake, empty if the protocol
+    /// revision is older than 54474 and the server doesn't send them.
+    pub settings: HashMap<String, String>,
+}
+
+impl ServerInfo {
+    /// The `(major, minor, patch)` version, e.g. to check that the server
+    /// is newer than some release: `info.version() >= (20, 1, 0)`. The patch
+    /// is 0 if the server didn't send it.
+    pub fn version(&self) -> (u64, u64, u64) {
+        (
+            self.major_version,
+            self.minor_version,
+            self.patch_version.unwrap_or(0),
+        )
+    }
+
+    /// The protocol revision both sides speak, the older of the server
+    /// revision and the revision of this client. It decides which fields the
+    /// packets carry, e.g. server logs need revision 54406 or newer.
+    pub fn protocol_revision(&self) -> u64 {
+        cmp::min(self.revision, client_info::CLICK_HOUSE_REVISION)
+    }
 }
 
 impl fmt::Debug for ServerInfo {
@@ -89,7 +243,11 @@ impl fmt::Debug for ServerInfo {
         write!(
             f,
             "{} {}.{}.{} ({:?})",
-            self.name, self.major_version, self.minor_version, self.revision, self.timezone
+            self.name,
+            self.major_version,
+            self.minor_version,
+            self.patch_version.unwrap_or(0),
+            self.timezone
         )
     }
 }
@@ -99,16 +257,25 @@ pub(crate) struct Context {
     pub(crate) server_info: ServerInfo,
     pub(crate) hostname: String,
     pub(crate) options: OptionsSource,
+    /// Address of the server the connection is established to.
+    pub(crate) addr: Option<Url>,
+    /// Whether the handle is a session pinned to its connection.
+    pub(crate) session: bool,
+    /// Database selected with `ClientHandle::use_database`.
+    pub(crate) database: Option<String>,
 }
 
 impl Default for ServerInfo {
     fn default() -> Self {
         Self {
             name: String::new(),
+            display_name: String::new(),
             revision: 0,
             minor_version: 0,
             major_version: 0,
+            patch_version: None,
             timezone: Tz::Zulu,
+            settings: HashMap::new(),
         }
     }
 }
@@ -118,6 +285,9 @@ impl fmt::Debug for Context {
         f.debug_struct("Context")
             .field("options", &self.options)
             .field("hostname", &self.hostname)
+            .field("addr", &self.addr)
+            .field("session", &self.session)
+            .field("database", &self.database)
             .finish()
     }
 }
@@ -128,18 +298,39 @@ impl Default for Context {
             server_info: ServerInfo::default(),
             hostname: get().unwrap().into_string().unwrap(),
             options: OptionsSource::default(),
+            addr: None,
+            session: false,
+            database: None,
         }
     }
 }
 
+/// A packet sent by the server, see `PacketReader`.
+///
+/// `S` is the connection the client hands on with the packets that end a
+/// response, `()` for packets read with a `PacketReader`.
 #[derive(Clone)]
-pub(crate) enum Packet<S> {
+pub enum Packet<S = ()> {
+    /// Answer to the handshake.
     Hello(S, ServerInfo),
+    /// Answer to a ping.
     Pong(S),
+    /// Increment of the query execution progress.
     Progress(Progress),
+    /// Statistics of the result, sent after the last data block.
     ProfileInfo(ProfileInfo),
+    /// The query failed, no more packets follow.
     Exception(ServerError, Option<S>),
+    /// A block of the result.
     Block(Block),
+    /// A single row with the totals of a `WITH TOTALS` query.
+    Totals(Block),
+    /// Two rows with the minimums and maximums of every column, sent when
+    /// the `extremes` setting is on.
+    Extremes(Block),
+    /// Server log entries, sent when `send_logs_level` is set.
+    Log(Vec<LogEntry>),
+    /// The query is complete, no more packets follow.
     Eof(S),
 }
 
@@ -152,13 +343,16 @@ impl<S> fmt::Debug for Packet<S> {
             Packet::ProfileInfo(info) => write!(f, "ProfileInfo({:?})", info),
             Packet::Exception(e, _) => write!(f, "Exception({:?})", e),
             Packet::Block(b) => write!(f, "Block({:?})", b),
+            Packet::Totals(b) => write!(f, "Totals({:?})", b),
+            Packet::Extremes(b) => write!(f, "Extremes({:?})", b),
+            Packet::Log(entries) => write!(f, "Log({:?})", entries),
             Packet::Eof(_) => write!(f, "Eof"),
         }
     }
 }
 
 impl<S> Packet<S> {
-    pub fn bind<N>(self, transport: &mut Option<N>) -> Packet<N> {
+    pub(crate) fn bind<N>(self, transport: &mut Option<N>) -> Packet<N> {
         match self {
             Packet::Hello(_, server_info) => Packet::Hello(transport.take().unwrap(), server_info),
             Packet::Pong(_) => Packet::Pong(transport.take().unwrap()),
@@ -166,6 +360,9 @@ impl<S> Packet<S> {
             Packet::ProfileInfo(profile_info) => Packet::ProfileInfo(profile_info),
             Packet::Exception(exception, _) => Packet::Exception(exception, transport.take()),
             Packet::Block(block) => Packet::Block(block),
+            Packet::Totals(block) => Packet::Totals(block),
+            Packet::Extremes(block) => Packet::Extremes(block),
+            Packet::Log(entries) => Packet::Log(entries),
             Packet::Eof(_) => Packet::Eof(transport.take().unwrap()),
         }
     }
@@ -200,8 +397,16 @@ has_sql_type! {
     String: SqlType::String,
     f32: SqlType::Float32,
     f64: SqlType::Float64,
+    Vec<u8>: SqlType::String,
+    &[u8]: SqlType::String,
     Date<Tz>: SqlType::Date,
-    DateTime<Tz>: SqlType::DateTime(DateTimeType::DateTime32)
+    DateTime<Tz>: SqlType::DateTime(DateTimeType::DateTime32),
+    Decimal: SqlType::Decimal(18, 4),
+    Enum8: SqlType::Enum8(Vec::new()),
+    Enum16: SqlType::Enum16(Vec::new()),
+    std::net::Ipv4Addr: SqlType::Ipv4,
+    std::net::Ipv6Addr: SqlType::Ipv6,
+    uuid::Uuid: SqlType::Uuid
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
@@ -235,6 +440,14 @@ pub enum SqlType {
     Decimal(u8, u8),
     Enum8(Vec<(String, i8)>),
     Enum16(Vec<(String, i16)>),
+    /// Type of `NULL` literals, e.g. `Nullable(Nothing)` for `SELECT NULL`.
+    Nothing,
+    /// Experimental `Object('json')` type, values are read and written as
+    /// JSON text.
+    Json,
+    /// Elements with their names, if the tuple is named, e.g.
+    /// `Tuple(a UInt32, b String)`.
+    Tuple(Vec<(Option<String>, &'static SqlType)>),
 }
 
 lazy_static! {
@@ -256,6 +469,8 @@ impl From<SqlType> for &'static SqlType {
             SqlType::Float32 => &SqlType::Float32,
             SqlType::Float64 => &SqlType::Float64,
             SqlType::Date => &SqlType::Date,
+            SqlType::Nothing => &SqlType::Nothing,
+            SqlType::Json => &SqlType::Json,
             _ => {
                 let mut guard = TYPES_CACHE.lock().unwrap();
                 loop {
@@ -292,11 +507,15 @@ impl SqlType {
             SqlType::Float32 => "Float32".into(),
             SqlType::Float64 => "Float64".into(),
             SqlType::Date => "Date".into(),
-            SqlType::DateTime(DateTimeType::DateTime64(precision, tz)) => format!("DateTime64({}, '{:?}')", precision, tz).into(),
+            SqlType::DateTime(DateTimeType::DateTime64(precision, tz)) => {
+                format!("DateTime64({}, {})", precision, quote_name(tz.name())).into()
+            }
             SqlType::DateTime(_) => "DateTime".into(),
             SqlType::Ipv4 => "IPv4".into(),
             SqlType::Ipv6 => "IPv6".into(),
             SqlType::Uuid => "UUID".into(),
+            SqlType::Nothing => "Nothing".into(),
+            SqlType::Json => "Object('json')".into(),
             SqlType::Nullable(nested) => format!("Nullable({})", &nested).into(),
             SqlType::Array(nested) => format!("Array({})", &nested).into(),
             SqlType::Decimal(precision, scale) => {
@@ -305,17 +524,37 @@ impl SqlType {
             SqlType::Enum8(values) => {
                 let a: Vec<String> = values
                     .iter()
-                    .map(|(name, value)| format!("'{}' = {}", name, value))
+                    .map(|(name, value)| format!("{} = {}", quote_name(name), value))
                     .collect();
-                format!("Enum8({})", a.join(",")).into()
+                format!("Enum8({})", a.join(", ")).into()
             }
             SqlType::Enum16(values) => {
                 let a: Vec<String> = values
                     .iter()
-                    .map(|(name, value)| format!("'{}' = {}", name, value))
+                    .map(|(name, value)| format!("{} = {}", quote_name(name), value))
                     .collect();
-                format!("Enum16({})", a.join(",")).into()
+                format!("Enum16({})", a.join(", ")).into()
             }
+            SqlType::Tuple(elements) => {
+                let a: Vec<String> = elements
+                    .iter()
+                    .map(|(name, element)| match name {
+                        None => SqlType::to_string(element).into_owned(),
+                        Some(name) => format!("{} {}", quote_identifier(name), element),
+                    })
+                    .collect();
+                format!("Tuple({})", a.join(", ")).into()
+            }
+        }
+    }
+
+    /// Returns the position of the element called `name` of a named tuple.
+    pub(crate) fn tuple_position(&self, name: &str) -> Option<usize> {
+        match self {
+            SqlType::Tuple(elements) => elements
+                .iter()
+                .position(|(element, _)| element.as_deref() == Some(name)),
+            _ => None,
         }
     }
 
@@ -328,12 +567,127 @@ impl SqlType {
     }
 }
 
+/// Back-quotes a tuple element name unless it is a plain identifier.
+fn quote_identifier(name: &str) -> Cow<'_, str> {
+    let plain = name.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_')
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+    if plain {
+        return name.into();
+    }
+
+    let mut result = String::with_capacity(name.len() + 2);
+    result.push('`');
+    for ch in name.chars() {
+        if ch == '`' || ch == '\\' {
+            result.push('\\');
+        }
+        result.push(ch);
+    }
+    result.push('`');
+    result.into()
+}
+
+/// Quotes an enum item or a timezone name the way the server writes it in
+/// type names.
+fn quote_name(name: &str) -> String {
+    let mut result = String::with_capacity(name.len() + 2);
+    result.push('\'');
+    for ch in name.chars() {
+        match ch {
+            '\'' | '\\' => {
+                result.push('\\');
+                result.push(ch);
+            }
+            '\x07' => result.push_str("\\a"),
+            '\x08' => result.push_str("\\b"),
+            '\x0c' => result.push_str("\\f"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '\x0b' => result.push_str("\\v"),
+            '\0' => result.push_str("\\0"),
+            _ => result.push(ch),
+        }
+    }
+    result.push('\'');
+    result
+}
+
 impl fmt::Display for SqlType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", Self::to_string(self))
     }
 }
 
+#[test]
+fn test_protocol_revision() {
+    let info = ServerInfo {
+        revision: 54460,
+        ..ServerInfo::default()
+    };
+    assert_eq!(info.protocol_revision(), client_info::CLICK_HOUSE_REVISION);
+
+    let info = ServerInfo {
+        revision: 54060,
+        ..ServerInfo::default()
+    };
+    assert_eq!(info.protocol_revision(), 54060);
+}
+
+#[test]
+fn test_insert_result() {
+    let sent = Block::new().column("id", vec![1_u32, 2, 3]);
+    let mut stats = QueryStats::default();
+    assert_eq!(
+        InsertResult::new(&sent, &stats),
+        InsertResult {
+            rows_written: 3,
+            bytes_written: 12
+        }
+    );
+
+    stats.progress.written_rows = 2;
+    stats.progress.written_bytes = 16;
+    assert_eq!(
+        InsertResult::new(&sent, &stats),
+        InsertResult {
+            rows_written: 2,
+            bytes_written: 16
+        }
+    );
+}
+
+#[test]
+fn test_execute_result() {
+    let mut stats = QueryStats::default();
+    stats.progress += Progress {
+        rows: 100,
+        bytes: 800,
+        total_rows: 100,
+        written_rows: 0,
+        written_bytes: 0,
+    };
+    stats.progress += Progress {
+        written_rows: 100,
+        written_bytes: 400,
+        ..Progress::default()
+    };
+
+    let elapsed = Duration::from_millis(5);
+    assert_eq!(
+        ExecuteResult::new(&stats, elapsed),
+        ExecuteResult {
+            written_rows: 100,
+            written_bytes: 400,
+            read_rows: 100,
+            read_bytes: 800,
+            elapsed,
+        }
+    );
+}
+
 #[test]
 fn test_display() {
     let expected = "UInt8".to_string();
@@ -347,3 +701,41 @@ fn test_to_string() {
     let actual = SqlType::Nullable(&SqlType::UInt8).to_string();
     assert_eq!(expected, actual)
 }
+
+#[test]
+fn test_display_enum() {
+    let sql_type = SqlType::Enum8(vec![
+        ("a".to_string(), 1),
+        ("it's".to_string(), 2),
+        ("back\\slash".to_string(), -3),
+    ]);
+    assert_eq!(
+        sql_type.to_string(),
+        "Enum8('a' = 1, 'it\\'s' = 2, 'back\\\\slash' = -3)"
+    );
+}
+
+#[test]
+fn test_query_stats_update() {
+    let mut stats = QueryStats::default();
+    let progress = Progress {
+        rows: 10,
+        bytes: 80,
+        ..Progress::default()
+    };
+    let info = ProfileInfo {
+        rows: 10,
+        applied_limit: true,
+        rows_before_limit: 100,
+        ..ProfileInfo::default()
+    };
+
+    stats.update::<()>(&Packet::Progress(progress));
+    stats.update::<()>(&Packet::Progress(progress));
+    stats.update::<()>(&Packet::ProfileInfo(info));
+    stats.update::<()>(&Packet::Pong(()));
+
+    assert_eq!(stats.progress.rows, 20);
+    assert_eq!(stats.progress.bytes, 160);
+    assert_eq!(stats.profile_info, Some(info));
+}
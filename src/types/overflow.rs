@@ -0,0 +1,417 @@
+use std::{fmt, sync::Arc};
+
+use chrono::prelude::*;
+
+use crate::{
+    errors::Error,
+    types::{
+        column::Either, decimal::NoBits, DateTimeType, Decimal, Enum16, Enum8, SqlType, Value,
+    },
+};
+
+/// Number of days from 0001-01-01 to 1970-01-01.
+const UNIX_EPOCH_DAY: i64 = 719_163;
+
+/// How values that don't fit the type of the column they are written to are
+/// handled, set with `Block::with_overflow_mode`.
+///
+/// This applies to narrowing conversions only: `Decimal` values with more
+/// integer digits than the column allows, dates before 1970-01-01 or after
+/// 2149-06-06 for `Date` and times outside of the `u32` seconds of
+/// `DateTime`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum OverflowMode {
+    /// Fail with `Error::ValueOutOfRange`.
+    #[default]
+    Checked,
+    /// Clamp the value to the nearest one the column can hold.
+    Saturating,
+    /// Keep the low bits of the value, as casting with `as` does.
+    Wrapping,
+}
+
+/// How the sub-second digits of times that don't fit the precision of the
+/// `DateTime64` column they are written to are handled, set with
+/// `Block::with_rounding_mode`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum RoundingMode {
+    /// Drop the digits.
+    #[default]
+    Truncate,
+    /// Round to the nearest tick, halves up.
+    HalfUp,
+    /// Fail with `Error::ValueOutOfRange` unless the digits are zeros.
+    Checked,
+}
+
+/// Converts nanoseconds since the epoch to ticks of a `DateTime64` of
+/// `precision`, `None` if digits would be lost in `RoundingMode::Checked`
+/// mode.
+pub(crate) fn round_ticks(nanos: i64, precision: u32, mode: RoundingMode) -> Option<i64> {
+    let scale = 10_i64.pow(9 - precision);
+    match mode {
+        RoundingMode::Truncate => Some(nanos / scale),
+        RoundingMode::HalfUp => Some((nanos + scale / 2).div_euclid(scale)),
+        RoundingMode::Checked if nanos % scale == 0 => Some(nanos / scale),
+        RoundingMode::Checked => None,
+    }
+}
+
+/// Returns the precision of `dst_type` if it is a `DateTime64` that holds
+/// fewer sub-second digits than `src_type`.
+pub(crate) fn rounded_precision(src_type: &SqlType, dst_type: &SqlType) -> Option<u32> {
+    match (src_type, dst_type) {
+        (SqlType::Nullable(src), SqlType::Nullable(dst)) => rounded_precision(src, dst),
+        (
+            SqlType::DateTime(DateTimeType::Chrono),
+            SqlType::DateTime(DateTimeType::DateTime64(precision, _)),
+        ) if *precision < 9 => Some(*precision),
+        (
+            SqlType::DateTime(DateTimeType::DateTime64(src_precision, _)),
+            SqlType::DateTime(DateTimeType::DateTime64(precision, _)),
+        ) if precision < src_precision => Some(*precision),
+        _ => None,
+    }
+}
+
+/// Returns the days since 1970-01-01 of `date`.
+pub(crate) fn date_days(date: NaiveDate) -> i64 {
+    i64::from(date.num_days_from_ce()) - UNIX_EPOCH_DAY
+}
+
+/// Fits the days since 1970-01-01 of a `Date`, `None` if it is out of range
+/// in `OverflowMode::Checked` mode.
+pub(crate) fn fit_days(days: i64, mode: OverflowMode) -> Option<u16> {
+    fit(days, i64::from(u16::MAX), mode).map(|days| days as u16)
+}
+
+/// Fits the seconds since the epoch of a `DateTime`, `None` if it is out of
+/// range in `OverflowMode::Checked` mode.
+pub(crate) fn fit_seconds(seconds: i64, mode: OverflowMode) -> Option<u32> {
+    fit(seconds, i64::from(u32::MAX), mode).map(|seconds| seconds as u32)
+}
+
+fn fit(value: i64, max: i64, mode: OverflowMode) -> Option<i64> {
+    match mode {
+        _ if (0..=max).contains(&value) => Some(value),
+        OverflowMode::Checked => None,
+        OverflowMode::Saturating => Some(value.clamp(0, max)),
+        OverflowMode::Wrapping => Some(value),
+    }
+}
+
+/// Converts `value` to the exact type `sql_type` if it is a narrowing
+/// conversion, `None` if it is out of range in `OverflowMode::Checked` mode.
+/// Other values are returned as they are.
+pub(crate) fn fit_value(value: Value, sql_type: &SqlType, mode: OverflowMode) -> Option<Value> {
+    match (sql_type, value) {
+        (SqlType::Nullable(inner), Value::Nullable(Either::Right(value))) => {
+            let value = fit_value(*value, inner, mode)?;
+            Some(Value::Nullable(Either::Right(Box::new(value))))
+        }
+        (SqlType::Nullable(_), value @ Value::Nullable(Either::Left(_))) => Some(value),
+        (SqlType::Nullable(inner), value) => fit_value(value, inner, mode),
+        (SqlType::Array(inner), Value::Array(element_type, values)) => {
+            let values = values
+                .iter()
+                .map(|value| fit_value(value.clone(), inner, mode))
+                .collect::<Option<_>>()?;
+            Some(Value::Array(element_type, Arc::new(values)))
+        }
+        (SqlType::Tuple(elements), Value::Tuple(tuple_type, values)) => {
+            let values = elements
+                .iter()
+                .zip(values.iter())
+                .map(|((_, element), value)| fit_value(value.clone(), element, mode))
+                .collect::<Option<_>>()?;
+            Some(Value::Tuple(tuple_type, Arc::new(values)))
+        }
+        (SqlType::Decimal(precision, scale), Value::Decimal(v)) => {
+            fit_decimal(v, *precision, *scale, mode).map(Value::Decimal)
+        }
+        (SqlType::Enum8(values), Value::String(label)) => {
+            let value = enum_by_label(values, &label)?;
+            Some(Value::Enum8(values.clone(), Enum8::of(value)))
+        }
+        (SqlType::Enum16(values), Value::String(label)) => {
+            let value = enum_by_label(values, &label)?;
+            Some(Value::Enum16(values.clone(), Enum16::of(value)))
+        }
+        (
+            SqlType::DateTime(DateTimeType::DateTime32),
+            Value::DateTime64(ticks, (precision, tz)),
+        ) => {
+            let seconds = ticks.div_euclid(10_i64.pow(precision));
+            fit_seconds(seconds, mode).map(|seconds| Value::DateTime(seconds, tz))
+        }
+        (_, value) => Some(value),
+    }
+}
+
+fn enum_by_label<T: Copy>(values: &[(String, T)], label: &[u8]) -> Option<T> {
+    values
+        .iter()
+        .find(|(name, _)| name.as_bytes() == label)
+        .map(|(_, value)| *value)
+}
+
+/// Returns whether `src_type` holds enum labels to convert to the values of
+/// `dst_type`, which has to be done in every `OverflowMode`.
+pub(crate) fn is_enum_by_label(src_type: &SqlType, dst_type: &SqlType) -> bool {
+    match (src_type, dst_type) {
+        (SqlType::Nullable(src), SqlType::Nullable(dst)) => is_enum_by_label(src, dst),
+        (SqlType::String, SqlType::Enum8(_)) | (SqlType::String, SqlType::Enum16(_)) => true,
+        _ => false,
+    }
+}
+
+fn fit_decimal(value: Decimal, precision: u8, scale: u8, mode: OverflowMode) -> Option<Decimal> {
+    let underlying = i128::from(value.underlying);
+    let underlying = if scale >= value.scale {
+        underlying * 10_i128.pow(u32::from(scale - value.scale))
+    } else {
+        underlying / 10_i128.pow(u32::from(value.scale - scale))
+    };
+
+    // The integer must have at most `precision` digits and fit the `Int32`
+    // or `Int64` the column stores it as.
+    let nobits = NoBits::from_precision(precision).unwrap_or(NoBits::N64);
+    let width = match nobits {
+        NoBits::N32 => i128::from(i32::MAX),
+        NoBits::N64 => i128::from(i64::MAX),
+    };
+    let max = (10_i128.pow(u32::from(precision)) - 1).min(width);
+    let underlying = match mode {
+        _ if (-max..=max).contains(&underlying) => underlying as i64,
+        OverflowMode::Checked => return None,
+        OverflowMode::Saturating => underlying.clamp(-max, max) as i64,
+        OverflowMode::Wrapping => match nobits {
+            NoBits::N32 => i64::from(underlying as i32),
+            NoBits::N64 => underlying as i64,
+        },
+    };
+
+    Some(Decimal {
+        underlying,
+        precision,
+        scale,
+        nobits,
+    })
+}
+
+/// Returns whether values of `src_type` converted to `dst_type` may not fit.
+pub(crate) fn is_narrowing(src_type: &SqlType, dst_type: &SqlType) -> bool {
+    match (src_type, dst_type) {
+        (SqlType::Nullable(src), SqlType::Nullable(dst)) => is_narrowing(src, dst),
+        (
+            SqlType::Decimal(src_precision, src_scale),
+            SqlType::Decimal(dst_precision, dst_scale),
+        ) => {
+            i16::from(*dst_precision) - i16::from(*dst_scale)
+                < i16::from(*src_precision) - i16::from(*src_scale)
+        }
+        (SqlType::DateTime(DateTimeType::Chrono), SqlType::DateTime(DateTimeType::DateTime32)) => {
+            true
+        }
+        _ => is_enum_by_label(src_type, dst_type),
+    }
+}
+
+/// Builds the error for `value` in row `row_index`, the column name is set
+/// with `in_column` when it is known.
+pub(crate) fn out_of_range(
+    row_index: usize,
+    value: impl fmt::Display,
+    target_type: &SqlType,
+) -> Error {
+    Error::ValueOutOfRange {
+        column: String::new(),
+        row_index,
+        value: value.to_string(),
+        target_type: target_type.to_string().into_owned(),
+    }
+}
+
+/// Sets the column of an `Error::ValueOutOfRange`.
+pub(crate) fn in_column(err: Error, name: &str) -> Error {
+    match err {
+        Error::ValueOutOfRange {
+            row_index,
+            value,
+            target_type,
+            ..
+        } => Error::ValueOutOfRange {
+            column: name.to_string(),
+            row_index,
+            value,
+            target_type,
+        },
+        err => err,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono_tz::Tz;
+
+    use super::*;
+
+    #[test]
+    fn test_fit_days() {
+        let date = |y, m, d| Tz::UTC.ymd(y, m, d);
+        assert_eq!(date_days(date(1970, 1, 2).naive_local()), 1);
+        assert_eq!(date_days(date(1969, 12, 31).naive_local()), -1);
+
+        let last = date_days(date(2149, 6, 6).naive_local());
+        assert_eq!(fit_days(last, OverflowMode::Checked), Some(u16::MAX));
+        assert_eq!(fit_days(last + 1, OverflowMode::Checked), None);
+        assert_eq!(fit_days(last + 1, OverflowMode::Saturating), Some(u16::MAX));
+        assert_eq!(fit_days(last + 1, OverflowMode::Wrapping), Some(0));
+        assert_eq!(fit_days(-1, OverflowMode::Saturating), Some(0));
+        assert_eq!(fit_days(-1, OverflowMode::Wrapping), Some(u16::MAX));
+    }
+
+    #[test]
+    fn test_fit_decimal() {
+        let sql_type = SqlType::Decimal(5, 2);
+        let fit = |v: f64, mode| fit_value(Value::Decimal(Decimal::of(v, 3)), &sql_type, mode);
+
+        assert_eq!(
+            fit(123.456, OverflowMode::Checked),
+            Some(Value::Decimal(Decimal::of(123.45, 2)))
+        );
+        assert_eq!(fit(1234.5, OverflowMode::Checked), None);
+        assert_eq!(
+            fit(-1234.5, OverflowMode::Saturating),
+            Some(Value::Decimal(Decimal::of(-999.99, 2)))
+        );
+        assert_eq!(
+            fit(1234.5, OverflowMode::Wrapping),
+            Some(Value::Decimal(Decimal::of(1234.5, 2)))
+        );
+
+        let decimal32 = SqlType::Decimal(9, 4);
+        let mantissa = |underlying| Decimal {
+            underlying,
+            precision: 18,
+            scale: 4,
+            nobits: NoBits::N64,
+        };
+        let fit32 = |underlying, mode| match fit_value(
+            Value::Decimal(mantissa(underlying)),
+            &decimal32,
+            mode,
+        ) {
+            Some(Value::Decimal(d)) => Some((d.underlying, d.nobits)),
+            _ => None,
+        };
+        assert_eq!(
+            fit32(999_999_999, OverflowMode::Checked),
+            Some((999_999_999, NoBits::N32))
+        );
+        assert_eq!(
+            fit32(-999_999_999, OverflowMode::Checked),
+            Some((-999_999_999, NoBits::N32))
+        );
+        assert_eq!(fit32(1_000_000_000, OverflowMode::Checked), None);
+        assert_eq!(
+            fit32(5_000_000_000, OverflowMode::Saturating),
+            Some((999_999_999, NoBits::N32))
+        );
+        assert_eq!(
+            fit32(5_000_000_000, OverflowMode::Wrapping),
+            Some((i64::from(5_000_000_000_i64 as i32), NoBits::N32))
+        );
+
+        let nullable = SqlType::Nullable(SqlType::Decimal(3, 0).into());
+        let value = Value::Nullable(Either::Right(Box::new(Value::Decimal(Decimal::of(
+            1000, 0,
+        )))));
+        assert_eq!(fit_value(value, &nullable, OverflowMode::Checked), None);
+    }
+
+    #[test]
+    fn test_fit_datetime() {
+        let sql_type = SqlType::DateTime(DateTimeType::DateTime32);
+        let late = Value::DateTime64(5_000_000_000_000, (3, Tz::UTC));
+        assert_eq!(
+            fit_value(late.clone(), &sql_type, OverflowMode::Checked),
+            None
+        );
+        assert_eq!(
+            fit_value(late, &sql_type, OverflowMode::Saturating),
+            Some(Value::DateTime(u32::MAX, Tz::UTC))
+        );
+
+        let early = Value::DateTime64(-1, (3, Tz::UTC));
+        assert_eq!(
+            fit_value(early, &sql_type, OverflowMode::Saturating),
+            Some(Value::DateTime(0, Tz::UTC))
+        );
+    }
+
+    #[test]
+    fn test_round_ticks() {
+        let nanos = 1_546_300_800_123_456_789;
+        assert_eq!(
+            round_ticks(nanos, 3, RoundingMode::Truncate),
+            Some(1_546_300_800_123)
+        );
+        assert_eq!(
+            round_ticks(nanos, 3, RoundingMode::HalfUp),
+            Some(1_546_300_800_123)
+        );
+        assert_eq!(
+            round_ticks(nanos, 6, RoundingMode::HalfUp),
+            Some(1_546_300_800_123_457)
+        );
+        assert_eq!(round_ticks(nanos, 3, RoundingMode::Checked), None);
+        assert_eq!(round_ticks(nanos, 9, RoundingMode::Checked), Some(nanos));
+        assert_eq!(round_ticks(1_500_000, 3, RoundingMode::HalfUp), Some(2));
+        assert_eq!(round_ticks(-1_500_000, 3, RoundingMode::HalfUp), Some(-1));
+        assert_eq!(round_ticks(2_000_000, 3, RoundingMode::Checked), Some(2));
+    }
+
+    #[test]
+    fn test_rounded_precision() {
+        let datetime64 =
+            |precision| SqlType::DateTime(DateTimeType::DateTime64(precision, Tz::UTC));
+        assert_eq!(
+            rounded_precision(&SqlType::DateTime(DateTimeType::Chrono), &datetime64(3)),
+            Some(3)
+        );
+        assert_eq!(
+            rounded_precision(&SqlType::DateTime(DateTimeType::Chrono), &datetime64(9)),
+            None
+        );
+        assert_eq!(rounded_precision(&datetime64(6), &datetime64(3)), Some(3));
+        assert_eq!(rounded_precision(&datetime64(3), &datetime64(6)), None);
+        assert_eq!(
+            rounded_precision(
+                &SqlType::Nullable(SqlType::DateTime(DateTimeType::Chrono).into()),
+                &SqlType::Nullable(datetime64(0).into()),
+            ),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_is_narrowing() {
+        assert!(is_narrowing(
+            &SqlType::Decimal(18, 4),
+            &SqlType::Decimal(9, 2)
+        ));
+        assert!(!is_narrowing(
+            &SqlType::Decimal(9, 2),
+            &SqlType::Decimal(18, 4)
+        ));
+        assert!(!is_narrowing(
+            &SqlType::Decimal(18, 4),
+            &SqlType::Decimal(18, 2)
+        ));
+        assert!(is_narrowing(
+            &SqlType::Nullable(SqlType::DateTime(DateTimeType::Chrono).into()),
+            &SqlType::Nullable(SqlType::DateTime(DateTimeType::DateTime32).into()),
+        ));
+    }
+}
@@ -0,0 +1,304 @@
+use std::io::Read;
+
+use chrono_tz::Tz;
+
+use crate::{
+    binary::Parser,
+    client_info,
+    errors::Result,
+    types::{Packet, RecycledBlock},
+};
+
+/// Reads raw native protocol packets from a byte stream.
+///
+/// This is a low-level API for tools that drive queries themselves. The
+/// client decodes the packets of its connections with it as well, but
+/// leaves dispatching of the packets to the caller here.
+///
+/// ```rust
+/// # use std::io::Cursor;
+/// # use clickhouse_rs::types::{Packet, PacketReader};
+/// let source = [5_u8]; // end of stream
+/// let mut reader = PacketReader::new(Cursor::new(&source[..]));
+/// match reader.read_packet().unwrap() {
+///     Packet::Eof(()) => {}
+///     packet => panic!("unexpected packet {:?}", packet),
+/// }
+/// ```
+pub struct PacketReader<R> {
+    reader: R,
+    timezone: Option<Tz>,
+    tz_override: Option<Tz>,
+    compression: bool,
+    revision: u64,
+    recycled: Option<RecycledBlock>,
+    lazy_columns: bool,
+}
+
+impl<R: Read> PacketReader<R> {
+    /// Creates a reader for uncompressed packets of the client revision
+    /// with `UTC` as the server timezone.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            timezone: Some(Tz::UTC),
+            tz_override: None,
+            compression: false,
+            revision: client_info::CLICK_HOUSE_REVISION,
+            recycled: None,
+            lazy_columns: false,
+        }
+    }
+
+    /// Timezone of the server, used for `Date` and `DateTime` columns
+    /// without an explicit timezone.
+    pub fn timezone(self, timezone: Tz) -> Self {
+        Self {
+            timezone: Some(timezone),
+            ..self
+        }
+    }
+
+    /// Timezones of a connection: the server one, `None` until the
+    /// handshake is done, and the one the current query asked for.
+    pub(crate) fn with_timezones(self, timezone: Option<Tz>, tz_override: Option<Tz>) -> Self {
+        Self {
+            timezone,
+            tz_override,
+            ..self
+        }
+    }
+
+    /// Whether data blocks are compressed.
+    pub fn compression(self, compression: bool) -> Self {
+        Self {
+            compression,
+            ..self
+        }
+    }
+
+    /// Protocol revision negotiated with the server.
+    pub fn revision(self, revision: u64) -> Self {
+        Self { revision, ..self }
+    }
+
+    /// Whether data blocks are decoded into the storage of the previous data
+    /// block once the caller has dropped it, see
+    /// `QueryResult::with_block_reuse`.
+    pub fn reuse_blocks(self, reuse: bool) -> Self {
+        let recycled = if reuse {
+            Some(RecycledBlock::default())
+        } else {
+            None
+        };
+        Self { recycled, ..self }
+    }
+
+    /// Decodes data blocks into the storage of `recycled`, see
+    /// `reuse_blocks`.
+    pub(crate) fn with_recycled(self, recycled: Option<RecycledBlock>) -> Self {
+        Self { recycled, ..self }
+    }
+
+    /// Returns the storage for the next data block, if blocks are reused.
+    pub(crate) fn into_recycled(self) -> Option<RecycledBlock> {
+        self.recycled
+    }
+
+    /// Whether the values of data block columns are only decoded when they
+    /// are first accessed, see `QueryResult::with_lazy_columns`.
+    pub fn lazy_columns(self, lazy_columns: bool) -> Self {
+        Self {
+            lazy_columns,
+            ..self
+        }
+    }
+
+    /// Reads the next packet, blocking until it is complete.
+    pub fn read_packet(&mut self) -> Result<Packet> {
+        let mut parser = Parser::new(
+            &mut self.reader,
+            self.timezone,
+            self.tz_override,
+            self.compression,
+            self.revision,
+        )
+        .with_recycled(self.recycled.take())
+        .with_lazy_columns(self.lazy_columns);
+        let packet = parser.parse_packet();
+        self.recycled = parser.into_recycled();
+        packet
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Cursor, sync::Arc};
+
+    use crate::{
+        binary::{protocol, Encoder},
+        types::Block,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_read_packet_sequence() {
+        let block = Block::new().column("n", vec![1_u32, 2, 3]);
+        let totals = Block::new().column("n", vec![6_u32]);
+
+        let mut encoder = Encoder::new();
+        encoder.uvarint(protocol::SERVER_PROGRESS);
+        encoder.uvarint(3);
+        encoder.uvarint(12);
+        encoder.uvarint(3);
+        encoder.uvarint(0); // written rows
+        encoder.uvarint(0); // written bytes
+
+        encoder.uvarint(protocol::SERVER_DATA);
+        encoder.string("");
+        block.write(&mut encoder, false);
+
+        encoder.uvarint(protocol::SERVER_TOTALS);
+        encoder.string("");
+        totals.write(&mut encoder, false);
+
+        encoder.uvarint(protocol::SERVER_PROFILE_INFO);
+        encoder.uvarint(3);
+        encoder.uvarint(1);
+        encoder.uvarint(12);
+        encoder.write(0_u8);
+        encoder.uvarint(0);
+        encoder.write(0_u8);
+
+        encoder.uvarint(protocol::SERVER_EXCEPTION);
+        encoder.write(60_u32);
+        encoder.string("DB::Exception");
+        encoder.string("Table doesn't exist");
+        encoder.string("");
+        encoder.write(0_u8); // no nested exception
+
+        encoder.uvarint(protocol::SERVER_END_OF_STREAM);
+        let source = encoder.get_buffer();
+
+        let mut reader = PacketReader::new(Cursor::new(source));
+
+        match reader.read_packet().unwrap() {
+            Packet::Progress(progress) => assert_eq!(progress.rows, 3),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+        match reader.read_packet().unwrap() {
+            Packet::Block(data) => assert_eq!(data, block),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+        match reader.read_packet().unwrap() {
+            Packet::Totals(data) => assert_eq!(data, totals),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+        match reader.read_packet().unwrap() {
+            Packet::ProfileInfo(info) => {
+                assert_eq!(info.rows, 3);
+                assert_eq!(info.bytes, 12);
+            }
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+        match reader.read_packet().unwrap() {
+            Packet::Exception(exception, None) => {
+                assert_eq!(exception.code, 60);
+                assert_eq!(exception.message, "Table doesn't exist");
+            }
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+        match reader.read_packet().unwrap() {
+            Packet::Eof(()) => {}
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+        assert!(reader.read_packet().is_err());
+    }
+
+    fn data_packets(blocks: &[Block]) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        for block in blocks {
+            encoder.uvarint(protocol::SERVER_DATA);
+            encoder.string("");
+            block.write(&mut encoder, false);
+        }
+        encoder.get_buffer()
+    }
+
+    fn read_data<R: Read>(reader: &mut PacketReader<R>) -> Block {
+        match reader.read_packet().unwrap() {
+            Packet::Block(block) => block,
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+    }
+
+    fn storage(block: &Block, column: usize) -> *const u8 {
+        Arc::as_ptr(&block.columns()[column].data) as *const u8
+    }
+
+    #[test]
+    fn test_reuse_blocks() {
+        let blocks: Vec<Block> = (0..4_u32)
+            .map(|i| {
+                Block::new()
+                    .column("n", vec![i, i + 1])
+                    .column("s", vec![format!("a{}", i), format!("b{}", i)])
+                    .column("x", vec![Some(i), None])
+            })
+            .collect();
+        let mut reader = PacketReader::new(Cursor::new(data_packets(&blocks))).reuse_blocks(true);
+
+        // A block that is still held is never overwritten.
+        let first = read_data(&mut reader);
+        let second = read_data(&mut reader);
+        assert_eq!(first, blocks[0]);
+        assert_eq!(second, blocks[1]);
+        assert_ne!(storage(&first, 0), storage(&second, 0));
+
+        // Once dropped, its storage receives the next block.
+        let reused: Vec<_> = (0..3).map(|column| storage(&second, column)).collect();
+        drop(second);
+        let third = read_data(&mut reader);
+        assert_eq!(third, blocks[2]);
+        assert_eq!(
+            (0..3)
+                .map(|column| storage(&third, column))
+                .collect::<Vec<_>>(),
+            reused
+        );
+        assert_eq!(first, blocks[0]);
+
+        drop(third);
+        assert_eq!(read_data(&mut reader), blocks[3]);
+    }
+
+    #[test]
+    fn test_lazy_columns() {
+        let blocks: Vec<Block> = (0..2_u32)
+            .map(|i| {
+                Block::new()
+                    .column("n", vec![i, i + 1])
+                    .column("s", vec![format!("a{}", i), format!("b{}", i)])
+                    .column("a", vec![vec![i], vec![i, i + 1]])
+            })
+            .collect();
+        let mut reader = PacketReader::new(Cursor::new(data_packets(&blocks))).lazy_columns(true);
+
+        let first = read_data(&mut reader);
+        assert_eq!(first.get::<&str, _>(1, "s").unwrap(), "b0");
+        assert_eq!(first, blocks[0]);
+        assert_eq!(read_data(&mut reader), blocks[1]);
+    }
+
+    #[test]
+    fn test_read_unknown_packet() {
+        let mut reader = PacketReader::new(Cursor::new(vec![42_u8]));
+        assert!(reader.read_packet().is_err());
+    }
+}
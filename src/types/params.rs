@@ -0,0 +1,388 @@
+use std::{
+    cmp,
+    net::{Ipv4Addr, Ipv6Addr},
+    str,
+};
+
+use chrono::prelude::*;
+use uuid::Uuid;
+
+use crate::{
+    errors::{DriverError, Error, Result},
+    types::{column::Either, decimal::NoBits, Value},
+};
+
+/// Replaces every `?` placeholder of `sql` by the literal of the
+/// corresponding value. Placeholders inside string literals, quoted
+/// identifiers and comments are left untouched.
+pub(crate) fn bind_params(sql: &str, params: &[Value]) -> Result<String> {
+    let placeholders = find_placeholders(sql);
+    if placeholders.len() != params.len() {
+        return Err(Error::Driver(DriverError::ParamsCount {
+            placeholders: placeholders.len(),
+            params: params.len(),
+        }));
+    }
+
+    for (index, param) in params.iter().enumerate() {
+        check_precision(index, param)?;
+    }
+
+    let mut result = String::with_capacity(sql.len());
+    let mut last = 0;
+    for (position, param) in placeholders.into_iter().zip(params) {
+        result.push_str(&sql[last..position]);
+        result.push_str(&escape_literal(param));
+        last = position + 1;
+    }
+    result.push_str(&sql[last..]);
+
+    Ok(result)
+}
+
+/// Fails for `DateTime64` values with more than the 9 digits of sub-second
+/// precision ClickHouse supports, `index` is the position of the parameter.
+fn check_precision(index: usize, value: &Value) -> Result<()> {
+    match value {
+        Value::DateTime64(_, (precision, _)) if *precision > 9 => {
+            Err(Error::Driver(DriverError::ParamPrecision {
+                index,
+                precision: *precision,
+            }))
+        }
+        Value::Nullable(Either::Right(inner)) => check_precision(index, inner),
+        Value::Array(_, values) | Value::Tuple(_, values) => values
+            .iter()
+            .try_for_each(|value| check_precision(index, value)),
+        _ => Ok(()),
+    }
+}
+
+fn find_placeholders(sql: &str) -> Vec<usize> {
+    let bytes = sql.as_bytes();
+    let mut placeholders = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ b'\'' | quote @ b'"' | quote @ b'`' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            b'-' | b'#' if bytes[i] == b'#' || bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !bytes[i..].starts_with(b"*/") {
+                    i += 1;
+                }
+                i += 1;
+            }
+            b'?' => placeholders.push(i),
+            _ => {}
+        }
+        i += 1;
+    }
+
+    placeholders
+}
+
+/// Values that can be written as a ClickHouse literal, see `escape_literal`.
+pub trait ToSqlLiteral {
+    fn to_sql_literal(&self) -> String;
+}
+
+impl<T> ToSqlLiteral for T
+where
+    T: Clone + Into<Value>,
+{
+    fn to_sql_literal(&self) -> String {
+        escape_literal(&self.clone().into())
+    }
+}
+
+/// Back-quotes `name` for use as a table or column name in a query, with
+/// back quotes, backslashes and control characters escaped.
+///
+/// ```rust
+/// # use clickhouse_rs::types::escape_identifier;
+/// assert_eq!(escape_identifier("weird`name"), "`weird\\`name`");
+/// ```
+pub fn escape_identifier(name: &str) -> String {
+    let mut result = String::with_capacity(name.len() + 2);
+    result.push('`');
+    for ch in name.chars() {
+        match ch {
+            '`' => result.push_str("\\`"),
+            ch => escape_char(&mut result, ch),
+        }
+    }
+    result.push('`');
+    result
+}
+
+/// Formats `value` as a ClickHouse literal, the way values bound with
+/// `Query::bind` are sent.
+///
+/// Strings are quoted with quotes, backslashes and control characters
+/// escaped, bytes that aren't valid UTF-8 as `\xNN`. Dates and times are
+/// wrapped in a conversion naming their timezone, arrays and tuples are
+/// written element by element and a `NULL` of any type as `NULL`.
+///
+/// ```rust
+/// # use clickhouse_rs::types::{escape_literal, Value};
+/// assert_eq!(escape_literal(&Value::from("it's")), "'it\\'s'");
+/// assert_eq!(escape_literal(&Value::from(None::<u8>)), "NULL");
+/// ```
+pub fn escape_literal(value: &Value) -> String {
+    match value {
+        Value::UInt8(v) => v.to_string(),
+        Value::UInt16(v) => v.to_string(),
+        Value::UInt32(v) => v.to_string(),
+        Value::UInt64(v) => v.to_string(),
+        Value::Int8(v) => v.to_string(),
+        Value::Int16(v) => v.to_string(),
+        Value::Int32(v) => v.to_string(),
+        Value::Int64(v) => v.to_string(),
+        Value::Float32(v) => float_literal(f64::from(*v)),
+        Value::Float64(v) => float_literal(*v),
+        Value::String(bytes) => quote(bytes),
+        Value::Date(_, _) => format!("toDate('{}')", value),
+        Value::DateTime(timestamp, tz) => {
+            let time = tz.timestamp_opt(i64::from(*timestamp), 0).unwrap();
+            format!(
+                "toDateTime('{}', '{}')",
+                time.format("%Y-%m-%d %H:%M:%S"),
+                tz.name()
+            )
+        }
+        Value::DateTime64(value, (precision, tz)) => {
+            // In i128, so that no precision overflows the scale.
+            let scale = 10_i128.pow(cmp::min(*precision, 38));
+            let seconds = i128::from(*value).div_euclid(scale) as i64;
+            let fraction = i128::from(*value).rem_euclid(scale);
+            let time = tz.timestamp_opt(seconds, 0).unwrap();
+            format!(
+                "toDateTime64('{}.{:0width$}', {}, '{}')",
+                time.format("%Y-%m-%d %H:%M:%S"),
+                fraction,
+                precision,
+                tz.name(),
+                width = *precision as usize
+            )
+        }
+        Value::Ipv4(v) => format!("toIPv4('{}')", Ipv4Addr::from(*v)),
+        Value::Ipv6(v) => format!("toIPv6('{}')", Ipv6Addr::from(*v)),
+        Value::Uuid(v) => match Uuid::from_slice(v) {
+            Ok(uuid) => format!("toUUID('{}')", uuid),
+            Err(_) => "NULL".to_string(),
+        },
+        Value::Nullable(v) => match v {
+            Either::Left(_) => "NULL".to_string(),
+            Either::Right(inner) => escape_literal(inner),
+        },
+        Value::Array(_, values) => {
+            let items: Vec<String> = values.iter().map(escape_literal).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Tuple(_, values) => {
+            let items: Vec<String> = values.iter().map(escape_literal).collect();
+            format!("tuple({})", items.join(", "))
+        }
+        Value::Decimal(v) => {
+            let function = match v.nobits {
+                NoBits::N32 => "toDecimal32",
+                NoBits::N64 => "toDecimal64",
+            };
+            format!("{}('{}', {})", function, v, v.scale)
+        }
+        Value::Enum8(items, v) => enum_literal(items, v.internal()),
+        Value::Enum16(items, v) => enum_literal(items, v.internal()),
+    }
+}
+
+fn float_literal(value: f64) -> String {
+    if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn enum_literal<T: PartialEq + ToString>(items: &[(String, T)], value: T) -> String {
+    match items.iter().find(|(_, v)| *v == value) {
+        Some((name, _)) => quote(name.as_bytes()),
+        None => value.to_string(),
+    }
+}
+
+fn quote(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len() + 2);
+
+    result.push('\'');
+    match str::from_utf8(bytes) {
+        Ok(text) => {
+            for ch in text.chars() {
+                escape_char(&mut result, ch);
+            }
+        }
+        Err(_) => {
+            for &b in bytes {
+                if b.is_ascii() {
+                    escape_char(&mut result, char::from(b));
+                } else {
+                    result.push_str(&format!("\\x{:02X}", b));
+                }
+            }
+        }
+    }
+    result.push('\'');
+    result
+}
+
+fn escape_char(result: &mut String, ch: char) {
+    match ch {
+        '\\' => result.push_str("\\\\"),
+        '\'' => result.push_str("\\'"),
+        '\n' => result.push_str("\\n"),
+        '\r' => result.push_str("\\r"),
+        '\t' => result.push_str("\\t"),
+        '\0' => result.push_str("\\0"),
+        ch if ch.is_ascii_control() => result.push_str(&format!("\\x{:02X}", ch as u8)),
+        ch => result.push(ch),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use chrono_tz::Tz;
+
+    use crate::types::{Decimal, SqlType};
+
+    use super::*;
+
+    #[test]
+    fn test_escape_identifier() {
+        assert_eq!(escape_identifier("db"), "`db`");
+        assert_eq!(escape_identifier("my`db\\"), "`my\\`db\\\\`");
+        assert_eq!(escape_identifier("it's\n"), "`it\\'s\\n`");
+    }
+
+    #[test]
+    fn test_to_sql_literal() {
+        assert_eq!("o'brien".to_sql_literal(), "'o\\'brien'");
+        assert_eq!(42_u32.to_sql_literal(), "42");
+        assert_eq!(Some(1.5_f64).to_sql_literal(), "1.5");
+        assert_eq!(None::<String>.to_sql_literal(), "NULL");
+        assert_eq!(vec![1_u8, 2].to_sql_literal(), "'\\x01\\x02'");
+    }
+
+    #[test]
+    fn test_bind_params() {
+        let sql = "SELECT * FROM t WHERE name = ? AND id > ? AND s = '?' -- ?\n AND `?` = ? # ?";
+        let params = vec![
+            Value::from("o'brien"),
+            Value::from(42_u32),
+            Value::from(1_u8),
+        ];
+
+        assert_eq!(
+            bind_params(sql, &params).unwrap(),
+            "SELECT * FROM t WHERE name = 'o\\'brien' AND id > 42 AND s = '?' -- ?\n AND `?` = 1 # ?"
+        );
+    }
+
+    #[test]
+    fn test_bind_params_count_mismatch() {
+        let sql = "SELECT ?, ?";
+        assert!(bind_params(sql, &[Value::from(1_u8)]).is_err());
+        assert!(bind_params("SELECT 1", &[Value::from(1_u8)]).is_err());
+    }
+
+    #[test]
+    fn test_string_literal() {
+        assert_eq!(
+            escape_literal(&Value::from("a\\b'c\nd")),
+            "'a\\\\b\\'c\\nd'"
+        );
+        assert_eq!(escape_literal(&Value::from("привет")), "'привет'");
+        assert_eq!(escape_literal(&Value::from(vec![0_u8, 0xff])), "'\\0\\xFF'");
+    }
+
+    #[test]
+    fn test_date_literals() {
+        let time = Tz::Europe__Moscow
+            .with_ymd_and_hms(2020, 1, 2, 3, 4, 5)
+            .unwrap();
+        assert_eq!(
+            escape_literal(&Value::from(time)),
+            "toDateTime('2020-01-02 03:04:05', 'Europe/Moscow')"
+        );
+        assert_eq!(
+            escape_literal(&Value::from(Tz::UTC.ymd(2020, 1, 2))),
+            "toDate('2020-01-02')"
+        );
+        assert_eq!(
+            escape_literal(&Value::DateTime64(1_577_934_245_123, (3, Tz::UTC))),
+            "toDateTime64('2020-01-02 03:04:05.123', 3, 'UTC')"
+        );
+        assert_eq!(
+            escape_literal(&Value::DateTime64(-1, (3, Tz::UTC))),
+            "toDateTime64('1969-12-31 23:59:59.999', 3, 'UTC')"
+        );
+        assert_eq!(
+            escape_literal(&Value::DateTime64(5, (9, Tz::UTC))),
+            "toDateTime64('1970-01-01 00:00:00.000000005', 9, 'UTC')"
+        );
+    }
+
+    #[test]
+    fn test_bind_datetime64_precision() {
+        let time = Value::DateTime64(1_577_934_245_123, (12, Tz::UTC));
+        match bind_params("SELECT ?", std::slice::from_ref(&time)) {
+            Err(Error::Driver(DriverError::ParamPrecision {
+                index: 0,
+                precision: 12,
+            })) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let nullable = Value::Nullable(Either::Right(Box::new(time)));
+        match bind_params("SELECT ?, ?", &[Value::from(1_u8), nullable]) {
+            Err(Error::Driver(DriverError::ParamPrecision {
+                index: 1,
+                precision: 12,
+            })) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_compound_literals() {
+        let array = Value::Array(
+            SqlType::String.into(),
+            Arc::new(vec![Value::from("a"), Value::from("b")]),
+        );
+        assert_eq!(escape_literal(&array), "['a', 'b']");
+        assert_eq!(escape_literal(&Value::from(None::<u8>)), "NULL");
+        assert_eq!(escape_literal(&Value::from(Some(5_i8))), "5");
+        assert_eq!(
+            escape_literal(&Value::from(Decimal::of(1.5, 2))),
+            "toDecimal64('1.50', 2)"
+        );
+        assert_eq!(escape_literal(&Value::from(f64::NAN)), "nan");
+        assert_eq!(escape_literal(&Value::from(-1.5_f64)), "-1.5");
+    }
+}
@@ -1,7 +1,39 @@
+use chrono_tz::Tz;
+use uuid::Uuid;
+
+use crate::{
+    errors::Result,
+    types::{params::bind_params, Block, LogLevel, Value},
+};
+
 #[derive(Clone, Debug)]
 pub struct Query {
     sql: String,
     id: String,
+    // Whether `id` was generated by `ensure_id`
+    generated_id: bool,
+    timezone: Option<Tz>,
+    logs_level: Option<LogLevel>,
+    external_tables: Vec<(String, Block)>,
+    params: Vec<Value>,
+    idempotent: Option<bool>,
+    reuse_blocks: bool,
+    lazy_columns: bool,
+    reorder_columns: bool,
+    max_insert_block_size: Option<usize>,
+    max_block_size: Option<usize>,
+    async_insert: Option<bool>,
+    wait_for_async_insert: Option<bool>,
+    quota_key: Option<String>,
+    trace_context: Option<TraceContext>,
+}
+
+/// W3C trace context of the span a query is sent from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct TraceContext {
+    pub(crate) trace_id: u128,
+    pub(crate) span_id: u64,
+    pub(crate) flags: u8,
 }
 
 impl Query {
@@ -9,16 +41,162 @@ impl Query {
         Self {
             sql: sql.as_ref().to_string(),
             id: "".to_string(),
+            generated_id: false,
+            timezone: None,
+            logs_level: None,
+            external_tables: Vec::new(),
+            params: Vec::new(),
+            idempotent: None,
+            reuse_blocks: false,
+            lazy_columns: false,
+            reorder_columns: false,
+            max_insert_block_size: None,
+            max_block_size: None,
+            async_insert: None,
+            wait_for_async_insert: None,
+            quota_key: None,
+            trace_context: None,
         }
     }
 
     pub fn id(self, id: impl AsRef<str>) -> Self {
         Self {
             id: id.as_ref().to_string(),
+            generated_id: false,
+            ..self
+        }
+    }
+
+    /// Interprets `Date`/`DateTime` columns of the result in `tz`, ignoring
+    /// both the server timezone and the timezone declared by the column type.
+    pub fn with_timezone(self, tz: Tz) -> Self {
+        Self {
+            timezone: Some(tz),
+            ..self
+        }
+    }
+
+    /// Binds `value` to the next `?` placeholder of the query. The value is
+    /// rendered as a ClickHouse literal on the client before the query is sent.
+    pub fn bind(mut self, value: impl Into<Value>) -> Self {
+        self.params.push(value.into());
+        self
+    }
+
+    /// Marks the query as safe (or unsafe) to send again after a transient
+    /// failure, overriding the default: only queries that read data, e.g.
+    /// `SELECT` and `SHOW`, are retried, inserts if `Options::retry_inserts`
+    /// is set.
+    pub fn idempotent(self, idempotent: bool) -> Self {
+        Self {
+            idempotent: Some(idempotent),
+            ..self
+        }
+    }
+
+    /// Lets an insert send the columns of its blocks in the order the table
+    /// expects them in, instead of failing with `Error::SchemaMismatch`,
+    /// e.g. for the blocks of `insert_stream` built from rows with their
+    /// columns in another order than the first one.
+    pub fn reorder_columns(self, reorder_columns: bool) -> Self {
+        Self {
+            reorder_columns,
+            ..self
+        }
+    }
+
+    /// Splits the data of an insert into blocks of at most `rows` rows,
+    /// overriding `Options::max_insert_block_size`.
+    pub fn max_insert_block_size(self, rows: usize) -> Self {
+        Self {
+            max_insert_block_size: Some(rows),
+            ..self
+        }
+    }
+
+    /// Sends the `max_block_size` setting: the server returns the result of a
+    /// `SELECT` in blocks of at most `rows` rows. Smaller blocks reach a
+    /// streaming consumer sooner, larger ones are cheaper to process.
+    pub fn max_block_size(self, rows: usize) -> Self {
+        Self {
+            max_block_size: Some(rows),
+            ..self
+        }
+    }
+
+    /// Sends the `async_insert` setting with an insert: the server collects
+    /// the data of small inserts in a buffer and writes them together.
+    pub fn async_insert(self, async_insert: bool) -> Self {
+        Self {
+            async_insert: Some(async_insert),
+            ..self
+        }
+    }
+
+    /// Sends the `wait_for_async_insert` setting with an `async_insert`
+    /// insert. Without waiting the insert completes as soon as the server
+    /// has buffered the data, before it is written to the table, and errors
+    /// of the write are not reported to the client.
+    pub fn wait_for_async_insert(self, wait: bool) -> Self {
+        Self {
+            wait_for_async_insert: Some(wait),
+            ..self
+        }
+    }
+
+    /// Accounts the query to the quota of `key` instead of
+    /// `Options::quota_key`, e.g. for services that share a pool between
+    /// tenants.
+    pub fn quota_key(self, key: impl AsRef<str>) -> Self {
+        Self {
+            quota_key: Some(key.as_ref().to_string()),
+            ..self
+        }
+    }
+
+    /// Attaches the W3C trace context of the calling span (the parts of a
+    /// `traceparent` header), so that the server records its spans of the
+    /// query in `system.opentelemetry_span_log` under `trace_id`.
+    ///
+    /// The context is sent from protocol revision 54442 on, see
+    /// `ServerInfo::protocol_revision`; at older revisions it is omitted.
+    pub fn with_trace_context(self, trace_id: u128, span_id: u64, flags: u8) -> Self {
+        Self {
+            trace_context: Some(TraceContext {
+                trace_id,
+                span_id,
+                flags,
+            }),
+            ..self
+        }
+    }
+
+    pub(crate) fn logs_level(self, level: LogLevel) -> Self {
+        Self {
+            logs_level: Some(level),
             ..self
         }
     }
 
+    pub(crate) fn reuse_blocks(self, reuse_blocks: bool) -> Self {
+        Self {
+            reuse_blocks,
+            ..self
+        }
+    }
+
+    pub(crate) fn lazy_columns(self, lazy_columns: bool) -> Self {
+        Self {
+            lazy_columns,
+            ..self
+        }
+    }
+
+    pub(crate) fn external_table(mut self, name: &str, block: Block) -> Self {
+        self.external_tables.push((name.to_string(), block));
+        self
+    }
+
     pub(crate) fn get_sql(&self) -> &str {
         &self.sql
     }
@@ -27,6 +205,110 @@ impl Query {
         &self.id
     }
 
+    pub(crate) fn get_timezone(&self) -> Option<Tz> {
+        self.timezone
+    }
+
+    pub(crate) fn get_logs_level(&self) -> Option<LogLevel> {
+        self.logs_level
+    }
+
+    pub(crate) fn get_quota_key(&self) -> Option<&str> {
+        self.quota_key.as_deref()
+    }
+
+    pub(crate) fn get_trace_context(&self) -> Option<TraceContext> {
+        self.trace_context
+    }
+
+    pub(crate) fn get_reuse_blocks(&self) -> bool {
+        self.reuse_blocks
+    }
+
+    pub(crate) fn get_lazy_columns(&self) -> bool {
+        self.lazy_columns
+    }
+
+    pub(crate) fn get_reorder_columns(&self) -> bool {
+        self.reorder_columns
+    }
+
+    pub(crate) fn get_max_insert_block_size(&self) -> Option<usize> {
+        self.max_insert_block_size
+    }
+
+    pub(crate) fn get_max_block_size(&self) -> Option<usize> {
+        self.max_block_size
+    }
+
+    pub(crate) fn get_async_insert(&self) -> Option<bool> {
+        self.async_insert
+    }
+
+    pub(crate) fn get_wait_for_async_insert(&self) -> Option<bool> {
+        self.wait_for_async_insert
+    }
+
+    pub(crate) fn get_external_tables(&self) -> &[(String, Block)] {
+        &self.external_tables
+    }
+
+    /// Gives the query a random id, the way the server would, unless it
+    /// already has one, so the id of every query sent is known.
+    pub(crate) fn ensure_id(self) -> Self {
+        if !self.id.is_empty() {
+            return self;
+        }
+        Self {
+            id: Uuid::new_v4().to_string(),
+            generated_id: true,
+            ..self
+        }
+    }
+
+    /// The query to send again after a failed attempt. A generated id is
+    /// replaced by a new one, as the server may still be running the failed
+    /// attempt and refuses a second query with its id; an id set with `id`
+    /// is kept.
+    pub(crate) fn renew_id(self) -> Self {
+        if !self.generated_id {
+            return self;
+        }
+        Self {
+            id: String::new(),
+            ..self
+        }
+        .ensure_id()
+    }
+
+    pub(crate) fn get_idempotent(&self) -> Option<bool> {
+        self.idempotent
+    }
+
+    /// Whether the query may be retried, either as marked by `idempotent` or
+    /// because it only reads data.
+    pub(crate) fn is_idempotent(&self) -> bool {
+        match self.idempotent {
+            Some(idempotent) => idempotent,
+            None => is_read_only(&self.sql),
+        }
+    }
+
+    /// Substitutes the bound parameters into the sql. Queries without bound
+    /// parameters are returned as is, so a bare `?` keeps its meaning.
+    pub(crate) fn bind_params(self) -> Result<Self> {
+        if self.params.is_empty() {
+            return Ok(self);
+        }
+
+        let sql = bind_params(&self.sql, &self.params)?;
+        Ok(Self {
+            sql,
+            params: Vec::new(),
+            ..self
+        })
+    }
+
     pub(crate) fn map_sql<F>(self, f: F) -> Self
     where
         F: Fn(&str) -> String,
@@ -38,6 +320,19 @@ impl Query {
     }
 }
 
+fn is_read_only(sql: &str) -> bool {
+    let statement = sql.trim_start_matches(|c: char| c.is_whitespace() || c == '(');
+    let keyword: String = statement
+        .chars()
+        .take_while(char::is_ascii_alphabetic)
+        .collect();
+
+    matches!(
+        keyword.to_ascii_uppercase().as_str(),
+        "SELECT" | "WITH" | "SHOW" | "DESC" | "DESCRIBE" | "EXISTS" | "EXPLAIN"
+    )
+}
+
 impl<T> From<T> for Query
 where
     T: AsRef<str>,
@@ -46,3 +341,47 @@ where
         Self::new(source)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_idempotent() {
+        assert!(Query::new("SELECT 1").is_idempotent());
+        assert!(Query::new("  (select 1) UNION ALL (SELECT 2)").is_idempotent());
+        assert!(Query::new("WITH 1 AS x SELECT x").is_idempotent());
+        assert!(Query::new("show tables").is_idempotent());
+        assert!(!Query::new("INSERT INTO t VALUES").is_idempotent());
+        assert!(!Query::new("ALTER TABLE t DELETE WHERE 1").is_idempotent());
+        assert!(!Query::new("SELECT 1").idempotent(false).is_idempotent());
+        assert!(Query::new("DROP TABLE IF EXISTS t")
+            .idempotent(true)
+            .is_idempotent());
+        assert_eq!(Query::new("SELECT 1").get_idempotent(), None);
+    }
+
+    #[test]
+    fn test_ensure_id() {
+        assert_eq!(
+            Query::new("SELECT 1").id("trace").ensure_id().get_id(),
+            "trace"
+        );
+
+        let first = Query::new("SELECT 1").ensure_id();
+        let second = Query::new("SELECT 1").ensure_id();
+        assert_eq!(first.get_id().len(), 36);
+        assert_ne!(first.get_id(), second.get_id());
+    }
+
+    #[test]
+    fn test_renew_id() {
+        let query = Query::new("SELECT 1").ensure_id();
+        let renewed = query.clone().renew_id();
+        assert_eq!(renewed.get_id().len(), 36);
+        assert_ne!(renewed.get_id(), query.get_id());
+
+        let query = Query::new("SELECT 1").id("trace").ensure_id();
+        assert_eq!(query.renew_id().get_id(), "trace");
+    }
+}
@@ -1,15 +1,17 @@
-use std::{marker, sync::Arc};
+use std::{marker, sync::Arc, time::Duration};
 
 use tokio::prelude::*;
 
 use crate::{
     errors::{codes, DriverError, Error, ServerError},
-    io::{BoxFuture, BoxStream, ClickhouseTransport},
+    io::{transport::TimeoutFlag, BoxFuture, BoxStream, ClickhouseTransport},
+    pool::PoolBinding,
+    retry_policy::with_retries,
     types::{
-        block::BlockRef, query_result::stream_blocks::BlockStream, Block, Cmd,
-        Complex, Packet, Query, Row, Rows, Simple, either::Either, Context,
+        block::BlockRef, either::Either, query_result::stream_blocks::BlockStream, Block, Cmd,
+        Complex, Context, FromRow, FromSql, LogEntry, LogLevel, Packet, Progress, Query,
+        QueryStats, Row, Rows, Simple, Value,
     },
-    pool::PoolBinding,
     ClientHandle,
 };
 
@@ -36,13 +38,183 @@ macro_rules! try_opt {
     };
 }
 
+pub(crate) type ProgressCallback = Arc<dyn Fn(Progress) + Send + Sync>;
+
+pub(crate) type LogCallback = Arc<dyn Fn(LogEntry) + Send + Sync>;
+
+pub(crate) type BlockCallback = Arc<dyn Fn(Block) + Send + Sync>;
+
+/// Everything the server sends besides the data blocks.
+#[derive(Default)]
+struct Extras {
+    stats: QueryStats,
+    totals: Option<Block>,
+    extremes: Option<Block>,
+}
+
 /// Result of a query or statement execution.
 pub struct QueryResult {
     pub(crate) client: ClientHandle,
     pub(crate) query: Query,
+    pub(crate) progress: Option<ProgressCallback>,
+    pub(crate) logs: Option<LogCallback>,
+    pub(crate) totals: Option<BlockCallback>,
+    pub(crate) extremes: Option<BlockCallback>,
+    pub(crate) timeout: Option<Duration>,
 }
 
 impl QueryResult {
+    /// Sets the `query_id` sent to the server, which can later be passed to
+    /// `ClientHandle::cancel_query` (or `KILL QUERY`) to stop the query, or
+    /// looked up in `system.query_log`.
+    ///
+    /// An empty id is replaced by a random one, so `query_id` always returns
+    /// the id the server sees.
+    pub fn with_query_id(self, id: impl AsRef<str>) -> Self {
+        Self {
+            query: self.query.id(id).ensure_id(),
+            ..self
+        }
+    }
+
+    /// Same as `with_query_id`.
+    pub fn with_id(self, id: impl AsRef<str>) -> Self {
+        self.with_query_id(id)
+    }
+
+    /// Calls `f` for every `Progress` packet the server sends while the
+    /// query runs. The callback is invoked in between data blocks, in the
+    /// order the packets arrive.
+    pub fn with_progress<F>(self, f: F) -> Self
+    where
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
+        Self {
+            progress: Some(Arc::new(f)),
+            ..self
+        }
+    }
+
+    /// Asks the server to send its log lines of at least `level` verbosity
+    /// (the `send_logs_level` setting) and calls `f` for each of them.
+    ///
+    /// Servers older than protocol revision 54406 don't send logs.
+    pub fn with_logs<F>(self, level: LogLevel, f: F) -> Self
+    where
+        F: Fn(LogEntry) + Send + Sync + 'static,
+    {
+        Self {
+            query: self.query.logs_level(level),
+            logs: Some(Arc::new(f)),
+            ..self
+        }
+    }
+
+    /// Calls `f` with the totals row of a `WITH TOTALS` query, which the
+    /// server sends after the data blocks. Unlike `Block::totals`, this also
+    /// works with `stream_blocks` and `fold_blocks`.
+    pub fn with_totals<F>(self, f: F) -> Self
+    where
+        F: Fn(Block) + Send + Sync + 'static,
+    {
+        Self {
+            totals: Some(Arc::new(f)),
+            ..self
+        }
+    }
+
+    /// Calls `f` with the extremes of the result, a block of the minimum and
+    /// the maximum row, if the query runs with the `extremes` setting
+    /// enabled. The server sends them after the data blocks.
+    pub fn with_extremes<F>(self, f: F) -> Self
+    where
+        F: Fn(Block) + Send + Sync + 'static,
+    {
+        Self {
+            extremes: Some(Arc::new(f)),
+            ..self
+        }
+    }
+
+    /// Sends `block` along with the query as the temporary table `name`, so
+    /// the query can use it like any other table, e.g.
+    /// `WHERE id IN ids`. Adding several blocks with the same name appends
+    /// them to one table.
+    pub fn with_external_table(self, name: &str, block: Block) -> Self {
+        Self {
+            query: self.query.external_table(name, block),
+            ..self
+        }
+    }
+
+    /// Binds `value` to the next `?` placeholder of the query, e.g.
+    /// `c.query("SELECT * FROM users WHERE name = ?").bind("o'brien")`.
+    ///
+    /// Values are escaped and inlined into the sql on the client. A `?`
+    /// inside a string literal, a quoted identifier or a comment is not a
+    /// placeholder. The query fails without being sent if the number of
+    /// placeholders differs from the number of bound values.
+    pub fn bind<T>(self, value: T) -> Self
+    where
+        T: Into<Value>,
+    {
+        Self {
+            query: self.query.bind(value),
+            ..self
+        }
+    }
+
+    /// Bounds how long the query may take, overriding `Options::query_timeout`.
+    ///
+    /// A query still running when the timeout elapses fails with
+    /// `DriverError::Timeout` and its connection is closed instead of being
+    /// returned to the pool. For `stream_blocks` the timeout bounds the whole
+    /// stream.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Decodes every data block into the storage of the previous one, so
+    /// that streaming a large result doesn't allocate for each block.
+    ///
+    /// Storage is reused only once the previous block has been dropped by
+    /// the consumer, a block that is still referenced is never overwritten.
+    /// This pays off with `stream_blocks`, `stream` and `fold`, which drop
+    /// each block before the next one arrives; `fetch_all` keeps all blocks.
+    /// Only numeric, `String` and `Nullable` columns of those types reuse
+    /// their storage. With compression on, the buffers blocks are
+    /// decompressed through are kept for the whole query as well.
+    pub fn with_block_reuse(self) -> Self {
+        Self {
+            query: self.query.reuse_blocks(true),
+            ..self
+        }
+    }
+
+    /// Decodes the values of a column of a data block only when they are
+    /// read for the first time, e.g. by `Block::get`.
+    ///
+    /// This pays off for wide results of which only a few columns are read:
+    /// every other column costs a copy of its bytes instead of the values
+    /// being decoded. Accessed columns hold the same values as with eager
+    /// decoding. Columns of `Object('json')` are always decoded eagerly.
+    /// Block reuse has no effect on lazily decoded blocks.
+    pub fn with_lazy_columns(self) -> Self {
+        Self {
+            query: self.query.lazy_columns(true),
+            ..self
+        }
+    }
+
+    /// Returns the `query_id` this query is sent with. Retries of a query
+    /// whose id wasn't set with `with_query_id` get a new random id each.
+    pub fn query_id(&self) -> &str {
+        self.query.get_id()
+    }
+
     /// Method that applies a function to each row, producing a single, final value.
     ///
     /// example:
@@ -80,17 +252,136 @@ impl QueryResult {
     }
 
     /// Fetch data from table. It returns a block that contains all rows.
+    ///
+    /// The totals of a `WITH TOTALS` query and the extremes are not mixed into
+    /// the rows, they are available through `Block::totals` and
+    /// `Block::extremes` of the returned block.
+    ///
+    /// Failed idempotent queries are retried as described for
+    /// `fetch_all_with_stats`.
     pub fn fetch_all(self) -> BoxFuture<(ClientHandle, Block<Complex>)> {
-        Box::new(
-            self.fold_blocks(Vec::new(), |mut blocks, block| {
-                if !block.is_empty() {
-                    blocks.push(block);
-                }
-                Ok(blocks)
-            })
-                .map_err(Error::from)
-                .map(|(h, blocks)| (h, Block::concat(blocks.as_slice()))),
-        )
+        Box::new(self.fetch_all_with_stats().map(|(h, block, _)| (h, block)))
+    }
+
+    /// Fetches the only row of the result, failing with `DriverError::NoRows`
+    /// or `DriverError::TooManyRows` unless there is exactly one.
+    ///
+    /// example:
+    /// ```rust
+    /// # extern crate clickhouse_rs;
+    /// # extern crate futures;
+    /// # use futures::Future;
+    /// # use clickhouse_rs::Pool;
+    /// # use std::env;
+    /// # let database_url = env::var("DATABASE_URL").unwrap_or("tcp://localhost:9000?compression=lz4".into());
+    /// # let pool = Pool::new(database_url);
+    /// # let done =
+    /// pool.get_handle()
+    ///     .and_then(|c| c.query("SELECT 1 AS id, 'foo' AS name").fetch_one())
+    ///     .map(|(_, (id, name)): (_, (u8, String))| println!("{} {}", id, name))
+    /// #   .map_err(|err| eprintln!("database error: {}", err));
+    /// # tokio::run(done)
+    /// ```
+    pub fn fetch_one<T>(self) -> impl Future<Item = (ClientHandle, T), Error = Error>
+    where
+        T: FromRow + Send + 'static,
+    {
+        self.fetch_optional().and_then(|(c, row)| match row {
+            Some(row) => Ok((c, row)),
+            None => Err(Error::Driver(DriverError::NoRows)),
+        })
+    }
+
+    /// Same as `fetch_one`, but returns `None` if there are no rows.
+    ///
+    /// The rows are not buffered, only the first one is converted. A second
+    /// row fails the query right away with `DriverError::TooManyRows` and the
+    /// rest of the result is discarded.
+    pub fn fetch_optional<T>(self) -> impl Future<Item = (ClientHandle, Option<T>), Error = Error>
+    where
+        T: FromRow + Send + 'static,
+    {
+        self.fold_blocks((None, 0), |(first, rows): (Option<T>, usize), block| {
+            let rows = rows + block.row_count();
+            if rows > 1 {
+                return Err(Error::Driver(DriverError::TooManyRows));
+            }
+
+            let first = match (first, block.rows().next()) {
+                (Some(first), _) => Some(first),
+                (None, Some(row)) => Some(T::from_row(&row)?),
+                (None, None) => None,
+            };
+            Ok((first, rows))
+        })
+        .map(|(c, (first, _))| (c, first))
+    }
+
+    /// Fetches the only value of a result with one row of one column, e.g.
+    /// of `SELECT count() FROM t`.
+    ///
+    /// Other results fail as described for `fetch_one`, or with
+    /// `DriverError::ColumnCount` if there is more than one column.
+    pub fn fetch_scalar<T>(self) -> impl Future<Item = (ClientHandle, T), Error = Error>
+    where
+        T: for<'a> FromSql<'a> + Send + 'static,
+    {
+        self.fetch_one().map(|(c, (value,))| (c, value))
+    }
+
+    /// Same as `fetch_all`, but also returns the statistics the server sent
+    /// along with the result, e.g. `rows_before_limit`.
+    ///
+    /// With `Options::query_retries` set, a query that fails with a
+    /// transient error is sent again if it is idempotent, see
+    /// `Query::idempotent`; the progress, log, totals and extremes callbacks
+    /// then see the packets of every attempt.
+    pub fn fetch_all_with_stats(self) -> BoxFuture<(ClientHandle, Block<Complex>, QueryStats)> {
+        let Self {
+            client,
+            query,
+            progress,
+            logs,
+            totals,
+            extremes,
+            timeout,
+        } = self;
+        let idempotent = query.is_idempotent();
+
+        with_retries(client, query, idempotent, move |client, query| {
+            let this = Self {
+                client,
+                query,
+                progress: progress.clone(),
+                logs: logs.clone(),
+                totals: totals.clone(),
+                extremes: extremes.clone(),
+                timeout,
+            };
+            Box::new(
+                this.fold_blocks_with_extras(Vec::new(), |mut blocks, block| {
+                    if !block.is_empty() {
+                        blocks.push(block);
+                    }
+                    Ok(blocks)
+                })
+                .map(|(h, blocks, extras)| {
+                    let mut block = Block::concat(blocks.as_slice());
+                    block.set_totals(extras.totals);
+                    block.set_extremes(extras.extremes);
+                    (h, block, extras.stats)
+                }),
+            )
+        })
+    }
+
+    /// Marks the query as safe (or unsafe) to send again after a transient
+    /// failure, see `Query::idempotent`.
+    pub fn idempotent(self, idempotent: bool) -> Self {
+        Self {
+            query: self.query.idempotent(idempotent),
+            ..self
+        }
     }
 
     /// Method that applies a function to each block, producing a single, final value.
@@ -101,56 +392,130 @@ impl QueryResult {
             Fut::Future: Send,
             T: Send + 'static,
     {
-        let timeout = try_opt!(self.client.context.options.get()).query_timeout;
-        let context = self.client.context.clone();
-        let pool = self.client.pool.clone();
+        self.fold_blocks_with_extras(init, f)
+            .map(|(h, acc, _)| (h, acc))
+    }
 
-        let acc = (None, init);
+    fn fold_blocks_with_extras<F, T, Fut>(
+        self,
+        init: T,
+        f: F,
+    ) -> impl Future<Item = (ClientHandle, T, Extras), Error = Error>
+    where
+        F: Fn(T, Block) -> Fut + Send + 'static,
+        Fut: IntoFuture<Item = T, Error = Error> + Send + 'static,
+        Fut::Future: Send,
+        T: Send + 'static,
+    {
+        let timeout = match self.timeout {
+            Some(timeout) => Some(timeout),
+            None => try_opt!(self.client.context.options.get()).query_timeout,
+        };
+        let this = Self {
+            query: try_opt!(self.query.bind_params()),
+            ..self
+        };
+        let context = this.client.context.clone();
+        let pool = this.client.pool.clone();
+        let progress = this.progress.clone();
+        let logs = this.logs.clone();
+        let totals = this.totals.clone();
+        let extremes = this.extremes.clone();
+        let timed_out = TimeoutFlag::default();
+
+        let acc = (None, init, Extras::default());
 
-        let future = self.fold_packets(acc, move |(h, acc), packet| match packet {
-            Packet::Block(b) => {
-                if b.is_empty() {
-                    Either::Right(future::ok((h, acc)))
-                } else {
-                    Either::Left(f(acc, b).into_future().map(move |a| (h, a)))
+        let future = this.fold_packets(
+            acc,
+            timed_out.clone(),
+            move |(h, acc, mut extras), packet| match packet {
+                Packet::Block(b) => {
+                    if b.is_empty() {
+                        Either::Right(future::ok((h, acc, extras)))
+                    } else {
+                        Either::Left(f(acc, b).into_future().map(move |a| (h, a, extras)))
+                    }
                 }
-            }
-            Packet::Eof(inner) => Either::Right(future::ok((
-                Some(ClientHandle {
-                    inner: Some(inner),
-                    context: context.clone(),
-                    pool: pool.clone(),
-                }),
-                acc,
-            ))),
-            Packet::ProfileInfo(_) | Packet::Progress(_) => Either::Right(future::ok((h, acc))),
-            Packet::Exception(mut exception, transport) => {
-                set_exception_handle(&mut exception, transport, context.clone(), pool.clone());
-                Either::Right(future::err(Error::Server(exception)))
+                Packet::Totals(b) => {
+                    if let Some(ref callback) = totals {
+                        callback(b.clone());
+                    }
+                    extras.totals = Some(b);
+                    Either::Right(future::ok((h, acc, extras)))
+                }
+                Packet::Extremes(b) => {
+                    if let Some(ref callback) = extremes {
+                        callback(b.clone());
+                    }
+                    extras.extremes = Some(b);
+                    Either::Right(future::ok((h, acc, extras)))
+                }
+                Packet::Eof(inner) => Either::Right(future::ok((
+                    Some(ClientHandle {
+                        inner: Some(inner),
+                        context: context.clone(),
+                        pool: pool.clone(),
+                    }),
+                    acc,
+                    extras,
+                ))),
+                Packet::Progress(p) => {
+                    extras.stats.update(&packet);
+                    if let Some(ref callback) = progress {
+                        callback(p);
+                    }
+                    Either::Right(future::ok((h, acc, extras)))
+                }
+                Packet::ProfileInfo(_) => {
+                    extras.stats.update(&packet);
+                    Either::Right(future::ok((h, acc, extras)))
+                }
+                Packet::Log(entries) => {
+                    if let Some(ref callback) = logs {
+                        for entry in entries {
+                            callback(entry);
+                        }
+                    }
+                    Either::Right(future::ok((h, acc, extras)))
+                }
+                Packet::Exception(mut exception, transport) => {
+                    set_exception_handle(&mut exception, transport, context.clone(), pool.clone());
+                    Either::Right(future::err(Error::Server(exception)))
+                }
+                _ => Either::Right(future::err(Error::Driver(DriverError::UnexpectedPacket))),
             },
-            _ => Either::Right(future::err(Error::Driver(DriverError::UnexpectedPacket))),
-        });
+        );
 
         let fut = if let Some(timeout) = timeout {
             Either::Left(
                 future
-                    .map(|(c, t)| (c.unwrap(), t))
+                    .map(|(c, t, s)| (c.unwrap(), t, s))
                     .timeout(timeout)
-                    .map_err(move |err| err.into()),
+                    .map_err(move |err| {
+                        if err.is_elapsed() {
+                            timed_out.set();
+                        }
+                        err.into()
+                    }),
             )
         } else {
-            Either::Right(future.map(|(c, t)| (c.unwrap(), t)))
+            Either::Right(future.map(|(c, t, s)| (c.unwrap(), t, s)))
         };
 
         Either::Right(fut)
     }
 
-    fn fold_packets<F, T, Fut>(self, init: T, f: F) -> impl Future<Item=T, Error=Error>
-        where
-            F: Fn(T, Packet<ClickhouseTransport>) -> Fut + Send + 'static,
-            Fut: IntoFuture<Item = T, Error = Error> + Send + 'static,
-            Fut::Future: Send,
-            T: Send + 'static,
+    fn fold_packets<F, T, Fut>(
+        self,
+        init: T,
+        timed_out: TimeoutFlag,
+        f: F,
+    ) -> impl Future<Item = T, Error = Error>
+    where
+        F: Fn(T, Packet<ClickhouseTransport>) -> Fut + Send + 'static,
+        Fut: IntoFuture<Item = T, Error = Error> + Send + 'static,
+        Fut::Future: Send,
+        T: Send + 'static,
     {
         let context = self.client.context.clone();
         let query = self.query;
@@ -162,12 +527,18 @@ impl QueryResult {
                 .take()
                 .unwrap()
                 .call(Cmd::SendQuery(query, context.clone()))
+                .cancel_on_drop(context, c.pool.clone())
+                .close_on_timeout(timed_out)
                 .fold(init, f)
         })
     }
 
     /// Method that produces a stream of blocks containing rows
     ///
+    /// The totals of a `WITH TOTALS` query and the extremes are not part of
+    /// the stream, use `with_totals` and `with_extremes` (or `fetch_all`) to
+    /// get them.
+    ///
     /// example:
     /// ```rust
     /// # extern crate clickhouse_rs;
@@ -193,8 +564,14 @@ impl QueryResult {
     /// # tokio::run(done)
     /// ```
     pub fn stream_blocks(self) -> BoxStream<Block> {
-        let query = self.query;
+        let query = try_opt_stream!(self.query.bind_params());
+        let progress = self.progress;
+        let logs = self.logs;
+        let totals = self.totals;
+        let extremes = self.extremes;
+        let deadline = self.timeout;
         let timeout = try_opt_stream!(self.client.context.options.get()).query_block_timeout;
+        let timed_out = TimeoutFlag::default();
 
         self.client.wrap_stream(move |mut c| -> BoxStream<Block> {
             info!("[send query] {}", query.get_sql());
@@ -208,13 +585,24 @@ impl QueryResult {
                 c.inner
                     .take()
                     .unwrap()
-                    .call(Cmd::SendQuery(query, context.clone())),
+                    .call(Cmd::SendQuery(query, context.clone()))
+                    .cancel_on_drop(context.clone(), pool.clone())
+                    .close_on_timeout(timed_out.clone()),
                 context,
                 pool,
-            );
+                progress,
+                logs,
+            )
+            .extras(totals, extremes)
+            .deadline(deadline);
 
             if let Some(timeout) = timeout {
-                Box::new(stream.timeout(timeout).map_err(|err| err.into()))
+                Box::new(stream.timeout(timeout).map_err(move |err| {
+                    if err.is_elapsed() {
+                        timed_out.set();
+                    }
+                    err.into()
+                }))
             } else {
                 Box::new(stream)
             }
@@ -239,26 +627,48 @@ impl QueryResult {
     }
 }
 
-pub(crate) fn set_exception_handle(exception: &mut ServerError, transport: Option<ClickhouseTransport>, context: Context, pool: PoolBinding) {
+/// Keeps the connection the exception was received on in the error, it
+/// goes back to the pool once the error is dropped.
+///
+/// An exception ends the response, the server sends nothing after it even
+/// if data blocks were streamed before, so the connection is in sync.
+/// Only connections of errors a query can run into on a healthy server are
+/// kept, including limits hit in the middle of a result.
+pub(crate) fn set_exception_handle(
+    exception: &mut ServerError,
+    transport: Option<ClickhouseTransport>,
+    context: Context,
+    mut pool: PoolBinding,
+) {
     if let Some(transport) = transport {
         match exception.code {
-            codes::CANNOT_PARSE_DATE |
-            codes::CANNOT_PARSE_DATETIME |
-            codes::UNKNOWN_FUNCTION |
-            codes::UNKNOWN_IDENTIFIER |
-            codes::SYNTAX_ERROR |
-            codes::UNKNOWN_AGGREGATE_FUNCTION |
-            codes::ILLEGAL_KEY_OF_AGGREGATION |
-            codes::ARGUMENT_OUT_OF_BOUND |
-            codes::CANNOT_CONVERT_TYPE |
-            codes::CANNOT_WRITE_AFTER_END_OF_BUFFER |
-            codes::CANNOT_PARSE_NUMBER |
-            codes::ILLEGAL_DIVISION |
-            codes::READONLY |
-            codes::BARRIER_TIMEOUT |
-            codes::TIMEOUT_EXCEEDED |
-            codes::SOCKET_TIMEOUT |
-            codes::INVALID_SESSION_TIMEOUT => {
+            codes::CANNOT_PARSE_DATE
+            | codes::CANNOT_PARSE_DATETIME
+            | codes::UNKNOWN_FUNCTION
+            | codes::UNKNOWN_IDENTIFIER
+            | codes::SYNTAX_ERROR
+            | codes::UNKNOWN_AGGREGATE_FUNCTION
+            | codes::ILLEGAL_KEY_OF_AGGREGATION
+            | codes::ARGUMENT_OUT_OF_BOUND
+            | codes::CANNOT_CONVERT_TYPE
+            | codes::CANNOT_WRITE_AFTER_END_OF_BUFFER
+            | codes::CANNOT_PARSE_NUMBER
+            | codes::ILLEGAL_DIVISION
+            | codes::READONLY
+            | codes::BARRIER_TIMEOUT
+            | codes::TIMEOUT_EXCEEDED
+            | codes::SOCKET_TIMEOUT
+            | codes::QUERY_WAS_CANCELLED
+            | codes::INVALID_SESSION_TIMEOUT
+            | codes::MEMORY_LIMIT_EXCEEDED
+            | codes::TOO_MANY_ROWS
+            | codes::TOO_MANY_BYTES
+            | codes::TOO_MANY_ROWS_OR_BYTES
+            | codes::TOO_SLOW => {
+                // Queries detach the handle from the pool while they run.
+                if pool.is_some() && !pool.is_attached() {
+                    pool.attach();
+                }
                 let client = ClientHandle {
                     inner: Some(transport),
                     context,
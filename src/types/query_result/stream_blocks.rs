@@ -1,29 +1,84 @@
-use futures::{Async, Poll, Stream};
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll, Stream};
+use tokio_timer::Delay;
 
 use crate::{
     errors::{DriverError, Error},
     io::transport::PacketStream,
     pool::PoolBinding,
-    types::{Block, Context, Packet, query_result::set_exception_handle},
+    types::{
+        query_result::{set_exception_handle, BlockCallback, LogCallback, ProgressCallback},
+        Block, Context, Packet,
+    },
     ClientHandle,
 };
 
 pub(crate) struct BlockStream {
     inner: PacketStream,
     rest: Option<(Context, PoolBinding)>,
+    progress: Option<ProgressCallback>,
+    logs: Option<LogCallback>,
+    totals: Option<BlockCallback>,
+    extremes: Option<BlockCallback>,
     eof: bool,
     block_index: usize,
+    deadline: Option<Delay>,
 }
 
 impl BlockStream {
-    pub(crate) fn new(inner: PacketStream, context: Context, pool: PoolBinding) -> BlockStream {
+    pub(crate) fn new(
+        inner: PacketStream,
+        context: Context,
+        pool: PoolBinding,
+        progress: Option<ProgressCallback>,
+        logs: Option<LogCallback>,
+    ) -> BlockStream {
         BlockStream {
             inner,
             rest: Some((context, pool)),
+            progress,
+            logs,
+            totals: None,
+            extremes: None,
             eof: false,
             block_index: 0,
+            deadline: None,
+        }
+    }
+
+    /// Calls `totals` and `extremes` with the blocks of these kinds, which
+    /// are not part of the stream.
+    pub(crate) fn extras(
+        self,
+        totals: Option<BlockCallback>,
+        extremes: Option<BlockCallback>,
+    ) -> Self {
+        Self {
+            totals,
+            extremes,
+            ..self
+        }
+    }
+
+    /// Fails the stream with `DriverError::Timeout` if it doesn't end
+    /// within `timeout`.
+    pub(crate) fn deadline(self, timeout: Option<Duration>) -> Self {
+        Self {
+            deadline: timeout.map(|timeout| Delay::new(Instant::now() + timeout)),
+            ..self
         }
     }
+
+    fn poll_deadline(&mut self) -> Poll<Option<Block>, Error> {
+        if let Some(deadline) = self.deadline.as_mut() {
+            if deadline.poll()?.is_ready() {
+                self.inner.mark_timed_out();
+                return Err(Error::Driver(DriverError::Timeout));
+            }
+        }
+        Ok(Async::NotReady)
+    }
 }
 
 impl Stream for BlockStream {
@@ -38,7 +93,7 @@ impl Stream for BlockStream {
 
             let packet = match self.inner.poll() {
                 Err(err) => return Err(err),
-                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Ok(Async::NotReady) => return self.poll_deadline(),
                 Ok(Async::Ready(None)) => {
                     self.eof = true;
                     continue;
@@ -59,7 +114,29 @@ impl Stream for BlockStream {
                     }
                     self.eof = true;
                 }
-                Packet::ProfileInfo(_) | Packet::Progress(_) => {}
+                Packet::Progress(progress) => {
+                    if let Some(ref callback) = self.progress {
+                        callback(progress);
+                    }
+                }
+                Packet::ProfileInfo(_) => {}
+                Packet::Totals(block) => {
+                    if let Some(ref callback) = self.totals {
+                        callback(block);
+                    }
+                }
+                Packet::Extremes(block) => {
+                    if let Some(ref callback) = self.extremes {
+                        callback(block);
+                    }
+                }
+                Packet::Log(entries) => {
+                    if let Some(ref callback) = self.logs {
+                        for entry in entries {
+                            callback(entry);
+                        }
+                    }
+                }
                 Packet::Exception(mut exception, transport) => {
                     let (context, pool) = self.rest.take().unwrap();
                     set_exception_handle(&mut exception, transport, context, pool);
@@ -8,10 +8,13 @@ use std::{
 use chrono::prelude::*;
 use chrono_tz::Tz;
 
-use crate::types::{
-    column::{datetime64::to_datetime, Either},
-    decimal::{Decimal, NoBits},
-    DateConverter, DateTimeType, Enum16, Enum8, SqlType, HasSqlType,
+use crate::{
+    errors::{Error, FromSqlError, Result},
+    types::{
+        column::{datetime64::to_datetime, Either},
+        decimal::{Decimal, NoBits},
+        DateConverter, DateTimeType, Enum16, Enum8, HasSqlType, SqlType,
+    },
 };
 
 use uuid::Uuid;
@@ -20,6 +23,10 @@ pub(crate) type AppDateTime = DateTime<Tz>;
 pub(crate) type AppDate = Date<Tz>;
 
 /// Client side representation of a value of Clickhouse column.
+///
+/// A cell of any column can be read as a `Value`, e.g. with
+/// `let value: Value = row.get(0)?;`, and is displayed the way ClickHouse
+/// prints it, with strings in arrays and tuples quoted.
 #[derive(Clone, Debug)]
 pub enum Value {
     UInt8(u8),
@@ -41,6 +48,8 @@ pub enum Value {
     Uuid([u8; 16]),
     Nullable(Either<&'static SqlType, Box<Value>>),
     Array(&'static SqlType, Arc<Vec<Value>>),
+    /// Elements of a tuple of the given `SqlType::Tuple` type.
+    Tuple(&'static SqlType, Arc<Vec<Value>>),
     Decimal(Decimal),
     Enum8(Vec<(String, i8)>, Enum8),
     Enum16(Vec<(String, i16)>, Enum16),
@@ -70,9 +79,19 @@ impl PartialEq for Value {
                 let time_b = tz_b.timestamp(i64::from(*b), 0);
                 time_a == time_b
             }
+            (Value::DateTime64(a, (prec_a, _)), Value::DateTime64(b, (prec_b, _))) => {
+                *a == *b && *prec_a == *prec_b
+            }
+            (Value::Ipv4(a), Value::Ipv4(b)) => *a == *b,
+            (Value::Ipv6(a), Value::Ipv6(b)) => *a == *b,
+            (Value::Uuid(a), Value::Uuid(b)) => *a == *b,
             (Value::Nullable(a), Value::Nullable(b)) => *a == *b,
             (Value::Array(ta, a), Value::Array(tb, b)) => *ta == *tb && *a == *b,
+            (Value::Tuple(ta, a), Value::Tuple(tb, b)) => *ta == *tb && *a == *b,
             (Value::Decimal(a), Value::Decimal(b)) => *a == *b,
+            (Value::Enum8(values_a, val_a), Value::Enum8(values_b, val_b)) => {
+                *values_a == *values_b && *val_a == *val_b
+            }
             (Value::Enum16(values_a, val_a), Value::Enum16(values_b, val_b)) => {
                 *values_a == *values_b && *val_a == *val_b
             }
@@ -103,6 +122,13 @@ impl Value {
             SqlType::DateTime(_) => 0_u32.to_date(Tz::Zulu).into(),
             SqlType::Nullable(inner) => Value::Nullable(Either::Left(inner)),
             SqlType::Array(inner) => Value::Array(inner, Arc::new(Vec::default())),
+            SqlType::Tuple(ref elements) => {
+                let values = elements
+                    .iter()
+                    .map(|(_, element)| Value::default((*element).clone()))
+                    .collect();
+                Value::Tuple(sql_type.into(), Arc::new(values))
+            }
             SqlType::Decimal(precision, scale) => Value::Decimal(Decimal {
                 underlying: 0,
                 precision,
@@ -112,12 +138,104 @@ impl Value {
             SqlType::Ipv4 => Value::Ipv4([0_u8; 4]),
             SqlType::Ipv6 => Value::Ipv6([0_u8; 16]),
             SqlType::Uuid => Value::Uuid([0_u8; 16]),
+            SqlType::Nothing => Value::Nullable(Either::Left(&SqlType::Nothing)),
+            SqlType::Json => Value::String(Arc::new(b"{}".to_vec())),
             SqlType::Enum8(values) => Value::Enum8(values, Enum8(0)),
             SqlType::Enum16(values) => Value::Enum16(values, Enum16(0)),
         }
     }
 }
 
+impl Value {
+    /// Returns the element of a named tuple called `name`, or `None` if the
+    /// value is not a tuple or has no such element.
+    pub fn tuple_element(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Tuple(sql_type, values) => values.get(sql_type.tuple_position(name)?),
+            _ => None,
+        }
+    }
+
+    /// Returns an integer value of any width as `i64`.
+    ///
+    /// Fails with `FromSqlError::OutOfRange` for a `UInt64` above
+    /// `i64::MAX` and with `FromSqlError::InvalidType` for any value other
+    /// than an integer, including floating point ones. A non-null
+    /// `Nullable` value is converted as its inner value.
+    pub fn as_i64(&self) -> Result<i64> {
+        match *self {
+            Value::UInt8(v) => Ok(v.into()),
+            Value::UInt16(v) => Ok(v.into()),
+            Value::UInt32(v) => Ok(v.into()),
+            Value::UInt64(v) if v <= i64::MAX as u64 => Ok(v as i64),
+            Value::UInt64(_) => Err(Error::FromSql(FromSqlError::OutOfRange)),
+            Value::Int8(v) => Ok(v.into()),
+            Value::Int16(v) => Ok(v.into()),
+            Value::Int32(v) => Ok(v.into()),
+            Value::Int64(v) => Ok(v),
+            Value::Nullable(Either::Right(ref inner)) => inner.as_i64(),
+            _ => Err(self.invalid_type("i64")),
+        }
+    }
+
+    /// Returns an integer value of any width as `u64`.
+    ///
+    /// Fails with `FromSqlError::OutOfRange` for a negative value and with
+    /// `FromSqlError::InvalidType` for any value other than an integer,
+    /// including floating point ones. A non-null `Nullable` value is
+    /// converted as its inner value.
+    pub fn as_u64(&self) -> Result<u64> {
+        let signed = match *self {
+            Value::UInt8(v) => return Ok(v.into()),
+            Value::UInt16(v) => return Ok(v.into()),
+            Value::UInt32(v) => return Ok(v.into()),
+            Value::UInt64(v) => return Ok(v),
+            Value::Int8(v) => i64::from(v),
+            Value::Int16(v) => i64::from(v),
+            Value::Int32(v) => i64::from(v),
+            Value::Int64(v) => v,
+            Value::Nullable(Either::Right(ref inner)) => return inner.as_u64(),
+            _ => return Err(self.invalid_type("u64")),
+        };
+
+        if signed < 0 {
+            return Err(Error::FromSql(FromSqlError::OutOfRange));
+        }
+        Ok(signed as u64)
+    }
+
+    /// Returns an integer, floating point or decimal value as `f64`.
+    ///
+    /// Never overflows: integers beyond 2^53 in magnitude and decimals are
+    /// rounded to the nearest `f64`. Fails with `FromSqlError::InvalidType`
+    /// for any other value. A non-null `Nullable` value is converted as its
+    /// inner value.
+    pub fn as_f64(&self) -> Result<f64> {
+        match *self {
+            Value::UInt8(v) => Ok(v.into()),
+            Value::UInt16(v) => Ok(v.into()),
+            Value::UInt32(v) => Ok(v.into()),
+            Value::UInt64(v) => Ok(v as f64),
+            Value::Int8(v) => Ok(v.into()),
+            Value::Int16(v) => Ok(v.into()),
+            Value::Int32(v) => Ok(v.into()),
+            Value::Int64(v) => Ok(v as f64),
+            Value::Float32(v) => Ok(v.into()),
+            Value::Float64(v) => Ok(v),
+            Value::Decimal(ref v) => Ok(v.clone().into()),
+            Value::Nullable(Either::Right(ref inner)) => inner.as_f64(),
+            _ => Err(self.invalid_type("f64")),
+        }
+    }
+
+    fn invalid_type(&self, dst: &'static str) -> Error {
+        Error::FromSql(FromSqlError::InvalidType {
+            src: SqlType::from(self.clone()).to_string(),
+            dst: dst.into(),
+        })
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -141,12 +259,17 @@ impl fmt::Display for Value {
             }
             Value::DateTime(u, tz) => {
                 let time = tz.timestamp(i64::from(*u), 0);
-                write!(f, "{}", time.to_rfc2822())
+                fmt::Display::fmt(&time.format("%Y-%m-%d %H:%M:%S"), f)
             }
             Value::DateTime64(value, params) => {
                 let (precision, tz) = params;
                 let time = to_datetime(*value, *precision, *tz);
-                write!(f, "{}", time.to_rfc2822())
+                fmt::Display::fmt(&time.format("%Y-%m-%d %H:%M:%S"), f)?;
+                if *precision > 0 {
+                    let fraction = time.nanosecond() / 10_u32.pow(9 - *precision);
+                    write!(f, ".{:0width$}", fraction, width = *precision as usize)?;
+                }
+                Ok(())
             }
             Value::Date(v, tz) if f.alternate() => {
                 let time = tz.timestamp(i64::from(*v) * 24 * 3600, 0);
@@ -163,9 +286,13 @@ impl fmt::Display for Value {
                 Either::Right(data) => data.fmt(f),
             },
             Value::Array(_, vs) => {
-                let cells: Vec<String> = vs.iter().map(|v| format!("{}", v)).collect();
+                let cells: Vec<String> = vs.iter().map(Value::to_nested_string).collect();
                 write!(f, "[{}]", cells.join(", "))
             }
+            Value::Tuple(_, vs) => {
+                let cells: Vec<String> = vs.iter().map(Value::to_nested_string).collect();
+                write!(f, "({})", cells.join(", "))
+            }
             Value::Decimal(v) => fmt::Display::fmt(v, f),
             Value::Ipv4(v) => {
                 write!(f, "{}", Ipv4Addr::from(*v))
@@ -179,12 +306,54 @@ impl fmt::Display for Value {
                     Err(e) => write!(f, "{}", e),
                 }
             }
-            Value::Enum8(ref _v1, ref v2) => write!(f, "Enum8, {}", v2),
-            Value::Enum16(ref _v1, ref v2) => write!(f, "Enum16, {}", v2),
+            Value::Enum8(ref items, ref v) => fmt_enum(f, items, v.internal()),
+            Value::Enum16(ref items, ref v) => fmt_enum(f, items, v.internal()),
+        }
+    }
+}
+
+impl Value {
+    /// Formats an element of an array or tuple, with the values ClickHouse
+    /// quotes there in single quotes.
+    fn to_nested_string(&self) -> String {
+        match self {
+            Value::String(_)
+            | Value::Date(..)
+            | Value::DateTime(..)
+            | Value::DateTime64(..)
+            | Value::Enum8(..)
+            | Value::Enum16(..)
+            | Value::Ipv4(_)
+            | Value::Ipv6(_)
+            | Value::Uuid(_) => {
+                let text = self.to_string();
+                let mut result = String::with_capacity(text.len() + 2);
+                result.push('\'');
+                for ch in text.chars() {
+                    if ch == '\'' || ch == '\\' {
+                        result.push('\\');
+                    }
+                    result.push(ch);
+                }
+                result.push('\'');
+                result
+            }
+            Value::Nullable(Either::Right(inner)) => inner.to_nested_string(),
+            _ => self.to_string(),
         }
     }
 }
 
+fn fmt_enum<T>(f: &mut fmt::Formatter, items: &[(String, T)], value: T) -> fmt::Result
+where
+    T: Copy + PartialEq + fmt::Display,
+{
+    match items.iter().find(|(_, item)| *item == value) {
+        Some((name, _)) => fmt::Display::fmt(name, f),
+        None => fmt::Display::fmt(&value, f),
+    }
+}
+
 impl convert::From<Value> for SqlType {
     fn from(source: Value) -> Self {
         match source {
@@ -209,6 +378,7 @@ impl convert::From<Value> for SqlType {
                 }
             },
             Value::Array(t, _) => SqlType::Array(t),
+            Value::Tuple(t, _) => t.clone(),
             Value::Decimal(v) => SqlType::Decimal(v.precision, v.scale),
             Value::Ipv4(_) => SqlType::Ipv4,
             Value::Ipv6(_) => SqlType::Ipv6,
@@ -268,6 +438,24 @@ impl convert::From<Enum16> for Value {
     }
 }
 
+impl convert::From<Ipv4Addr> for Value {
+    fn from(v: Ipv4Addr) -> Value {
+        Value::Ipv4(v.octets())
+    }
+}
+
+impl convert::From<Ipv6Addr> for Value {
+    fn from(v: Ipv6Addr) -> Value {
+        Value::Ipv6(v.octets())
+    }
+}
+
+impl convert::From<Uuid> for Value {
+    fn from(v: Uuid) -> Value {
+        Value::Uuid(*v.as_bytes())
+    }
+}
+
 impl convert::From<AppDateTime> for Value {
     fn from(v: AppDateTime) -> Value {
         Value::DateTime(v.timestamp() as u32, v.timezone())
@@ -280,6 +468,14 @@ impl convert::From<String> for Value {
     }
 }
 
+/// JSON text, e.g. to insert into an `Object('json')` column.
+#[cfg(feature = "json")]
+impl convert::From<serde_json::Value> for Value {
+    fn from(v: serde_json::Value) -> Value {
+        Value::String(Arc::new(v.to_string().into_bytes()))
+    }
+}
+
 impl convert::From<Vec<u8>> for Value {
     fn from(v: Vec<u8>) -> Value {
         Value::String(Arc::new(v))
@@ -404,6 +600,8 @@ mod test {
     use chrono_tz::Tz::{self, UTC};
     use std::fmt;
 
+    use crate::types::Block;
+
     use rand::{
         distributions::{Distribution, Standard},
         random,
@@ -569,6 +767,78 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_display_like_clickhouse() {
+        let tz = Tz::Europe__Moscow;
+        assert_eq!(
+            Value::DateTime(1_577_934_245, tz).to_string(),
+            "2020-01-02 06:04:05"
+        );
+        assert_eq!(
+            Value::DateTime64(1_577_934_245_012, (3, Tz::UTC)).to_string(),
+            "2020-01-02 03:04:05.012"
+        );
+        assert_eq!(
+            Value::DateTime64(1_577_934_245, (0, Tz::UTC)).to_string(),
+            "2020-01-02 03:04:05"
+        );
+
+        let items = vec![("foo".to_string(), 1_i8), ("bar".to_string(), 2)];
+        assert_eq!(Value::Enum8(items.clone(), Enum8::of(2)).to_string(), "bar");
+        assert_eq!(Value::Enum8(items, Enum8::of(3)).to_string(), "3");
+
+        let strings = Value::Array(
+            SqlType::Nullable(SqlType::String.into()).into(),
+            Arc::new(vec![
+                Value::from(Some("it's")),
+                Value::Nullable(Either::Left(SqlType::String.into())),
+            ]),
+        );
+        assert_eq!(strings.to_string(), "['it\\'s', NULL]");
+
+        let tuple = Value::Tuple(
+            SqlType::Tuple(vec![
+                (None, SqlType::UInt8.into()),
+                (None, SqlType::Date.into()),
+            ])
+            .into(),
+            Arc::new(vec![Value::UInt8(1), Value::Date(18263, Tz::UTC)]),
+        );
+        assert_eq!(tuple.to_string(), "(1, '2020-01-02')");
+    }
+
+    #[test]
+    fn test_row_values() {
+        let block = Block::new()
+            .column("id", vec![7_u32])
+            .column("name", vec![Some("foo")])
+            .column("price", vec![Decimal::of(2.5_f64, 2)])
+            .column(
+                "at",
+                vec![Tz::UTC.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap()],
+            )
+            .column("tags", vec![vec!["a", "b"]])
+            .column("ip", vec![Ipv4Addr::new(10, 0, 0, 1)]);
+
+        let row = block.rows().next().unwrap();
+        let values: Vec<Value> = (0..row.len()).map(|i| row.get(i).unwrap()).collect();
+        assert_eq!(values[0], Value::UInt32(7));
+        assert_eq!(values[1], Value::from(Some("foo")));
+
+        let printed: Vec<String> = values.iter().map(Value::to_string).collect();
+        assert_eq!(
+            printed,
+            vec![
+                "7",
+                "foo",
+                "2.50",
+                "2020-01-02 03:04:05",
+                "['a', 'b']",
+                "10.0.0.1"
+            ]
+        );
+    }
+
     #[test]
     fn test_default_fixed_str() {
         for n in 0_usize..1000_usize {
@@ -581,6 +851,48 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_widening_conversions() {
+        assert_eq!(Value::UInt8(200).as_i64().unwrap(), 200);
+        assert_eq!(Value::Int16(-3).as_i64().unwrap(), -3);
+        assert_eq!(Value::UInt64(i64::MAX as u64).as_i64().unwrap(), i64::MAX);
+        assert_eq!(Value::from(Some(7_u32)).as_i64().unwrap(), 7);
+
+        assert_eq!(Value::Int64(5).as_u64().unwrap(), 5);
+        assert_eq!(Value::UInt64(u64::MAX).as_u64().unwrap(), u64::MAX);
+
+        assert_eq!(Value::UInt64(u64::MAX).as_f64().unwrap(), u64::MAX as f64);
+        assert_eq!(Value::Float32(0.5).as_f64().unwrap(), 0.5);
+        assert_eq!(Value::Int8(-1).as_f64().unwrap(), -1.0);
+        assert_eq!(
+            Value::Decimal(Decimal::of(1.25_f64, 2)).as_f64().unwrap(),
+            1.25
+        );
+    }
+
+    #[test]
+    fn test_widening_conversion_errors() {
+        let out_of_range =
+            |result: Result<()>| matches!(result, Err(Error::FromSql(FromSqlError::OutOfRange)));
+        assert!(out_of_range(Value::UInt64(u64::MAX).as_i64().map(drop)));
+        assert!(out_of_range(
+            Value::UInt64(i64::MAX as u64 + 1).as_i64().map(drop)
+        ));
+        assert!(out_of_range(Value::Int8(-1).as_u64().map(drop)));
+        assert!(out_of_range(Value::Int64(i64::MIN).as_u64().map(drop)));
+
+        let invalid_type = |result: Result<()>| {
+            matches!(
+                result,
+                Err(Error::FromSql(FromSqlError::InvalidType { .. }))
+            )
+        };
+        assert!(invalid_type(Value::Float64(1.0).as_i64().map(drop)));
+        assert!(invalid_type(Value::Float32(1.0).as_u64().map(drop)));
+        assert!(invalid_type(Value::from("1").as_f64().map(drop)));
+        assert!(invalid_type(Value::from(None::<u8>).as_i64().map(drop)));
+    }
+
     #[test]
     fn test_size_of() {
         use std::mem;
@@ -606,4 +918,12 @@ mod test {
             Value::Nullable(Either::Right(Value::DateTime(1_546_300_800, Tz::UTC).into()))
         );
     }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_json_value() {
+        let json = serde_json::json!({"a": 1, "b": "x"});
+        let value = Value::from(json.clone());
+        assert_eq!(value, Value::String(br#"{"a":1,"b":"x"}"#.to_vec().into()));
+    }
 }
@@ -34,12 +34,14 @@ pub enum ValueRef<'a> {
     DateTime64(i64, &'a (u32, Tz)),
     Nullable(Either<&'static SqlType, Box<ValueRef<'a>>>),
     Array(&'static SqlType, Arc<Vec<ValueRef<'a>>>),
+    /// Elements of a tuple of the given `SqlType::Tuple` type.
+    Tuple(&'static SqlType, Arc<Vec<ValueRef<'a>>>),
     Decimal(Decimal),
     Ipv4([u8; 4]),
     Ipv6([u8; 16]),
     Uuid([u8; 16]),
-    Enum16(Vec<(String, i16)>, Enum16),
-    Enum8(Vec<(String, i8)>, Enum8),
+    Enum16(&'a [(String, i16)], Enum16),
+    Enum8(&'a [(String, i8)], Enum8),
 }
 
 impl<'a> PartialEq for ValueRef<'a> {
@@ -68,6 +70,7 @@ impl<'a> PartialEq for ValueRef<'a> {
             }
             (ValueRef::Nullable(a), ValueRef::Nullable(b)) => *a == *b,
             (ValueRef::Array(ta, a), ValueRef::Array(tb, b)) => *ta == *tb && *a == *b,
+            (ValueRef::Tuple(ta, a), ValueRef::Tuple(tb, b)) => *ta == *tb && *a == *b,
             (ValueRef::Decimal(a), ValueRef::Decimal(b)) => *a == *b,
             (ValueRef::Enum8(a0, a1), ValueRef::Enum8(b0, b1)) => *a1 == *b1 && *a0 == *b0,
             (ValueRef::Enum16(a0, a1), ValueRef::Enum16(b0, b1)) => *a1 == *b1 && *a0 == *b0,
@@ -133,6 +136,10 @@ impl<'a> fmt::Display for ValueRef<'a> {
                 let cells: Vec<String> = vs.iter().map(|v| format!("{}", v)).collect();
                 write!(f, "[{}]", cells.join(", "))
             }
+            ValueRef::Tuple(_, vs) => {
+                let cells: Vec<String> = vs.iter().map(|v| format!("{}", v)).collect();
+                write!(f, "({})", cells.join(", "))
+            }
             ValueRef::Decimal(v) => fmt::Display::fmt(v, f),
             ValueRef::Ipv4(v) => {
                 write!(f, "{}", Ipv4Addr::from(*v))
@@ -173,9 +180,10 @@ impl<'a> convert::From<ValueRef<'a>> for SqlType {
                 Either::Right(value_ref) => SqlType::Nullable(SqlType::from(*value_ref).into()),
             },
             ValueRef::Array(t, _) => SqlType::Array(t),
+            ValueRef::Tuple(t, _) => t.clone(),
             ValueRef::Decimal(v) => SqlType::Decimal(v.precision, v.scale),
-            ValueRef::Enum8(values, _) => SqlType::Enum8(values),
-            ValueRef::Enum16(values, _) => SqlType::Enum16(values),
+            ValueRef::Enum8(values, _) => SqlType::Enum8(values.to_vec()),
+            ValueRef::Enum16(values, _) => SqlType::Enum16(values.to_vec()),
             ValueRef::Ipv4(_) => SqlType::Ipv4,
             ValueRef::Ipv6(_) => SqlType::Ipv6,
             ValueRef::Uuid(_) => SqlType::Uuid,
@@ -188,9 +196,23 @@ impl<'a> convert::From<ValueRef<'a>> for SqlType {
 }
 
 impl<'a> ValueRef<'a> {
+    /// Returns the element of a named tuple called `name`, or `None` if the
+    /// value is not a tuple or has no such element.
+    pub fn tuple_element(&self, name: &str) -> Option<&ValueRef<'a>> {
+        match self {
+            ValueRef::Tuple(sql_type, values) => values.get(sql_type.tuple_position(name)?),
+            _ => None,
+        }
+    }
+
+    /// Returns the text of a `String` value or the label of an `Enum8` or
+    /// `Enum16` value.
     pub fn as_str(&self) -> Result<&'a str> {
-        if let ValueRef::String(t) = self {
-            return Ok(str::from_utf8(t)?);
+        match self {
+            ValueRef::String(t) => return Ok(str::from_utf8(t)?),
+            ValueRef::Enum8(values, v) => return enum_label(values, v.internal()),
+            ValueRef::Enum16(values, v) => return enum_label(values, v.internal()),
+            _ => {}
         }
         let from = SqlType::from(self.clone()).to_string();
         Err(Error::FromSql(FromSqlError::InvalidType {
@@ -216,6 +238,18 @@ impl<'a> ValueRef<'a> {
     }
 }
 
+fn enum_label<T: Copy + PartialEq + fmt::Display>(
+    values: &[(String, T)],
+    value: T,
+) -> Result<&str> {
+    match values.iter().find(|(_, v)| *v == value) {
+        Some((label, _)) => Ok(label),
+        None => Err(Error::FromSql(FromSqlError::UnknownEnumValue {
+            value: value.to_string(),
+        })),
+    }
+}
+
 impl<'a> From<ValueRef<'a>> for Value {
     fn from(borrowed: ValueRef<'a>) -> Self {
         match borrowed {
@@ -247,9 +281,13 @@ impl<'a> From<ValueRef<'a>> for Value {
                 }
                 Value::Array(t, Arc::new(value_list))
             }
+            ValueRef::Tuple(t, vs) => {
+                let values = vs.iter().map(|v| Value::from(v.clone())).collect();
+                Value::Tuple(t, Arc::new(values))
+            }
             ValueRef::Decimal(v) => Value::Decimal(v),
-            ValueRef::Enum8(e_v, v) => Value::Enum8(e_v, v),
-            ValueRef::Enum16(e_v, v) => Value::Enum16(e_v, v),
+            ValueRef::Enum8(e_v, v) => Value::Enum8(e_v.to_vec(), v),
+            ValueRef::Enum16(e_v, v) => Value::Enum16(e_v.to_vec(), v),
             ValueRef::Ipv4(v) => Value::Ipv4(v),
             ValueRef::Ipv6(v) => Value::Ipv6(v),
             ValueRef::Uuid(v) => Value::Uuid(v),
@@ -329,9 +367,13 @@ impl<'a> From<&'a Value> for ValueRef<'a> {
                 }
                 ValueRef::Array(*t, Arc::new(ref_vec))
             }
+            Value::Tuple(t, vs) => {
+                let values = vs.iter().map(ValueRef::from).collect();
+                ValueRef::Tuple(t, Arc::new(values))
+            }
             Value::Decimal(v) => ValueRef::Decimal(v.clone()),
-            Value::Enum8(values, v) => ValueRef::Enum8(values.to_vec(), *v),
-            Value::Enum16(values, v) => ValueRef::Enum16(values.to_vec(), *v),
+            Value::Enum8(values, v) => ValueRef::Enum8(values, *v),
+            Value::Enum16(values, v) => ValueRef::Enum16(values, *v),
             Value::Ipv4(v) => ValueRef::Ipv4(*v),
             Value::Ipv6(v) => ValueRef::Ipv6(*v),
             Value::Uuid(v) => ValueRef::Uuid(*v),
@@ -484,7 +526,7 @@ mod test {
     #[test]
     fn test_size_of() {
         use std::mem;
-        assert_eq!(32, mem::size_of::<[ValueRef<'_>; 1]>());
+        assert_eq!(24, mem::size_of::<[ValueRef<'_>; 1]>());
     }
 
     #[test]
@@ -0,0 +1,315 @@
+//! Measures the allocations made while decoding large multi-block results.
+//! Lives in its own test binary because it installs a counting global
+//! allocator; the counters are per thread so the tests can run in parallel.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    io::Cursor,
+};
+
+use clickhouse_rs::types::{Packet, PacketReader};
+use clickhouse_rs_cityhash_sys::city_hash_128;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+}
+
+fn count(size: usize) {
+    let _ = ALLOCATIONS.try_with(|c| {
+        let (allocations, bytes) = c.get();
+        c.set((allocations + 1, bytes + size));
+    });
+}
+
+/// Allocations and allocated bytes of the current thread so far.
+fn allocations() -> (usize, usize) {
+    ALLOCATIONS.with(Cell::get)
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const BLOCKS: usize = 50;
+const ROWS: usize = 10_000;
+
+fn uvarint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn string(buffer: &mut Vec<u8>, value: &str) {
+    uvarint(buffer, value.len() as u64);
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+/// Block info, columns and `rows` rows of
+/// `(n UInt64, s String, x Nullable(Int32))` of the `block`th data packet.
+fn block_body(buffer: &mut Vec<u8>, block: usize, rows: usize) {
+    buffer.extend_from_slice(&[1, 0, 2, 0xff, 0xff, 0xff, 0xff, 0]); // block info
+    uvarint(buffer, 3);
+    uvarint(buffer, rows as u64);
+
+    string(buffer, "n");
+    string(buffer, "UInt64");
+    for i in 0..rows {
+        buffer.extend_from_slice(&((block * rows + i) as u64).to_le_bytes());
+    }
+
+    string(buffer, "s");
+    string(buffer, "String");
+    for i in 0..rows {
+        string(buffer, &format!("row-{}", block * rows + i));
+    }
+
+    string(buffer, "x");
+    string(buffer, "Nullable(Int32)");
+    for i in 0..rows {
+        buffer.push((i % 2 == 0) as u8);
+    }
+    for i in 0..rows {
+        buffer.extend_from_slice(&(i as i32).to_le_bytes());
+    }
+}
+
+/// `BLOCKS` data packets of `ROWS` rows, then the end of stream.
+fn response() -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    for block in 0..BLOCKS {
+        uvarint(&mut buffer, 1); // data packet
+        string(&mut buffer, ""); // temporary table name
+        block_body(&mut buffer, block, ROWS);
+    }
+
+    uvarint(&mut buffer, 5); // end of stream
+    buffer
+}
+
+/// Same as `response`, but with `blocks` LZ4 compressed blocks of `rows`
+/// rows.
+fn compressed_response(blocks: usize, rows: usize) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    let mut body = Vec::new();
+
+    for block in 0..blocks {
+        uvarint(&mut buffer, 1); // data packet
+        string(&mut buffer, ""); // temporary table name
+
+        body.clear();
+        block_body(&mut body, block, rows);
+
+        let data = lz4::block::compress(&body, None, false).unwrap();
+        let mut chunk = vec![0x82];
+        chunk.extend_from_slice(&(data.len() as u32 + 9).to_le_bytes());
+        chunk.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(&data);
+
+        let hash = city_hash_128(&chunk);
+        buffer.extend_from_slice(&hash.lo.to_le_bytes());
+        buffer.extend_from_slice(&hash.hi.to_le_bytes());
+        buffer.extend_from_slice(&chunk);
+    }
+
+    uvarint(&mut buffer, 5); // end of stream
+    buffer
+}
+
+/// Decodes `source` with `blocks` blocks of `rows` rows, dropping each block
+/// before reading the next one, and returns the number of allocations and
+/// allocated bytes per block.
+fn allocations_per_block(
+    source: &[u8],
+    blocks: usize,
+    rows: usize,
+    compression: bool,
+    reuse: bool,
+) -> (usize, usize) {
+    let mut reader = PacketReader::new(Cursor::new(source))
+        .compression(compression)
+        .reuse_blocks(reuse);
+    let (start, start_bytes) = allocations();
+
+    let mut count = 0;
+    let mut checksum = 0_u64;
+    loop {
+        match reader.read_packet().unwrap() {
+            Packet::Block(block) => {
+                assert_eq!(block.row_count(), rows);
+                let n: u64 = block.get(rows - 1, "n").unwrap();
+                let s: &str = block.get(rows - 1, "s").unwrap();
+                let x: Option<i32> = block.get(rows - 1, "x").unwrap();
+                assert_eq!(s.strip_prefix("row-").unwrap().parse::<u64>().unwrap(), n);
+                assert_eq!(x, Some(rows as i32 - 1));
+                checksum += n;
+                count += 1;
+            }
+            Packet::Eof(()) => break,
+            _ => panic!("unexpected packet"),
+        }
+    }
+
+    assert_eq!(count, blocks);
+    assert_eq!(
+        checksum,
+        (1..=blocks as u64)
+            .map(|b| b * rows as u64 - 1)
+            .sum::<u64>()
+    );
+
+    let (total, total_bytes) = allocations();
+    (
+        (total - start) / blocks,
+        (total_bytes - start_bytes) / blocks,
+    )
+}
+
+#[test]
+fn test_block_reuse_allocations() {
+    let source = response();
+
+    let (fresh, fresh_bytes) = allocations_per_block(&source, BLOCKS, ROWS, false, false);
+    let (reused, reused_bytes) = allocations_per_block(&source, BLOCKS, ROWS, false, true);
+
+    assert!(reused < fresh);
+    assert!(reused_bytes * 20 < fresh_bytes);
+}
+
+#[test]
+fn test_compressed_block_reuse_allocations() {
+    let (blocks, rows) = (1_000, ROWS / 10);
+    let source = compressed_response(blocks, rows);
+
+    let (fresh, fresh_bytes) = allocations_per_block(&source, blocks, rows, true, false);
+    let (reused, reused_bytes) = allocations_per_block(&source, blocks, rows, true, true);
+
+    assert!(reused < fresh);
+    // Neither the columns nor the decompression buffers are allocated again.
+    assert!(reused_bytes * 20 < fresh_bytes);
+}
+
+/// `BLOCKS` data packets of `rows` rows of `(s String, f FixedString(8))`,
+/// then the end of stream.
+fn string_response(rows: usize) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    for block in 0..BLOCKS {
+        uvarint(&mut buffer, 1); // data packet
+        string(&mut buffer, ""); // temporary table name
+        buffer.extend_from_slice(&[1, 0, 2, 0xff, 0xff, 0xff, 0xff, 0]); // block info
+        uvarint(&mut buffer, 2);
+        uvarint(&mut buffer, rows as u64);
+
+        string(&mut buffer, "s");
+        string(&mut buffer, "String");
+        for i in 0..rows {
+            string(&mut buffer, &format!("row-{}", block * rows + i));
+        }
+
+        string(&mut buffer, "f");
+        string(&mut buffer, "FixedString(8)");
+        for i in 0..rows {
+            buffer.extend_from_slice(&((block * rows + i) as u64).to_le_bytes());
+        }
+    }
+
+    uvarint(&mut buffer, 5); // end of stream
+    buffer
+}
+
+/// Decodes `source` without block reuse and returns the number of
+/// allocations per block spent on decoding and on borrowing every value.
+fn string_allocations_per_block(source: &[u8], rows: usize) -> (usize, usize) {
+    let mut reader = PacketReader::new(Cursor::new(source));
+    let mut decoding = 0;
+    let mut borrowing = 0;
+
+    loop {
+        let (before, _) = allocations();
+        let packet = reader.read_packet().unwrap();
+        let (decoded, _) = allocations();
+        decoding += decoded - before;
+
+        match packet {
+            Packet::Block(block) => {
+                let mut total = 0;
+                for row in 0..rows {
+                    let s: &str = block.get(row, "s").unwrap();
+                    let f: &[u8] = block.get(row, "f").unwrap();
+                    total += s.len() + f.len();
+                }
+                assert!(total > rows * 8);
+                let (borrowed, _) = allocations();
+                borrowing += borrowed - decoded;
+            }
+            Packet::Eof(()) => break,
+            _ => panic!("unexpected packet"),
+        }
+    }
+
+    (decoding / BLOCKS, borrowing / BLOCKS)
+}
+
+#[test]
+fn test_string_allocations() {
+    let (small, small_borrowing) = string_allocations_per_block(&string_response(ROWS), ROWS);
+    let (large, large_borrowing) =
+        string_allocations_per_block(&string_response(ROWS * 10), ROWS * 10);
+
+    // Values land in a few arenas, so the count does not grow with the rows.
+    assert!(large <= small + 2);
+    // Borrowed values point into the arenas.
+    assert_eq!(small_borrowing, 0);
+    assert_eq!(large_borrowing, 0);
+}
+
+#[test]
+fn test_string_column_load_allocations() {
+    let rows = 1_000_000;
+    let mut buffer = Vec::new();
+    uvarint(&mut buffer, 1); // data packet
+    string(&mut buffer, ""); // temporary table name
+    buffer.extend_from_slice(&[1, 0, 2, 0xff, 0xff, 0xff, 0xff, 0]); // block info
+    uvarint(&mut buffer, 1);
+    uvarint(&mut buffer, rows as u64);
+    string(&mut buffer, "s");
+    string(&mut buffer, "String");
+    for i in 0..rows {
+        string(&mut buffer, &format!("{:08}", i));
+    }
+    uvarint(&mut buffer, 5); // end of stream
+
+    let mut reader = PacketReader::new(Cursor::new(&buffer[..]));
+    let (before, before_bytes) = allocations();
+    let block = match reader.read_packet().unwrap() {
+        Packet::Block(block) => block,
+        _ => panic!("unexpected packet"),
+    };
+    let (after, after_bytes) = allocations();
+
+    assert_eq!(block.get::<&str, _>(rows - 1, "s").unwrap(), "00999999");
+    // The bytes of all the strings share one buffer.
+    assert!(after - before <= 10);
+    assert!(after_bytes - before_bytes < rows * 48);
+}
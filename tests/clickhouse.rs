@@ -8,20 +8,28 @@ use std::{
     f64::EPSILON,
     fmt::Debug,
     net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
     sync::{
         atomic::{AtomicUsize, Ordering},
-        Arc,
+        Arc, Mutex,
     },
+    time::{Duration, Instant},
 };
 
 use chrono::prelude::*;
 use chrono_tz::Tz::{self, UCT, UTC};
-use tokio::prelude::*;
+use tokio::{prelude::*, timer::Delay};
 
 use clickhouse_rs::{
-    errors::{codes, Error},
-    types::{Block, Decimal, FromSql, Enum16, Enum8,},
-    ClientHandle, Pool,
+    clickhouse_enum,
+    errors::{codes, DriverError, Error, FromSqlError, SchemaProblem},
+    row,
+    types::{
+        escape_identifier, escape_literal, schema_to_ddl, ArrayColumnData, Block, CloseReason,
+        Decimal, Enum16, Enum8, FromSql, LogLevel, Options, OverflowMode, PoolEvents, Query,
+        QueryCounts, QueryLog, SqlType, StringColumnData, Value, VectorColumnData,
+    },
+    BufferedInserter, ClientHandle, Pool,
 };
 use uuid::Uuid;
 
@@ -384,22 +392,23 @@ fn test_temporary_table() {
 fn test_with_totals() {
     let ddl = "
         CREATE TABLE clickhouse_test_with_totals (
-            country String
+            k String,
+            v UInt64
         ) Engine=Memory";
 
     let query = "
-        SELECT
-            country,
-            COUNT(*)
+        SELECT k, sum(v) AS s
         FROM clickhouse_test_with_totals
-        GROUP BY country
-            WITH TOTALS";
+        GROUP BY k WITH TOTALS
+        ORDER BY k";
 
-    let block = Block::new().column("country", vec!["RU", "EN", "RU", "RU", "EN", "RU"]);
+    let block = Block::new()
+        .column("k", vec!["RU", "EN", "RU", "RU", "EN", "RU"])
+        .column("v", vec![1_u64, 2, 3, 4, 5, 6]);
 
     let expected = Block::new()
-        .column("country", vec!["EN", "RU", ""])
-        .column("country", vec![2u64, 4, 6]);
+        .column("k", vec!["EN", "RU"])
+        .column("s", vec![7_u64, 14]);
 
     let pool = Pool::new(database_url());
     let done = pool
@@ -408,11 +417,59 @@ fn test_with_totals() {
         .and_then(move |c| c.execute(ddl))
         .and_then(move |c| c.insert("clickhouse_test_with_totals", block))
         .and_then(move |c| c.query(query).fetch_all())
-        .map(move |(_, block)| assert_eq!(&expected, &block));
+        .and_then(move |(_, block)| {
+            assert_eq!(&expected, &block);
+
+            let totals = block.totals().unwrap();
+            assert_eq!(totals.row_count(), 1);
+            assert_eq!(totals.get::<u64, _>(0, "s")?, 21);
+            assert!(block.extremes().is_none());
+            Ok(())
+        });
 
     run(done).unwrap();
 }
 
+#[test]
+fn test_stream_totals() {
+    let ddl = "
+        CREATE TABLE clickhouse_test_stream_totals (
+            k String,
+            v UInt64
+        ) Engine=Memory";
+
+    let query = "SELECT k, sum(v) AS s FROM clickhouse_test_stream_totals GROUP BY k WITH TOTALS";
+
+    let block = Block::new()
+        .column("k", vec!["RU", "EN", "RU", "RU", "EN", "RU"])
+        .column("v", vec![1_u64, 2, 3, 4, 5, 6]);
+
+    let totals = Arc::new(Mutex::new(None));
+    let extremes = Arc::new(Mutex::new(None));
+    let (totals_in, extremes_in) = (totals.clone(), extremes.clone());
+
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_stream_totals"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert("clickhouse_test_stream_totals", block))
+        .and_then(move |c| {
+            c.query(query)
+                .with_totals(move |block| *totals_in.lock().unwrap() = Some(block))
+                .with_extremes(move |block| *extremes_in.lock().unwrap() = Some(block))
+                .stream_blocks()
+                .fold(0, |rows, block| Ok::<_, Error>(rows + block.row_count()))
+        });
+
+    assert_eq!(run(done).unwrap(), 2);
+
+    let totals = totals.lock().unwrap().take().unwrap();
+    assert_eq!(totals.row_count(), 1);
+    assert_eq!(totals.get::<u64, _>(0, "s").unwrap(), 21);
+    assert!(extremes.lock().unwrap().is_none());
+}
+
 #[test]
 fn test_stream_rows() {
     let pool = Pool::new(database_url());
@@ -941,7 +998,7 @@ fn test_decimal() {
             let ox0: Option<Decimal> = block.get(0, "ox")?;
 
             assert_eq!(2, block.row_count());
-            assert_eq!(1.234, x.into());
+            assert_eq!(1.234, f64::from(x));
             assert_eq!(Some(1.23), ox.map(|v| v.into()));
             assert_eq!(None, ox0);
 
@@ -976,6 +1033,1945 @@ fn test_reconnect() {
     assert_eq!(2, counter.load(Ordering::SeqCst))
 }
 
+#[test]
+fn test_query_progress() {
+    let rows_read = Arc::new(AtomicUsize::new(0));
+    let pool = Pool::new(database_url());
+
+    let counter = rows_read.clone();
+    let done = pool
+        .get_handle()
+        .and_then(move |c| {
+            c.query("SELECT count() FROM numbers(10000000)")
+                .with_progress(move |progress| {
+                    counter.fetch_add(progress.rows as usize, Ordering::SeqCst);
+                })
+                .fetch_all()
+        })
+        .and_then(|(_, block)| {
+            let count: u64 = block.get(0, 0)?;
+            assert_eq!(count, 10_000_000);
+            Ok(())
+        });
+
+    run(done).unwrap();
+    assert_eq!(rows_read.load(Ordering::SeqCst), 10_000_000);
+}
+
+#[test]
+fn test_query_stats() {
+    let pool = Pool::new(database_url());
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| {
+            c.query("SELECT number FROM numbers(100) LIMIT 10")
+                .fetch_all_with_stats()
+        })
+        .and_then(|(_, block, stats)| {
+            assert_eq!(block.row_count(), 10);
+
+            let info = stats.profile_info.unwrap();
+            assert!(info.applied_limit);
+            assert_eq!(info.rows_before_limit, 100);
+            assert_eq!(info.rows, 10);
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_query_with_logs() {
+    let pool = Pool::new(database_url());
+    let entries = Arc::new(AtomicUsize::new(0));
+    let counter = entries.clone();
+
+    let done = pool
+        .get_handle()
+        .and_then(move |c| {
+            c.query("SELECT 1")
+                .with_logs(LogLevel::Trace, move |entry| {
+                    assert!(!entry.text.is_empty());
+                    counter.fetch_add(1, Ordering::SeqCst);
+                })
+                .fetch_all()
+        })
+        .and_then(|(_, block)| {
+            let value: u8 = block.get(0, 0)?;
+            assert_eq!(value, 1);
+            Ok(())
+        });
+
+    run(done).unwrap();
+    assert!(entries.load(Ordering::SeqCst) > 0);
+}
+
+#[test]
+fn test_totals_and_extremes() {
+    let query = "
+        SELECT number % 3 AS k, sum(number) AS s
+        FROM numbers(10)
+        GROUP BY k WITH TOTALS
+        ORDER BY k
+        SETTINGS extremes = 1";
+
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(move |c| c.query(query).fetch_all())
+        .and_then(|(_, block)| {
+            assert_eq!(block.row_count(), 3);
+
+            let mut sum = 0;
+            for row in block.rows() {
+                let s: u64 = row.get("s")?;
+                sum += s;
+            }
+
+            let totals = block.totals().unwrap();
+            assert_eq!(totals.row_count(), 1);
+            assert_eq!(totals.get::<u64, _>(0, "s")?, sum);
+
+            let (min, max) = block.extremes().unwrap();
+            assert_eq!(min.get::<u8, _>(0, "k")?, 0);
+            assert_eq!(max.get::<u8, _>(0, "k")?, 2);
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_extremes_for() {
+    use std::collections::HashMap;
+
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(|c| {
+            c.query("SELECT number * 7 % 10 AS x, toFloat64(number) / 4 AS y FROM numbers(10) SETTINGS extremes = 1")
+                .fetch_all()
+        })
+        .and_then(|(c, block)| {
+            let (min, max) = block.extremes_for::<HashMap<String, f64>>().unwrap().unwrap();
+            assert!(min["x"] <= max["x"]);
+            assert!(min["y"] <= max["y"]);
+            assert_eq!((min["x"], max["x"]), (0.0, 9.0));
+            assert_eq!((min["y"], max["y"]), (0.0, 2.25));
+
+            c.query("SELECT number AS x FROM numbers(10)").fetch_all()
+        })
+        .and_then(|(_, block)| {
+            assert!(block.extremes_for::<HashMap<String, u64>>()?.is_none());
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_external_tables() {
+    let ids: Vec<u64> = (0..100_000).map(|i| i * 2).collect();
+    let ids = Block::new().column("id", ids);
+    let names = Block::new()
+        .column("id", vec![0_u64, 1, 4])
+        .column("name", vec!["zero", "one", "four"]);
+
+    let query = "
+        SELECT count(), sum(number)
+        FROM numbers(200000)
+        WHERE number IN ids";
+
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(move |c| c.query(query).with_external_table("ids", ids).fetch_all())
+        .and_then(move |(c, block)| {
+            let count: u64 = block.get(0, 0).unwrap();
+            let sum: u64 = block.get(0, 1).unwrap();
+            assert_eq!(count, 100_000);
+            assert_eq!(sum, (0..100_000_u64).map(|i| i * 2).sum::<u64>());
+
+            let query = "
+                SELECT name
+                FROM names
+                WHERE id IN (SELECT id FROM evens)
+                ORDER BY id";
+
+            c.query(query)
+                .with_external_table("names", names)
+                .with_external_table("evens", Block::new().column("id", vec![0_u64, 2, 4]))
+                .fetch_all()
+        })
+        .and_then(|(_, block)| {
+            let names: Vec<String> = block
+                .rows()
+                .map(|row| row.get("name"))
+                .collect::<Result<_, _>>()?;
+            assert_eq!(names, vec!["zero", "four"]);
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_schema() {
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.query("SELECT 1 AS a, 'x' AS b").fetch_all())
+        .and_then(|(_, block)| {
+            assert_eq!(
+                block.schema(),
+                vec![("a", SqlType::UInt8), ("b", SqlType::String)]
+            );
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_bind_params() {
+    let pool = Pool::new(database_url());
+    let time = Tz::Europe__Moscow
+        .with_ymd_and_hms(2020, 1, 2, 3, 4, 5)
+        .unwrap();
+    let done = pool
+        .get_handle()
+        .and_then(move |c| {
+            c.query("SELECT ? AS name, ? AS time, ? AS n, '?' AS q")
+                .bind("o'brien\\")
+                .bind(time)
+                .bind(None::<u32>)
+                .fetch_all()
+        })
+        .and_then(move |(c, block)| {
+            let name: &str = block.get(0, "name").unwrap();
+            let actual: DateTime<Tz> = block.get(0, "time").unwrap();
+            let n: Option<u32> = block.get(0, "n").unwrap();
+            let q: &str = block.get(0, "q").unwrap();
+            assert_eq!(name, "o'brien\\");
+            assert_eq!(actual, time);
+            assert_eq!(n, None);
+            assert_eq!(q, "?");
+            c.query("SELECT ?, ?").bind(1_u8).fetch_all()
+        })
+        .then(|result| {
+            match result {
+                Err(Error::Driver(_)) => {}
+                other => panic!("unexpected result: {:?}", other.map(|(_, b)| b)),
+            }
+            Ok::<_, Error>(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_failover() {
+    let url = database_url().replacen("://", "://127.0.0.1:1,", 1);
+    let pool = Pool::new(url);
+    let done = pool
+        .get_handle()
+        .and_then(|c| {
+            assert_ne!(c.host().unwrap().port(), Some(1));
+            c.query("SELECT 1 AS a").fetch_all()
+        })
+        .and_then(|(_, block)| {
+            assert_eq!(block.row_count(), 1);
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_insert_stream() {
+    let ddl = "
+        CREATE TABLE clickhouse_test_insert_stream (
+            id UInt64,
+            name String
+        ) Engine=Memory";
+
+    let rows = stream::iter_ok::<_, Error>((0..10_000_u64).map(|id| {
+        let name = format!("row {}", id);
+        row! { id, name }
+    }));
+
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_insert_stream"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert_stream("clickhouse_test_insert_stream", rows, 3_000))
+        .and_then(|c| {
+            c.query("SELECT count() AS n, sum(id) AS s FROM clickhouse_test_insert_stream")
+                .fetch_all()
+        })
+        .and_then(|(c, block)| {
+            let n: u64 = block.get(0, "n").unwrap();
+            let sum: u64 = block.get(0, "s").unwrap();
+            assert_eq!(n, 10_000);
+            assert_eq!(sum, 9_999 * 10_000 / 2);
+
+            let empty = stream::iter_ok::<_, Error>(Vec::<Vec<(String, Value)>>::new());
+            c.insert_stream("clickhouse_test_insert_stream", empty, 3_000)
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_load_balancing() {
+    let url = format!(
+        "{}&load_balancing=first_alive&pool_min=2&pool_max=2",
+        database_url().replacen("://", "://127.0.0.1:1,", 1)
+    );
+    let pool = Pool::new(url);
+    let first = pool.get_handle();
+    let second = pool.get_handle();
+    let done = first.join(second).and_then(|(a, b)| {
+        assert_eq!(a.host(), b.host());
+        assert_ne!(a.host().unwrap().port(), Some(1));
+        Ok(())
+    });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_insert_column_major() {
+    let ddl = "
+        CREATE TABLE clickhouse_test_insert_column_major (
+            id UInt64,
+            name String,
+            score Float64
+        ) Engine=Memory";
+
+    let n = 1000_u64;
+    let mut ids = VectorColumnData::with_capacity(n as usize);
+    let mut names = StringColumnData::with_capacity(n as usize);
+    let mut scores = VectorColumnData::with_capacity(n as usize);
+    for i in 0..n {
+        ids.append(i);
+        names.append(format!("name-{}", i));
+        scores.append(i as f64 / 2.0);
+    }
+
+    let block = Block::new()
+        .try_add_column("id", ids)
+        .and_then(|block| block.try_add_column("name", names))
+        .and_then(|block| block.try_add_column("score", scores))
+        .unwrap();
+
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_insert_column_major"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert("clickhouse_test_insert_column_major", block))
+        .and_then(move |c| {
+            c.query(
+                "SELECT count(), sum(id), max(name), sum(score)
+                 FROM clickhouse_test_insert_column_major",
+            )
+            .fetch_all()
+        })
+        .and_then(move |(_, block)| {
+            assert_eq!(block.get::<u64, _>(0, 0)?, n);
+            assert_eq!(block.get::<u64, _>(0, 1)?, n * (n - 1) / 2);
+            assert_eq!(block.get::<&str, _>(0, 2)?, "name-999");
+            assert!((block.get::<f64, _>(0, 3)? - (n * (n - 1) / 4) as f64).abs() < f64::EPSILON);
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_query_id() {
+    let pool = Pool::new(database_url());
+    let query_id = "clickhouse_rs_test_query_id";
+
+    let query = pool.get_handle().and_then(move |c| {
+        let result = c
+            .query(
+                "SELECT query_id FROM system.processes WHERE query LIKE '%test_query_id_marker%'",
+            )
+            .with_query_id(query_id);
+        assert_eq!(result.query_id(), query_id);
+        result.fetch_all()
+    });
+
+    let generated = pool.get_handle().and_then(|c| {
+        let result = c.query("SELECT 1").with_query_id("");
+        let generated = result.query_id().to_string();
+        assert!(!generated.is_empty());
+        result.fetch_all().map(move |(c, _)| (c, generated))
+    });
+
+    let done = query
+        .join(generated)
+        .and_then(move |((_, block), (_, generated))| {
+            let ids: Vec<String> = block
+                .rows()
+                .map(|row| row.get("query_id"))
+                .collect::<Result<_, _>>()?;
+            assert_eq!(ids, vec![query_id.to_string()]);
+            assert_ne!(generated, query_id);
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_server_info() {
+    let pool = Pool::new(database_url());
+
+    let done = pool.get_handle().and_then(|c| {
+        let info = c.server_info().clone();
+        assert!(!info.name.is_empty());
+        assert!(info.version() >= (1, 1, 0));
+        assert!(info.revision > 0);
+        assert!(info.protocol_revision() > 0);
+        assert!(info.protocol_revision() <= info.revision);
+
+        c.query("SELECT timezone() AS tz, version() AS version")
+            .fetch_all()
+            .and_then(move |(_, block)| {
+                let tz: String = block.get(0, "tz")?;
+                let version: String = block.get(0, "version")?;
+                assert_eq!(info.timezone.name(), tz);
+                assert!(
+                    version.starts_with(&format!("{}.{}.", info.major_version, info.minor_version))
+                );
+                Ok(())
+            })
+    });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_default_timezone() {
+    let url = format!("{}{}", database_url(), "&timezone=Asia/Tokyo");
+    let pool = Pool::new(url);
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| {
+            c.query("SELECT toDateTime(0, 'UTC') AS utc, toDateTime(0) AS plain")
+                .fetch_all()
+        })
+        .and_then(|(_, block)| {
+            let utc: DateTime<Tz> = block.get(0, "utc")?;
+            let plain: DateTime<Tz> = block.get(0, "plain")?;
+            assert_eq!(utc.timezone(), Tz::UTC);
+            assert_eq!(plain.timezone(), Tz::Asia__Tokyo);
+            assert_eq!(utc, plain);
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_select_null() {
+    let pool = Pool::new(database_url());
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.query("SELECT NULL AS x").fetch_all())
+        .and_then(|(_, block)| {
+            assert_eq!(block.row_count(), 1);
+            assert_eq!(
+                block.columns()[0].sql_type(),
+                SqlType::Nullable(&SqlType::Nothing)
+            );
+
+            let x: Option<u8> = block.get(0, "x")?;
+            assert_eq!(x, None);
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_block_reuse() {
+    let pool = Pool::new(database_url());
+    let sql = "SELECT number, toString(number) AS s FROM system.numbers LIMIT 100000 \
+               SETTINGS max_block_size = 1000";
+
+    let done = pool.get_handle().and_then(move |c| {
+        c.query(sql)
+            .with_block_reuse()
+            .stream_blocks()
+            .fold(0_u64, |expected, block| {
+                for row in 0..block.row_count() {
+                    let number: u64 = block.get(row, "number")?;
+                    let s: String = block.get(row, "s")?;
+                    assert_eq!(number, expected + row as u64);
+                    assert_eq!(s, number.to_string());
+                }
+                Ok::<_, Error>(expected + block.row_count() as u64)
+            })
+            .map(|total| assert_eq!(total, 100_000))
+    });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_session() {
+    let pool = Pool::new(database_url());
+    let other_pool = pool.clone();
+    let later_pool = pool.clone();
+
+    let done = pool
+        .get_handle()
+        .map(ClientHandle::with_session)
+        .and_then(|c| {
+            assert!(c.is_session());
+            c.execute("CREATE TEMPORARY TABLE clickhouse_test_session (x UInt8)")
+        })
+        .and_then(|c| c.execute("INSERT INTO clickhouse_test_session VALUES (1), (2)"))
+        .and_then(|c| c.execute("SET max_block_size = 1234"))
+        .and_then(|c| {
+            c.query("SELECT count() AS n, getSetting('max_block_size') AS m FROM clickhouse_test_session")
+                .fetch_all()
+        })
+        .and_then(move |(c, block)| {
+            let n: u64 = block.get(0, "n").unwrap();
+            let m: u64 = block.get(0, "m").unwrap();
+            assert_eq!((n, m), (2, 1234));
+
+            // Other handles don't see the temporary table while the session
+            // is alive, nor after it ended.
+            other_pool
+                .get_handle()
+                .and_then(|other| other.query("SELECT * FROM clickhouse_test_session").fetch_all())
+                .then(move |result| {
+                    assert!(result.is_err());
+                    drop(c);
+                    later_pool.get_handle()
+                })
+        })
+        .and_then(|c| c.query("SELECT * FROM clickhouse_test_session").fetch_all().then(Ok))
+        .map(|result: Result<_, Error>| assert!(result.is_err()));
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_database() {
+    let pool = Pool::new(database_url());
+    let options = Options::from_str(&database_url())
+        .unwrap()
+        .database("clickhouse_test_db");
+    let db_pool = Pool::new(options);
+    let other_pool = pool.clone();
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("CREATE DATABASE IF NOT EXISTS clickhouse_test_db"))
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_db.clickhouse_test_database"))
+        .and_then(|c| {
+            c.execute(
+                "CREATE TABLE clickhouse_test_db.clickhouse_test_database (x UInt8) ENGINE = Memory",
+            )
+        })
+        .and_then(|c| c.execute("INSERT INTO clickhouse_test_db.clickhouse_test_database VALUES (7)"))
+        .and_then(move |_| db_pool.get_handle())
+        .and_then(|c| c.query("SELECT x FROM clickhouse_test_database").fetch_all())
+        .and_then(|(_, block)| {
+            let x: u8 = block.get(0, "x")?;
+            assert_eq!(x, 7);
+            Ok(())
+        })
+        .and_then(move |_| pool.get_handle())
+        .and_then(|c| c.use_database("clickhouse_test_db"))
+        .and_then(|c| c.query("SELECT x, currentDatabase() AS db FROM clickhouse_test_database").fetch_all())
+        .and_then(move |(c, block)| {
+            let db: String = block.get(0, "db").unwrap();
+            assert_eq!(db, "clickhouse_test_db");
+            drop(c);
+
+            // The switch doesn't leak to the next borrower.
+            other_pool.get_handle()
+        })
+        .and_then(|c| c.query("SELECT currentDatabase() AS db").fetch_all())
+        .and_then(|(_, block)| {
+            let db: String = block.get(0, "db")?;
+            assert_eq!(db, "default");
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_client_identity() {
+    let options = Options::from_str(&database_url())
+        .unwrap()
+        .client_name("clickhouse-rs-test")
+        .client_version(3, 4)
+        .quota_key("tenant-a");
+    let pool = Pool::new(options);
+    let query_id = Uuid::new_v4().to_string();
+    let log_query = format!(
+        "SELECT client_name, client_version_major AS major, quota_key FROM system.query_log \
+         WHERE query_id = '{}' AND type = 'QueryFinish'",
+        query_id
+    );
+
+    let done = pool
+        .get_handle()
+        .and_then(move |c| {
+            c.query(Query::new("SELECT 1").id(query_id).quota_key("tenant-b"))
+                .fetch_all()
+        })
+        .and_then(|(c, _)| c.execute("SYSTEM FLUSH LOGS"))
+        .and_then(move |c| c.query(log_query).fetch_all())
+        .and_then(|(_, block)| {
+            assert_eq!(block.row_count(), 1);
+            let name: String = block.get(0, "client_name")?;
+            let major: u64 = block.get(0, "major")?;
+            let quota_key: String = block.get(0, "quota_key")?;
+            assert_eq!(name, "clickhouse-rs-test");
+            assert_eq!(major, 3);
+            assert_eq!(quota_key, "tenant-b");
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_server_settings() {
+    let pool = Pool::new(database_url());
+
+    let done = pool.get_handle().map(|c| {
+        let settings = c.server_settings();
+        if c.server_info().protocol_revision() < 54474 {
+            assert!(settings.is_empty());
+        }
+        assert!(settings.keys().all(|name| !name.is_empty()));
+    });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_client_name_in_processes() {
+    let options = Options::from_str(&database_url())
+        .unwrap()
+        .client_name("my-service/1.2.3");
+    let pool = Pool::new(options);
+    let query_id = Uuid::new_v4().to_string();
+    let query = Query::new(format!(
+        "SELECT client_name FROM system.processes WHERE query_id = '{}'",
+        query_id
+    ))
+    .id(query_id);
+
+    let done = pool
+        .get_handle()
+        .and_then(move |c| c.query(query).fetch_all())
+        .and_then(|(_, block)| {
+            assert_eq!(block.row_count(), 1);
+            let name: String = block.get(0, "client_name")?;
+            assert_eq!(name, "my-service/1.2.3");
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_trace_context() {
+    let pool = Pool::new(database_url());
+    let trace_id = Uuid::new_v4().as_u128();
+    let query_id = Uuid::new_v4().to_string();
+    let span_query = format!(
+        "SELECT count() AS n FROM system.opentelemetry_span_log \
+         WHERE trace_id = toUUID('{}') AND attribute['clickhouse.query_id'] = '{}'",
+        Uuid::from_u128(trace_id),
+        query_id
+    );
+
+    let done = pool.get_handle().and_then(move |c| {
+        let query = Query::new("SELECT 1").id(query_id).with_trace_context(
+            trace_id,
+            0x00f0_67aa_0ba9_02b7,
+            1,
+        );
+        c.query(query)
+            .fetch_all()
+            .and_then(|(c, _)| c.execute("SYSTEM FLUSH LOGS"))
+            .and_then(move |c| c.query(span_query).fetch_all())
+            .and_then(|(_, block)| {
+                let n: u64 = block.get(0, "n")?;
+                assert!(n > 0);
+                Ok(())
+            })
+    });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_insert_values() {
+    let ddl = "
+        CREATE TABLE clickhouse_test_insert_values (
+            id UInt32,
+            name String,
+            tags Array(String),
+            day Date
+        ) Engine=Memory";
+
+    let block = Block::new()
+        .column("id", vec![1_u32, 2])
+        .column("name", vec!["o'brien", "back\\slash"])
+        .column("tags", vec![vec!["a".to_string()], vec![]])
+        .column(
+            "day",
+            vec![Tz::UTC.ymd(2020, 1, 2), Tz::UTC.ymd(1999, 12, 31)],
+        );
+
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_insert_values"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert_values("clickhouse_test_insert_values", block))
+        .and_then(|c| {
+            c.query("SELECT id, name, tags, toString(day) AS day FROM clickhouse_test_insert_values ORDER BY id")
+                .fetch_all()
+        })
+        .and_then(|(_, block)| {
+            assert_eq!(block.row_count(), 2);
+            assert_eq!(block.get::<&str, _>(0, "name").unwrap(), "o'brien");
+            assert_eq!(block.get::<&str, _>(1, "name").unwrap(), "back\\slash");
+            assert_eq!(block.get::<Vec<&str>, _>(0, "tags").unwrap(), vec!["a"]);
+            assert_eq!(block.get::<&str, _>(1, "day").unwrap(), "1999-12-31");
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_compression_min_block_bytes() {
+    let ddl = "
+        CREATE TABLE clickhouse_test_compression_threshold (
+            id UInt64,
+            name String
+        ) Engine=Memory";
+
+    let url = database_url();
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let url = format!(
+        "{}{}compression=lz4&compression_min_block_bytes=4096",
+        url, separator
+    );
+
+    let small = Block::new()
+        .column("id", vec![1_u64, 2])
+        .column("name", vec!["a", "b"]);
+    let large = Block::new()
+        .column("id", (0..10_000_u64).collect::<Vec<_>>())
+        .column(
+            "name",
+            (0..10_000)
+                .map(|i| format!("name-{}", i))
+                .collect::<Vec<_>>(),
+        );
+
+    let pool = Pool::new(url);
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_compression_threshold"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert("clickhouse_test_compression_threshold", small))
+        .and_then(move |c| c.insert("clickhouse_test_compression_threshold", large))
+        .and_then(|c| {
+            c.query("SELECT count() AS n, sum(id) AS s FROM clickhouse_test_compression_threshold")
+                .fetch_all()
+        })
+        .and_then(|(_, block)| {
+            assert_eq!(block.get::<u64, _>(0, "n")?, 10_002);
+            assert_eq!(block.get::<u64, _>(0, "s")?, 3 + 9_999 * 10_000 / 2);
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_totals_row() {
+    use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
+
+    #[derive(Debug, PartialEq)]
+    struct Totals {
+        sum: u64,
+    }
+
+    // What `#[derive(Deserialize)]` would generate, skipping unknown fields.
+    impl<'de> Deserialize<'de> for Totals {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct TotalsVisitor;
+
+            impl<'de> Visitor<'de> for TotalsVisitor {
+                type Value = Totals;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("struct Totals")
+                }
+
+                fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Totals, A::Error> {
+                    let mut sum = None;
+                    while let Some(key) = map.next_key::<String>()? {
+                        if key == "sum" {
+                            sum = Some(map.next_value()?);
+                        } else {
+                            map.next_value::<de::IgnoredAny>()?;
+                        }
+                    }
+                    let sum = sum.ok_or_else(|| de::Error::missing_field("sum"))?;
+                    Ok(Totals { sum })
+                }
+            }
+
+            deserializer.deserialize_struct("Totals", &["sum"], TotalsVisitor)
+        }
+    }
+
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(|c| {
+            c.query("SELECT number % 3 AS k, sum(number) AS sum FROM numbers(10) GROUP BY k WITH TOTALS")
+                .fetch_all()
+        })
+        .and_then(|(c, block)| {
+            assert_eq!(block.totals_row::<Totals>().unwrap(), Some(Totals { sum: 45 }));
+            c.query("SELECT number % 3 AS k, sum(number) AS sum FROM numbers(10) GROUP BY k")
+                .fetch_all()
+        })
+        .and_then(|(_, block)| {
+            assert_eq!(block.totals_row::<Totals>()?, None);
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_missing_table_error() {
+    let pool = Pool::new(database_url());
+    let done = pool.get_handle().and_then(|c| {
+        c.query("SELECT * FROM clickhouse_test_no_such_table")
+            .fetch_all()
+    });
+
+    match run(done).unwrap_err() {
+        Error::Server(err) => {
+            assert_eq!(err.code, codes::UNKNOWN_TABLE);
+            assert!(err.is_missing_table());
+            assert!(!err.is_timeout());
+        }
+        err => panic!("{:?}", err),
+    }
+}
+
+#[test]
+fn test_exception_after_data() {
+    let url = format!("{}{}", database_url(), "&pool_max=1&pool_min=1");
+    let pool = Pool::new(url);
+    let sql = "SELECT number FROM numbers(100000) \
+               SETTINGS max_block_size = 100, max_result_rows = 1000, \
+               result_overflow_mode = 'throw'";
+
+    let done = pool
+        .get_handle()
+        .and_then(move |c| c.query(sql).stream_blocks().then(Ok::<_, Error>).collect());
+    let mut results = run(done).unwrap();
+
+    let err = match results.pop() {
+        Some(Err(Error::Server(err))) => err,
+        result => panic!("unexpected result {:?}", result.map(|r| r.map(|_| ()))),
+    };
+    assert_eq!(err.code, codes::TOO_MANY_ROWS_OR_BYTES);
+    assert!(!results.is_empty());
+    let mut expected = 0_u64;
+    for block in results {
+        for n in block
+            .unwrap()
+            .get_column("number")
+            .unwrap()
+            .iter::<u64>()
+            .unwrap()
+        {
+            assert_eq!(*n, expected);
+            expected += 1;
+        }
+    }
+
+    // The connection is handed back to the pool with the error.
+    drop(err);
+    assert_eq!(pool.status().idle, 1);
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.query("SELECT 1 AS x").fetch_all())
+        .map(|(_, block)| block.get::<u8, _>(0, "x").unwrap());
+    assert_eq!(run(done).unwrap(), 1);
+}
+
+#[test]
+fn test_insert_schema_mismatch() {
+    let url = format!("{}{}", database_url(), "&pool_max=1&pool_min=1");
+    let pool = Pool::new(url);
+    let ddl = "CREATE TABLE clickhouse_test_schema_mismatch (id UInt32, name String) Engine=Memory";
+    let block = Block::new()
+        .column("id", vec![1_u32])
+        .column("name", vec![1_u8]);
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_schema_mismatch"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert("clickhouse_test_schema_mismatch", block));
+
+    match run(done) {
+        Err(Error::SchemaMismatch { problems }) => assert_eq!(
+            problems,
+            vec![SchemaProblem::TypeMismatch {
+                name: "name".into(),
+                expected: "String".into(),
+                actual: "UInt8".into(),
+            }]
+        ),
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+
+    // Nothing is inserted and the connection is still usable.
+    assert_eq!(pool.status().idle, 1);
+    let done = pool
+        .get_handle()
+        .and_then(|c| {
+            c.query("SELECT count() AS n FROM clickhouse_test_schema_mismatch")
+                .fetch_all()
+        })
+        .map(|(_, block)| block.get::<u64, _>(0, "n").unwrap());
+    assert_eq!(run(done).unwrap(), 0);
+}
+
+#[test]
+fn test_insert_ip_strings() {
+    let pool = Pool::new(database_url());
+    let ddl = "CREATE TABLE clickhouse_test_ip_strings (ip IPv4) Engine=Memory";
+    let valid = Block::new().column("ip", vec!["10.0.0.1"]);
+    let invalid = Block::new().column("ip", vec!["10.0.0.1", "10.0.0.300"]);
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_ip_strings"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert("clickhouse_test_ip_strings", valid))
+        .and_then(|c| {
+            c.query("SELECT ip FROM clickhouse_test_ip_strings")
+                .fetch_all()
+        })
+        .map(|(_, block)| block.get::<Ipv4Addr, _>(0, "ip").unwrap());
+    assert_eq!(run(done).unwrap(), Ipv4Addr::new(10, 0, 0, 1));
+
+    let done = pool
+        .get_handle()
+        .and_then(move |c| c.insert("clickhouse_test_ip_strings", invalid));
+    match run(done) {
+        Err(Error::Other(message)) => assert!(message.contains("`10.0.0.300`"), "{}", message),
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_insert_columns() {
+    let pool = Pool::new(database_url());
+    let ddl = "CREATE TABLE clickhouse_test_insert_columns (
+                   a UInt32,
+                   b String,
+                   c String DEFAULT concat(b, '!'),
+                   d UInt64 MATERIALIZED a + 1
+               ) Engine=Memory";
+    let block = Block::new()
+        .column("b", vec!["one", "two"])
+        .column("a", vec![1_u32, 2]);
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_insert_columns"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert_columns("clickhouse_test_insert_columns", &["a", "b"], block))
+        .and_then(|c| {
+            c.query("SELECT a, b, c, d FROM clickhouse_test_insert_columns ORDER BY a")
+                .fetch_all()
+        });
+
+    let (_, block) = run(done).unwrap();
+    assert_eq!(block.row_count(), 2);
+    assert_eq!(block.get::<u32, _>(1, "a").unwrap(), 2);
+    assert_eq!(block.get::<&str, _>(1, "b").unwrap(), "two");
+    assert_eq!(block.get::<&str, _>(1, "c").unwrap(), "two!");
+    assert_eq!(block.get::<u64, _>(1, "d").unwrap(), 3);
+
+    let extra = Block::new()
+        .column("a", vec![3_u32])
+        .column("b", vec!["three"])
+        .column("e", vec![1_u8]);
+    let done = pool
+        .get_handle()
+        .and_then(move |c| c.insert_columns("clickhouse_test_insert_columns", &["a", "b"], extra));
+    match run(done) {
+        Err(Error::SchemaMismatch { problems }) => {
+            assert_eq!(
+                problems,
+                vec![SchemaProblem::ExtraColumn { name: "e".into() }]
+            )
+        }
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_insert_in_blocks() {
+    let pool = Pool::new(database_url());
+    let ddl = "CREATE TABLE clickhouse_test_insert_blocks (n UInt32, a Array(UInt32), s Nullable(String)) Engine=Memory";
+    let n: Vec<_> = (0..10_u32).collect();
+    let a: Vec<_> = (0..10_u32).map(|i| (0..i).collect::<Vec<_>>()).collect();
+    let s: Vec<_> = (0..10_u32)
+        .map(|i| {
+            if i % 3 == 0 {
+                None
+            } else {
+                Some(i.to_string())
+            }
+        })
+        .collect();
+    let block = Block::new().column("n", n).column("a", a).column("s", s);
+    let query = Query::new("clickhouse_test_insert_blocks").max_insert_block_size(3);
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_insert_blocks"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert(query, block))
+        .and_then(|c| {
+            c.query("SELECT n, a, s FROM clickhouse_test_insert_blocks ORDER BY n")
+                .fetch_all()
+        });
+
+    let (_, block) = run(done).unwrap();
+    assert_eq!(block.row_count(), 10);
+    for row in block.rows() {
+        let n: u32 = row.get("n").unwrap();
+        assert_eq!(
+            row.get::<Vec<u32>, _>("a").unwrap(),
+            (0..n).collect::<Vec<_>>()
+        );
+        let s: Option<String> = row.get("s").unwrap();
+        let expected = match n % 3 {
+            0 => None,
+            _ => Some(n.to_string()),
+        };
+        assert_eq!(s, expected);
+    }
+}
+
+#[test]
+fn test_insert_jagged_arrays() {
+    let pool = Pool::new(database_url());
+    let ddl = "CREATE TABLE clickhouse_test_jagged_arrays (n UInt32, a Array(UInt32), s Array(String)) Engine=Memory";
+    let rows: Vec<Vec<u32>> = vec![vec![1, 2], vec![3], vec![], vec![4, 5, 6]];
+
+    let mut a = ArrayColumnData::new(SqlType::UInt32).unwrap();
+    for row in &rows {
+        a.push_array(row).unwrap();
+    }
+    let s: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|v| v.to_string()).collect())
+        .collect();
+    let block = Block::new()
+        .column("n", (0..rows.len() as u32).collect::<Vec<_>>())
+        .column("a", a)
+        .column("s", s);
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_jagged_arrays"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert("clickhouse_test_jagged_arrays", block))
+        .and_then(|c| {
+            c.query("SELECT n, a, s FROM clickhouse_test_jagged_arrays ORDER BY n")
+                .fetch_all()
+        });
+
+    let (_, block) = run(done).unwrap();
+    assert_eq!(block.row_count(), rows.len());
+    for (row, expected) in block.rows().zip(rows.iter()) {
+        assert_eq!(&row.get::<Vec<u32>, _>("a").unwrap(), expected);
+        let s: Vec<String> = expected.iter().map(|v| v.to_string()).collect();
+        assert_eq!(row.get::<Vec<String>, _>("s").unwrap(), s);
+    }
+}
+
+#[test]
+fn test_duplicate_column_names() {
+    let pool = Pool::new(database_url());
+    let ddl = "CREATE TABLE clickhouse_test_duplicate_columns (number UInt64) Engine=Memory";
+
+    let sql = "SELECT number, number * 10 AS number FROM numbers(3)";
+    let done = pool
+        .get_handle()
+        .and_then(move |c| {
+            c.query(sql)
+                .stream_blocks()
+                .into_future()
+                .map_err(|(err, _)| err)
+        })
+        .map(|(block, _)| block.unwrap());
+    let block = run(done).unwrap();
+
+    assert_eq!(block.columns_named("number"), vec![0, 1]);
+    for row in 0..block.row_count() {
+        assert_eq!(block.get::<u64, _>(row, "number").unwrap(), row as u64);
+        assert_eq!(block.get::<u64, _>(row, 1).unwrap(), row as u64 * 10);
+    }
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_duplicate_columns"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert("clickhouse_test_duplicate_columns", block));
+
+    match run(done) {
+        Err(Error::SchemaMismatch { problems }) => {
+            assert_eq!(problems.len(), 1);
+            assert_eq!(
+                problems[0].to_string(),
+                "column \"number\" is given more than once"
+            );
+        }
+        res => panic!("unexpected result {:?}", res.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_max_block_size() {
+    let pool = Pool::new(database_url());
+    let query = Query::new("SELECT number FROM system.numbers LIMIT 100000").max_block_size(1000);
+
+    let done = pool.get_handle().and_then(move |c| {
+        c.query(query)
+            .stream_blocks()
+            .fold((0, 0), |(blocks, rows), block| {
+                assert!(
+                    block.row_count() <= 1000,
+                    "{} rows in a block",
+                    block.row_count()
+                );
+                Ok::<_, Error>((blocks + 1, rows + block.row_count()))
+            })
+    });
+
+    let (blocks, rows) = run(done).unwrap();
+    assert_eq!(rows, 100_000);
+    assert!(blocks >= 100, "{} blocks", blocks);
+}
+
+clickhouse_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Status {
+        Active = "active",
+        Blocked = "blocked",
+    }
+}
+
+#[test]
+fn test_enum_labels_round_trip() {
+    let pool = Pool::new(database_url());
+    let ddl = "
+        CREATE TABLE clickhouse_test_enum_labels (
+            id     UInt32,
+            status Enum8('active' = 1, 'blocked' = 2, 'deleted' = 3),
+            prev   Nullable(Enum16('active' = 1, 'blocked' = 1000))
+        ) Engine=Memory";
+
+    let block = Block::new()
+        .column("id", vec![1_u32, 2])
+        .column("status", vec!["active", "deleted"])
+        .column("prev", vec![None, Some("blocked")]);
+
+    let mut rows = Block::new();
+    rows.push(row! { id: 3_u32, status: Status::Blocked, prev: Some(Status::Active) })
+        .unwrap();
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_enum_labels"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert("clickhouse_test_enum_labels", block))
+        .and_then(move |c| c.insert("clickhouse_test_enum_labels", rows))
+        .and_then(|c| {
+            let bad = Block::new()
+                .column("id", vec![4_u32])
+                .column("status", vec!["unknown"])
+                .column("prev", vec![Option::<&str>::None]);
+            c.insert("clickhouse_test_enum_labels", bad)
+                .then(|res| match res {
+                    Err(Error::ValueOutOfRange { column, value, .. }) => {
+                        assert_eq!(column, "status");
+                        assert_eq!(value, "unknown");
+                        Ok(())
+                    }
+                    res => panic!("unexpected result {:?}", res.map(|_| ())),
+                })
+        })
+        .and_then({
+            let pool = pool.clone();
+            move |_| pool.get_handle()
+        })
+        .and_then(|c| {
+            c.query("SELECT id, status, prev FROM clickhouse_test_enum_labels ORDER BY id")
+                .fetch_all()
+        });
+
+    let (_, block) = run(done).unwrap();
+    assert_eq!(block.row_count(), 3);
+
+    let statuses: Vec<&str> = (0..3)
+        .map(|row| block.get(row, "status").unwrap())
+        .collect();
+    assert_eq!(statuses, vec!["active", "deleted", "blocked"]);
+    assert_eq!(block.get::<Status, _>(0, "status").unwrap(), Status::Active);
+    assert_eq!(
+        block.get::<Status, _>(2, "status").unwrap(),
+        Status::Blocked
+    );
+
+    let prev: Vec<Option<Status>> = (0..3).map(|row| block.get(row, "prev").unwrap()).collect();
+    assert_eq!(
+        prev,
+        vec![None, Some(Status::Blocked), Some(Status::Active)]
+    );
+
+    match block.get::<Status, _>(1, "status").unwrap_err() {
+        Error::FromSql(FromSqlError::Column {
+            column,
+            row_index,
+            source,
+            ..
+        }) => {
+            assert_eq!(column, "status");
+            assert_eq!(row_index, 1);
+            assert!(
+                matches!(*source, FromSqlError::UnknownEnumValue { ref value } if value == "deleted")
+            );
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn test_column_null_map() {
+    let pool = Pool::new(database_url());
+    let query = "
+        SELECT
+            if(number % 3 = 0, NULL, number) AS n,
+            range(number) AS a
+        FROM system.numbers
+        LIMIT 6";
+
+    let done = pool.get_handle().and_then(move |c| {
+        c.query(query)
+            .stream_blocks()
+            .fold(Vec::new(), |mut blocks, block| {
+                blocks.push(block);
+                Ok::<_, Error>(blocks)
+            })
+    });
+
+    let blocks = run(done).unwrap();
+    let block = blocks.iter().find(|block| block.row_count() > 0).unwrap();
+
+    let n = block.get_column("n").unwrap();
+    assert!(n.is_nullable());
+    assert_eq!(n.nulls(), Some(&[1_u8, 0, 0, 1, 0, 0][..]));
+    assert_eq!(n.null_count(), 2);
+
+    let a = block.get_column("a").unwrap();
+    assert!(!a.is_nullable());
+    assert_eq!(a.null_count(), 0);
+    assert_eq!(a.as_array().unwrap().offsets(), &[0, 1, 3, 6, 10, 15]);
+}
+
+#[test]
+fn test_filter_and_reinsert() {
+    let pool = Pool::new(database_url());
+    let ddl = "
+        CREATE TABLE clickhouse_test_filter (
+            id   UInt64,
+            note Nullable(String),
+            tags Array(String)
+        ) Engine=Memory";
+    let query = "
+        SELECT
+            number AS id,
+            if(number % 2 = 0, NULL, toString(number)) AS note,
+            arrayMap(x -> toString(x), range(number)) AS tags
+        FROM system.numbers
+        LIMIT 10";
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_filter"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.query(query).fetch_all())
+        .and_then(|(c, block)| {
+            let ids: Vec<u64> = (0..block.row_count())
+                .map(|row| block.get(row, "id").unwrap())
+                .collect();
+            let mask: Vec<bool> = ids.iter().map(|id| id % 3 == 0).collect();
+            let filtered = block.filter(&mask).unwrap();
+            c.insert("clickhouse_test_filter", filtered)
+        })
+        .and_then(|c| {
+            c.query("SELECT id, note, tags FROM clickhouse_test_filter ORDER BY id")
+                .fetch_all()
+        });
+
+    let (_, block) = run(done).unwrap();
+    let ids: Vec<u64> = (0..block.row_count())
+        .map(|row| block.get(row, "id").unwrap())
+        .collect();
+    assert_eq!(ids, vec![0, 3, 6, 9]);
+    let notes: Vec<Option<&str>> = (0..4).map(|row| block.get(row, "note").unwrap()).collect();
+    assert_eq!(notes, vec![None, Some("3"), None, Some("9")]);
+    assert_eq!(
+        block.get::<Vec<&str>, _>(1, "tags").unwrap(),
+        vec!["0", "1", "2"]
+    );
+}
+
+#[test]
+fn test_insert_columns_of_different_lengths() {
+    let pool = Pool::new(database_url());
+    let ddl = "
+        CREATE TABLE clickhouse_test_column_lengths (
+            id   UInt32,
+            name String
+        ) Engine=Memory";
+
+    let mut block = Block::new();
+    block.push(row! { id: 1_u32, name: "a" }).unwrap();
+    block.push(row! { id: 2_u32 }).unwrap();
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_column_lengths"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| {
+            c.insert("clickhouse_test_column_lengths", block)
+                .then(|res| match res {
+                    Err(Error::Driver(DriverError::ColumnLength {
+                        name,
+                        rows,
+                        expected,
+                    })) => {
+                        assert_eq!((name.as_str(), rows, expected), ("name", 1, 2));
+                        Ok(())
+                    }
+                    res => panic!("unexpected result {:?}", res.map(|_| ())),
+                })
+        });
+
+    run(done).unwrap();
+}
+
+#[test]
+fn test_buffered_inserter() {
+    let pool = Pool::new(database_url());
+    let inserter =
+        BufferedInserter::new(pool.clone(), "clickhouse_test_buffered_inserter").max_rows(10);
+
+    let ddl =
+        "CREATE TABLE clickhouse_test_buffered_inserter (id UInt32, name String) Engine=Memory";
+    let setup = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_buffered_inserter"))
+        .and_then(move |c| c.execute(ddl));
+    run(setup).unwrap();
+
+    let pushes = stream::iter_ok::<_, Error>(0..25_u32).for_each({
+        let inserter = inserter.clone();
+        move |id| inserter.push_row(row! { id: id, name: id.to_string() })
+    });
+    let stats = run(pushes.and_then(move |_| inserter.close())).unwrap();
+    assert_eq!(stats.rows_flushed, 25);
+    assert_eq!(stats.rows_failed, 0);
+    assert_eq!(stats.flushes, 3);
+
+    let done = pool.get_handle().and_then(|c| {
+        c.query("SELECT COUNT(*) AS cnt FROM clickhouse_test_buffered_inserter")
+            .fetch_all()
+    });
+    let (_, block) = run(done).unwrap();
+    assert_eq!(block.get::<u64, _>(0, "cnt").unwrap(), 25);
+}
+
+#[test]
+fn test_fetch_one() {
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.query("SELECT 1 AS id, 'foo' AS name").fetch_one())
+        .and_then(|(c, row): (_, (u8, String))| {
+            assert_eq!(row, (1, "foo".to_string()));
+            c.query("SELECT count() FROM numbers(42)").fetch_scalar()
+        })
+        .and_then(|(c, count): (_, u64)| {
+            assert_eq!(count, 42);
+            c.query("SELECT number FROM numbers(0)").fetch_optional()
+        })
+        .map(|(_, row): (_, Option<(u64,)>)| assert_eq!(row, None));
+    run(done).unwrap();
+
+    let done = pool.get_handle().and_then(|c| {
+        c.query("SELECT number FROM numbers(0)")
+            .fetch_one::<(u64,)>()
+    });
+    match run(done) {
+        Err(Error::Driver(DriverError::NoRows)) => {}
+        res => panic!("unexpected result: {:?}", res.map(|(_, row)| row)),
+    }
+
+    let done = pool.get_handle().and_then(|c| {
+        c.query("SELECT number FROM system.numbers")
+            .fetch_optional::<(u64,)>()
+    });
+    match run(done) {
+        Err(Error::Driver(DriverError::TooManyRows)) => {}
+        res => panic!("unexpected result: {:?}", res.map(|(_, row)| row)),
+    }
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.query("SELECT 1, 2").fetch_scalar::<u8>());
+    match run(done) {
+        Err(Error::Driver(DriverError::ColumnCount {
+            columns: 2,
+            expected: 1,
+        })) => {}
+        res => panic!("unexpected result: {:?}", res.map(|(_, value)| value)),
+    }
+}
+
+#[test]
+fn test_async_insert() {
+    let pool = Pool::new(database_url());
+    let ddl =
+        "CREATE TABLE clickhouse_test_async_insert (id UInt32) ENGINE = MergeTree ORDER BY id";
+    let count = |c: ClientHandle| {
+        c.query("SELECT count() FROM clickhouse_test_async_insert")
+            .fetch_scalar::<u64>()
+    };
+
+    let waited = Query::new("clickhouse_test_async_insert")
+        .async_insert(true)
+        .wait_for_async_insert(true);
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_async_insert"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert(waited, Block::new().column("id", vec![1_u32, 2, 3])))
+        .and_then(count);
+    let (_, rows) = run(done).unwrap();
+    assert_eq!(rows, 3);
+
+    // Completes once the server buffered the rows, they show up later.
+    let fire_and_forget = Query::new("clickhouse_test_async_insert")
+        .async_insert(true)
+        .wait_for_async_insert(false);
+    let done = pool
+        .get_handle()
+        .and_then(move |c| c.insert(fire_and_forget, Block::new().column("id", vec![4_u32, 5])));
+    run(done).unwrap();
+
+    let started_at = Instant::now();
+    loop {
+        let (_, rows) = run(pool.get_handle().and_then(count)).unwrap();
+        if rows == 5 {
+            break;
+        }
+        assert!(
+            started_at.elapsed() < Duration::from_secs(10),
+            "rows: {}",
+            rows
+        );
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_schema_to_ddl() {
+    let sql = r"
+        SELECT
+            toUInt8(1) AS u8, toUInt16(2) AS u16, toUInt32(3) AS u32, toUInt64(4) AS u64,
+            toInt8(-1) AS i8, toInt16(-2) AS i16, toInt32(-3) AS i32, toInt64(-4) AS i64,
+            toFloat32(1.5) AS f32, toFloat64(2.5) AS f64,
+            'text' AS s, toFixedString('ab', 2) AS fs,
+            toDate('2020-01-02') AS d,
+            toDateTime('2020-01-02 03:04:05', 'Europe/Moscow') AS dt,
+            toDateTime64('2020-01-02 03:04:05.678', 3, 'Asia/Tokyo') AS dt64,
+            toDecimal32(1.25, 2) AS dec32, toDecimal64(-3.5, 4) AS dec64,
+            CAST('it''s' AS Enum8('it''s' = 1, 'b\\c' = -2)) AS e8,
+            CAST('x' AS Enum16('x' = 1000)) AS e16,
+            toUUID('61f0c404-5cb3-11e7-907b-a6006ad3dba0') AS uuid,
+            toIPv4('10.0.0.1') AS ip4, toIPv6('::1') AS ip6,
+            CAST(NULL AS Nullable(String)) AS nullable, [1, 2, 3] AS array,
+            1 AS `odd \`name\``
+    ";
+
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(move |c| {
+            c.query(sql)
+                .stream_blocks()
+                .into_future()
+                .map_err(|(err, _)| err)
+        })
+        .map(|(block, _)| block.unwrap());
+    let block = run(done).unwrap();
+
+    let ddl = schema_to_ddl(
+        "clickhouse_test_schema_ddl",
+        &block.schema(),
+        "ENGINE = Memory",
+    );
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_schema_ddl"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert("clickhouse_test_schema_ddl", block))
+        .and_then(|c| {
+            c.query("SELECT * FROM clickhouse_test_schema_ddl")
+                .fetch_all()
+        });
+
+    let (_, copy) = run(done).unwrap();
+    assert_eq!(copy.row_count(), 1);
+    assert_eq!(copy.get::<String, _>(0, "s").unwrap(), "text");
+    assert_eq!(
+        copy.get::<Decimal, _>(0, "dec64").unwrap(),
+        Decimal::of(-3.5, 4)
+    );
+    assert_eq!(copy.get::<Option<String>, _>(0, "nullable").unwrap(), None);
+    assert_eq!(copy.get::<u8, _>(0, "odd `name`").unwrap(), 1);
+}
+
+#[test]
+fn test_escaped_literals_round_trip() {
+    use rand::Rng;
+
+    let mut rng = rand::thread_rng();
+    let mut specials = vec![
+        b'\'', b'\\', b'`', b'"', b'?', 0, b'\n', b'\r', b'\t', 0x7f, 0xff,
+    ];
+    specials.extend("ж€".as_bytes());
+
+    let strings: Vec<Vec<u8>> = (0..100)
+        .map(|_| {
+            let len = rng.gen_range(0, 24);
+            (0..len)
+                .map(|_| {
+                    if rng.gen_bool(0.5) {
+                        specials[rng.gen_range(0, specials.len())]
+                    } else {
+                        rng.gen()
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    let names: Vec<String> = (0..20)
+        .map(|i| {
+            let len = rng.gen_range(1, 12);
+            let chars: String = (0..len)
+                .map(|_| ['a', '`', '\'', '\\', ' ', '\n', '\t', 'ж', '.'][rng.gen_range(0, 9)])
+                .collect();
+            format!("{}{}", i, chars)
+        })
+        .collect();
+
+    let mut columns: Vec<String> = strings
+        .iter()
+        .enumerate()
+        .map(|(i, s)| format!("{} AS s{}", escape_literal(&Value::from(s.clone())), i))
+        .collect();
+    columns.extend(
+        names
+            .iter()
+            .map(|name| format!("1 AS {}", escape_identifier(name))),
+    );
+    let sql = format!("SELECT {}", columns.join(", "));
+
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(move |c| c.query(sql).fetch_all());
+    let (_, block) = run(done).unwrap();
+
+    for (i, expected) in strings.iter().enumerate() {
+        let actual: Vec<u8> = block.get(0, i).unwrap();
+        assert_eq!(&actual, expected);
+    }
+    for (i, name) in names.iter().enumerate() {
+        assert_eq!(block.columns()[strings.len() + i].name(), name);
+    }
+}
+
+#[test]
+fn test_named_tuple() {
+    let pool = Pool::new(database_url());
+    let sql = "SELECT CAST((1, 'x'), 'Tuple(a UInt32, b String)') AS named, (2, 'y') AS unnamed";
+    let done = pool
+        .get_handle()
+        .and_then(move |c| c.query(sql).fetch_all());
+    let (_, block) = run(done).unwrap();
+
+    let named: Value = block.get(0, "named").unwrap();
+    assert_eq!(named.tuple_element("a"), Some(&Value::UInt32(1)));
+    assert_eq!(named.tuple_element("b"), Some(&Value::from("x")));
+    assert_eq!(named.tuple_element("c"), None);
+
+    let unnamed: Value = block.get(0, "unnamed").unwrap();
+    assert_eq!(unnamed.to_string(), "(2, 'y')");
+    assert_eq!(unnamed.tuple_element("1"), None);
+}
+
+#[test]
+fn test_insert_out_of_range() {
+    let ddl = "CREATE TABLE clickhouse_test_out_of_range (x Decimal(5, 2)) Engine=Memory";
+    let values = || vec![Decimal::of(1.5, 2), Decimal::of(1234.5, 2)];
+
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_out_of_range"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| {
+            let block = Block::new().column("x", values());
+            c.insert("clickhouse_test_out_of_range", block)
+                .then(Ok::<_, Error>)
+        });
+    match run(done).unwrap() {
+        Err(Error::ValueOutOfRange {
+            column, row_index, ..
+        }) => {
+            assert_eq!(column, "x");
+            assert_eq!(row_index, 1);
+        }
+        result => panic!("{:?}", result.map(drop)),
+    }
+
+    let block = Block::new()
+        .with_overflow_mode(OverflowMode::Saturating)
+        .column("x", values());
+    let done = pool
+        .get_handle()
+        .and_then(move |c| c.insert("clickhouse_test_out_of_range", block))
+        .and_then(|c| {
+            c.query("SELECT x FROM clickhouse_test_out_of_range ORDER BY x")
+                .fetch_all()
+        });
+    let (_, block) = run(done).unwrap();
+    let values: Vec<f64> = (0..2)
+        .map(|row| block.get::<Decimal, _>(row, "x").unwrap().into())
+        .collect();
+    assert_eq!(values, vec![1.5, 999.99]);
+}
+
+#[test]
+fn test_insert_with_result() {
+    let ddl = "CREATE TABLE clickhouse_test_insert_result (id UInt32) Engine=Memory";
+    let block = Block::new().column("id", (0..1000_u32).collect::<Vec<_>>());
+
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_insert_result"))
+        .and_then(move |c| c.execute(ddl))
+        .and_then(move |c| c.insert_with_result("clickhouse_test_insert_result", block))
+        .and_then(|(c, result)| {
+            c.query("SELECT count() AS n FROM clickhouse_test_insert_result")
+                .fetch_all()
+                .map(move |(_, block)| (result, block))
+        });
+
+    let (result, block) = run(done).unwrap();
+    assert_eq!(result.rows_written, 1000);
+    assert!(result.bytes_written >= 4000);
+    assert_eq!(block.get::<u64, _>(0, "n").unwrap(), 1000);
+}
+
+#[test]
+fn test_execute_with_stats() {
+    let ddl = "CREATE TABLE clickhouse_test_execute_stats (id UInt64) Engine=Memory";
+    let pool = Pool::new(database_url());
+    let done = pool
+        .get_handle()
+        .and_then(|c| c.execute("DROP TABLE IF EXISTS clickhouse_test_execute_stats"))
+        .and_then(move |c| c.execute_with_stats(ddl))
+        .and_then(|(c, created)| {
+            assert_eq!((created.read_rows, created.written_rows), (0, 0));
+            c.execute_with_stats(
+                "INSERT INTO clickhouse_test_execute_stats SELECT number FROM numbers(100)",
+            )
+        });
+
+    let (_, result) = run(done).unwrap();
+    assert_eq!(result.read_rows, 100);
+    assert_eq!(result.written_rows, 100);
+    assert!(result.written_bytes > 0);
+    assert!(result.elapsed > Duration::from_secs(0));
+}
+
+#[derive(Default)]
+struct RecordEvents(Mutex<Vec<String>>);
+
+impl PoolEvents for RecordEvents {
+    fn connection_opened(&self, _host: &url::Url) {
+        self.0.lock().unwrap().push("opened".into());
+    }
+
+    fn connection_closed(&self, _host: Option<&url::Url>, reason: CloseReason) {
+        self.0.lock().unwrap().push(format!("closed {:?}", reason));
+    }
+
+    fn checkout_finished(&self, _wait: Duration, error: Option<&Error>) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("checkout {}", error.is_none()));
+    }
+
+    fn query_started(&self, query_id: &str) {
+        self.0.lock().unwrap().push(format!("started {}", query_id));
+    }
+
+    fn query_finished(
+        &self,
+        query_id: &str,
+        counts: QueryCounts,
+        _duration: Duration,
+        error: Option<&Error>,
+    ) {
+        let outcome = if error.is_none() { "ok" } else { "failed" };
+        self.0.lock().unwrap().push(format!(
+            "finished {} {} {}",
+            query_id,
+            outcome,
+            counts.rows_read > 0
+        ));
+    }
+}
+
+#[test]
+fn test_event_handler() {
+    let events = Arc::new(RecordEvents::default());
+    let options = Options::from_str(&database_url())
+        .unwrap()
+        .pool_min(1)
+        .pool_max(1)
+        .ping_before_query(false)
+        .with_event_handler(events.clone());
+    let pool = Pool::new(options);
+
+    let done = pool.get_handle().and_then(|c| {
+        c.query("SELECT number FROM system.numbers LIMIT 10")
+            .with_id("numbers")
+            .fetch_all()
+            .and_then(|(c, _)| c.query("SELECT nope").with_id("typo").fetch_all())
+    });
+    drop(run(done).unwrap_err());
+    drop(pool);
+
+    let recorded = events.0.lock().unwrap().clone();
+    assert_eq!(
+        recorded,
+        vec![
+            "opened",
+            "checkout true",
+            "started numbers",
+            "finished numbers ok true",
+            "started typo",
+            "finished typo failed false",
+            "closed Released",
+        ]
+    );
+}
+
+#[test]
+fn test_reuse_connection_after_partial_read() {
+    let events = Arc::new(RecordEvents::default());
+    let options = Options::from_str(&database_url())
+        .unwrap()
+        .pool_min(1)
+        .pool_max(1)
+        .with_event_handler(events.clone());
+    let pool = Pool::new(options);
+
+    let done = pool
+        .get_handle()
+        .and_then(|c| {
+            c.query("SELECT number FROM system.numbers SETTINGS max_block_size = 10")
+                .stream_blocks()
+                .take(1)
+                .for_each(|block| {
+                    assert_eq!(block.row_count(), 10);
+                    Ok(())
+                })
+        })
+        // The only connection of the pool is handed out again once the rest
+        // of the first result has been drained.
+        .and_then({
+            let pool = pool.clone();
+            move |_| pool.get_handle()
+        })
+        .and_then(|c| c.query("SELECT 42 AS n").fetch_all())
+        .and_then(|(c, block)| {
+            assert_eq!(block.get::<u8, _>(0, "n").unwrap(), 42);
+            c.query("SELECT count() FROM numbers(10)")
+                .fetch_scalar::<u64>()
+        });
+
+    let (_, count) = run(done).unwrap();
+    assert_eq!(count, 10);
+
+    let recorded = events.0.lock().unwrap().clone();
+    assert_eq!(
+        recorded.iter().filter(|event| *event == "opened").count(),
+        1
+    );
+    assert!(!recorded.iter().any(|event| event.starts_with("closed")));
+}
+
+/// SQL, query id, duration, rows read and whether the query failed.
+type LoggedQuery = (String, String, Duration, u64, bool);
+
+#[derive(Default)]
+struct RecordQueries(Mutex<Vec<LoggedQuery>>);
+
+impl PoolEvents for RecordQueries {
+    fn query_logged(&self, log: &QueryLog) {
+        self.0.lock().unwrap().push((
+            log.sql.to_string(),
+            log.query_id.to_string(),
+            log.duration,
+            log.rows_read,
+            log.error.is_some(),
+        ));
+    }
+}
+
+#[test]
+fn test_query_log_hook() {
+    let queries = Arc::new(RecordQueries::default());
+    let options = Options::from_str(&database_url())
+        .unwrap()
+        .ping_before_query(false)
+        .with_event_handler(queries.clone());
+    let pool = Pool::new(options);
+
+    let sleep = "SELECT sleep(0.2)";
+    let started_at = Instant::now();
+    let done = pool.get_handle().and_then(move |c| {
+        c.query(sleep)
+            .with_id("sleep")
+            .fetch_all()
+            .and_then(|(c, _)| c.query("SELECT nope").with_id("typo").fetch_all())
+    });
+    drop(run(done).unwrap_err());
+    let elapsed = started_at.elapsed();
+
+    let recorded = queries.0.lock().unwrap().clone();
+    assert_eq!(recorded.len(), 2);
+
+    let (sql, id, duration, rows, failed) = &recorded[0];
+    assert_eq!(
+        (sql.as_str(), id.as_str(), failed),
+        (sleep, "sleep", &false)
+    );
+    assert_eq!(*rows, 1);
+    assert!(*duration >= Duration::from_millis(200));
+    assert!(*duration <= elapsed);
+
+    let (sql, id, duration, _, failed) = &recorded[1];
+    assert_eq!(
+        (sql.as_str(), id.as_str(), failed),
+        ("SELECT nope", "typo", &true)
+    );
+    assert!(*duration <= elapsed);
+}
+
+#[test]
+fn test_cancel_query() {
+    let url = format!("{}{}", database_url(), "&pool_max=2&pool_min=2");
+    let pool = Pool::new(url);
+    let query_id = "clickhouse_rs_test_cancel_query";
+
+    let slow = pool.get_handle().and_then(move |c| {
+        c.query("SELECT sleep(1) FROM numbers(30) SETTINGS max_block_size = 1")
+            .with_id(query_id)
+            .fetch_all()
+    });
+
+    let cancel = {
+        let pool = pool.clone();
+        Delay::new(Instant::now() + Duration::from_millis(500))
+            .map_err(|err| Error::Other(err.to_string().into()))
+            .and_then(move |_| pool.get_handle())
+            .and_then(move |c| c.cancel_query(query_id))
+    };
+
+    let done = slow
+        .then(Ok::<_, Error>)
+        .join(cancel)
+        .and_then(move |(result, _)| {
+            match result {
+                Err(Error::Server(err)) => assert_eq!(err.code, codes::QUERY_WAS_CANCELLED),
+                other => panic!("query was not cancelled: {:?}", other.map(|_| ())),
+            }
+            pool.get_handle()
+        })
+        .and_then(|c| c.query("SELECT 1").fetch_all())
+        .and_then(|(_, block)| {
+            let value: u8 = block.get(0, 0)?;
+            assert_eq!(value, 1);
+            Ok(())
+        });
+
+    run(done).unwrap();
+}
+
 #[test]
 fn test_column_iter() {
     let ddl = r"